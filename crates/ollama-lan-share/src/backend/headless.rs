@@ -0,0 +1,345 @@
+//! Startup shared by the headless CLIs (`ollama-lan-share` and
+//! `vas-ollama-share`).
+//!
+//! Both take the same discovery, group-key, proxy and TLS flags. [`ShareArgs`]
+//! parses them, [`ShareArgs::load`] checks them against each other and reads
+//! the files they name before anything starts, and [`ShareSetup::start`]
+//! brings up discovery and, when models are shared with `--proxy-port`, the
+//! share proxy in front of Ollama. Each CLI only decides which models to offer.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::network_discovery::{self, DiscoveryOptions, NetworkDiscovery};
+use super::share_proxy::{self, ClientToken, ProxyConfig, ProxyOptions, ShareProxy, TlsIdentity, DEFAULT_UPSTREAM};
+
+/// How often the clients file is checked for new or revoked tokens.
+const CLIENTS_RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The flags both CLIs accept.
+#[derive(Debug, Default, Clone)]
+pub struct ShareArgs {
+    pub name: Option<String>,
+    pub groups: Vec<String>,
+    pub group_keys_file: Option<String>,
+    pub seal_offer: bool,
+    pub ollama_base_url: Option<String>,
+    pub proxy_port: Option<u16>,
+    pub share_key_file: Option<String>,
+    pub proxy_endpoints: Option<String>,
+    pub clients_file: Option<String>,
+    pub audit_log: Option<String>,
+    pub tls_dir: Option<String>,
+    pub discovery: DiscoveryOptions,
+}
+
+impl ShareArgs {
+    /// Takes `flag`, and its value from `rest`, if it is one of ours:
+    ///   --name <name>
+    ///   --group <groupId>   (repeatable)
+    ///   --groups <csv>
+    ///   --group-keys-file <path>
+    ///   --seal-offer
+    ///   --ollama <base_url>
+    ///   --proxy-port <port>
+    ///   --share-key-file <path>
+    ///   --proxy-endpoints <csv>
+    ///   --clients-file <path>
+    ///   --audit-log <path>
+    ///   --tls-dir <dir>
+    ///   --discovery-port <port>
+    ///   --no-broadcast
+    ///   --multicast
+    ///   --multicast6
+    ///   --seed <host[:port]>   (repeatable)
+    ///   --no-gossip
+    ///
+    /// Returns `Ok(false)` for any other flag, which is left to the caller.
+    pub fn parse_flag(&mut self, flag: &str, rest: &mut impl Iterator<Item = String>) -> Result<bool, String> {
+        match flag {
+            "--name" => self.name = rest.next(),
+            "--group" => {
+                if let Some(g) = rest.next() {
+                    if !g.trim().is_empty() {
+                        self.groups.push(g);
+                    }
+                }
+            }
+            "--groups" => {
+                if let Some(csv) = rest.next() {
+                    self.groups.extend(split_list(&csv));
+                }
+            }
+            "--group-keys-file" => self.group_keys_file = rest.next(),
+            "--seal-offer" => self.seal_offer = true,
+            "--ollama" => self.ollama_base_url = rest.next(),
+            // Never fall back to sharing Ollama without the proxy.
+            "--proxy-port" => self.proxy_port = Some(parse_port(flag, rest.next())?),
+            "--share-key-file" => self.share_key_file = rest.next(),
+            "--proxy-endpoints" => self.proxy_endpoints = rest.next(),
+            "--clients-file" => self.clients_file = rest.next(),
+            "--audit-log" => self.audit_log = rest.next(),
+            "--tls-dir" => self.tls_dir = rest.next(),
+            "--discovery-port" => self.discovery.port = parse_port(flag, rest.next())?,
+            "--no-broadcast" => self.discovery.broadcast = false,
+            "--multicast" => self.discovery.multicast_v4 = Some(network_discovery::DEFAULT_MULTICAST_V4),
+            "--multicast6" => self.discovery.multicast_v6 = Some(network_discovery::DEFAULT_MULTICAST_V6),
+            "--seed" => {
+                if let Some(seed) = rest.next() {
+                    if !seed.trim().is_empty() {
+                        self.discovery.seeds.push(seed);
+                    }
+                }
+            }
+            "--no-gossip" => self.discovery.gossip = false,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Checks the flags against each other and reads the group keys, share
+    /// key, clients and TLS certificate, so mistakes surface before anything
+    /// is started.
+    pub fn load(self) -> Result<ShareSetup, String> {
+        if self.proxy_port.is_none() {
+            let proxy_flags = [
+                ("--share-key-file", self.share_key_file.is_some()),
+                ("--proxy-endpoints", self.proxy_endpoints.is_some()),
+                ("--clients-file", self.clients_file.is_some()),
+                ("--audit-log", self.audit_log.is_some()),
+                ("--tls-dir", self.tls_dir.is_some()),
+            ];
+            if let Some((flag, _)) = proxy_flags.iter().find(|(_, given)| *given) {
+                return Err(format!("{flag} needs --proxy-port"));
+            }
+        }
+
+        let d = &self.discovery;
+        if !d.broadcast && d.multicast_v4.is_none() && d.multicast_v6.is_none() && d.seeds.is_empty() {
+            return Err("--no-broadcast needs --multicast, --multicast6 or --seed".to_string());
+        }
+
+        if self.seal_offer && self.group_keys_file.is_none() {
+            return Err("--seal-offer needs --group-keys-file".to_string());
+        }
+
+        let group_keys = match &self.group_keys_file {
+            Some(path) => network_discovery::load_group_keys_file(Path::new(path))?,
+            None => BTreeMap::new(),
+        };
+        let share_key = match &self.share_key_file {
+            Some(path) => share_proxy::read_share_key_file(Path::new(path))?,
+            None => String::new(),
+        };
+        let endpoints = match &self.proxy_endpoints {
+            Some(csv) => share_proxy::parse_endpoints(csv)?,
+            None => ProxyConfig::default().endpoints,
+        };
+        let clients = match &self.clients_file {
+            Some(path) => share_proxy::load_clients_file(Path::new(path))?,
+            None => Vec::new(),
+        };
+        // The certificate is created on first run and reused after that, so
+        // peers that pinned its fingerprint keep trusting it.
+        let tls = match &self.tls_dir {
+            Some(dir) => Some(TlsIdentity::load_or_generate(Path::new(dir))?),
+            None => None,
+        };
+
+        Ok(ShareSetup { args: self, group_keys, share_key, endpoints, clients, tls })
+    }
+}
+
+/// Checked [`ShareArgs`] with the files they name already read.
+pub struct ShareSetup {
+    args: ShareArgs,
+    group_keys: BTreeMap<String, String>,
+    share_key: String,
+    endpoints: BTreeSet<String>,
+    clients: Vec<ClientToken>,
+    tls: Option<TlsIdentity>,
+}
+
+impl ShareSetup {
+    /// Ids of the groups joined with a secret.
+    pub fn keyed_groups(&self) -> Vec<&str> {
+        self.group_keys.keys().map(String::as_str).collect()
+    }
+
+    /// Starts discovery and offers `models` (nothing when `None`). With
+    /// `--proxy-port`, peers get the proxy's address instead of Ollama's and
+    /// the proxy enforces the key and the model list.
+    pub async fn start(self, models: Option<Vec<String>>) -> Result<ShareNode, String> {
+        let args = self.args;
+        let discovery = NetworkDiscovery::with_options(args.discovery.clone()).await;
+
+        if let Some(name) = args.name.clone() {
+            discovery.set_local_node_name(name).await;
+        }
+        if !args.groups.is_empty() {
+            discovery.set_local_groups(args.groups.clone()).await;
+        }
+        if !self.group_keys.is_empty() {
+            discovery.set_group_keys(self.group_keys).await;
+            discovery.set_seal_offer(args.seal_offer).await;
+        }
+
+        let mut base_url = args.ollama_base_url.clone();
+        let mut proxy = None;
+        if let (Some(port), Some(models)) = (args.proxy_port, &models) {
+            let options = ProxyOptions {
+                bind: SocketAddr::from(([0, 0, 0, 0], port)),
+                upstream: args.ollama_base_url.clone().unwrap_or_else(|| DEFAULT_UPSTREAM.to_string()),
+                audit_log: args.audit_log.clone().map(PathBuf::from),
+                tls: self.tls,
+                ..ProxyOptions::default()
+            };
+            let auth_required = !self.share_key.is_empty() || !self.clients.is_empty();
+            let config = ProxyConfig {
+                enabled: true,
+                key: self.share_key,
+                allowed_models: models.iter().cloned().collect(),
+                endpoints: self.endpoints,
+                clients: self.clients,
+                ..ProxyConfig::default()
+            };
+            let started = ShareProxy::start(options, config).await?;
+            base_url = Some(started.advertised_base_url().await);
+            discovery
+                .set_offer_auth(auth_required, Some(started.local_addr().port()))
+                .await;
+            discovery.set_offer_tls(started.tls_fingerprint().map(str::to_string)).await;
+            discovery.set_offer_load(Some(started.status().offer_load())).await;
+            // Edits to the file (new or revoked tokens) apply without a restart.
+            if let Some(path) = &args.clients_file {
+                started.watch_clients_file(path.into(), CLIENTS_RELOAD_INTERVAL);
+            }
+            proxy = Some(started);
+        }
+
+        discovery
+            .set_ollama_offer(models.is_some(), models.clone().unwrap_or_default(), base_url.clone())
+            .await;
+        discovery.broadcast_presence();
+
+        Ok(ShareNode { discovery, proxy, models, base_url })
+    }
+}
+
+/// A started node: discovery announcing the offer, and the proxy serving it.
+pub struct ShareNode {
+    pub discovery: NetworkDiscovery,
+    pub proxy: Option<ShareProxy>,
+    /// What is offered; `None` when nothing is shared.
+    pub models: Option<Vec<String>>,
+    /// The address peers are told to call.
+    pub base_url: Option<String>,
+}
+
+impl ShareNode {
+    /// Keeps the advertised load fresh and logs discovery progress every
+    /// `every`. Never returns; announcing itself runs in the background.
+    pub async fn run(&self, every: Duration) {
+        let mut interval = tokio::time::interval(every);
+        loop {
+            interval.tick().await;
+            // Peers pick the least busy offer, so keep the advertised load fresh.
+            if let Some(proxy) = &self.proxy {
+                self.discovery.set_offer_load(Some(proxy.status().offer_load())).await;
+            }
+            let d = self.discovery.debug_stats().await;
+            tracing::info!(
+                "discovery: bound={} tx(a/q/g)={}/{}/{} rx(a/q/g)={}/{}/{} last_rx={:?} {:?}",
+                d.socket_bound,
+                d.sent_announces,
+                d.sent_queries,
+                d.sent_gossip,
+                d.received_announces,
+                d.received_queries,
+                d.received_gossip,
+                d.last_received_kind,
+                d.last_received_from
+            );
+        }
+    }
+}
+
+/// Splits a comma-separated flag value, dropping empty entries.
+pub fn split_list(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_string())
+        .collect()
+}
+
+fn parse_port(flag: &str, value: Option<String>) -> Result<u16, String> {
+    value
+        .and_then(|p| p.parse().ok())
+        .ok_or_else(|| format!("{flag} needs a port number"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(flags: &[&str]) -> Result<(ShareArgs, Vec<String>), String> {
+        let mut args = ShareArgs::default();
+        let mut rest = Vec::new();
+        let mut it = flags.iter().map(|f| f.to_string());
+        while let Some(flag) = it.next() {
+            if !args.parse_flag(&flag, &mut it)? {
+                rest.push(flag);
+            }
+        }
+        Ok((args, rest))
+    }
+
+    fn load_err(flags: &[&str]) -> String {
+        let (args, _) = parse(flags).expect("parsed");
+        match args.load() {
+            Ok(_) => panic!("{flags:?} were accepted"),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn shared_flags_are_taken_and_the_rest_left() {
+        let (args, rest) = parse(&[
+            "--groups", "a, b,", "--group", "c", "--seed", "10.0.0.2:45555", "--no-gossip",
+            "--proxy-port", "11435", "--share-all", "--once",
+        ])
+        .expect("parsed");
+        assert_eq!(args.groups, ["a", "b", "c"]);
+        assert_eq!(args.discovery.seeds, ["10.0.0.2:45555"]);
+        assert!(!args.discovery.gossip);
+        assert_eq!(args.proxy_port, Some(11435));
+        assert_eq!(rest, ["--share-all", "--once"]);
+    }
+
+    #[test]
+    fn ports_must_be_numbers() {
+        let err = parse(&["--proxy-port", "auto"]).map(|_| ()).unwrap_err();
+        assert_eq!(err, "--proxy-port needs a port number");
+        assert!(parse(&["--discovery-port"]).is_err());
+    }
+
+    #[test]
+    fn proxy_flags_need_a_proxy_port() {
+        assert_eq!(load_err(&["--tls-dir", "certs"]), "--tls-dir needs --proxy-port");
+    }
+
+    #[test]
+    fn no_broadcast_needs_another_way_to_announce() {
+        load_err(&["--no-broadcast"]);
+        let (args, _) = parse(&["--no-broadcast", "--multicast"]).expect("parsed");
+        assert!(args.load().is_ok());
+    }
+
+    #[test]
+    fn sealing_needs_group_keys() {
+        assert_eq!(load_err(&["--seal-offer"]), "--seal-offer needs --group-keys-file");
+    }
+}
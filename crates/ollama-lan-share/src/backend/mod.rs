@@ -1,8 +1,11 @@
 //! Backend services for ollama-lan-share.
 
+pub mod headless;
 pub mod network_discovery;
 pub mod ollama_client;
+pub mod share_proxy;
 
-pub use network_discovery::NetworkDiscovery;
+pub use network_discovery::{DiscoveryOptions, NetworkDiscovery};
 pub use ollama_client::manager::OllamaManager;
-pub use ollama_client::types::OllamaModel;
\ No newline at end of file
+pub use ollama_client::types::OllamaModel;
+pub use share_proxy::{ProxyConfig, ProxyOptions, ShareProxy};
\ No newline at end of file
@@ -1,33 +1,107 @@
-use crate::shared::models::{ClientMode, OllamaOfferStatus, PeerStatus, VgaError};
+use crate::shared::models::{ClientMode, OfferLoad, OllamaOfferStatus, PeerStatus, VgaError};
+use aes_gcm::{
+    aead::{Aead, Payload},
+    Aes256Gcm, KeyInit, Nonce,
+};
 use hmac::{Hmac, Mac};
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_json::value::RawValue;
+use std::collections::{BTreeMap, HashMap};
 use std::future::Future;
-use std::net::SocketAddr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::net::UdpSocket;
 use tokio::sync::RwLock;
 use uuid;
 
 type HmacSha256 = Hmac<sha2::Sha256>;
 
-const DISCOVERY_PORT: u16 = 45555;
-const BROADCAST_ADDR: &str = "255.255.255.255:45555";
+pub const DISCOVERY_PORT: u16 = 45555;
+/// Organization-local scope, so routers that honour it keep it on site.
+pub const DEFAULT_MULTICAST_V4: Ipv4Addr = Ipv4Addr::new(239, 255, 45, 55);
+/// Link-local scope: never leaves the link it was sent on.
+pub const DEFAULT_MULTICAST_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x4555);
 const ANNOUNCE_INTERVAL_SECS: u64 = 10;
 const PEER_STALE_SECS: u64 = 5 * 60;
 const EMPTY_LOG_EVERY_SECS: u64 = 30;
 const MAX_PEERS: usize = 512;
+/// Envelope version sent by this build. Packets without one are version 1:
+/// no sequence number or timestamp, so they can't be checked for replays.
+const PACKET_VERSION: u32 = 2;
+/// How far a sender's clock may be from ours. Must stay below
+/// `PEER_STALE_SECS`, after which a sender's replay window is forgotten.
+const MAX_CLOCK_SKEW_SECS: u64 = 2 * 60;
+/// Sequence numbers this far behind a sender's newest are refused outright.
+const REPLAY_WINDOW: u64 = 64;
+/// Peers passed on per gossip packet, most recently seen first; keeps the
+/// packet well inside one datagram.
+const MAX_GOSSIP_PEERS: usize = 32;
+
+/// Where discovery listens and who it announces to; fixed at start.
+#[derive(Clone, Debug)]
+pub struct DiscoveryOptions {
+    /// UDP port, shared by every node that should find each other over
+    /// broadcast or multicast. Use port 0 to let the OS pick one (see
+    /// [`NetworkDiscovery::port`]), e.g. for several nodes on one host
+    /// that only find each other through seeds.
+    pub port: u16,
+    /// Announce to `255.255.255.255`.
+    pub broadcast: bool,
+    /// Join and announce to this IPv4 multicast group, e.g.
+    /// [`DEFAULT_MULTICAST_V4`]. Works on Wi-Fi that drops broadcast.
+    pub multicast_v4: Option<Ipv4Addr>,
+    /// Join and announce to this IPv6 multicast group, e.g.
+    /// [`DEFAULT_MULTICAST_V6`].
+    pub multicast_v6: Option<Ipv6Addr>,
+    /// Interface index for the IPv6 group; 0 lets the OS choose.
+    pub multicast_v6_interface: u32,
+    /// `host:port` of nodes to announce to and query directly, e.g. across
+    /// subnets or a VPN. The port defaults to `port`. A rendezvous node is
+    /// a seed everyone shares; with gossip it introduces them to each other.
+    pub seeds: Vec<String>,
+    /// Pass on the addresses of peers we've seen and announce to the ones
+    /// passed on to us. Without keys, gossip is only followed to private,
+    /// link-local and loopback addresses.
+    pub gossip: bool,
+    pub announce_interval: Duration,
+}
+
+impl Default for DiscoveryOptions {
+    fn default() -> Self {
+        Self {
+            port: DISCOVERY_PORT,
+            broadcast: true,
+            multicast_v4: None,
+            multicast_v6: None,
+            multicast_v6_interface: 0,
+            seeds: Vec::new(),
+            gossip: true,
+            announce_interval: Duration::from_secs(ANNOUNCE_INTERVAL_SECS),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct DiscoveryDebugStats {
     pub socket_bound: bool,
     pub bind: Option<String>,
+    #[serde(default)]
+    pub bind_v6: Option<String>,
 
     pub sent_announces: u64,
     pub sent_queries: u64,
     pub received_announces: u64,
     pub received_queries: u64,
+    #[serde(default)]
+    pub sent_gossip: u64,
+    #[serde(default)]
+    pub received_gossip: u64,
+    /// Authenticated packets dropped as replayed or outside the clock skew.
+    #[serde(default)]
+    pub rejected_stale: u64,
 
     pub last_received_from: Option<String>,
     pub last_received_kind: Option<String>,
@@ -36,96 +110,136 @@ pub struct DiscoveryDebugStats {
 
 #[derive(Clone)]
 pub struct NetworkDiscovery {
-    node_id: String,
-    mode: ClientMode,
+    local: LocalNode,
     rt: Option<tokio::runtime::Handle>,
-    local_name: Arc<RwLock<String>>,
-    local_groups: Arc<RwLock<Vec<String>>>,
-    local_ollama_offer: Arc<RwLock<OllamaOfferStatus>>,
-    auth_key: Arc<RwLock<Option<Vec<u8>>>>,
-    socket: Option<Arc<UdpSocket>>,
+    transport: Option<Transport>,
     discovered_peers: Arc<RwLock<HashMap<String, PeerInfo>>>,
     debug: Arc<RwLock<DiscoveryDebugStats>>,
     last_received_at: Arc<RwLock<Option<Instant>>>,
     last_empty_log: Arc<RwLock<Instant>>,
 }
 
+/// What this node announces and the keys it checks others with, shared
+/// with the background tasks.
+#[derive(Clone)]
+struct LocalNode {
+    node_id: String,
+    mode: ClientMode,
+    port: u16,
+    name: Arc<RwLock<String>>,
+    groups: Arc<RwLock<Vec<String>>>,
+    offer: Arc<RwLock<OllamaOfferStatus>>,
+    auth_key: Arc<RwLock<Option<Vec<u8>>>>,
+    group_keys: Arc<RwLock<Vec<GroupKey>>>,
+    seal_offer: Arc<RwLock<bool>>,
+    seq: Arc<AtomicU64>,
+    replay: Arc<RwLock<HashMap<String, ReplayWindow>>>,
+}
+
+/// Sequence numbers seen from one sender, newest and the `REPLAY_WINDOW`
+/// before it.
+#[derive(Clone, Debug)]
+struct ReplayWindow {
+    newest: u64,
+    seen: u64,
+    last_seen: Instant,
+}
+
 #[derive(Clone, Debug)]
 struct PeerInfo {
     status: PeerStatus,
     last_seen: Instant,
+    /// Vouched for by the shared key or a group proof.
+    authenticated: bool,
+}
+
+/// The sockets discovery runs on and everyone it sends to.
+#[derive(Clone)]
+struct Transport {
+    v4: Arc<UdpSocket>,
+    /// Only bound for IPv6 multicast or seeds.
+    v6: Option<Arc<UdpSocket>>,
+    options: Arc<DiscoveryOptions>,
+    /// Addresses passed on by gossip, with when they were last mentioned.
+    learned: Arc<RwLock<HashMap<SocketAddr, Instant>>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct DiscoveryPacket {
+    #[serde(default = "legacy_version")]
+    version: u32,
     kind: DiscoveryPacketKind,
-    status: PeerStatus,
+    /// Sender's clock, unix milliseconds.
+    #[serde(default)]
+    sent_at_ms: u64,
+    /// Counts up with every packet the sender sends.
+    #[serde(default)]
+    seq: u64,
+    #[serde(default)]
+    nonce: String,
+    /// A [`PeerStatus`], kept as sent: the MACs cover these exact bytes, so
+    /// fields this build doesn't know about don't break them.
+    status: Box<RawValue>,
     #[serde(default)]
     mac: Option<String>,
+    /// One entry per keyed group the sender is in; the group ids themselves
+    /// are not sent.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    group_proofs: Vec<GroupProof>,
+    /// Peers the sender has seen, in `Peers` packets.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    peers: Vec<GossipPeer>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct GossipPeer {
+    id: String,
+    address: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct GroupProof {
+    /// Derived from the group's secret, so only members can tell which group
+    /// this is.
+    tag: String,
+    mac: String,
+    /// The offer, encrypted for the group, when the sender seals it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    offer: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 enum DiscoveryPacketKind {
     Announce,
     Query,
+    /// Gossip. Older builds can't parse the kind and drop the packet.
+    Peers,
 }
 
 impl NetworkDiscovery {
     pub async fn new() -> Self {
-        let rt = tokio::runtime::Handle::try_current().ok();
-        let node_id = uuid::Uuid::new_v4().to_string();
-        let local_name = Arc::new(RwLock::new(get_machine_name()));
-        let local_groups = Arc::new(RwLock::new(Vec::new()));
-        let local_ollama_offer = Arc::new(RwLock::new(OllamaOfferStatus {
-            enabled: false,
-            base_url: Some("http://localhost:11434".to_string()),
-            models: Vec::new(),
-            auth_required: false,
-            proxy_port: None,
-        }));
-
-        let auth_key: Arc<RwLock<Option<Vec<u8>>>> = Arc::new(RwLock::new(None));
+        Self::with_options(DiscoveryOptions::default()).await
+    }
 
+    pub async fn with_options(options: DiscoveryOptions) -> Self {
+        let rt = tokio::runtime::Handle::try_current().ok();
         let debug = Arc::new(RwLock::new(DiscoveryDebugStats::default()));
-        let socket = match UdpSocket::bind(format!("0.0.0.0:{DISCOVERY_PORT}")).await {
-            Ok(sock) => {
-                if let Err(e) = sock.set_broadcast(true) {
-                    tracing::warn!("Failed to enable UDP broadcast: {e}");
-                }
-                {
-                    let mut d = debug.write().await;
-                    d.socket_bound = true;
-                    d.bind = sock.local_addr().ok().map(|a| a.to_string());
-                }
-                Some(Arc::new(sock))
-            }
-            Err(e) => {
-                tracing::warn!("Network discovery disabled (bind 0.0.0.0:{DISCOVERY_PORT} failed): {e}");
-                {
-                    let mut d = debug.write().await;
-                    d.socket_bound = false;
-                }
-                None
-            }
-        };
+        let port = options.port;
+        let transport = Transport::bind(options, &debug).await;
+        let local = LocalNode::new(transport.as_ref().map(Transport::port).unwrap_or(port));
 
         let this = Self {
-            node_id,
-            mode: ClientMode::Master,
+            local,
             rt,
-            local_name,
-            local_groups,
-            local_ollama_offer,
-            auth_key,
-            socket: socket.clone(),
+            transport: transport.clone(),
             discovered_peers: Arc::new(RwLock::new(HashMap::new())),
             debug,
             last_received_at: Arc::new(RwLock::new(None)),
             last_empty_log: Arc::new(RwLock::new(Instant::now() - Duration::from_secs(EMPTY_LOG_EVERY_SECS))),
         };
 
-        if let Some(sock) = socket {
-            this.start_background(sock);
+        if let Some(transport) = transport {
+            this.start_background(transport);
         }
 
         this
@@ -147,19 +261,41 @@ impl NetworkDiscovery {
     }
 
     pub fn local_node_id(&self) -> &str {
-        &self.node_id
+        &self.local.node_id
     }
 
     pub async fn local_node_name(&self) -> String {
-        self.local_name.read().await.clone()
+        self.local.name.read().await.clone()
     }
 
     pub async fn set_local_node_name(&self, name: String) {
-        *self.local_name.write().await = name;
+        *self.local.name.write().await = name;
     }
 
+    /// Groups announced by id. Anyone on the LAN can read these and claim
+    /// them; use [`set_group_keys`](Self::set_group_keys) for groups that
+    /// should stay private.
     pub async fn set_local_groups(&self, groups: Vec<String>) {
-        *self.local_groups.write().await = groups;
+        *self.local.groups.write().await = groups;
+    }
+
+    /// Secrets of the keyed groups this node is in, by group id. Membership
+    /// of a keyed group is announced as a proof only its members can check,
+    /// and a peer only counts as a member once its proof checks out; naming
+    /// the group id in plain is not enough.
+    pub async fn set_group_keys(&self, secrets: BTreeMap<String, String>) {
+        let keys = secrets
+            .iter()
+            .filter_map(|(id, secret)| GroupKey::derive(id, secret))
+            .collect();
+        *self.local.group_keys.write().await = keys;
+    }
+
+    /// Send the offer (models, proxy port, ...) only encrypted to each keyed
+    /// group, so nodes outside them don't see it. Has no effect without
+    /// group keys.
+    pub async fn set_seal_offer(&self, seal: bool) {
+        *self.local.seal_offer.write().await = seal;
     }
 
     /// Set an optional shared key used to authenticate discovery packets.
@@ -169,7 +305,7 @@ impl NetworkDiscovery {
             .map(|s| s.trim().to_string())
             .filter(|s| !s.is_empty())
             .map(|s| s.into_bytes());
-        *self.auth_key.write().await = v;
+        *self.local.auth_key.write().await = v;
     }
 
     pub async fn clear_discovered_peers(&self) {
@@ -177,7 +313,7 @@ impl NetworkDiscovery {
     }
 
     pub async fn set_ollama_offer(&self, enabled: bool, models: Vec<String>, base_url: Option<String>) {
-        let mut offer = self.local_ollama_offer.write().await;
+        let mut offer = self.local.offer.write().await;
         offer.enabled = enabled;
         offer.models = models;
         if let Some(url) = base_url {
@@ -185,36 +321,64 @@ impl NetworkDiscovery {
         }
     }
 
+    /// Advertise that the offer is served by the share proxy on `proxy_port`
+    /// and whether callers must send the share key.
+    pub async fn set_offer_auth(&self, auth_required: bool, proxy_port: Option<u16>) {
+        let mut offer = self.local.offer.write().await;
+        offer.auth_required = auth_required;
+        offer.proxy_port = proxy_port;
+    }
+
+    /// Advertise how busy the share proxy is; `None` when there is none.
+    pub async fn set_offer_load(&self, load: Option<OfferLoad>) {
+        self.local.offer.write().await.load = load;
+    }
+
+    /// Advertise the fingerprint of the share proxy's TLS certificate.
+    pub async fn set_offer_tls(&self, fingerprint: Option<String>) {
+        self.local.offer.write().await.tls_fingerprint = fingerprint;
+    }
+
+    /// Announce to every broadcast and multicast group, seed and gossiped
+    /// address now rather than at the next interval.
     pub fn broadcast_presence(&self) {
-        let Some(sock) = self.socket.clone() else {
+        let Some(transport) = self.transport.clone() else {
             return;
         };
-        let node_id = self.node_id.clone();
-        let mode = self.mode.clone();
-        let local_name = self.local_name.clone();
-        let local_groups = self.local_groups.clone();
-        let local_offer = self.local_ollama_offer.clone();
-        let auth_key = self.auth_key.clone();
+        let local = self.local.clone();
         let debug = self.debug.clone();
         self.spawn(async move {
-            let status = build_local_status(&node_id, mode, &local_name, &local_groups, &local_offer, None).await;
-            let mut packet = DiscoveryPacket {
-                kind: DiscoveryPacketKind::Announce,
-                status,
-                mac: None,
-            };
-
-            if let Some(key) = auth_key.read().await.clone() {
-                packet.mac = compute_packet_mac(&key, &packet.kind, &packet.status);
-            }
-            if let Ok(data) = serde_json::to_vec(&packet) {
-                let _ = sock.send_to(&data, BROADCAST_ADDR).await;
+            if let Some(data) = local.encode(DiscoveryPacketKind::Announce, Vec::new()).await {
+                transport.send_all(&data, &transport.targets().await).await;
                 let mut d = debug.write().await;
                 d.sent_announces = d.sent_announces.saturating_add(1);
             }
         });
     }
 
+    /// Ask every node we can reach to announce itself now rather than at its
+    /// next interval.
+    pub fn query_peers(&self) {
+        let Some(transport) = self.transport.clone() else {
+            return;
+        };
+        let local = self.local.clone();
+        let debug = self.debug.clone();
+        self.spawn(async move {
+            if let Some(data) = local.encode(DiscoveryPacketKind::Query, Vec::new()).await {
+                transport.send_all(&data, &transport.targets().await).await;
+                let mut d = debug.write().await;
+                d.sent_queries = d.sent_queries.saturating_add(1);
+            }
+        });
+    }
+
+    /// The UDP port discovery runs on; the one the OS picked if
+    /// [`DiscoveryOptions::port`] was 0.
+    pub fn port(&self) -> u16 {
+        self.local.port
+    }
+
     pub async fn discover_peers(&self) -> Result<Vec<PeerStatus>, VgaError> {
         let peers = self.discovered_peers.read().await;
         let out: Vec<PeerStatus> = peers
@@ -229,7 +393,7 @@ impl NetworkDiscovery {
         if out.is_empty() {
             let mut last = self.last_empty_log.write().await;
             if last.elapsed().as_secs() >= EMPTY_LOG_EVERY_SECS {
-                tracing::info!("No peers discovered for {}", self.node_id);
+                tracing::info!("No peers discovered for {}", self.local.node_id);
                 *last = Instant::now();
             }
         }
@@ -237,189 +401,745 @@ impl NetworkDiscovery {
         Ok(out)
     }
 
-    fn start_background(&self, socket: Arc<UdpSocket>) {
-        let discovered = self.discovered_peers.clone();
-        let node_id = self.node_id.clone();
-        let mode = self.mode.clone();
-        let local_name = self.local_name.clone();
-        let local_groups = self.local_groups.clone();
-        let local_offer = self.local_ollama_offer.clone();
-        let auth_key_recv = self.auth_key.clone();
-        let debug_recv = self.debug.clone();
-        let last_received_at = self.last_received_at.clone();
+    fn start_background(&self, transport: Transport) {
+        // Receivers
+        self.spawn(self.clone().receive(transport.clone(), transport.v4.clone()));
+        if let Some(v6) = transport.v6.clone() {
+            self.spawn(self.clone().receive(transport.clone(), v6));
+        }
 
-        // Receiver
-        let socket_recv = socket.clone();
+        // Broadcaster
+        let this = self.clone();
+        let transport_send = transport.clone();
         self.spawn(async move {
-            let mut buf = [0u8; 64 * 1024];
+            let mut interval = tokio::time::interval(transport_send.options.announce_interval);
             loop {
-                let Ok((len, addr)) = socket_recv.recv_from(&mut buf).await else {
-                    continue;
-                };
+                interval.tick().await;
+                let unicast = transport_send.unicast_targets().await;
+                let mut targets = transport_send.group_targets();
+                targets.extend(unicast.iter().copied());
+                if let Some(data) = this.local.encode(DiscoveryPacketKind::Announce, Vec::new()).await {
+                    transport_send.send_all(&data, &targets).await;
+                    let mut d = this.debug.write().await;
+                    d.sent_announces = d.sent_announces.saturating_add(1);
+                }
+                // Seeds and gossiped peers may not hear from us otherwise, so
+                // they are asked to answer each time.
+                if !unicast.is_empty() {
+                    if let Some(data) = this.local.encode(DiscoveryPacketKind::Query, Vec::new()).await {
+                        transport_send.send_all(&data, &unicast).await;
+                        let mut d = this.debug.write().await;
+                        d.sent_queries = d.sent_queries.saturating_add(1);
+                    }
+                }
+                if transport_send.options.gossip {
+                    let peers = this.gossip().await;
+                    if peers.is_empty() {
+                        continue;
+                    }
+                    if let Some(data) = this.local.encode(DiscoveryPacketKind::Peers, peers).await {
+                        transport_send.send_all(&data, &targets).await;
+                        let mut d = this.debug.write().await;
+                        d.sent_gossip = d.sent_gossip.saturating_add(1);
+                    }
+                }
+            }
+        });
 
-                let Ok(packet) = serde_json::from_slice::<DiscoveryPacket>(&buf[..len]) else {
+        // Cleanup
+        let discovered_cleanup = self.discovered_peers.clone();
+        let replay_cleanup = self.local.replay.clone();
+        let learned_cleanup = transport.learned.clone();
+        self.spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(30));
+            loop {
+                interval.tick().await;
+                let mut peers = discovered_cleanup.write().await;
+                peers.retain(|_, v| v.last_seen.elapsed().as_secs() <= PEER_STALE_SECS);
+                drop(peers);
+                // Anything a forgotten sender sent is past the clock skew by now.
+                let mut replay = replay_cleanup.write().await;
+                replay.retain(|_, w| w.last_seen.elapsed().as_secs() <= PEER_STALE_SECS);
+                drop(replay);
+                let mut learned = learned_cleanup.write().await;
+                learned.retain(|_, mentioned| mentioned.elapsed().as_secs() <= PEER_STALE_SECS);
+            }
+        });
+
+        // Initial query (kickstart)
+        self.query_peers();
+    }
+
+    async fn receive(self, transport: Transport, socket: Arc<UdpSocket>) {
+        let mut buf = [0u8; 64 * 1024];
+        loop {
+            let Ok((len, addr)) = socket.recv_from(&mut buf).await else {
+                continue;
+            };
+
+            let Ok(packet) = serde_json::from_slice::<DiscoveryPacket>(&buf[..len]) else {
+                continue;
+            };
+
+            let kind = packet.kind.clone();
+            let gossip = packet.peers.clone();
+            let (status, authenticated) = match self.local.open(packet).await {
+                Ok(opened) => opened,
+                Err(Rejected::Stale) => {
+                    let mut d = self.debug.write().await;
+                    d.rejected_stale = d.rejected_stale.saturating_add(1);
                     continue;
+                }
+                Err(Rejected::Unauthenticated | Rejected::Ours) => continue,
+            };
+
+            *self.last_received_at.write().await = Some(Instant::now());
+            {
+                let mut d = self.debug.write().await;
+                let (counter, label) = match kind {
+                    DiscoveryPacketKind::Announce => (&mut d.received_announces, "announce"),
+                    DiscoveryPacketKind::Query => (&mut d.received_queries, "query"),
+                    DiscoveryPacketKind::Peers => (&mut d.received_gossip, "peers"),
                 };
+                *counter = counter.saturating_add(1);
+                d.last_received_from = Some(addr.to_string());
+                d.last_received_kind = Some(label.to_string());
+                d.last_received_age_ms = None;
+            }
 
-                // If auth is enabled, require a valid MAC.
-                if let Some(key) = auth_key_recv.read().await.clone() {
-                    if !verify_packet_mac(&key, &packet) {
+            match kind {
+                DiscoveryPacketKind::Announce => {
+                    let mut status = status;
+                    status.address = normalize_addr(status.address, addr);
+                    let mut peers = self.discovered_peers.write().await;
+                    if !peers.contains_key(&status.id) && peers.len() >= MAX_PEERS {
+                        // Avoid unbounded growth if someone floods spoofed node ids on the LAN.
+                        continue;
+                    }
+                    // Node ids are sent in plain, so anyone could announce under
+                    // one; only another authenticated packet may update it.
+                    if peers.get(&status.id).is_some_and(|p| p.authenticated && !authenticated) {
+                        continue;
+                    }
+                    peers.insert(
+                        status.id.clone(),
+                        PeerInfo {
+                            status,
+                            last_seen: Instant::now(),
+                            authenticated,
+                        },
+                    );
+                }
+                DiscoveryPacketKind::Query => {
+                    if let Some(data) = self.local.encode(DiscoveryPacketKind::Announce, Vec::new()).await {
+                        let _ = socket.send_to(&data, addr).await;
+                    }
+                    if !transport.options.gossip {
+                        continue;
+                    }
+                    let peers = self.gossip().await;
+                    if peers.is_empty() {
                         continue;
                     }
+                    if let Some(data) = self.local.encode(DiscoveryPacketKind::Peers, peers).await {
+                        let _ = socket.send_to(&data, addr).await;
+                    }
+                }
+                DiscoveryPacketKind::Peers => {
+                    // With keys set, only peers that hold them get to point us
+                    // at other hosts.
+                    if transport.options.gossip && (authenticated || !self.local.has_keys().await) {
+                        self.learn(&transport, gossip, addr, authenticated).await;
+                    }
                 }
+            }
+        }
+    }
 
-                if packet.status.id == node_id {
+    /// Peers to pass on, most recently seen first.
+    async fn gossip(&self) -> Vec<GossipPeer> {
+        let peers = self.discovered_peers.read().await;
+        let mut seen: Vec<&PeerInfo> = peers.values().collect();
+        seen.sort_by_key(|p| p.last_seen.elapsed());
+        seen.into_iter()
+            .take(MAX_GOSSIP_PEERS)
+            .map(|p| GossipPeer {
+                id: p.status.id.clone(),
+                address: p.status.address.clone(),
+            })
+            .collect()
+    }
+
+    /// Announce to the addresses gossip passed on from now on, and ask the
+    /// ones we haven't heard from to announce themselves right away.
+    async fn learn(&self, transport: &Transport, gossip: Vec<GossipPeer>, sender: SocketAddr, authenticated: bool) {
+        let mut unknown = Vec::new();
+        {
+            let known = self.discovered_peers.read().await;
+            let mut learned = transport.learned.write().await;
+            for peer in gossip.into_iter().take(MAX_GOSSIP_PEERS) {
+                if peer.id == self.local.node_id {
+                    continue;
+                }
+                let Ok(addr) = peer.address.parse::<SocketAddr>() else {
+                    continue;
+                };
+                if addr.ip().is_unspecified() || addr.port() == 0 || !may_learn(addr, sender, authenticated) {
                     continue;
                 }
+                if !learned.contains_key(&addr) && learned.len() >= MAX_PEERS {
+                    continue;
+                }
+                learned.insert(addr, Instant::now());
+                if !known.contains_key(&peer.id) {
+                    unknown.push(addr);
+                }
+            }
+        }
+        if unknown.is_empty() {
+            return;
+        }
+        if let Some(data) = self.local.encode(DiscoveryPacketKind::Query, Vec::new()).await {
+            transport.send_all(&data, &unknown).await;
+            let mut d = self.debug.write().await;
+            d.sent_queries = d.sent_queries.saturating_add(1);
+        }
+    }
+}
 
-                match packet.kind {
-                    DiscoveryPacketKind::Announce => {
-                        *last_received_at.write().await = Some(Instant::now());
-                        {
-                            let mut d = debug_recv.write().await;
-                            d.received_announces = d.received_announces.saturating_add(1);
-                            d.last_received_from = Some(addr.to_string());
-                            d.last_received_kind = Some("announce".to_string());
-                            d.last_received_age_ms = None;
-                        }
-                        let mut status = packet.status;
-                        status.address = normalize_addr(status.address, addr);
-                        let mut peers = discovered.write().await;
-                        if !peers.contains_key(&status.id) && peers.len() >= MAX_PEERS {
-                            // Avoid unbounded growth if someone floods spoofed node ids on the LAN.
-                            continue;
-                        }
-                        peers.insert(
-                            status.id.clone(),
-                            PeerInfo {
-                                status,
-                                last_seen: Instant::now(),
-                            },
-                        );
-                    }
-                    DiscoveryPacketKind::Query => {
-                        *last_received_at.write().await = Some(Instant::now());
-                        {
-                            let mut d = debug_recv.write().await;
-                            d.received_queries = d.received_queries.saturating_add(1);
-                            d.last_received_from = Some(addr.to_string());
-                            d.last_received_kind = Some("query".to_string());
-                            d.last_received_age_ms = None;
-                        }
-                        let status = build_local_status(&node_id, mode.clone(), &local_name, &local_groups, &local_offer, Some(addr)).await;
-                        let mut response = DiscoveryPacket {
-                            kind: DiscoveryPacketKind::Announce,
-                            status,
-                            mac: None,
-                        };
-                        if let Some(key) = auth_key_recv.read().await.clone() {
-                            response.mac = compute_packet_mac(&key, &response.kind, &response.status);
-                        }
-                        if let Ok(data) = serde_json::to_vec(&response) {
-                            let _ = socket_recv.send_to(&data, addr).await;
+impl Transport {
+    async fn bind(options: DiscoveryOptions, debug: &RwLock<DiscoveryDebugStats>) -> Option<Self> {
+        let v4 = match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, options.port)).await {
+            Ok(sock) => sock,
+            Err(e) => {
+                tracing::warn!("Network discovery disabled (bind 0.0.0.0:{} failed): {e}", options.port);
+                debug.write().await.socket_bound = false;
+                return None;
+            }
+        };
+        if let Err(e) = v4.set_broadcast(true) {
+            tracing::warn!("Failed to enable UDP broadcast: {e}");
+        }
+        if let Some(group) = options.multicast_v4 {
+            if let Err(e) = v4.join_multicast_v4(group, Ipv4Addr::UNSPECIFIED) {
+                tracing::warn!("Failed to join multicast group {group}: {e}");
+            }
+        }
+
+        // With port 0 the IPv6 socket takes the port the OS gave the IPv4 one.
+        let port = v4.local_addr().map(|a| a.port()).unwrap_or(options.port);
+        let v6 = if options.multicast_v6.is_some() || !options.seeds.is_empty() {
+            match bind_v6(port, options.multicast_v6_interface) {
+                Ok(sock) => {
+                    if let Some(group) = options.multicast_v6 {
+                        if let Err(e) = sock.join_multicast_v6(&group, options.multicast_v6_interface) {
+                            tracing::warn!("Failed to join multicast group {group}: {e}");
                         }
                     }
+                    Some(Arc::new(sock))
+                }
+                Err(e) => {
+                    tracing::warn!("IPv6 discovery disabled (bind [::]:{port} failed): {e}");
+                    None
                 }
             }
-        });
+        } else {
+            None
+        };
 
-        // Broadcaster
-        let socket_send = socket.clone();
-        let node_id_send = self.node_id.clone();
-        let mode_send = self.mode.clone();
-        let local_name_send = self.local_name.clone();
-        let local_groups_send = self.local_groups.clone();
-        let local_offer_send = self.local_ollama_offer.clone();
-        let auth_key_send = self.auth_key.clone();
-        let debug_send = self.debug.clone();
-        self.spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(ANNOUNCE_INTERVAL_SECS));
-            loop {
-                interval.tick().await;
-                let status = build_local_status(
-                    &node_id_send,
-                    mode_send.clone(),
-                    &local_name_send,
-                    &local_groups_send,
-                    &local_offer_send,
-                    None,
-                )
-                .await;
-                let mut packet = DiscoveryPacket {
-                    kind: DiscoveryPacketKind::Announce,
-                    status,
-                    mac: None,
+        {
+            let mut d = debug.write().await;
+            d.socket_bound = true;
+            d.bind = v4.local_addr().ok().map(|a| a.to_string());
+            d.bind_v6 = v6.as_ref().and_then(|s| s.local_addr().ok()).map(|a| a.to_string());
+        }
+        Some(Self {
+            v4: Arc::new(v4),
+            v6,
+            options: Arc::new(options),
+            learned: Arc::new(RwLock::new(HashMap::new())),
+        })
+    }
+
+    fn port(&self) -> u16 {
+        self.v4.local_addr().map(|a| a.port()).unwrap_or(self.options.port)
+    }
+
+    /// Broadcast and multicast destinations.
+    fn group_targets(&self) -> Vec<SocketAddr> {
+        let port = self.port();
+        let mut out = Vec::new();
+        if self.options.broadcast {
+            out.push(SocketAddr::from((Ipv4Addr::BROADCAST, port)));
+        }
+        if let Some(group) = self.options.multicast_v4 {
+            out.push(SocketAddr::from((group, port)));
+        }
+        if let (Some(group), Some(_)) = (self.options.multicast_v6, &self.v6) {
+            out.push(SocketAddr::V6(SocketAddrV6::new(
+                group,
+                port,
+                0,
+                self.options.multicast_v6_interface,
+            )));
+        }
+        out
+    }
+
+    /// Seeds, resolved afresh each time so a rendezvous can move, and
+    /// addresses passed on by gossip.
+    async fn unicast_targets(&self) -> Vec<SocketAddr> {
+        let mut out = Vec::new();
+        for seed in &self.options.seeds {
+            let seed = seed_with_port(seed, self.options.port);
+            match tokio::net::lookup_host(seed.as_str()).await {
+                Ok(addrs) => out.extend(addrs),
+                Err(e) => tracing::debug!("Discovery seed {seed} did not resolve: {e}"),
+            };
+        }
+        out.extend(self.learned.read().await.keys().copied());
+        out.sort();
+        out.dedup();
+        out
+    }
+
+    async fn targets(&self) -> Vec<SocketAddr> {
+        let mut out = self.group_targets();
+        out.extend(self.unicast_targets().await);
+        out
+    }
+
+    async fn send_all(&self, data: &[u8], targets: &[SocketAddr]) {
+        for addr in targets {
+            let sock = match addr {
+                SocketAddr::V4(_) => &self.v4,
+                SocketAddr::V6(_) => match &self.v6 {
+                    Some(sock) => sock,
+                    None => continue,
+                },
+            };
+            if let Err(e) = sock.send_to(data, addr).await {
+                tracing::debug!("Discovery send to {addr} failed: {e}");
+            }
+        }
+    }
+}
+
+/// IPv6 only, so it can share its port with the IPv4 socket.
+fn bind_v6(port: u16, multicast_interface: u32) -> std::io::Result<UdpSocket> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let sock = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP))?;
+    sock.set_only_v6(true)?;
+    sock.set_nonblocking(true)?;
+    if multicast_interface != 0 {
+        sock.set_multicast_if_v6(multicast_interface)?;
+    }
+    sock.bind(&SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)).into())?;
+    UdpSocket::from_std(sock.into())
+}
+
+/// Whether gossip from `sender` may point us at `addr`. Unauthenticated
+/// gossip could come from anyone, and following it would have every node
+/// announce its offer to, and flood, whatever host it names; so it is only
+/// taken from and about hosts on private, link-local or loopback addresses.
+fn may_learn(addr: SocketAddr, sender: SocketAddr, authenticated: bool) -> bool {
+    authenticated || (is_local_scope(sender.ip()) && is_local_scope(addr.ip()))
+}
+
+fn is_local_scope(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_private() || ip.is_link_local() || ip.is_loopback(),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_local_scope(IpAddr::V4(ip)),
+            None => ip.is_unique_local() || ip.is_unicast_link_local() || ip.is_loopback(),
+        },
+    }
+}
+
+/// A seed without a port gets discovery's own.
+fn seed_with_port(seed: &str, port: u16) -> String {
+    let seed = seed.trim();
+    if seed.parse::<SocketAddr>().is_ok() {
+        return seed.to_string();
+    }
+    match seed.parse::<Ipv6Addr>() {
+        Ok(ip) => SocketAddr::from((ip, port)).to_string(),
+        Err(_) if seed.contains(':') => seed.to_string(),
+        Err(_) => format!("{seed}:{port}"),
+    }
+}
+
+impl LocalNode {
+    fn new(port: u16) -> Self {
+        Self {
+            node_id: uuid::Uuid::new_v4().to_string(),
+            mode: ClientMode::Master,
+            port,
+            name: Arc::new(RwLock::new(get_machine_name())),
+            groups: Arc::new(RwLock::new(Vec::new())),
+            offer: Arc::new(RwLock::new(OllamaOfferStatus {
+                enabled: false,
+                base_url: Some("http://localhost:11434".to_string()),
+                models: Vec::new(),
+                auth_required: false,
+                proxy_port: None,
+                load: None,
+                tls_fingerprint: None,
+            })),
+            auth_key: Arc::new(RwLock::new(None)),
+            group_keys: Arc::new(RwLock::new(Vec::new())),
+            seal_offer: Arc::new(RwLock::new(false)),
+            seq: Arc::new(AtomicU64::new(0)),
+            replay: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    async fn status(&self) -> PeerStatus {
+        let name = self.name.read().await.clone();
+        let groups = self.groups.read().await.clone();
+        let offer: OllamaOfferStatus = self.offer.read().await.clone();
+
+        PeerStatus {
+            id: self.node_id.clone(),
+            address: format!("0.0.0.0:{}", self.port),
+            mode: self.mode.clone(),
+            latency: None,
+            name: Some(name),
+            groups,
+            ollama: Some(offer),
+        }
+    }
+
+    async fn encode(&self, kind: DiscoveryPacketKind, peers: Vec<GossipPeer>) -> Option<Vec<u8>> {
+        serde_json::to_vec(&self.packet(kind, peers).await?).ok()
+    }
+
+    async fn packet(&self, kind: DiscoveryPacketKind, peers: Vec<GossipPeer>) -> Option<DiscoveryPacket> {
+        let mut status = self.status().await;
+        let keys = self.group_keys.read().await.clone();
+        // Keyed groups are only ever announced as proofs.
+        status.groups.retain(|g| !keys.iter().any(|k| k.id == *g));
+        let sealed = if *self.seal_offer.read().await && !keys.is_empty() {
+            status.ollama.take()
+        } else {
+            None
+        };
+
+        let mut nonce = [0u8; 16];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        let mut packet = DiscoveryPacket {
+            version: PACKET_VERSION,
+            kind,
+            sent_at_ms: unix_millis(),
+            seq: self.seq.fetch_add(1, Ordering::Relaxed),
+            nonce: hex::encode(nonce),
+            status: serde_json::value::to_raw_value(&status).ok()?,
+            mac: None,
+            group_proofs: Vec::new(),
+            peers,
+        };
+        let signed = signed_bytes(&packet);
+        packet.group_proofs = keys
+            .iter()
+            .filter_map(|key| key.prove(&signed, &status.id, sealed.as_ref()))
+            .collect();
+        if let Some(key) = self.auth_key.read().await.as_deref() {
+            packet.mac = compute_packet_mac(key, &signed);
+        }
+        Some(packet)
+    }
+
+    /// The sender's status as this node may see it, with the keyed groups it
+    /// proved and, if sealed, the offer it encrypted for them, and whether
+    /// the packet was authenticated (shared key or a group proof). Refused
+    /// if it fails the shared key, and if it is authenticated but was seen
+    /// before or sent too long ago.
+    async fn open(&self, packet: DiscoveryPacket) -> Result<(PeerStatus, bool), Rejected> {
+        let mut status: PeerStatus = serde_json::from_str(packet.status.get()).map_err(|_| Rejected::Unauthenticated)?;
+        if status.id == self.node_id {
+            return Err(Rejected::Ours);
+        }
+        // Version 1 packets can't be checked for replays, so they're only
+        // good for what nobody vouches for anyway.
+        let signed = (packet.version >= PACKET_VERSION).then(|| signed_bytes(&packet));
+        let mut authenticated = false;
+        if let Some(key) = self.auth_key.read().await.as_deref() {
+            match (&signed, &packet.mac) {
+                (Some(signed), Some(given)) if verify_packet_mac(key, signed, given) => authenticated = true,
+                _ => return Err(Rejected::Unauthenticated),
+            }
+        }
+
+        let keys = self.group_keys.read().await;
+        // Knowing a keyed group's id proves nothing.
+        status.groups.retain(|g| !keys.iter().any(|k| k.id == *g));
+        if let Some(signed) = &signed {
+            for proof in &packet.group_proofs {
+                let Some(key) = keys.iter().find(|k| k.tag == proof.tag) else {
+                    continue;
                 };
-                if let Some(key) = auth_key_send.read().await.clone() {
-                    packet.mac = compute_packet_mac(&key, &packet.kind, &packet.status);
+                if !key.verify(signed, proof) {
+                    continue;
                 }
-                if let Ok(data) = serde_json::to_vec(&packet) {
-                    let _ = socket_send.send_to(&data, BROADCAST_ADDR).await;
-                    let mut d = debug_send.write().await;
-                    d.sent_announces = d.sent_announces.saturating_add(1);
+                authenticated = true;
+                if !status.groups.contains(&key.id) {
+                    status.groups.push(key.id.clone());
+                }
+                if status.ollama.is_none() {
+                    status.ollama = proof.offer.as_deref().and_then(|sealed| key.unseal(sealed, &status.id));
                 }
             }
-        });
+        }
+        drop(keys);
 
-        // Cleanup
-        let discovered_cleanup = self.discovered_peers.clone();
-        self.spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(30));
-            loop {
-                interval.tick().await;
-                let mut peers = discovered_cleanup.write().await;
-                peers.retain(|_, v| v.last_seen.elapsed().as_secs() <= PEER_STALE_SECS);
+        if authenticated {
+            let skew = unix_millis().abs_diff(packet.sent_at_ms);
+            if skew > MAX_CLOCK_SKEW_SECS * 1000 || !self.fresh(&status.id, packet.seq).await {
+                return Err(Rejected::Stale);
             }
-        });
+        }
+        Ok((status, authenticated))
+    }
 
-        // Initial query (kickstart)
-        let socket_query = socket;
-        let node_id_q = self.node_id.clone();
-        let mode_q = self.mode.clone();
-        let local_name_q = self.local_name.clone();
-        let local_groups_q = self.local_groups.clone();
-        let local_offer_q = self.local_ollama_offer.clone();
-        let auth_key_q = self.auth_key.clone();
-        let debug_query = self.debug.clone();
-        self.spawn(async move {
-            let status = build_local_status(&node_id_q, mode_q, &local_name_q, &local_groups_q, &local_offer_q, None).await;
-            let mut packet = DiscoveryPacket {
-                kind: DiscoveryPacketKind::Query,
-                status,
-                mac: None,
+    async fn has_keys(&self) -> bool {
+        self.auth_key.read().await.is_some() || !self.group_keys.read().await.is_empty()
+    }
+
+    /// Record `seq` from `node_id`; false if it was seen before or is too old
+    /// to tell.
+    async fn fresh(&self, node_id: &str, seq: u64) -> bool {
+        let mut replay = self.replay.write().await;
+        if !replay.contains_key(node_id) && replay.len() >= MAX_PEERS {
+            return false;
+        }
+        let Some(window) = replay.get_mut(node_id) else {
+            let window = ReplayWindow {
+                newest: seq,
+                seen: 1,
+                last_seen: Instant::now(),
             };
-            if let Some(key) = auth_key_q.read().await.clone() {
-                packet.mac = compute_packet_mac(&key, &packet.kind, &packet.status);
+            replay.insert(node_id.to_string(), window);
+            return true;
+        };
+        if seq > window.newest {
+            let shift = seq - window.newest;
+            window.seen = if shift >= REPLAY_WINDOW { 0 } else { window.seen << shift };
+            window.seen |= 1;
+            window.newest = seq;
+        } else {
+            let age = window.newest - seq;
+            if age >= REPLAY_WINDOW || window.seen & (1 << age) != 0 {
+                return false;
             }
-            if let Ok(data) = serde_json::to_vec(&packet) {
-                let _ = socket_query.send_to(&data, BROADCAST_ADDR).await;
-                let mut d = debug_query.write().await;
-                d.sent_queries = d.sent_queries.saturating_add(1);
+            window.seen |= 1 << age;
+        }
+        window.last_seen = Instant::now();
+        true
+    }
+}
+
+/// Keys derived from a group's secret. The secret itself is never sent.
+#[derive(Clone)]
+struct GroupKey {
+    id: String,
+    tag: String,
+    mac_key: [u8; 32],
+    seal_key: [u8; 32],
+}
+
+impl GroupKey {
+    fn derive(id: &str, secret: &str) -> Option<Self> {
+        let (id, secret) = (id.trim(), secret.trim());
+        if id.is_empty() || secret.is_empty() {
+            return None;
+        }
+        let derive = |label: &str| -> Option<[u8; 32]> {
+            let mut mac = <HmacSha256 as Mac>::new_from_slice(secret.as_bytes()).ok()?;
+            mac.update(label.as_bytes());
+            mac.update(id.as_bytes());
+            Some(mac.finalize().into_bytes().into())
+        };
+        Some(Self {
+            id: id.to_string(),
+            tag: hex::encode(&derive("vas-discovery group tag")?[..16]),
+            mac_key: derive("vas-discovery group mac")?,
+            seal_key: derive("vas-discovery group seal")?,
+        })
+    }
+
+    fn prove(&self, signed: &[u8], node_id: &str, offer: Option<&OllamaOfferStatus>) -> Option<GroupProof> {
+        let offer = match offer {
+            Some(offer) => Some(self.seal(offer, node_id)?),
+            None => None,
+        };
+        let mac = self.mac(signed, offer.as_deref())?;
+        Some(GroupProof {
+            tag: self.tag.clone(),
+            mac,
+            offer,
+        })
+    }
+
+    fn verify(&self, signed: &[u8], proof: &GroupProof) -> bool {
+        let Some(expected) = self.mac(signed, proof.offer.as_deref()) else {
+            return false;
+        };
+        constant_time_eq(proof.mac.as_bytes(), expected.as_bytes())
+    }
+
+    fn mac(&self, signed: &[u8], offer: Option<&str>) -> Option<String> {
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(&self.mac_key).ok()?;
+        mac.update(signed);
+        mac.update(offer.unwrap_or_default().as_bytes());
+        Some(hex::encode(mac.finalize().into_bytes()))
+    }
+
+    /// AES-256-GCM over the offer's JSON, hex of `nonce || ciphertext`. The
+    /// sender's node id is the associated data, so a sealed offer can't be
+    /// passed off as another node's.
+    fn seal(&self, offer: &OllamaOfferStatus, node_id: &str) -> Option<String> {
+        let plaintext = serde_json::to_vec(offer).ok()?;
+        let cipher = Aes256Gcm::new_from_slice(&self.seal_key).ok()?;
+        let mut nonce = [0u8; 12];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        let ciphertext = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &plaintext,
+                    aad: node_id.as_bytes(),
+                },
+            )
+            .ok()?;
+        Some(hex::encode([nonce.as_slice(), &ciphertext].concat()))
+    }
+
+    fn unseal(&self, sealed: &str, node_id: &str) -> Option<OllamaOfferStatus> {
+        let payload = hex::decode(sealed).ok()?;
+        if payload.len() < 12 {
+            return None;
+        }
+        let (nonce, ciphertext) = payload.split_at(12);
+        let cipher = Aes256Gcm::new_from_slice(&self.seal_key).ok()?;
+        let plaintext = cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: node_id.as_bytes(),
+                },
+            )
+            .ok()?;
+        serde_json::from_slice(&plaintext).ok()
+    }
+}
+
+/// A new random group secret.
+pub fn generate_group_secret() -> String {
+    let mut secret = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+    hex::encode(secret)
+}
+
+/// What a member hands to someone joining a keyed group: `<group id>:<secret>`.
+pub fn group_invite(group_id: &str, secret: &str) -> String {
+    format!("{}:{}", group_id.trim(), secret.trim())
+}
+
+/// Split an invite into group id and secret. A bare group id (an open
+/// group) has no secret.
+pub fn parse_group_invite(invite: &str) -> (String, Option<String>) {
+    match invite.trim().split_once(':') {
+        Some((id, secret)) if !secret.trim().is_empty() => (id.trim().to_string(), Some(secret.trim().to_string())),
+        Some((id, _)) => (id.trim().to_string(), None),
+        None => (invite.trim().to_string(), None),
+    }
+}
+
+/// Read group secrets from a file with one invite per line; blank lines and
+/// lines starting with `#` are skipped.
+pub fn load_group_keys_file(path: &std::path::Path) -> Result<BTreeMap<String, String>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let mut keys = BTreeMap::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_group_invite(line) {
+            (id, Some(secret)) if !id.is_empty() => {
+                keys.insert(id, secret);
             }
-        });
+            _ => {
+                return Err(format!(
+                    "{}:{}: expected <group id>:<secret>",
+                    path.display(),
+                    n + 1
+                ))
+            }
+        }
     }
+    Ok(keys)
+}
+
+fn legacy_version() -> u32 {
+    1
+}
+
+/// Why [`LocalNode::open`] refused a packet.
+#[derive(Debug)]
+enum Rejected {
+    /// Our own broadcast, looped back.
+    Ours,
+    Unauthenticated,
+    Stale,
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
 }
 
 fn kind_byte(kind: &DiscoveryPacketKind) -> u8 {
     match kind {
         DiscoveryPacketKind::Announce => 1,
         DiscoveryPacketKind::Query => 2,
+        DiscoveryPacketKind::Peers => 3,
     }
 }
 
-fn compute_packet_mac(key: &[u8], kind: &DiscoveryPacketKind, status: &PeerStatus) -> Option<String> {
-    let mut mac = HmacSha256::new_from_slice(key).ok()?;
-    mac.update(&[kind_byte(kind)]);
-    let status_bytes = serde_json::to_vec(status).ok()?;
-    mac.update(&status_bytes);
+/// What the MACs cover: the envelope header, the status as sent and any
+/// gossiped peers.
+fn signed_bytes(packet: &DiscoveryPacket) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&packet.version.to_be_bytes());
+    out.push(kind_byte(&packet.kind));
+    out.extend_from_slice(&packet.sent_at_ms.to_be_bytes());
+    out.extend_from_slice(&packet.seq.to_be_bytes());
+    out.extend_from_slice(&(packet.nonce.len() as u32).to_be_bytes());
+    out.extend_from_slice(packet.nonce.as_bytes());
+    out.extend_from_slice(packet.status.get().as_bytes());
+    // Only gossip carries peers, so announces sign the same bytes as before.
+    for peer in &packet.peers {
+        for field in [&peer.id, &peer.address] {
+            out.extend_from_slice(&(field.len() as u32).to_be_bytes());
+            out.extend_from_slice(field.as_bytes());
+        }
+    }
+    out
+}
+
+fn compute_packet_mac(key: &[u8], signed: &[u8]) -> Option<String> {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key).ok()?;
+    mac.update(signed);
     Some(hex::encode(mac.finalize().into_bytes()))
 }
 
-fn verify_packet_mac(key: &[u8], packet: &DiscoveryPacket) -> bool {
-    let Some(given) = &packet.mac else {
-        return false;
-    };
-    let Some(expected) = compute_packet_mac(key, &packet.kind, &packet.status) else {
+fn verify_packet_mac(key: &[u8], signed: &[u8], given: &str) -> bool {
+    let Some(expected) = compute_packet_mac(key, signed) else {
         return false;
     };
     constant_time_eq(given.as_bytes(), expected.as_bytes())
@@ -436,36 +1156,12 @@ fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
     out == 0
 }
 
-async fn build_local_status(
-    node_id: &str,
-    mode: ClientMode,
-    local_name: &Arc<RwLock<String>>,
-    local_groups: &Arc<RwLock<Vec<String>>>,
-    local_offer: &Arc<RwLock<OllamaOfferStatus>>,
-    reply_to: Option<SocketAddr>,
-) -> PeerStatus {
-    let name = local_name.read().await.clone();
-    let groups = local_groups.read().await.clone();
-    let offer: OllamaOfferStatus = local_offer.read().await.clone();
-
-    PeerStatus {
-        id: node_id.to_string(),
-        address: reply_to
-            .map(|a| a.to_string())
-            .unwrap_or_else(|| format!("0.0.0.0:{DISCOVERY_PORT}")),
-        mode,
-        latency: None,
-        name: Some(name),
-        groups,
-        ollama: Some(offer),
-    }
-}
-
 fn normalize_addr(payload_addr: String, recv_addr: SocketAddr) -> String {
     // Treat the UDP sender IP as the source of truth, otherwise a malicious host can
     // broadcast a packet claiming to be at a different LAN address.
+    // Discovery sends from the port it listens on, so that's where to reach the sender.
     let recv_ip = recv_addr.ip();
-    let recv_norm = format!("{}:{}", recv_ip, DISCOVERY_PORT);
+    let recv_norm = recv_addr.to_string();
 
     let p = payload_addr.trim();
     if p.is_empty() || p.starts_with("0.0.0.0") {
@@ -485,4 +1181,298 @@ fn get_machine_name() -> String {
         .or_else(|_| std::env::var("USER"))
         .or_else(|_| std::env::var("USERNAME"))
         .unwrap_or_else(|_| "vas-node".to_string())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().expect("socket address")
+    }
+
+    fn node() -> LocalNode {
+        LocalNode::new(DISCOVERY_PORT)
+    }
+
+    async fn keyed(key: &str) -> LocalNode {
+        let node = node();
+        *node.auth_key.write().await = Some(key.as_bytes().to_vec());
+        node
+    }
+
+    /// Sign `packet` again after editing its header, as a key holder could.
+    fn resign(packet: &mut DiscoveryPacket, key: &str) {
+        packet.mac = compute_packet_mac(key.as_bytes(), &signed_bytes(packet));
+    }
+
+    #[tokio::test]
+    async fn duplicate_seq_is_refused() {
+        let node = node();
+        assert!(node.fresh("a", 5).await);
+        assert!(!node.fresh("a", 5).await);
+        // Windows are per sender.
+        assert!(node.fresh("b", 5).await);
+    }
+
+    #[tokio::test]
+    async fn out_of_order_seqs_within_the_window_are_accepted_once() {
+        let node = node();
+        for seq in [10, 7, 9, 8] {
+            assert!(node.fresh("a", seq).await, "{seq}");
+        }
+        for seq in [7, 8, 9, 10] {
+            assert!(!node.fresh("a", seq).await, "{seq}");
+        }
+    }
+
+    #[tokio::test]
+    async fn seqs_older_than_the_window_are_refused() {
+        let node = node();
+        assert!(node.fresh("a", 100).await);
+        assert!(!node.fresh("a", 100 - REPLAY_WINDOW).await);
+        assert!(node.fresh("a", 100 - REPLAY_WINDOW + 1).await);
+    }
+
+    #[tokio::test]
+    async fn the_window_keeps_what_it_saw_as_it_moves() {
+        let node = node();
+        assert!(node.fresh("a", 0).await);
+        assert!(node.fresh("a", REPLAY_WINDOW - 1).await);
+        assert!(!node.fresh("a", 0).await);
+        assert!(node.fresh("a", 1).await);
+    }
+
+    #[tokio::test]
+    async fn a_jump_past_the_window_starts_it_afresh() {
+        let node = node();
+        assert!(node.fresh("a", 5).await);
+        assert!(node.fresh("a", 6).await);
+        let newest = 6 + REPLAY_WINDOW;
+        assert!(node.fresh("a", newest).await);
+        assert!(!node.fresh("a", 6).await);
+        assert!(node.fresh("a", 7).await);
+        assert!(node.fresh("a", newest - 1).await);
+        assert!(!node.fresh("a", newest).await);
+        assert!(node.fresh("a", u64::MAX).await);
+    }
+
+    #[tokio::test]
+    async fn a_replayed_packet_is_refused() {
+        let (sender, receiver) = (keyed("k").await, keyed("k").await);
+        let packet = sender.packet(DiscoveryPacketKind::Announce, Vec::new()).await.expect("packet");
+        let (status, authenticated) = receiver.open(packet.clone()).await.expect("first copy");
+        assert_eq!(status.id, sender.node_id);
+        assert!(authenticated);
+        assert!(matches!(receiver.open(packet).await, Err(Rejected::Stale)));
+    }
+
+    #[tokio::test]
+    async fn packets_outside_the_clock_skew_are_refused() {
+        let (sender, receiver) = (keyed("k").await, keyed("k").await);
+        let skew_ms = MAX_CLOCK_SKEW_SECS * 1000;
+        let cases = [
+            (unix_millis() - skew_ms + 5_000, true),
+            (unix_millis() + skew_ms - 5_000, true),
+            (unix_millis() - skew_ms - 5_000, false),
+            (unix_millis() + skew_ms + 5_000, false),
+        ];
+        for (sent_at_ms, accepted) in cases {
+            let mut packet = sender.packet(DiscoveryPacketKind::Announce, Vec::new()).await.expect("packet");
+            packet.sent_at_ms = sent_at_ms;
+            resign(&mut packet, "k");
+            let opened = receiver.open(packet).await;
+            if accepted {
+                assert!(opened.is_ok(), "{sent_at_ms}: {opened:?}");
+            } else {
+                assert!(matches!(opened, Err(Rejected::Stale)), "{sent_at_ms}: {opened:?}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn an_edited_header_fails_the_mac() {
+        let (sender, receiver) = (keyed("k").await, keyed("k").await);
+        let mut packet = sender.packet(DiscoveryPacketKind::Announce, Vec::new()).await.expect("packet");
+        packet.seq += 1;
+        assert!(matches!(receiver.open(packet).await, Err(Rejected::Unauthenticated)));
+    }
+
+    #[tokio::test]
+    async fn version_1_packets_are_refused_under_a_shared_key() {
+        let (sender, receiver) = (keyed("k").await, keyed("k").await);
+        let mut packet = sender.packet(DiscoveryPacketKind::Announce, Vec::new()).await.expect("packet");
+        packet.version = 1;
+        resign(&mut packet, "k");
+        assert!(matches!(receiver.open(packet).await, Err(Rejected::Unauthenticated)));
+    }
+
+    #[tokio::test]
+    async fn version_1_packets_still_parse_without_keys() {
+        let legacy = r#"{"kind":"Announce","status":{"id":"old","address":"0.0.0.0:45555","mode":"Master","latency":null,"groups":["lab"]},"mac":null}"#;
+        let packet: DiscoveryPacket = serde_json::from_str(legacy).expect("legacy packet");
+        assert_eq!(packet.version, 1);
+
+        let receiver = node();
+        let (status, authenticated) = receiver.open(packet.clone()).await.expect("opened");
+        assert_eq!(status.id, "old");
+        assert_eq!(status.groups, ["lab"]);
+        assert!(!authenticated);
+        // Nothing vouches for it, so there is nothing to check for replays.
+        assert!(receiver.open(packet).await.is_ok());
+    }
+
+    async fn in_groups(groups: &[&str], keys: &[(&str, &str)]) -> LocalNode {
+        let node = node();
+        *node.groups.write().await = groups.iter().map(|g| g.to_string()).collect();
+        *node.group_keys.write().await = keys
+            .iter()
+            .map(|(id, secret)| GroupKey::derive(id, secret).expect("group key"))
+            .collect();
+        node
+    }
+
+    async fn sharing(node: &LocalNode) {
+        let mut offer = node.offer.write().await;
+        offer.enabled = true;
+        offer.models = vec!["llama3".to_string()];
+        offer.proxy_port = Some(11435);
+    }
+
+    #[tokio::test]
+    async fn keyed_groups_are_only_sent_as_proofs() {
+        let member = in_groups(&["team", "lab"], &[("team", "s3cret")]).await;
+        let packet = member.packet(DiscoveryPacketKind::Announce, Vec::new()).await.expect("packet");
+        let wire = serde_json::to_string(&packet).expect("json");
+        assert!(!wire.contains("team"), "{wire}");
+        assert!(wire.contains("lab"), "{wire}");
+        assert_eq!(packet.group_proofs.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn members_see_the_keyed_group_and_others_do_not() {
+        let sender = in_groups(&["lab"], &[("team", "s3cret")]).await;
+        let packet = sender.packet(DiscoveryPacketKind::Announce, Vec::new()).await.expect("packet");
+
+        let member = in_groups(&[], &[("team", "s3cret")]).await;
+        let (status, authenticated) = member.open(packet.clone()).await.expect("member");
+        assert_eq!(status.groups, ["lab", "team"]);
+        assert!(authenticated);
+
+        for outsider in [node(), in_groups(&[], &[("team", "guess")]).await] {
+            let (status, authenticated) = outsider.open(packet.clone()).await.expect("outsider");
+            assert_eq!(status.groups, ["lab"]);
+            assert!(!authenticated);
+        }
+    }
+
+    #[tokio::test]
+    async fn naming_a_keyed_group_in_plain_is_not_membership() {
+        let claimant = in_groups(&["team"], &[]).await;
+        let packet = claimant.packet(DiscoveryPacketKind::Announce, Vec::new()).await.expect("packet");
+        let member = in_groups(&[], &[("team", "s3cret")]).await;
+        let (status, authenticated) = member.open(packet).await.expect("opened");
+        assert!(status.groups.is_empty(), "{:?}", status.groups);
+        assert!(!authenticated);
+    }
+
+    #[tokio::test]
+    async fn a_proof_only_counts_for_the_packet_it_came_with() {
+        let sender = in_groups(&[], &[("team", "s3cret")]).await;
+        let proven = sender.packet(DiscoveryPacketKind::Announce, Vec::new()).await.expect("packet");
+        let outsider = node();
+        let mut packet = outsider.packet(DiscoveryPacketKind::Announce, Vec::new()).await.expect("packet");
+        packet.group_proofs = proven.group_proofs;
+
+        let member = in_groups(&[], &[("team", "s3cret")]).await;
+        let (status, _) = member.open(packet).await.expect("opened");
+        assert!(status.groups.is_empty(), "{:?}", status.groups);
+    }
+
+    #[tokio::test]
+    async fn a_sealed_offer_only_opens_for_members() {
+        let sender = in_groups(&[], &[("team", "s3cret")]).await;
+        sharing(&sender).await;
+        *sender.seal_offer.write().await = true;
+        let packet = sender.packet(DiscoveryPacketKind::Announce, Vec::new()).await.expect("packet");
+        let wire = serde_json::to_string(&packet).expect("json");
+        assert!(!wire.contains("llama3") && !wire.contains("11435"), "{wire}");
+
+        let member = in_groups(&[], &[("team", "s3cret")]).await;
+        let (status, _) = member.open(packet.clone()).await.expect("member");
+        let offer = status.ollama.expect("unsealed offer");
+        assert_eq!(offer.models, ["llama3"]);
+        assert_eq!(offer.proxy_port, Some(11435));
+
+        let outsider = in_groups(&[], &[("team", "guess")]).await;
+        let (status, _) = outsider.open(packet).await.expect("outsider");
+        assert!(status.ollama.is_none());
+    }
+
+    #[tokio::test]
+    async fn an_unsealed_offer_is_sent_in_plain() {
+        let sender = in_groups(&[], &[("team", "s3cret")]).await;
+        sharing(&sender).await;
+        let packet = sender.packet(DiscoveryPacketKind::Announce, Vec::new()).await.expect("packet");
+        assert!(packet.group_proofs.iter().all(|p| p.offer.is_none()));
+        let (status, _) = node().open(packet).await.expect("opened");
+        assert_eq!(status.ollama.expect("offer").models, ["llama3"]);
+    }
+
+    #[test]
+    fn a_sealed_offer_is_bound_to_the_senders_node_id() {
+        let key = GroupKey::derive("team", "s3cret").expect("group key");
+        let offer = OllamaOfferStatus {
+            enabled: true,
+            models: vec!["llama3".to_string()],
+            ..Default::default()
+        };
+        let sealed = key.seal(&offer, "node-a").expect("sealed");
+        assert_eq!(key.unseal(&sealed, "node-a").expect("opened").models, ["llama3"]);
+        assert!(key.unseal(&sealed, "node-b").is_none());
+
+        let other = GroupKey::derive("team", "guess").expect("group key");
+        assert!(other.unseal(&sealed, "node-a").is_none());
+        let mut tampered = hex::decode(&sealed).expect("hex");
+        *tampered.last_mut().expect("byte") ^= 1;
+        assert!(key.unseal(&hex::encode(tampered), "node-a").is_none());
+    }
+
+    #[test]
+    fn group_keys_depend_on_id_and_secret() {
+        let key = GroupKey::derive("team", "s3cret").expect("group key");
+        assert_eq!(key.tag, GroupKey::derive(" team ", " s3cret ").expect("trimmed").tag);
+        assert_ne!(key.tag, GroupKey::derive("team", "other").expect("group key").tag);
+        assert_ne!(key.tag, GroupKey::derive("crew", "s3cret").expect("group key").tag);
+        assert!(GroupKey::derive("team", " ").is_none());
+        assert!(GroupKey::derive("", "s3cret").is_none());
+    }
+
+    #[test]
+    fn unauthenticated_gossip_stays_on_local_networks() {
+        let lan = addr("192.168.1.20:45555");
+        let local = [
+            "10.0.0.7:45555",
+            "172.16.3.4:1",
+            "169.254.1.1:45555",
+            "127.0.0.1:40000",
+            "[fd00::1]:45555",
+            "[fe80::1]:45555",
+            "[::1]:45555",
+            "[::ffff:192.168.1.9]:45555",
+        ];
+        for local in local {
+            assert!(may_learn(addr(local), lan, false), "{local}");
+        }
+        for public in ["203.0.113.5:45555", "8.8.8.8:53", "[2001:db8::1]:45555", "[::ffff:8.8.8.8]:53"] {
+            assert!(!may_learn(addr(public), lan, false), "{public}");
+        }
+        // Nor from a sender out on the internet, whatever it names.
+        assert!(!may_learn(addr("10.0.0.7:45555"), addr("203.0.113.5:45555"), false));
+    }
+
+    #[test]
+    fn authenticated_gossip_may_name_any_host() {
+        assert!(may_learn(addr("203.0.113.5:45555"), addr("198.51.100.1:45555"), true));
+    }
+}
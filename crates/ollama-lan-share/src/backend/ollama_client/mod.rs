@@ -3,11 +3,14 @@
 //! - `types`: API request/response types
 //! - `client`: Low-level HTTP client (OllamaClient)
 //! - `manager`: High-level manager with usage stats (OllamaManager)
+//! - `session`: Multi-turn chat history (ChatSession)
 
 pub mod types;
 pub mod client;
 pub mod manager;
+pub mod session;
 
 pub use types::*;
 pub use client::OllamaClient;
-pub use manager::OllamaManager;
\ No newline at end of file
+pub use manager::OllamaManager;
+pub use session::ChatSession;
\ No newline at end of file
@@ -0,0 +1,333 @@
+//! Multi-turn chat history with persistence and export.
+//!
+//! Mirrors `vas-core`'s `ChatSession` (`src/backend/chat_session.rs`): the
+//! same context window, summary folding and storage, without the failover
+//! routing. This crate is built and shipped on its own, and `vas-core` would
+//! bring the whole app backend along with a `ChatMessage` of its own, so the
+//! type is kept here in step with that one rather than shared. The LAN group
+//! chat sends `context()` to whichever peer serves the turn and records the
+//! peer as the reply's author. Token counts are estimated at four characters
+//! per token; the oldest turns are dropped once the history no longer fits
+//! `context_tokens`.
+
+use super::types::{ChatMessage, ChatOptions, ChatRequest};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_CONTEXT_TOKENS: usize = 4096;
+/// Recent messages a summary leaves untouched.
+pub const DEFAULT_KEEP_RECENT: usize = 6;
+
+/// One transcript entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionMessage {
+    #[serde(flatten)]
+    pub message: ChatMessage,
+    /// Peer that produced the reply.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatSession {
+    pub id: String,
+    pub title: String,
+    pub system: Option<String>,
+    pub messages: Vec<SessionMessage>,
+    /// Summary of the turns before `summarized`; sent after the system prompt.
+    #[serde(default)]
+    pub summary: Option<String>,
+    /// Messages before this index are covered by `summary` and no longer sent.
+    #[serde(default)]
+    pub summarized: usize,
+    pub context_tokens: usize,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl ChatSession {
+    pub fn new(id: impl Into<String>, title: impl Into<String>) -> Self {
+        let now = Utc::now();
+        Self {
+            id: id.into(),
+            title: title.into(),
+            system: None,
+            messages: Vec::new(),
+            summary: None,
+            summarized: 0,
+            context_tokens: DEFAULT_CONTEXT_TOKENS,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn with_system(mut self, system: impl Into<String>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    pub fn with_context_tokens(mut self, tokens: usize) -> Self {
+        self.context_tokens = tokens;
+        self
+    }
+
+    pub fn push(&mut self, message: ChatMessage, author: Option<String>) {
+        self.messages.push(SessionMessage { message, author, at: Utc::now() });
+        self.updated_at = Utc::now();
+    }
+
+    pub fn push_user(&mut self, content: impl Into<String>) {
+        self.push(ChatMessage::new("user", content), None);
+    }
+
+    pub fn push_assistant(&mut self, content: impl Into<String>, author: Option<String>) {
+        self.push(ChatMessage::new("assistant", content), author);
+    }
+
+    /// Forget every turn, keeping the system prompt.
+    pub fn clear(&mut self) {
+        self.messages.clear();
+        self.summary = None;
+        self.summarized = 0;
+        self.updated_at = Utc::now();
+    }
+
+    /// Messages to send: system prompt, summary, then the newest messages
+    /// that fit `context_tokens`. The newest message is always included, and
+    /// the history never starts with a tool result whose call was cut off.
+    pub fn context(&self) -> Vec<ChatMessage> {
+        let mut head = Vec::new();
+        if let Some(system) = &self.system {
+            head.push(ChatMessage::new("system", system.as_str()));
+        }
+        if let Some(summary) = &self.summary {
+            head.push(ChatMessage::new("system", format!("Summary of the earlier conversation:\n{summary}")));
+        }
+        let mut budget = self.context_tokens.saturating_sub(head.iter().map(estimate_tokens).sum());
+
+        let history = &self.messages[self.summarized.min(self.messages.len())..];
+        let mut start = history.len();
+        for (idx, entry) in history.iter().enumerate().rev() {
+            let cost = estimate_tokens(&entry.message);
+            if cost > budget && start < history.len() {
+                break;
+            }
+            budget = budget.saturating_sub(cost);
+            start = idx;
+        }
+        while start + 1 < history.len() && history[start].message.role == "tool" {
+            start += 1;
+        }
+        head.extend(history[start..].iter().map(|e| e.message.clone()));
+        head
+    }
+
+    pub fn chat_request(&self, model: &str, options: Option<ChatOptions>) -> ChatRequest {
+        ChatRequest {
+            model: model.to_string(),
+            messages: self.context(),
+            stream: Some(false),
+            format: None,
+            options,
+        }
+    }
+
+    /// The prompt asking for a summary of everything but the last
+    /// `keep_recent` messages, or `None` if there is nothing new to fold.
+    pub fn summary_prompt(&self, keep_recent: usize) -> Option<String> {
+        let end = self.messages.len().saturating_sub(keep_recent);
+        if end <= self.summarized {
+            return None;
+        }
+        let mut prompt = String::from(
+            "Summarize the conversation below in a few sentences. Keep names, decisions, \
+             open questions and facts the assistant will need later.\n\n",
+        );
+        if let Some(summary) = &self.summary {
+            let _ = writeln!(prompt, "Earlier summary:\n{summary}\n");
+        }
+        for entry in &self.messages[self.summarized..end] {
+            let _ = writeln!(prompt, "{}: {}", entry.message.role, entry.message.content);
+        }
+        Some(prompt)
+    }
+
+    /// Replace everything but the last `keep_recent` messages with `summary`.
+    /// The transcript itself is kept for export.
+    pub fn apply_summary(&mut self, keep_recent: usize, summary: impl Into<String>) {
+        self.summarized = self.messages.len().saturating_sub(keep_recent).max(self.summarized);
+        self.summary = Some(summary.into());
+        self.updated_at = Utc::now();
+    }
+
+    /// Whether every unsummarized message fits the context budget.
+    pub fn fits(&self) -> bool {
+        let pending = self.messages.len() - self.summarized.min(self.messages.len());
+        let sent = self.context().len() - usize::from(self.system.is_some()) - usize::from(self.summary.is_some());
+        sent >= pending
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Failed to serialize session: {e}"))
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# {}\n\n", self.title);
+        let _ = writeln!(out, "- Started: {}", self.created_at.format("%Y-%m-%d %H:%M UTC"));
+        if let Some(system) = &self.system {
+            let _ = write!(out, "\n## System\n\n{system}\n");
+        }
+        for entry in &self.messages {
+            let role = match entry.message.role.as_str() {
+                "user" => "User",
+                "assistant" => "Assistant",
+                "system" => "System",
+                other => other,
+            };
+            let _ = write!(out, "\n## {role}");
+            if let Some(author) = &entry.author {
+                let _ = write!(out, " ({author})");
+            }
+            let _ = write!(out, " · {}\n\n{}\n", entry.at.format("%H:%M:%S"), entry.message.content);
+        }
+        out
+    }
+
+    /// `<dir>/<id>.json`. Group ids are whatever the user typed, so
+    /// characters unsafe in file names are replaced.
+    pub fn path_in(&self, dir: &Path) -> PathBuf {
+        file_in(dir, &self.id)
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<PathBuf, String> {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+        let path = self.path_in(dir);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, self.to_json()?)
+            .and_then(|_| fs::rename(&tmp, &path))
+            .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+        Ok(path)
+    }
+
+    pub fn load(dir: &Path, id: &str) -> Result<Self, String> {
+        let path = file_in(dir, id);
+        let text = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        serde_json::from_str(&text).map_err(|e| format!("Failed to parse {}: {e}", path.display()))
+    }
+}
+
+fn file_in(dir: &Path, id: &str) -> PathBuf {
+    let name: String = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    dir.join(format!("{name}.json"))
+}
+
+/// Rough token count: four characters per token plus per-message framing.
+pub fn estimate_tokens(message: &ChatMessage) -> usize {
+    message.content.chars().count().div_ceil(4) + 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 36 characters: 9 tokens plus 4 of framing.
+    fn turn(tag: char) -> String {
+        std::iter::repeat_n(tag, 36).collect()
+    }
+
+    /// Room for the system prompt and two turns.
+    fn session() -> ChatSession {
+        let system = ChatMessage::new("system", "be brief");
+        ChatSession::new("team", "Team")
+            .with_system("be brief")
+            .with_context_tokens(estimate_tokens(&system) + 2 * 13)
+    }
+
+    fn contents(messages: &[ChatMessage]) -> Vec<&str> {
+        messages.iter().map(|m| m.content.as_str()).collect()
+    }
+
+    #[test]
+    fn context_keeps_the_newest_turns_that_fit() {
+        let mut session = session();
+        for tag in ['a', 'b', 'c', 'd'] {
+            session.push_user(turn(tag));
+        }
+        assert_eq!(contents(&session.context()), ["be brief", &turn('c'), &turn('d')]);
+        assert!(!session.fits());
+
+        session.messages.truncate(2);
+        assert!(session.fits());
+    }
+
+    #[test]
+    fn the_newest_turn_is_sent_even_when_it_is_too_long() {
+        let mut session = session();
+        session.push_user(turn('a'));
+        session.push_assistant("x".repeat(1000), Some("peer-1".to_string()));
+        assert_eq!(contents(&session.context()), ["be brief", "x".repeat(1000).as_str()]);
+    }
+
+    #[test]
+    fn context_never_starts_with_an_orphaned_tool_result() {
+        let mut session = session();
+        session.push_user(turn('a'));
+        session.push(ChatMessage::new("assistant", turn('b')), None);
+        session.push(ChatMessage::new("tool", turn('c')), None);
+        session.push_assistant(turn('d'), None);
+        assert_eq!(contents(&session.context()), ["be brief", &turn('d')]);
+    }
+
+    #[test]
+    fn a_summary_replaces_the_turns_it_covers() {
+        let mut session = session();
+        for tag in ['a', 'b', 'c', 'd'] {
+            session.push_user(turn(tag));
+        }
+        let prompt = session.summary_prompt(2).expect("prompt");
+        assert!(prompt.contains(&turn('b')) && !prompt.contains(&turn('c')));
+
+        session.context_tokens = 1000;
+        session.apply_summary(2, "they said a and b");
+        let context = session.context();
+        assert_eq!(context.len(), 4);
+        assert!(context[1].content.ends_with("they said a and b"));
+        assert_eq!(contents(&context[2..]), [&turn('c'), &turn('d')]);
+        assert!(session.summary_prompt(2).is_none());
+        assert_eq!(session.messages.len(), 4, "the transcript is kept for export");
+    }
+
+    #[test]
+    fn sessions_survive_a_save_and_load() {
+        let dir = std::env::temp_dir().join(format!("lan-chat-session-{}", uuid::Uuid::new_v4()));
+        // Group ids are free text; the file name is made safe.
+        let mut session = ChatSession::new("../team α", "Team");
+        session.push_user(turn('a'));
+        session.push_assistant(turn('b'), Some("peer-1".to_string()));
+
+        let path = session.save(&dir).expect("saved");
+        assert_eq!(path, dir.join("___team__.json"));
+        let loaded = ChatSession::load(&dir, "../team α").expect("loaded");
+        assert_eq!(loaded.to_json(), session.to_json());
+        assert_eq!(loaded.messages[1].author.as_deref(), Some("peer-1"));
+
+        assert!(ChatSession::load(&dir, "other").is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn sessions_saved_before_summaries_still_load() {
+        let old = r#"{"id":"team","title":"Team","system":null,"messages":[],"context_tokens":4096,
+            "created_at":"2026-01-01T00:00:00Z","updated_at":"2026-01-01T00:00:00Z"}"#;
+        let session: ChatSession = serde_json::from_str(old).expect("parsed");
+        assert!(session.summary.is_none());
+        assert_eq!(session.summarized, 0);
+    }
+}
@@ -0,0 +1,163 @@
+//! Append-only audit log of proxied requests, one JSON object per line.
+//!
+//! Every request gets an [`AuditEntry`], including the ones the proxy
+//! refuses. A streamed reply is logged when the stream ends, so `latency_ms`
+//! covers the whole reply and the token counts come from its last frame.
+
+use super::clients::UsageBook;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub at: DateTime<Utc>,
+    /// Client token name; `shared` for the share key, `anonymous` when the
+    /// proxy is open, `unknown` when no key matched.
+    pub client: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote: Option<String>,
+    pub method: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub status: u16,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub latency_ms: u64,
+}
+
+pub(super) type AuditSender = mpsc::UnboundedSender<AuditEntry>;
+
+/// Open `path` for appending and write entries to it in the background.
+pub(super) fn spawn_writer(path: &Path) -> Result<AuditSender, String> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    }
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open audit log {}: {e}", path.display()))?;
+    let mut file = tokio::fs::File::from_std(file);
+    let shown = path.display().to_string();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<AuditEntry>();
+    tokio::spawn(async move {
+        while let Some(entry) = rx.recv().await {
+            let Ok(mut line) = serde_json::to_string(&entry) else { continue };
+            line.push('\n');
+            if let Err(e) = async {
+                file.write_all(line.as_bytes()).await?;
+                file.flush().await
+            }
+            .await
+            {
+                tracing::warn!("Failed to write audit log {shown}: {e}");
+            }
+        }
+    });
+    Ok(tx)
+}
+
+/// One request on its way through the proxy. A streamed reply holds a clone;
+/// when the last clone is dropped the client's usage is settled and the
+/// entry is logged.
+#[derive(Clone)]
+pub(super) struct Exchange(Arc<Inner>);
+
+struct Inner {
+    entry: Mutex<AuditEntry>,
+    started: Instant,
+    /// Client whose in-flight count this request holds.
+    admitted: Mutex<Option<String>>,
+    usage: Arc<UsageBook>,
+    log: Option<AuditSender>,
+}
+
+impl Exchange {
+    pub fn new(
+        method: &str,
+        path: &str,
+        remote: Option<String>,
+        usage: Arc<UsageBook>,
+        log: Option<AuditSender>,
+    ) -> Self {
+        let entry = AuditEntry {
+            at: Utc::now(),
+            client: "unknown".to_string(),
+            remote,
+            method: method.to_string(),
+            path: path.to_string(),
+            model: None,
+            status: 0,
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            latency_ms: 0,
+        };
+        Self(Arc::new(Inner {
+            entry: Mutex::new(entry),
+            started: Instant::now(),
+            admitted: Mutex::new(None),
+            usage,
+            log,
+        }))
+    }
+
+    fn entry(&self) -> std::sync::MutexGuard<'_, AuditEntry> {
+        self.0.entry.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_client(&self, client: &str) {
+        self.entry().client = client.to_string();
+    }
+
+    pub fn set_model(&self, model: &str) {
+        self.entry().model = Some(model.to_string());
+    }
+
+    pub fn set_status(&self, status: u16) {
+        self.entry().status = status;
+    }
+
+    /// Record Ollama's token counts if `reply` (a reply or its last frame) has them.
+    pub fn note_usage(&self, reply: &serde_json::Value) {
+        let prompt = reply.get("prompt_eval_count").and_then(|v| v.as_u64());
+        let completion = reply.get("eval_count").and_then(|v| v.as_u64());
+        if prompt.is_none() && completion.is_none() {
+            return;
+        }
+        let mut entry = self.entry();
+        entry.prompt_tokens = prompt.unwrap_or(0);
+        entry.completion_tokens = completion.unwrap_or(0);
+    }
+
+    /// The request counts against `client`'s concurrency until it ends.
+    pub fn admitted(&self, client: &str) {
+        *self.0.admitted.lock().unwrap_or_else(|e| e.into_inner()) = Some(client.to_string());
+    }
+
+    /// The admitted request was turned away before it ran; it doesn't count.
+    pub fn refund(&self) {
+        if let Some(client) = self.0.admitted.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            self.0.usage.refund(&client);
+        }
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        let entry = self.entry.get_mut().unwrap_or_else(|e| e.into_inner());
+        entry.latency_ms = self.started.elapsed().as_millis() as u64;
+        if let Some(client) = self.admitted.get_mut().unwrap_or_else(|e| e.into_inner()).take() {
+            self.usage.finish(&client, entry.prompt_tokens + entry.completion_tokens);
+        }
+        if let Some(log) = &self.log {
+            let _ = log.send(entry.clone());
+        }
+    }
+}
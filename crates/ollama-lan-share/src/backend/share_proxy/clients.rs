@@ -0,0 +1,168 @@
+//! Named client tokens with their own limits.
+//!
+//! A [`ClientToken`] can narrow the shared models, cap its concurrent
+//! requests, expire, and carry daily request and token quotas that reset at
+//! midnight UTC. Tokens are counted from Ollama's `prompt_eval_count` and
+//! `eval_count` when a reply ends, so the request that crosses the token
+//! quota still completes and the next one is refused.
+
+use chrono::{DateTime, Duration as ChronoDuration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::Mutex;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClientToken {
+    /// Shown in the audit log and in [`ClientUsage`] reports.
+    pub name: String,
+    /// May be empty when the client authenticates with `cert_fingerprint`.
+    #[serde(default)]
+    pub token: String,
+    /// SHA-256 of a TLS client certificate accepted in place of the token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cert_fingerprint: Option<String>,
+    /// Narrows the proxy's `allowed_models`; empty means all of them.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub allowed_models: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_requests: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<usize>,
+    /// Share of the proxy's turns relative to other callers when requests
+    /// queue up; 1 by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// A revoked token is refused but stays listed for the record.
+    #[serde(default)]
+    pub revoked: bool,
+}
+
+impl ClientToken {
+    pub fn new(name: impl Into<String>, token: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            token: token.into(),
+            cert_fingerprint: None,
+            allowed_models: BTreeSet::new(),
+            daily_requests: None,
+            daily_tokens: None,
+            max_concurrency: None,
+            weight: None,
+            expires_at: None,
+            revoked: false,
+        }
+    }
+
+    pub fn allows_model(&self, model: &str) -> bool {
+        self.allowed_models.is_empty() || self.allowed_models.contains(model)
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+}
+
+/// What a client used today.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClientUsage {
+    /// UTC day the counters belong to.
+    pub day: NaiveDate,
+    pub requests: u64,
+    pub tokens: u64,
+    pub in_flight: usize,
+}
+
+impl ClientUsage {
+    fn new(day: NaiveDate) -> Self {
+        Self { day, requests: 0, tokens: 0, in_flight: 0 }
+    }
+}
+
+/// Why a client's request was not admitted.
+pub(super) struct Rejection {
+    pub message: &'static str,
+    /// Seconds until trying again makes sense.
+    pub retry_after: u64,
+}
+
+/// Per-client counters, kept across [`ShareProxy::configure`](super::ShareProxy::configure)
+/// calls so replacing the token list does not reset anyone's quota.
+#[derive(Default)]
+pub(super) struct UsageBook(Mutex<HashMap<String, ClientUsage>>);
+
+impl UsageBook {
+    /// Count a request against `client`'s limits, or say why it can't run.
+    pub fn admit(&self, client: &ClientToken, now: DateTime<Utc>) -> Result<(), Rejection> {
+        let today = now.date_naive();
+        let mut book = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let usage = book.entry(client.name.clone()).or_insert_with(|| ClientUsage::new(today));
+        if usage.day != today {
+            *usage = ClientUsage { in_flight: usage.in_flight, ..ClientUsage::new(today) };
+        }
+
+        let until_midnight = || {
+            let midnight = (today + ChronoDuration::days(1)).and_hms_opt(0, 0, 0).map(|t| t.and_utc());
+            midnight.map_or(60, |m| (m - now).num_seconds().max(1) as u64)
+        };
+        if client.daily_requests.is_some_and(|max| usage.requests >= max) {
+            return Err(Rejection { message: "daily request quota exceeded", retry_after: until_midnight() });
+        }
+        if client.daily_tokens.is_some_and(|max| usage.tokens >= max) {
+            return Err(Rejection { message: "daily token quota exceeded", retry_after: until_midnight() });
+        }
+        if client.max_concurrency.is_some_and(|max| usage.in_flight >= max) {
+            return Err(Rejection { message: "too many concurrent requests", retry_after: 1 });
+        }
+
+        usage.requests += 1;
+        usage.in_flight += 1;
+        Ok(())
+    }
+
+    /// Settle a request [`admit`](Self::admit) let through.
+    pub fn finish(&self, name: &str, tokens: u64) {
+        let mut book = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(usage) = book.get_mut(name) {
+            usage.in_flight = usage.in_flight.saturating_sub(1);
+            usage.tokens += tokens;
+        }
+    }
+
+    /// Undo an [`admit`](Self::admit) for a request that never ran.
+    pub fn refund(&self, name: &str) {
+        let mut book = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(usage) = book.get_mut(name) {
+            usage.in_flight = usage.in_flight.saturating_sub(1);
+            usage.requests = usage.requests.saturating_sub(1);
+        }
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, ClientUsage> {
+        let book = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        book.iter().map(|(name, usage)| (name.clone(), usage.clone())).collect()
+    }
+}
+
+/// Client tokens from a JSON array of [`ClientToken`]s.
+pub fn load_clients_file(path: &Path) -> Result<Vec<ClientToken>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read clients file {}: {e}", path.display()))?;
+    let clients: Vec<ClientToken> = serde_json::from_str(&text)
+        .map_err(|e| format!("Failed to parse clients file {}: {e}", path.display()))?;
+
+    let mut names = BTreeSet::new();
+    for client in &clients {
+        if client.token.trim().is_empty() && client.cert_fingerprint.is_none() {
+            return Err(format!("Client {} in {} has no token or certificate", client.name, path.display()));
+        }
+        if !names.insert(client.name.as_str()) {
+            return Err(format!("Client {} is listed twice in {}", client.name, path.display()));
+        }
+    }
+    Ok(clients)
+}
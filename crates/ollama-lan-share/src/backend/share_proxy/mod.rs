@@ -0,0 +1,661 @@
+//! Authenticated proxy in front of the local Ollama server.
+//!
+//! Peers that call a shared Ollama through the proxy must send the share key
+//! in [`SHARE_KEY_HEADER`] (when one is set) and may only use models from
+//! `allowed_models`. Only the endpoints in [`ProxyConfig::endpoints`] are
+//! forwarded; every request that names a model is checked, and `/api/tags`
+//! lists only the shared models. The OpenAI-compatible `/v1` endpoints are
+//! translated to the Ollama API (see [`openai`]) and also accept the key as
+//! `Authorization: Bearer <key>`. At most `max_concurrency` requests are
+//! forwarded at a time; a streamed reply holds its slot until the stream ends.
+//! Requests beyond that wait in a bounded, weighted fair queue (see
+//! [`queue`]), and `GET /status` reports how busy the proxy is.
+//!
+//! Besides the shared key, each teammate can get a named [`ClientToken`] with
+//! its own models, quotas, concurrency cap and expiry. Every request is
+//! written to the audit log (see [`audit`]) when one is configured.
+//!
+//! With [`ProxyOptions::tls`] the proxy serves HTTPS with a self-signed
+//! certificate that peers pin by fingerprint, and accepts known client
+//! certificates instead of a key (see [`tls`]).
+//!
+//! The [`ProxyConfig`] can be replaced while the proxy runs, so changing the
+//! key, the model list, the endpoints or the client tokens (including
+//! revoking one) takes effect on the next request.
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{ConnectInfo, DefaultBodyLimit, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Json, Router,
+};
+use futures_util::StreamExt;
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+pub mod audit;
+pub mod clients;
+mod openai;
+mod queue;
+pub mod tls;
+
+use audit::{AuditSender, Exchange};
+pub use clients::{load_clients_file, ClientToken, ClientUsage};
+use clients::UsageBook;
+pub use queue::QueueStatus;
+use queue::{FairQueue, QueueLimits};
+use tls::PeerCertificate;
+pub use tls::{TlsIdentity, TofuPins};
+
+pub const DEFAULT_PROXY_PORT: u16 = 11435;
+pub const DEFAULT_UPSTREAM: &str = "http://127.0.0.1:11434";
+pub const SHARE_KEY_HEADER: &str = "x-vas-key";
+/// Unauthenticated load report, answered even when sharing is paused.
+pub const STATUS_PATH: &str = "/status";
+
+/// Endpoints the proxy can forward. The model lists are the only `GET`s.
+pub const SHAREABLE_ENDPOINTS: &[&str] = &[
+    "/api/chat",
+    "/api/generate",
+    "/api/embed",
+    "/api/embeddings",
+    "/api/show",
+    "/api/tags",
+    "/v1/chat/completions",
+    "/v1/embeddings",
+    "/v1/models",
+];
+
+/// What the proxy allows; replaceable at runtime with [`ShareProxy::configure`].
+#[derive(Clone, Debug)]
+pub struct ProxyConfig {
+    /// When `false` every request gets 404, as if nothing were shared.
+    pub enabled: bool,
+    /// Required in [`SHARE_KEY_HEADER`]; empty disables the check.
+    pub key: String,
+    pub allowed_models: BTreeSet<String>,
+    /// Subset of [`SHAREABLE_ENDPOINTS`]; all of them by default.
+    pub endpoints: BTreeSet<String>,
+    /// Accepted alongside `key`; with clients and no key, a client token is required.
+    pub clients: Vec<ClientToken>,
+    /// Fingerprints of TLS client certificates accepted in place of `key`,
+    /// e.g. the pinned certificates of the group's other nodes.
+    pub trusted_certs: BTreeSet<String>,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            key: String::new(),
+            allowed_models: BTreeSet::new(),
+            endpoints: SHAREABLE_ENDPOINTS.iter().map(|e| e.to_string()).collect(),
+            clients: Vec::new(),
+            trusted_certs: BTreeSet::new(),
+        }
+    }
+}
+
+/// How the proxy listens and where it forwards to; fixed at start.
+#[derive(Clone, Debug)]
+pub struct ProxyOptions {
+    /// Use port 0 to let the OS pick one (see [`ShareProxy::local_addr`]).
+    pub bind: SocketAddr,
+    /// Base URL of the Ollama server to forward to.
+    pub upstream: String,
+    pub max_concurrency: usize,
+    /// Requests allowed to wait for a slot, overall and per caller.
+    pub max_queued: usize,
+    pub max_queued_per_caller: usize,
+    /// A request that waits longer than this gets 429.
+    pub max_queue_wait: Duration,
+    pub max_body_bytes: usize,
+    /// Limit for non-streamed replies; streams only have the connect timeout.
+    pub request_timeout: Duration,
+    /// JSON-lines file every request is appended to.
+    pub audit_log: Option<PathBuf>,
+    /// Serve HTTPS with this certificate instead of plain HTTP.
+    pub tls: Option<TlsIdentity>,
+}
+
+impl Default for ProxyOptions {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([0, 0, 0, 0], DEFAULT_PROXY_PORT)),
+            upstream: DEFAULT_UPSTREAM.to_string(),
+            max_concurrency: 4,
+            max_queued: 32,
+            max_queued_per_caller: 8,
+            max_queue_wait: Duration::from_secs(120),
+            max_body_bytes: 256 * 1024,
+            request_timeout: Duration::from_secs(300),
+            audit_log: None,
+            tls: None,
+        }
+    }
+}
+
+struct ProxyState {
+    cfg: RwLock<ProxyConfig>,
+    queue: Arc<FairQueue>,
+    upstream: String,
+    client: reqwest::Client,
+    /// No overall timeout: streamed generations run as long as tokens flow.
+    stream_client: reqwest::Client,
+    usage: Arc<UsageBook>,
+    audit: Option<AuditSender>,
+}
+
+/// A running proxy. Dropping it does not stop the server; call [`shutdown`](Self::shutdown).
+pub struct ShareProxy {
+    state: Arc<ProxyState>,
+    local_addr: SocketAddr,
+    tls_fingerprint: Option<String>,
+    task: JoinHandle<()>,
+}
+
+impl ShareProxy {
+    /// Bind `options.bind` and serve in the background on the current runtime.
+    pub async fn start(options: ProxyOptions, config: ProxyConfig) -> Result<Self, String> {
+        let build = |timeout: Option<Duration>| {
+            let mut builder = reqwest::Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .connect_timeout(Duration::from_secs(5))
+                .pool_idle_timeout(Duration::from_secs(90));
+            if let Some(timeout) = timeout {
+                builder = builder.timeout(timeout);
+            }
+            builder.build().map_err(|e| format!("Failed to create proxy client: {e}"))
+        };
+        let state = Arc::new(ProxyState {
+            cfg: RwLock::new(config),
+            queue: FairQueue::new(QueueLimits {
+                capacity: options.max_concurrency.max(1),
+                max_queued: options.max_queued,
+                max_queued_per_caller: options.max_queued_per_caller.max(1),
+                max_wait: options.max_queue_wait,
+            }),
+            upstream: options.upstream.trim_end_matches('/').to_string(),
+            client: build(Some(options.request_timeout))?,
+            stream_client: build(None)?,
+            usage: Arc::default(),
+            audit: options.audit_log.as_deref().map(audit::spawn_writer).transpose()?,
+        });
+
+        let listener = tokio::net::TcpListener::bind(options.bind)
+            .await
+            .map_err(|e| format!("Failed to bind proxy {}: {e}", options.bind))?;
+        let local_addr = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to read proxy address: {e}"))?;
+
+        let app = Router::new()
+            .route(STATUS_PATH, get(status))
+            .fallback(forward)
+            .layer(DefaultBodyLimit::max(options.max_body_bytes))
+            .with_state(state.clone());
+        let tls_fingerprint = options.tls.as_ref().map(|identity| identity.fingerprint().to_string());
+        let task = tokio::spawn(async move {
+            let served = match &options.tls {
+                Some(identity) => tls::serve(listener, identity, app).await,
+                None => {
+                    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+                    axum::serve(listener, app).await.map_err(|e| e.to_string())
+                }
+            };
+            if let Err(e) = served {
+                tracing::warn!("Share proxy on {local_addr} stopped: {e}");
+            }
+        });
+        let scheme = if tls_fingerprint.is_some() { "https" } else { "http" };
+        tracing::info!("Share proxy listening on {scheme}://{local_addr}, forwarding to {}", state.upstream);
+
+        Ok(Self { state, local_addr, tls_fingerprint, task })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// `http(s)://<LAN address>:<port>`, the base URL to put in the discovery offer.
+    pub async fn advertised_base_url(&self) -> String {
+        let host = local_ip_best_effort().await.unwrap_or_else(|| "localhost".to_string());
+        let scheme = if self.tls_fingerprint.is_some() { "https" } else { "http" };
+        format!("{scheme}://{host}:{}", self.local_addr.port())
+    }
+
+    /// Fingerprint of the certificate served, to advertise for pinning.
+    pub fn tls_fingerprint(&self) -> Option<&str> {
+        self.tls_fingerprint.as_deref()
+    }
+
+    pub async fn config(&self) -> ProxyConfig {
+        self.state.cfg.read().await.clone()
+    }
+
+    pub async fn configure(&self, config: ProxyConfig) {
+        *self.state.cfg.write().await = config;
+    }
+
+    /// Refuse the client token `name` from the next request on; `false` if
+    /// there is no such client.
+    pub async fn revoke(&self, name: &str) -> bool {
+        let mut cfg = self.state.cfg.write().await;
+        let client = cfg.clients.iter_mut().find(|c| c.name == name);
+        client.map(|c| c.revoked = true).is_some()
+    }
+
+    /// Running and queued requests, for `/status` and the discovery offer.
+    pub fn status(&self) -> QueueStatus {
+        self.state.queue.status()
+    }
+
+    /// Today's usage per client token.
+    pub fn client_usage(&self) -> BTreeMap<String, ClientUsage> {
+        self.state.usage.snapshot()
+    }
+
+    /// Reload the client tokens from `path` whenever it changes, so tokens
+    /// can be added or revoked by editing the file. A file that fails to
+    /// load leaves the current tokens in place.
+    pub fn watch_clients_file(&self, path: PathBuf, every: Duration) -> JoinHandle<()> {
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+            let mut seen = modified(&path);
+            loop {
+                tokio::time::sleep(every).await;
+                let now = modified(&path);
+                if now == seen {
+                    continue;
+                }
+                seen = now;
+                match load_clients_file(&path) {
+                    Ok(clients) => {
+                        tracing::info!("Reloaded {} client tokens from {}", clients.len(), path.display());
+                        state.cfg.write().await.clients = clients;
+                    }
+                    Err(e) => tracing::warn!("{e}"),
+                }
+            }
+        })
+    }
+
+    pub fn shutdown(self) {
+        self.task.abort();
+    }
+}
+
+/// The share key stored in `path`, without surrounding whitespace.
+pub fn read_share_key_file(path: &std::path::Path) -> Result<String, String> {
+    let key = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read share key file {}: {e}", path.display()))?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(format!("Share key file {} is empty", path.display()));
+    }
+    Ok(key.to_string())
+}
+
+/// Comma-separated endpoints, as `/api/chat` or just `chat`; the OpenAI ones
+/// need their full path.
+pub fn parse_endpoints(csv: &str) -> Result<BTreeSet<String>, String> {
+    let mut out = BTreeSet::new();
+    for item in csv.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let path = if item.starts_with('/') {
+            item.to_string()
+        } else {
+            format!("/api/{item}")
+        };
+        if !SHAREABLE_ENDPOINTS.contains(&path.as_str()) {
+            return Err(format!(
+                "Unknown endpoint {item}; expected some of {}",
+                SHAREABLE_ENDPOINTS.join(", ")
+            ));
+        }
+        out.insert(path);
+    }
+    Ok(out)
+}
+
+/// LAN address other machines can reach this host on, if one can be found.
+pub async fn local_ip_best_effort() -> Option<String> {
+    let socket = tokio::net::UdpSocket::bind("0.0.0.0:0").await.ok()?;
+    socket.connect("8.8.8.8:80").await.ok()?;
+    let addr = socket.local_addr().ok()?;
+    Some(addr.ip().to_string())
+}
+
+fn error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(serde_json::json!({"error": message.into()}))).into_response()
+}
+
+/// The key from [`SHARE_KEY_HEADER`], or the bearer token OpenAI SDKs send.
+fn given_key(headers: &HeaderMap) -> &str {
+    if let Some(key) = headers.get(SHARE_KEY_HEADER).and_then(|v| v.to_str().ok()) {
+        return key;
+    }
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or("")
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut out = 0u8;
+    for i in 0..a.len() {
+        out |= a[i] ^ b[i];
+    }
+    out == 0
+}
+
+/// The model a request body names. `/api/show` still accepts the older `name`.
+fn requested_model(body: &serde_json::Value) -> Option<&str> {
+    body.get("model")
+        .or_else(|| body.get("name"))
+        .and_then(|v| v.as_str())
+        .filter(|m| !m.is_empty())
+}
+
+/// Drop the models the caller may not use from an `/api/tags` reply.
+fn filter_tags(mut tags: serde_json::Value, allowed: impl Fn(&str) -> bool) -> serde_json::Value {
+    if let Some(models) = tags.get_mut("models").and_then(|m| m.as_array_mut()) {
+        models.retain(|m| {
+            ["name", "model"]
+                .iter()
+                .filter_map(|k| m.get(*k).and_then(|v| v.as_str()))
+                .any(&allowed)
+        });
+    }
+    tags
+}
+
+/// Who is calling, by the key or certificate they sent.
+enum Caller {
+    /// Neither a share key nor client tokens are configured.
+    Anyone,
+    SharedKey,
+    Client(ClientToken),
+    /// A certificate from `trusted_certs`; shown by its fingerprint's prefix.
+    TrustedCert(String),
+}
+
+impl Caller {
+    fn name(&self) -> &str {
+        match self {
+            Caller::Anyone => "anonymous",
+            Caller::SharedKey => "shared",
+            Caller::Client(client) => &client.name,
+            Caller::TrustedCert(name) => name,
+        }
+    }
+
+    fn allows_model(&self, model: &str) -> bool {
+        match self {
+            Caller::Client(client) => client.allows_model(model),
+            _ => true,
+        }
+    }
+}
+
+/// The caller for the key `given` or the client certificate `cert`, or the
+/// client name (if any) and why it was refused.
+fn authenticate(
+    cfg: &ProxyConfig,
+    given: &str,
+    cert: Option<&str>,
+) -> Result<Caller, (Option<String>, &'static str)> {
+    if cfg.key.is_empty() && cfg.clients.is_empty() && cfg.trusted_certs.is_empty() {
+        return Ok(Caller::Anyone);
+    }
+    if !cfg.key.is_empty() && constant_time_eq(given.as_bytes(), cfg.key.as_bytes()) {
+        return Ok(Caller::SharedKey);
+    }
+    let by_token = cfg
+        .clients
+        .iter()
+        .filter(|c| !c.token.is_empty())
+        .find(|c| constant_time_eq(given.as_bytes(), c.token.as_bytes()));
+    let by_cert = || {
+        let cert = cert?;
+        cfg.clients.iter().find(|c| {
+            c.cert_fingerprint
+                .as_deref()
+                .is_some_and(|fp| tls::normalize_fingerprint(fp) == cert)
+        })
+    };
+    let Some(client) = by_token.or_else(by_cert) else {
+        let trusted = cert.filter(|cert| {
+            cfg.trusted_certs.iter().any(|fp| tls::normalize_fingerprint(fp) == *cert)
+        });
+        return match trusted {
+            Some(cert) => Ok(Caller::TrustedCert(format!("cert:{}", &cert[..16]))),
+            None => Err((None, "unauthorized")),
+        };
+    };
+    if client.revoked {
+        return Err((Some(client.name.clone()), "token revoked"));
+    }
+    if client.is_expired(chrono::Utc::now()) {
+        return Err((Some(client.name.clone()), "token expired"));
+    }
+    Ok(Caller::Client(client.clone()))
+}
+
+fn upstream_status(status: reqwest::StatusCode) -> StatusCode {
+    StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY)
+}
+
+async fn status(State(state): State<Arc<ProxyState>>) -> Json<QueueStatus> {
+    Json(state.queue.status())
+}
+
+/// The other end of a connection.
+struct Peer {
+    ip: String,
+    /// Fingerprint of its TLS client certificate.
+    cert: Option<String>,
+}
+
+async fn forward(
+    State(state): State<Arc<ProxyState>>,
+    ConnectInfo(remote): ConnectInfo<SocketAddr>,
+    cert: Option<Extension<PeerCertificate>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let exchange = Exchange::new(
+        method.as_str(),
+        uri.path(),
+        Some(remote.ip().to_string()),
+        state.usage.clone(),
+        state.audit.clone(),
+    );
+    let peer = Peer {
+        ip: remote.ip().to_string(),
+        cert: cert.and_then(|Extension(PeerCertificate(cert))| cert),
+    };
+    let response = handle(&state, &exchange, &peer, method, uri.path(), &headers, body).await;
+    exchange.set_status(response.status().as_u16());
+    response
+}
+
+async fn handle(
+    state: &ProxyState,
+    exchange: &Exchange,
+    peer: &Peer,
+    method: Method,
+    path: &str,
+    headers: &HeaderMap,
+    body: Bytes,
+) -> Response {
+    // OpenAI clients expect errors in OpenAI's shape.
+    let v1 = path.starts_with("/v1/");
+    let fail = |status: StatusCode, message: &str| {
+        if v1 {
+            openai::error(status, message)
+        } else {
+            error(status, message)
+        }
+    };
+
+    if !SHAREABLE_ENDPOINTS.contains(&path) {
+        return fail(StatusCode::NOT_FOUND, "not found");
+    }
+    let list = matches!(path, "/api/tags" | "/v1/models");
+    let expected = if list { Method::GET } else { Method::POST };
+    if method != expected {
+        return fail(StatusCode::METHOD_NOT_ALLOWED, "method not allowed");
+    }
+
+    let cfg = state.cfg.read().await.clone();
+    if !cfg.enabled {
+        return fail(StatusCode::NOT_FOUND, "not sharing");
+    }
+
+    let caller = match authenticate(&cfg, given_key(headers), peer.cert.as_deref()) {
+        Ok(caller) => caller,
+        Err((client, message)) => {
+            if let Some(client) = client {
+                exchange.set_client(&client);
+            }
+            return fail(StatusCode::UNAUTHORIZED, message);
+        }
+    };
+    exchange.set_client(caller.name());
+
+    if !cfg.endpoints.contains(path) {
+        return fail(StatusCode::FORBIDDEN, "endpoint not shared");
+    }
+
+    if list {
+        let allowed = |name: &str| cfg.allowed_models.contains(name) && caller.allows_model(name);
+        let url = format!("{}/api/tags", state.upstream);
+        return match state.client.get(url).send().await {
+            Ok(r) => {
+                let status = upstream_status(r.status());
+                match r.json::<serde_json::Value>().await {
+                    Ok(v) if v1 => (status, Json(openai::models(&filter_tags(v, allowed)))).into_response(),
+                    Ok(v) => (status, Json(filter_tags(v, allowed))).into_response(),
+                    Err(e) => fail(StatusCode::BAD_GATEWAY, &format!("bad upstream json: {e}")),
+                }
+            }
+            Err(e) => fail(StatusCode::BAD_GATEWAY, &format!("upstream error: {e}")),
+        };
+    }
+
+    let Ok(json) = serde_json::from_slice::<serde_json::Value>(&body) else {
+        return fail(StatusCode::BAD_REQUEST, "invalid json body");
+    };
+    let Some(model) = requested_model(&json) else {
+        return fail(StatusCode::BAD_REQUEST, "missing model");
+    };
+    exchange.set_model(model);
+    if !cfg.allowed_models.contains(model) || !caller.allows_model(model) {
+        return fail(StatusCode::FORBIDDEN, "model not allowed");
+    }
+
+    let too_many = |message: &str, retry_after: u64| {
+        let mut response = fail(StatusCode::TOO_MANY_REQUESTS, message);
+        response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        response
+    };
+    if let Caller::Client(client) = &caller {
+        if let Err(rejection) = state.usage.admit(client, chrono::Utc::now()) {
+            return too_many(rejection.message, rejection.retry_after);
+        }
+        exchange.admitted(&client.name);
+    }
+
+    // Limit concurrent work to avoid overwhelming the local Ollama; the rest
+    // wait their turn per client token or certificate, or per peer address.
+    // A streamed reply keeps its slot until the stream ends.
+    let (queue_key, weight) = match &caller {
+        Caller::Client(client) => (format!("client:{}", client.name), client.weight.unwrap_or(1)),
+        Caller::TrustedCert(name) => (name.clone(), 1),
+        _ => (format!("ip:{}", peer.ip), 1),
+    };
+    let slot = match state.queue.acquire(&queue_key, weight).await {
+        Ok(slot) => slot,
+        Err(busy) => {
+            exchange.refund();
+            return too_many(busy.message, busy.retry_after);
+        }
+    };
+
+    match path {
+        "/v1/chat/completions" => return openai::chat(state, exchange, json, slot).await,
+        "/v1/embeddings" => return openai::embeddings(state, exchange, json).await,
+        _ => {}
+    }
+
+    // Chat and generate stream unless told otherwise.
+    let url = format!("{}{path}", state.upstream);
+    let streams = matches!(path, "/api/chat" | "/api/generate")
+        && json.get("stream").and_then(|v| v.as_bool()).unwrap_or(true);
+    let client = if streams { &state.stream_client } else { &state.client };
+    let sent = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .await;
+    let r = match sent {
+        Ok(r) => r,
+        Err(e) => return error(StatusCode::BAD_GATEWAY, format!("upstream error: {e}")),
+    };
+
+    let status = upstream_status(r.status());
+    let content_type = r
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or(if streams { "application/x-ndjson" } else { "application/json" })
+        .to_string();
+
+    if streams {
+        // Pass the NDJSON frames through as they arrive, reading the token
+        // counts off the last one.
+        let exchange = exchange.clone();
+        let mut line = Vec::new();
+        let frames = r.bytes_stream().map(move |chunk| {
+            let _held = &slot;
+            if let Ok(bytes) = &chunk {
+                for piece in bytes.split_inclusive(|b| *b == b'\n') {
+                    line.extend_from_slice(piece);
+                    if line.ends_with(b"\n") {
+                        if let Ok(frame) = serde_json::from_slice(&line) {
+                            exchange.note_usage(&frame);
+                        }
+                        line.clear();
+                    }
+                }
+            }
+            chunk
+        });
+        return (status, [(header::CONTENT_TYPE, content_type)], Body::from_stream(frames)).into_response();
+    }
+
+    match r.bytes().await {
+        Ok(bytes) => {
+            if let Ok(reply) = serde_json::from_slice(&bytes) {
+                exchange.note_usage(&reply);
+            }
+            (status, [(header::CONTENT_TYPE, content_type)], bytes).into_response()
+        }
+        Err(e) => error(StatusCode::BAD_GATEWAY, format!("upstream error: {e}")),
+    }
+}
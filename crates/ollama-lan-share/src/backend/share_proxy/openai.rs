@@ -0,0 +1,462 @@
+//! OpenAI-compatible endpoints, translated to the Ollama API.
+//!
+//! `/v1/chat/completions` goes to `/api/chat`; with `stream: true` the NDJSON
+//! frames come back as `chat.completion.chunk` server-sent events ending in
+//! `data: [DONE]`. `/v1/embeddings` goes to `/api/embed` and `/v1/models` is
+//! built from the filtered `/api/tags`. Ollama's `prompt_eval_count` and
+//! `eval_count` become `usage.prompt_tokens` and `usage.completion_tokens`.
+//!
+//! Sampling parameters map to Ollama `options` (`max_tokens` to
+//! `num_predict`), `response_format` to `format`, and tool definitions pass
+//! through; tool call arguments are converted between OpenAI's JSON strings
+//! and Ollama's objects.
+
+use super::audit::Exchange;
+use super::queue::Slot;
+use super::{upstream_status, ProxyState};
+use axum::{
+    body::{Body, Bytes},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::Engine as _;
+use futures_util::{Stream, StreamExt};
+use serde_json::{json, Map, Value};
+use std::collections::VecDeque;
+use std::pin::Pin;
+
+pub(super) fn error(status: StatusCode, message: &str) -> Response {
+    let kind = match status {
+        StatusCode::UNAUTHORIZED => "authentication_error",
+        StatusCode::FORBIDDEN => "permission_error",
+        StatusCode::NOT_FOUND => "not_found_error",
+        s if s.is_server_error() => "api_error",
+        _ => "invalid_request_error",
+    };
+    let body = json!({"error": {"message": message, "type": kind, "code": null}});
+    (status, Json(body)).into_response()
+}
+
+/// `/api/tags` as an OpenAI model list.
+pub(super) fn models(tags: &Value) -> Value {
+    let data: Vec<Value> = tags["models"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|m| {
+            let id = m["name"].as_str().or_else(|| m["model"].as_str())?;
+            let created = m["modified_at"]
+                .as_str()
+                .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+                .map(|t| t.timestamp())
+                .unwrap_or(0);
+            Some(json!({"id": id, "object": "model", "created": created, "owned_by": "library"}))
+        })
+        .collect();
+    json!({"object": "list", "data": data})
+}
+
+/// The `/api/chat` body for an OpenAI chat completion request.
+fn chat_request(body: &Value) -> Result<Value, String> {
+    let messages = body["messages"]
+        .as_array()
+        .ok_or("messages must be an array")?
+        .iter()
+        .map(ollama_message)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut options = Map::new();
+    for (from, to) in [
+        ("temperature", "temperature"),
+        ("top_p", "top_p"),
+        ("seed", "seed"),
+        ("frequency_penalty", "frequency_penalty"),
+        ("presence_penalty", "presence_penalty"),
+        ("max_tokens", "num_predict"),
+        ("max_completion_tokens", "num_predict"),
+    ] {
+        if let Some(v) = body.get(from).filter(|v| !v.is_null()) {
+            options.insert(to.to_string(), v.clone());
+        }
+    }
+    match body.get("stop") {
+        Some(Value::String(s)) => {
+            options.insert("stop".to_string(), json!([s]));
+        }
+        Some(v @ Value::Array(_)) => {
+            options.insert("stop".to_string(), v.clone());
+        }
+        _ => {}
+    }
+
+    let mut out = json!({
+        "model": body["model"],
+        "messages": messages,
+        "stream": body["stream"].as_bool().unwrap_or(false),
+    });
+    if !options.is_empty() {
+        out["options"] = Value::Object(options);
+    }
+    match body["response_format"]["type"].as_str() {
+        Some("json_object") => out["format"] = json!("json"),
+        Some("json_schema") => out["format"] = body["response_format"]["json_schema"]["schema"].clone(),
+        _ => {}
+    }
+    if let Some(tools) = body.get("tools").filter(|t| t.is_array()) {
+        out["tools"] = tools.clone();
+    }
+    Ok(out)
+}
+
+/// One OpenAI message in Ollama's shape: text parts joined, `data:` image
+/// URLs moved to `images`, tool call arguments parsed into objects.
+fn ollama_message(message: &Value) -> Result<Value, String> {
+    let role = message["role"].as_str().ok_or("message without role")?;
+    let role = if role == "developer" { "system" } else { role };
+    let mut content = String::new();
+    let mut images = Vec::new();
+    match &message["content"] {
+        Value::String(text) => content.push_str(text),
+        Value::Array(parts) => {
+            for part in parts {
+                match part["type"].as_str() {
+                    Some("text") => content.push_str(part["text"].as_str().unwrap_or("")),
+                    Some("image_url") => {
+                        let url = part["image_url"]["url"].as_str().unwrap_or("");
+                        let data = url
+                            .strip_prefix("data:")
+                            .and_then(|u| u.split_once(";base64,"))
+                            .map(|(_, data)| data)
+                            .ok_or("only base64 data: image URLs are supported")?;
+                        images.push(json!(data));
+                    }
+                    _ => return Err("unsupported content part".to_string()),
+                }
+            }
+        }
+        _ => {}
+    }
+
+    let mut out = json!({"role": role, "content": content});
+    if !images.is_empty() {
+        out["images"] = json!(images);
+    }
+    if let Some(calls) = message["tool_calls"].as_array() {
+        let calls: Vec<Value> = calls
+            .iter()
+            .map(|call| {
+                let arguments = &call["function"]["arguments"];
+                let arguments = arguments
+                    .as_str()
+                    .and_then(|a| serde_json::from_str(a).ok())
+                    .unwrap_or_else(|| arguments.clone());
+                json!({"function": {"name": call["function"]["name"], "arguments": arguments}})
+            })
+            .collect();
+        out["tool_calls"] = json!(calls);
+    }
+    Ok(out)
+}
+
+/// Ollama tool calls with the ids, `type` and string arguments OpenAI uses.
+fn openai_tool_calls(calls: &Value) -> Option<Value> {
+    let calls = calls.as_array().filter(|c| !c.is_empty())?;
+    let calls: Vec<Value> = calls
+        .iter()
+        .enumerate()
+        .map(|(index, call)| {
+            let arguments = match &call["function"]["arguments"] {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            json!({
+                "index": index,
+                "id": format!("call_{}", uuid::Uuid::new_v4().simple()),
+                "type": "function",
+                "function": {"name": call["function"]["name"], "arguments": arguments},
+            })
+        })
+        .collect();
+    Some(json!(calls))
+}
+
+fn finish_reason(frame: &Value, tool_calls: bool) -> &'static str {
+    if tool_calls {
+        return "tool_calls";
+    }
+    match frame["done_reason"].as_str() {
+        Some("length") => "length",
+        _ => "stop",
+    }
+}
+
+fn usage(frame: &Value) -> Value {
+    let prompt = frame["prompt_eval_count"].as_u64().unwrap_or(0);
+    let completion = frame["eval_count"].as_u64().unwrap_or(0);
+    json!({
+        "prompt_tokens": prompt,
+        "completion_tokens": completion,
+        "total_tokens": prompt + completion,
+    })
+}
+
+/// The error message of a failed upstream reply.
+async fn upstream_error(r: reqwest::Response) -> Response {
+    let status = upstream_status(r.status());
+    let message = match r.json::<Value>().await {
+        Ok(v) => v["error"].as_str().unwrap_or("upstream error").to_string(),
+        Err(e) => format!("bad upstream json: {e}"),
+    };
+    error(status, &message)
+}
+
+pub(super) async fn chat(
+    state: &ProxyState,
+    exchange: &Exchange,
+    body: Value,
+    slot: Slot,
+) -> Response {
+    let request = match chat_request(&body) {
+        Ok(r) => r,
+        Err(e) => return error(StatusCode::BAD_REQUEST, &e),
+    };
+    let model = body["model"].as_str().unwrap_or_default().to_string();
+    let stream = request["stream"].as_bool().unwrap_or(false);
+    let url = format!("{}/api/chat", state.upstream);
+    let client = if stream { &state.stream_client } else { &state.client };
+    let r = match client.post(url).json(&request).send().await {
+        Ok(r) if r.status().is_success() => r,
+        Ok(r) => return upstream_error(r).await,
+        Err(e) => return error(StatusCode::BAD_GATEWAY, &format!("upstream error: {e}")),
+    };
+
+    let id = format!("chatcmpl-{}", uuid::Uuid::new_v4().simple());
+    let created = chrono::Utc::now().timestamp();
+
+    if stream {
+        let include_usage = body["stream_options"]["include_usage"].as_bool().unwrap_or(false);
+        let events = SseTranslator {
+            upstream: Box::pin(r.bytes_stream()),
+            buf: Vec::new(),
+            pending: VecDeque::new(),
+            finished: false,
+            started: false,
+            tool_calls: false,
+            include_usage,
+            id,
+            created,
+            model,
+            exchange: exchange.clone(),
+            _slot: slot,
+        };
+        return (
+            [(header::CONTENT_TYPE, "text/event-stream"), (header::CACHE_CONTROL, "no-cache")],
+            Body::from_stream(events.into_stream()),
+        )
+            .into_response();
+    }
+
+    let reply: Value = match r.json().await {
+        Ok(v) => v,
+        Err(e) => return error(StatusCode::BAD_GATEWAY, &format!("bad upstream json: {e}")),
+    };
+    exchange.note_usage(&reply);
+    let tool_calls = openai_tool_calls(&reply["message"]["tool_calls"]);
+    let content = reply["message"]["content"].as_str().unwrap_or("");
+    let mut message = json!({"role": "assistant", "content": content});
+    if let Some(calls) = &tool_calls {
+        message["tool_calls"] = calls.clone();
+    }
+    Json(json!({
+        "id": id,
+        "object": "chat.completion",
+        "created": created,
+        "model": model,
+        "choices": [{
+            "index": 0,
+            "message": message,
+            "finish_reason": finish_reason(&reply, tool_calls.is_some()),
+        }],
+        "usage": usage(&reply),
+    }))
+    .into_response()
+}
+
+type UpstreamBytes = Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>>;
+
+/// Turns Ollama's NDJSON chat frames into OpenAI SSE events as they arrive.
+struct SseTranslator {
+    upstream: UpstreamBytes,
+    /// Bytes of a frame split across chunks.
+    buf: Vec<u8>,
+    pending: VecDeque<String>,
+    finished: bool,
+    /// Whether the first chunk (carrying the role) went out.
+    started: bool,
+    /// Ollama sends tool calls before the `done` frame.
+    tool_calls: bool,
+    include_usage: bool,
+    id: String,
+    created: i64,
+    model: String,
+    /// Logged once the stream ends.
+    exchange: Exchange,
+    /// Held until the stream ends.
+    _slot: Slot,
+}
+
+impl SseTranslator {
+    fn into_stream(self) -> impl Stream<Item = Result<String, std::io::Error>> + Send {
+        futures_util::stream::unfold(self, |mut t| async move {
+            loop {
+                if let Some(event) = t.pending.pop_front() {
+                    return Some((Ok(event), t));
+                }
+                if t.finished {
+                    return None;
+                }
+                match t.upstream.next().await {
+                    Some(Ok(bytes)) => {
+                        t.buf.extend_from_slice(&bytes);
+                        while let Some(pos) = t.buf.iter().position(|b| *b == b'\n') {
+                            let line: Vec<u8> = t.buf.drain(..=pos).collect();
+                            t.frame(&line);
+                        }
+                    }
+                    Some(Err(e)) => t.fail(&format!("upstream error: {e}")),
+                    None => {
+                        let rest = std::mem::take(&mut t.buf);
+                        t.frame(&rest);
+                        if !t.finished {
+                            t.fail("upstream stream ended early");
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    fn chunk(&self, delta: Value, finish_reason: Option<&str>) -> Value {
+        json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}],
+        })
+    }
+
+    fn send(&mut self, event: &Value) {
+        self.pending.push_back(format!("data: {event}\n\n"));
+    }
+
+    fn fail(&mut self, message: &str) {
+        self.send(&json!({"error": {"message": message, "type": "api_error", "code": null}}));
+        self.pending.push_back("data: [DONE]\n\n".to_string());
+        self.finished = true;
+    }
+
+    fn frame(&mut self, line: &[u8]) {
+        if self.finished || line.iter().all(u8::is_ascii_whitespace) {
+            return;
+        }
+        let frame: Value = match serde_json::from_slice(line) {
+            Ok(v) => v,
+            Err(e) => return self.fail(&format!("bad upstream frame: {e}")),
+        };
+        if let Some(message) = frame["error"].as_str() {
+            return self.fail(message);
+        }
+
+        let mut delta = Map::new();
+        if !self.started {
+            delta.insert("role".to_string(), json!("assistant"));
+            self.started = true;
+        }
+        if let Some(content) = frame["message"]["content"].as_str().filter(|c| !c.is_empty()) {
+            delta.insert("content".to_string(), json!(content));
+        }
+        if let Some(calls) = openai_tool_calls(&frame["message"]["tool_calls"]) {
+            delta.insert("tool_calls".to_string(), calls);
+            self.tool_calls = true;
+        }
+        if !delta.is_empty() {
+            let chunk = self.chunk(Value::Object(delta), None);
+            self.send(&chunk);
+        }
+
+        if frame["done"].as_bool().unwrap_or(false) {
+            self.exchange.note_usage(&frame);
+            let chunk = self.chunk(json!({}), Some(finish_reason(&frame, self.tool_calls)));
+            self.send(&chunk);
+            if self.include_usage {
+                let mut chunk = self.chunk(json!({}), None);
+                chunk["choices"] = json!([]);
+                chunk["usage"] = usage(&frame);
+                self.send(&chunk);
+            }
+            self.pending.push_back("data: [DONE]\n\n".to_string());
+            self.finished = true;
+        }
+    }
+}
+
+pub(super) async fn embeddings(state: &ProxyState, exchange: &Exchange, body: Value) -> Response {
+    let input = match &body["input"] {
+        Value::String(s) => json!([s]),
+        Value::Array(items) if items.iter().all(Value::is_string) => json!(items),
+        _ => return error(StatusCode::BAD_REQUEST, "input must be a string or an array of strings"),
+    };
+    let base64 = match body["encoding_format"].as_str() {
+        None | Some("float") => false,
+        Some("base64") => true,
+        Some(other) => return error(StatusCode::BAD_REQUEST, &format!("unknown encoding_format {other}")),
+    };
+
+    let mut request = json!({"model": body["model"], "input": input});
+    if let Some(dimensions) = body.get("dimensions").filter(|d| d.is_u64()) {
+        request["dimensions"] = dimensions.clone();
+    }
+    let url = format!("{}/api/embed", state.upstream);
+    let r = match state.client.post(url).json(&request).send().await {
+        Ok(r) if r.status().is_success() => r,
+        Ok(r) => return upstream_error(r).await,
+        Err(e) => return error(StatusCode::BAD_GATEWAY, &format!("upstream error: {e}")),
+    };
+    let reply: Value = match r.json().await {
+        Ok(v) => v,
+        Err(e) => return error(StatusCode::BAD_GATEWAY, &format!("bad upstream json: {e}")),
+    };
+    exchange.note_usage(&reply);
+
+    let data: Vec<Value> = reply["embeddings"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .map(|(index, vector)| {
+            let embedding = if base64 {
+                // Little-endian f32s, as OpenAI encodes them.
+                let bytes: Vec<u8> = vector
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .flat_map(|x| (x.as_f64().unwrap_or(0.0) as f32).to_le_bytes())
+                    .collect();
+                json!(base64::engine::general_purpose::STANDARD.encode(bytes))
+            } else {
+                vector.clone()
+            };
+            json!({"object": "embedding", "index": index, "embedding": embedding})
+        })
+        .collect();
+    let prompt = reply["prompt_eval_count"].as_u64().unwrap_or(0);
+    Json(json!({
+        "object": "list",
+        "data": data,
+        "model": body["model"],
+        "usage": {"prompt_tokens": prompt, "total_tokens": prompt},
+    }))
+    .into_response()
+}
@@ -0,0 +1,296 @@
+//! Weighted fair queuing in front of the upstream Ollama.
+//!
+//! At most `capacity` requests run at once. The rest wait in a bounded queue
+//! and are served by start-time fair queuing: each caller (a client token, or
+//! the peer's IP for everyone else) gets a virtual start tag of
+//! `max(now, its previous finish)` and a finish tag `1 / weight` later, and
+//! the waiter with the smallest start tag goes next. A caller that keeps
+//! sending therefore falls behind callers that send occasionally, and a
+//! caller with weight 2 gets about twice the turns of one with weight 1.
+//!
+//! A full queue (overall or for one caller) or a wait longer than
+//! `max_wait` is refused with a `Retry-After` estimate based on recent
+//! service times.
+
+use crate::shared::models::OfferLoad;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+/// Queue limits, from [`ProxyOptions`](super::ProxyOptions).
+#[derive(Clone, Copy, Debug)]
+pub(super) struct QueueLimits {
+    pub capacity: usize,
+    pub max_queued: usize,
+    pub max_queued_per_caller: usize,
+    pub max_wait: Duration,
+}
+
+/// Queue state as reported on `/status` and advertised in the discovery offer.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct QueueStatus {
+    pub running: usize,
+    pub capacity: usize,
+    pub queued: usize,
+    pub max_queued: usize,
+    /// Callers with at least one queued request.
+    pub queued_callers: usize,
+    /// Moving average of the time admitted requests waited.
+    pub avg_wait_ms: u64,
+    /// Longest wait of a request now in the queue.
+    pub oldest_wait_ms: u64,
+    /// Moving average of how long a request holds its slot.
+    pub avg_service_ms: u64,
+    pub served: u64,
+    pub rejected: u64,
+}
+
+impl QueueStatus {
+    /// The part of the status worth advertising in the discovery offer.
+    pub fn offer_load(&self) -> OfferLoad {
+        OfferLoad {
+            running: self.running,
+            capacity: self.capacity,
+            queued: self.queued,
+            avg_wait_ms: self.avg_wait_ms,
+        }
+    }
+}
+
+/// Why a request did not get a slot.
+pub(super) struct Busy {
+    pub message: &'static str,
+    pub retry_after: u64,
+}
+
+struct Waiter {
+    start_tag: f64,
+    /// Arrival order, to break ties between equal tags.
+    seq: u64,
+    caller: String,
+    enqueued: Instant,
+    tx: oneshot::Sender<Slot>,
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiter {
+    /// Reversed so the `BinaryHeap` pops the smallest tag first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .start_tag
+            .total_cmp(&self.start_tag)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+#[derive(Default)]
+struct State {
+    running: usize,
+    waiting: BinaryHeap<Waiter>,
+    /// Queued requests per caller.
+    queued: HashMap<String, usize>,
+    /// Finish tag of each caller's latest request.
+    finish_tags: HashMap<String, f64>,
+    virtual_time: f64,
+    seq: u64,
+    avg_wait_ms: f64,
+    avg_service_ms: f64,
+    served: u64,
+    completed: u64,
+    rejected: u64,
+}
+
+impl State {
+    /// Give `caller`'s next request its start tag.
+    fn tag(&mut self, caller: &str, weight: u32) -> f64 {
+        let last = self.finish_tags.get(caller).copied().unwrap_or(0.0);
+        let start = self.virtual_time.max(last);
+        self.finish_tags.insert(caller.to_string(), start + 1.0 / f64::from(weight.max(1)));
+        // Tags of callers that fell behind the clock no longer matter.
+        if self.finish_tags.len() > 1024 {
+            let now = self.virtual_time;
+            self.finish_tags.retain(|_, tag| *tag > now);
+        }
+        start
+    }
+
+    fn forget_queued(&mut self, caller: &str) {
+        if let Some(n) = self.queued.get_mut(caller) {
+            *n -= 1;
+            if *n == 0 {
+                self.queued.remove(caller);
+            }
+        }
+    }
+
+    /// Drop waiters whose request went away.
+    fn prune(&mut self) {
+        let (live, gone): (Vec<_>, Vec<_>) = std::mem::take(&mut self.waiting)
+            .into_iter()
+            .partition(|w| !w.tx.is_closed());
+        for waiter in gone {
+            self.forget_queued(&waiter.caller);
+        }
+        self.waiting = live.into();
+    }
+
+    fn retry_after(&self, capacity: usize) -> u64 {
+        let ahead = (self.waiting.len() + 1) as f64 / capacity.max(1) as f64;
+        ((self.avg_service_ms * ahead / 1000.0).ceil() as u64).clamp(1, 60)
+    }
+}
+
+/// Exponential moving average; the first sample sets it.
+fn ewma(avg: f64, sample: f64, served: u64) -> f64 {
+    if served == 0 {
+        sample
+    } else {
+        avg * 0.8 + sample * 0.2
+    }
+}
+
+pub(super) struct FairQueue {
+    limits: QueueLimits,
+    state: Mutex<State>,
+}
+
+/// The right to send one request upstream; freed (and handed to the next
+/// waiter) on drop.
+pub(super) struct Slot {
+    queue: Arc<FairQueue>,
+    since: Instant,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        let service_ms = self.since.elapsed().as_secs_f64() * 1000.0;
+        self.queue.release(service_ms);
+    }
+}
+
+impl FairQueue {
+    pub fn new(limits: QueueLimits) -> Arc<Self> {
+        Arc::new(Self { limits, state: Mutex::default() })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wait for a slot for `caller`.
+    pub async fn acquire(self: &Arc<Self>, caller: &str, weight: u32) -> Result<Slot, Busy> {
+        let rx = {
+            let mut st = self.lock();
+            if st.running < self.limits.capacity.max(1) && st.waiting.is_empty() {
+                let start_tag = st.tag(caller, weight);
+                st.running += 1;
+                st.virtual_time = start_tag;
+                st.avg_wait_ms = ewma(st.avg_wait_ms, 0.0, st.served);
+                st.served += 1;
+                return Ok(Slot { queue: self.clone(), since: Instant::now() });
+            }
+
+            if st.waiting.len() >= self.limits.max_queued {
+                st.prune();
+            }
+            let full = if st.waiting.len() >= self.limits.max_queued {
+                Some("queue full")
+            } else if st.queued.get(caller).copied().unwrap_or(0) >= self.limits.max_queued_per_caller {
+                Some("too many queued requests")
+            } else {
+                None
+            };
+            if let Some(message) = full {
+                st.rejected += 1;
+                return Err(Busy { message, retry_after: st.retry_after(self.limits.capacity) });
+            }
+
+            let start_tag = st.tag(caller, weight);
+            let (tx, rx) = oneshot::channel();
+            st.seq += 1;
+            let seq = st.seq;
+            let enqueued = Instant::now();
+            st.waiting.push(Waiter { start_tag, seq, caller: caller.to_string(), enqueued, tx });
+            *st.queued.entry(caller.to_string()).or_default() += 1;
+            rx
+        };
+
+        match tokio::time::timeout(self.limits.max_wait, rx).await {
+            Ok(Ok(slot)) => Ok(slot),
+            // Timed out; the receiver is gone, so pruning drops our waiter.
+            _ => {
+                let mut st = self.lock();
+                st.prune();
+                st.rejected += 1;
+                let retry_after = st.retry_after(self.limits.capacity);
+                Err(Busy { message: "timed out waiting in queue", retry_after })
+            }
+        }
+    }
+
+    fn release(self: &Arc<Self>, service_ms: f64) {
+        let next = {
+            let mut st = self.lock();
+            st.running = st.running.saturating_sub(1);
+            st.avg_service_ms = ewma(st.avg_service_ms, service_ms, st.completed);
+            st.completed += 1;
+            let mut next = st.waiting.pop();
+            while let Some(gone) = next.take_if(|w| w.tx.is_closed()) {
+                st.forget_queued(&gone.caller);
+                next = st.waiting.pop();
+            }
+            if let Some(waiter) = &next {
+                let caller = waiter.caller.clone();
+                st.forget_queued(&caller);
+                st.running += 1;
+                st.virtual_time = waiter.start_tag;
+                let waited = waiter.enqueued.elapsed().as_secs_f64() * 1000.0;
+                st.avg_wait_ms = ewma(st.avg_wait_ms, waited, st.served);
+                st.served += 1;
+            }
+            next
+        };
+        if let Some(waiter) = next {
+            // If the waiter went away just now, the returned slot is dropped
+            // here, which hands it on to the next one.
+            let _ = waiter.tx.send(Slot { queue: self.clone(), since: Instant::now() });
+        }
+    }
+
+    pub fn status(&self) -> QueueStatus {
+        let st = self.lock();
+        QueueStatus {
+            running: st.running,
+            capacity: self.limits.capacity,
+            queued: st.waiting.len(),
+            max_queued: self.limits.max_queued,
+            queued_callers: st.queued.len(),
+            avg_wait_ms: st.avg_wait_ms.round() as u64,
+            oldest_wait_ms: st
+                .waiting
+                .iter()
+                .map(|w| w.enqueued.elapsed().as_millis() as u64)
+                .max()
+                .unwrap_or(0),
+            avg_service_ms: st.avg_service_ms.round() as u64,
+            served: st.served,
+            rejected: st.rejected,
+        }
+    }
+}
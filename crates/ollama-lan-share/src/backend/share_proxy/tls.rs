@@ -0,0 +1,296 @@
+//! Optional TLS for the share proxy.
+//!
+//! Each node generates a self-signed certificate on first run and keeps it
+//! next to its other state. Peers don't check it against a CA; they pin its
+//! SHA-256 fingerprint, which the node advertises in its discovery offer (and
+//! so under the group's MAC), the first time they see it ([`TofuPins`]).
+//!
+//! The proxy also asks callers for a certificate without requiring one. A
+//! caller whose certificate fingerprint matches a client token's
+//! `cert_fingerprint` or one of the proxy's `trusted_certs` needs no key.
+
+use axum::{extract::ConnectInfo, Extension, Router};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::service::TowerToHyperService;
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::server::{ClientCertVerified, ClientCertVerifier};
+use rustls::{Certificate, DistinguishedName, Error as TlsError, PrivateKey, ServerName};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+
+pub const CERT_FILE: &str = "share-proxy.crt";
+pub const KEY_FILE: &str = "share-proxy.key";
+
+/// A certificate and its key, used to serve the proxy and to present to
+/// other nodes' proxies.
+#[derive(Clone)]
+pub struct TlsIdentity {
+    cert_pem: String,
+    key_pem: String,
+    cert: Certificate,
+    key: PrivateKey,
+    fingerprint: String,
+}
+
+impl std::fmt::Debug for TlsIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsIdentity").field("fingerprint", &self.fingerprint).finish_non_exhaustive()
+    }
+}
+
+impl TlsIdentity {
+    /// A fresh self-signed certificate, kept only in memory.
+    pub fn generate() -> Result<Self, String> {
+        let mut names = vec!["localhost".to_string()];
+        if let Ok(host) = std::env::var("HOSTNAME").or_else(|_| std::env::var("COMPUTERNAME")) {
+            names.push(host);
+        }
+        let cert = rcgen::generate_simple_self_signed(names)
+            .map_err(|e| format!("Failed to generate certificate: {e}"))?;
+        let cert_pem = cert
+            .serialize_pem()
+            .map_err(|e| format!("Failed to encode certificate: {e}"))?;
+        Self::from_pem(&cert_pem, &cert.serialize_private_key_pem())
+    }
+
+    /// The certificate in `dir`, generating and saving one on first run.
+    pub fn load_or_generate(dir: &Path) -> Result<Self, String> {
+        let (cert_path, key_path) = (dir.join(CERT_FILE), dir.join(KEY_FILE));
+        if cert_path.exists() || key_path.exists() {
+            let read = |path: &Path| {
+                std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))
+            };
+            return Self::from_pem(&read(&cert_path)?, &read(&key_path)?);
+        }
+
+        let identity = Self::generate()?;
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+        std::fs::write(&cert_path, &identity.cert_pem)
+            .map_err(|e| format!("Failed to write {}: {e}", cert_path.display()))?;
+        write_private(&key_path, &identity.key_pem)?;
+        tracing::info!(
+            "Generated share proxy certificate {} (fingerprint {})",
+            cert_path.display(),
+            identity.fingerprint
+        );
+        Ok(identity)
+    }
+
+    pub fn from_pem(cert_pem: &str, key_pem: &str) -> Result<Self, String> {
+        let cert = rustls_pemfile::certs(&mut cert_pem.as_bytes())
+            .ok()
+            .and_then(|certs| certs.into_iter().next())
+            .ok_or("No certificate in PEM")?;
+        let key = rustls_pemfile::pkcs8_private_keys(&mut key_pem.as_bytes())
+            .ok()
+            .and_then(|keys| keys.into_iter().next())
+            .ok_or("No PKCS#8 private key in PEM")?;
+        Ok(Self {
+            cert_pem: cert_pem.to_string(),
+            key_pem: key_pem.to_string(),
+            fingerprint: fingerprint(&cert),
+            cert: Certificate(cert),
+            key: PrivateKey(key),
+        })
+    }
+
+    /// Lowercase hex SHA-256 of the DER certificate.
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    pub fn cert_pem(&self) -> &str {
+        &self.cert_pem
+    }
+
+    fn server_config(&self) -> Result<Arc<rustls::ServerConfig>, String> {
+        let config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(Arc::new(OptionalClientCert))
+            .with_single_cert(vec![self.cert.clone()], self.key.clone())
+            .map_err(|e| format!("Invalid share proxy certificate: {e}"))?;
+        Ok(Arc::new(config))
+    }
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &str) -> Result<(), String> {
+    use std::io::Write as _;
+    use std::os::unix::fs::OpenOptionsExt as _;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+        .and_then(|mut f| f.write_all(contents.as_bytes()))
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &str) -> Result<(), String> {
+    std::fs::write(path, contents).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+/// Lowercase hex SHA-256 of a DER certificate.
+pub fn fingerprint(der: &[u8]) -> String {
+    hex::encode(Sha256::digest(der))
+}
+
+/// `fingerprint` as typed by a person: colons and case don't matter.
+pub fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint.trim().replace(':', "").to_ascii_lowercase()
+}
+
+/// Make `builder` talk TLS to a proxy whose certificate has fingerprint
+/// `pin`, presenting `identity` as the client certificate if given. Host
+/// names are not checked; the pin is the only trust.
+pub fn pinned_client(
+    builder: reqwest::ClientBuilder,
+    pin: &str,
+    identity: Option<&TlsIdentity>,
+) -> Result<reqwest::ClientBuilder, String> {
+    let config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(PinnedServer(normalize_fingerprint(pin))));
+    let config = match identity {
+        Some(identity) => config
+            .with_client_auth_cert(vec![identity.cert.clone()], identity.key.clone())
+            .map_err(|e| format!("Invalid client certificate: {e}"))?,
+        None => config.with_no_client_auth(),
+    };
+    Ok(builder.use_preconfigured_tls(config))
+}
+
+struct PinnedServer(String);
+
+impl ServerCertVerifier for PinnedServer {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, TlsError> {
+        if fingerprint(&end_entity.0) == self.0 {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(TlsError::General("certificate does not match the pinned fingerprint".into()))
+        }
+    }
+}
+
+/// Asks for a client certificate but lets callers without one through; the
+/// proxy decides what a fingerprint is allowed to do. rustls still checks
+/// that the caller holds the certificate's key.
+struct OptionalClientCert;
+
+impl ClientCertVerifier for OptionalClientCert {
+    fn client_auth_mandatory(&self) -> bool {
+        false
+    }
+
+    fn client_auth_root_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _now: SystemTime,
+    ) -> Result<ClientCertVerified, TlsError> {
+        Ok(ClientCertVerified::assertion())
+    }
+}
+
+/// Fingerprint of the certificate the caller presented, if any.
+#[derive(Clone, Debug)]
+pub(super) struct PeerCertificate(pub Option<String>);
+
+/// Serve `app` over TLS on `listener`, adding each connection's address and
+/// client certificate to its requests.
+pub(super) async fn serve(listener: TcpListener, identity: &TlsIdentity, app: Router) -> Result<(), String> {
+    let acceptor = TlsAcceptor::from(identity.server_config()?);
+    loop {
+        let (tcp, remote) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                tracing::debug!("Share proxy accept failed: {e}");
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+        tokio::spawn(async move {
+            let tls = match tokio::time::timeout(Duration::from_secs(10), acceptor.accept(tcp)).await {
+                Ok(Ok(tls)) => tls,
+                Ok(Err(e)) => return tracing::debug!("TLS handshake with {remote} failed: {e}"),
+                Err(_) => return tracing::debug!("TLS handshake with {remote} timed out"),
+            };
+            let cert = tls
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .map(|cert| fingerprint(&cert.0));
+            let app = app
+                .layer(Extension(ConnectInfo::<SocketAddr>(remote)))
+                .layer(Extension(PeerCertificate(cert)));
+            let served = auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(tls), TowerToHyperService::new(app))
+                .await;
+            if let Err(e) = served {
+                tracing::debug!("Share proxy connection from {remote} ended: {e}");
+            }
+        });
+    }
+}
+
+/// What [`TofuPins::check`] found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PinCheck {
+    /// First time this peer was seen; its fingerprint is pinned now.
+    New,
+    Known,
+    /// The peer now shows a different certificate than the pinned one.
+    Changed { pinned: String },
+}
+
+/// Certificate fingerprints pinned on first use, by peer name (node ids
+/// change on every start). A changed certificate is refused until the pin is
+/// [forgotten](Self::forget).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TofuPins(BTreeMap<String, String>);
+
+impl TofuPins {
+    pub fn check(&mut self, peer: &str, fingerprint: &str) -> PinCheck {
+        let fingerprint = normalize_fingerprint(fingerprint);
+        match self.0.get(peer) {
+            Some(pinned) if *pinned == fingerprint => PinCheck::Known,
+            Some(pinned) => PinCheck::Changed { pinned: pinned.clone() },
+            None => {
+                self.0.insert(peer.to_string(), fingerprint);
+                PinCheck::New
+            }
+        }
+    }
+
+    pub fn get(&self, peer: &str) -> Option<&str> {
+        self.0.get(peer).map(String::as_str)
+    }
+
+    pub fn forget(&mut self, peer: &str) -> bool {
+        self.0.remove(peer).is_some()
+    }
+}
@@ -9,12 +9,15 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use uuid::Uuid;
-use ollama_lan_share::backend::{NetworkDiscovery, OllamaManager, OllamaModel};
+use ollama_lan_share::backend::ollama_client::{ChatMessage, ChatSession};
+use ollama_lan_share::backend::network_discovery::{generate_group_secret, group_invite, parse_group_invite};
+use ollama_lan_share::backend::share_proxy::tls::{self, PinCheck, TlsIdentity, TofuPins};
+use ollama_lan_share::backend::share_proxy::{local_ip_best_effort, DEFAULT_PROXY_PORT, SHARE_KEY_HEADER};
+use ollama_lan_share::backend::{NetworkDiscovery, OllamaManager, OllamaModel, ProxyConfig, ProxyOptions, ShareProxy};
 use ollama_lan_share::shared::models::{PeerStatus, VgaError};
 
-const PROXY_PORT: u16 = 11435;
+const PROXY_PORT: u16 = DEFAULT_PROXY_PORT;
 const OLLAMA_PORT: u16 = 11434;
-const SHARE_KEY_HEADER: &str = "x-vas-key";
 
 fn main() -> eframe::Result<()> {
     tracing_subscriber::fmt()
@@ -46,10 +49,22 @@ struct PersistedGuiState {
     share_key: Option<String>,
     #[serde(default)]
     require_share_key: bool,
+    /// Serve the share proxy over HTTPS.
+    #[serde(default)]
+    use_tls: bool,
+    /// Peers' certificate fingerprints, pinned on first use.
+    #[serde(default)]
+    tls_pins: TofuPins,
     #[serde(default)]
     chat_key: Option<String>,
     my_groups: Vec<String>,
     group_names: BTreeMap<String, String>,
+    /// Secrets of keyed groups, by group id. Groups without one are open.
+    #[serde(default)]
+    group_keys: BTreeMap<String, String>,
+    /// Only let members of keyed groups see our offer.
+    #[serde(default)]
+    seal_offer: bool,
 }
 
 fn gui_state_path() -> PathBuf {
@@ -61,6 +76,22 @@ fn gui_state_path() -> PathBuf {
     base
 }
 
+/// This node's share proxy certificate, created on first use.
+fn tls_dir() -> PathBuf {
+    let mut base = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    base.push("vault");
+    base.push("ollama-lan-share-tls");
+    base
+}
+
+/// Group chat histories, one `<group id>.json` per group.
+fn chat_sessions_dir() -> PathBuf {
+    let mut base = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    base.push("vault");
+    base.push("ollama-lan-share-chats");
+    base
+}
+
 struct OllamaLanShareApp {
     runtime: tokio::runtime::Runtime,
     discovery: Arc<NetworkDiscovery>,
@@ -73,12 +104,19 @@ struct OllamaLanShareApp {
     share_key: String,
     edit_share_key: String,
 
+    use_tls: bool,
+    /// Loaded on first use; also presented as our client certificate.
+    tls_identity: Option<TlsIdentity>,
+    tls_pins: TofuPins,
+
     chat_key: String,
     edit_chat_key: String,
 
     // LAN groups
     my_groups: Vec<String>,
     group_names: BTreeMap<String, String>,
+    group_keys: BTreeMap<String, String>,
+    seal_offer: bool,
     active_group: Option<String>,
 
     active_tab: ActiveTab,
@@ -86,9 +124,11 @@ struct OllamaLanShareApp {
     create_group_name: String,
     pending_create_group_id: Option<String>,
     pending_create_group_name: Option<String>,
+    pending_create_group_secret: Option<String>,
 
     join_group_id: String,
     pending_join_group_id: Option<String>,
+    pending_join_group_secret: Option<String>,
 
     show_create_group_ui: bool,
     show_join_group_ui: bool,
@@ -102,7 +142,7 @@ struct OllamaLanShareApp {
     models: Vec<String>,
     selected: BTreeSet<String>,
 
-    proxy: Option<Arc<ProxyState>>,
+    proxy: Option<ShareProxy>,
 
     // Chat
     chat_group_id: Option<String>,
@@ -116,7 +156,8 @@ struct OllamaLanShareApp {
     chat_model: String,
 
     chat_prompt: String,
-    chat_transcript: String,
+    /// Conversation per group id, loaded from disk on first use.
+    chat_sessions: BTreeMap<String, ChatSession>,
 
     // Round-robin + per-peer queue
     chat_rr_index_by_group: BTreeMap<String, usize>,
@@ -134,33 +175,28 @@ struct OllamaLanShareApp {
     lang: UiLang,
 }
 
-struct ProxyState {
-    cfg: tokio::sync::RwLock<ProxyConfig>,
-    sem: tokio::sync::Semaphore,
-    client: reqwest::Client,
-}
-
-#[derive(Clone, Debug, Default)]
-struct ProxyConfig {
-    enabled: bool,
-    key: String,
-    allowed_models: BTreeSet<String>,
-}
-
 #[derive(Debug, Clone)]
 struct ChatJob {
     job_id: u64,
+    group_id: String,
     peer_id: String,
     who: String,
     base_url: String,
+    /// Pinned certificate fingerprint for an `https` base URL.
+    tls_pin: Option<String>,
+    /// Presented as our client certificate to TLS peers.
+    identity: Option<TlsIdentity>,
     model: String,
     prompt: String,
+    /// History sent with the prompt, ending with it.
+    messages: Vec<ChatMessage>,
     key: String,
 }
 
 #[derive(Debug, Clone)]
 struct ChatResult {
     job_id: u64,
+    group_id: String,
     peer_id: String,
     who: String,
     model: String,
@@ -211,11 +247,17 @@ impl OllamaLanShareApp {
             share_key: String::new(),
             edit_share_key: String::new(),
 
+            use_tls: false,
+            tls_identity: None,
+            tls_pins: TofuPins::default(),
+
             chat_key: String::new(),
             edit_chat_key: String::new(),
 
             my_groups: Vec::new(),
             group_names: BTreeMap::new(),
+            group_keys: BTreeMap::new(),
+            seal_offer: false,
             active_group: None,
 
             active_tab: ActiveTab::Groups,
@@ -223,9 +265,11 @@ impl OllamaLanShareApp {
             create_group_name: String::new(),
             pending_create_group_id: None,
             pending_create_group_name: None,
+            pending_create_group_secret: None,
 
             join_group_id: String::new(),
             pending_join_group_id: None,
+            pending_join_group_secret: None,
 
             show_create_group_ui: false,
             show_join_group_ui: false,
@@ -247,7 +291,7 @@ impl OllamaLanShareApp {
             chat_model_auto: true,
             chat_model: String::new(),
             chat_prompt: String::new(),
-            chat_transcript: String::new(),
+            chat_sessions: BTreeMap::new(),
 
             status: String::new(),
             last_error: None,
@@ -290,6 +334,7 @@ impl OllamaLanShareApp {
                 });
                 self.peers = peers;
                 self.last_peer_refresh = Instant::now();
+                self.pin_peer_certs();
             }
             Err(err) => {
                 self.last_error = Some(format!(
@@ -339,6 +384,7 @@ impl OllamaLanShareApp {
         let mut groups = self.my_groups.clone();
         groups.sort();
         groups.dedup();
+        let keys = self.group_keys.clone();
 
         let discovery = self.discovery.clone();
         let res = self.runtime.block_on(async move {
@@ -346,6 +392,7 @@ impl OllamaLanShareApp {
                 discovery.set_local_node_name(name).await;
             }
             discovery.set_local_groups(groups).await;
+            discovery.set_group_keys(keys).await;
         });
         let _ = res;
 
@@ -373,6 +420,8 @@ impl OllamaLanShareApp {
         };
 
         self.require_share_key = state.require_share_key;
+        self.use_tls = state.use_tls;
+        self.tls_pins = state.tls_pins;
         if let Some(key) = state.share_key.clone() {
             self.share_key = key;
             self.edit_share_key = self.share_key.clone();
@@ -395,9 +444,13 @@ impl OllamaLanShareApp {
         self.my_groups.dedup();
 
         self.group_names = state.group_names;
+        self.group_keys = state.group_keys;
+        self.group_keys.retain(|g, _| self.my_groups.contains(g));
+        self.seal_offer = state.seal_offer;
 
         // Push loaded settings into discovery so we actually broadcast them.
         let groups = self.my_groups.clone();
+        let keys = self.group_keys.clone();
         let name = self.edit_name.trim().to_string();
         let discovery = self.discovery.clone();
         let _ = self.runtime.block_on(async move {
@@ -405,6 +458,7 @@ impl OllamaLanShareApp {
                 discovery.set_local_node_name(name).await;
             }
             discovery.set_local_groups(groups).await;
+            discovery.set_group_keys(keys).await;
         });
         self.discovery.broadcast_presence();
     }
@@ -418,9 +472,13 @@ impl OllamaLanShareApp {
             local_name: Some(self.edit_name.trim().to_string()).filter(|s| !s.is_empty()),
             share_key: Some(self.share_key.trim().to_string()).filter(|s| !s.is_empty()),
             require_share_key: self.require_share_key,
+            use_tls: self.use_tls,
+            tls_pins: self.tls_pins.clone(),
             chat_key: Some(self.chat_key.trim().to_string()).filter(|s| !s.is_empty()),
             my_groups: self.my_groups.clone(),
             group_names: self.group_names.clone(),
+            group_keys: self.group_keys.clone(),
+            seal_offer: self.seal_offer,
         };
         match serde_json::to_string_pretty(&state) {
             Ok(json) => {
@@ -454,6 +512,7 @@ impl OllamaLanShareApp {
         let id = Uuid::new_v4().to_string();
         self.pending_create_group_id = Some(id);
         self.pending_create_group_name = Some(name);
+        self.pending_create_group_secret = Some(generate_group_secret());
     }
 
     fn confirm_create_group(&mut self) {
@@ -464,6 +523,9 @@ impl OllamaLanShareApp {
         if let Some(name) = self.pending_create_group_name.take() {
             self.group_names.insert(id.clone(), name);
         }
+        if let Some(secret) = self.pending_create_group_secret.take() {
+            self.group_keys.insert(id.clone(), secret);
+        }
 
         if !self.my_groups.contains(&id) {
             self.my_groups.push(id.clone());
@@ -481,18 +543,23 @@ impl OllamaLanShareApp {
 
     fn start_join_group(&mut self) {
         self.last_error = None;
-        let id = self.join_group_id.trim().to_string();
+        let (id, secret) = parse_group_invite(&self.join_group_id);
         if id.is_empty() {
-            self.last_error = Some(self.tr("请输入 Group ID", "Enter a Group ID").to_string());
+            self.last_error = Some(self.tr("请输入邀请码", "Enter an invite code").to_string());
             return;
         }
         self.pending_join_group_id = Some(id);
+        self.pending_join_group_secret = secret;
     }
 
     fn confirm_join_group(&mut self) {
         let Some(id) = self.pending_join_group_id.take() else {
             return;
         };
+        if let Some(secret) = self.pending_join_group_secret.take() {
+            self.group_keys.insert(id.clone(), secret);
+        }
+        self.join_group_id.clear();
 
         if !self.my_groups.contains(&id) {
             self.my_groups.push(id.clone());
@@ -509,6 +576,7 @@ impl OllamaLanShareApp {
     fn leave_group(&mut self, group_id: &str) {
         self.last_error = None;
         self.my_groups.retain(|g| g != group_id);
+        self.group_keys.remove(group_id);
         if self.active_group.as_deref() == Some(group_id) {
             self.active_group = self.my_groups.first().cloned();
             self.chat_target_peer_id = None;
@@ -573,7 +641,7 @@ impl OllamaLanShareApp {
 
     fn local_ollama_base_url(&self) -> String {
         // Best-effort: compute LAN-reachable URL so peers can call us.
-        let ip = self.runtime.block_on(async { get_local_ip_best_effort().await });
+        let ip = self.runtime.block_on(async { local_ip_best_effort().await });
         match ip {
             Some(ip) => format!("http://{ip}:{OLLAMA_PORT}"),
             None => format!("http://localhost:{OLLAMA_PORT}"),
@@ -581,162 +649,155 @@ impl OllamaLanShareApp {
     }
 
     fn local_proxy_base_url(&self) -> String {
-        let ip = self.runtime.block_on(async { get_local_ip_best_effort().await });
+        let ip = self.runtime.block_on(async { local_ip_best_effort().await });
+        let scheme = if self.use_tls { "https" } else { "http" };
         match ip {
-            Some(ip) => format!("http://{ip}:{PROXY_PORT}"),
-            None => format!("http://localhost:{PROXY_PORT}"),
+            Some(ip) => format!("{scheme}://{ip}:{PROXY_PORT}"),
+            None => format!("{scheme}://localhost:{PROXY_PORT}"),
+        }
+    }
+
+    /// Peers call the proxy rather than Ollama when it checks a key or serves TLS.
+    fn shares_through_proxy(&self) -> bool {
+        self.require_share_key || self.use_tls
+    }
+
+    fn tls_identity(&mut self) -> Option<TlsIdentity> {
+        if self.tls_identity.is_none() {
+            match TlsIdentity::load_or_generate(&tls_dir()) {
+                Ok(identity) => self.tls_identity = Some(identity),
+                Err(e) => tracing::warn!("{e}"),
+            }
         }
+        self.tls_identity.clone()
     }
 
     fn ensure_proxy_started(&mut self) {
-        if self.proxy.is_some() {
+        // Switching TLS on or off needs a new listener.
+        if let Some(proxy) = &self.proxy {
+            if proxy.tls_fingerprint().is_some() == self.use_tls {
+                return;
+            }
+            if let Some(proxy) = self.proxy.take() {
+                proxy.shutdown();
+            }
+        }
+        let tls = if self.use_tls { self.tls_identity() } else { None };
+        if self.use_tls && tls.is_none() {
+            self.last_error = Some(self.tr("无法创建 TLS 证书", "Failed to create the TLS certificate").to_string());
             return;
         }
-
-        let state = Arc::new(ProxyState {
-            cfg: tokio::sync::RwLock::new(ProxyConfig::default()),
-            sem: tokio::sync::Semaphore::new(4),
-            client: reqwest::Client::builder()
-                .redirect(reqwest::redirect::Policy::none())
-                .connect_timeout(Duration::from_secs(5))
-                .timeout(Duration::from_secs(300)) // 5 minutes max for LLM generation
-                .pool_idle_timeout(Duration::from_secs(90))
-                .build()
-                .expect("failed to create proxy client"),
-        });
-
-        let state_for_task = state.clone();
-        self.runtime.spawn(async move {
-            use axum::{
-                extract::State,
-                extract::DefaultBodyLimit,
-                http::{HeaderMap, StatusCode},
-                routing::post,
-                Json, Router,
-            };
-
-            fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
-                if a.len() != b.len() {
-                    return false;
-                }
-                let mut out = 0u8;
-                for i in 0..a.len() {
-                    out |= a[i] ^ b[i];
+        let options = ProxyOptions { tls, ..ProxyOptions::default() };
+        // The old listener closes asynchronously; give it a moment before rebinding.
+        let started = self.runtime.block_on(async {
+            let mut started = ShareProxy::start(options.clone(), ProxyConfig::default()).await;
+            for _ in 0..10 {
+                if started.is_ok() {
+                    break;
                 }
-                out == 0
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                started = ShareProxy::start(options.clone(), ProxyConfig::default()).await;
             }
+            started
+        });
+        match started {
+            Ok(proxy) => self.proxy = Some(proxy),
+            Err(e) => tracing::warn!("{e}"),
+        }
+    }
 
-            async fn handler(
-                State(state): State<Arc<ProxyState>>,
-                headers: HeaderMap,
-                Json(body): Json<serde_json::Value>,
-            ) -> (StatusCode, Json<serde_json::Value>) {
-                // Limit concurrent work to avoid overwhelming the local Ollama.
-                let _permit = match state.sem.acquire().await {
-                    Ok(p) => p,
-                    Err(_) => {
-                        return (
-                            StatusCode::SERVICE_UNAVAILABLE,
-                            Json(serde_json::json!({"error": "busy"})),
-                        )
-                    }
-                };
-
-                let cfg = state.cfg.read().await.clone();
-                if !cfg.enabled {
-                    return (StatusCode::NOT_FOUND, Json(serde_json::json!({"error": "not sharing"})));
-                }
-
-                // Only support non-streaming chat.
-                let stream = body.get("stream").and_then(|v| v.as_bool()).unwrap_or(false);
-                if stream {
-                    return (
-                        StatusCode::BAD_REQUEST,
-                        Json(serde_json::json!({"error": "stream not supported"})),
-                    );
-                }
-
-                if !cfg.key.is_empty() {
-                    let given = headers
-                        .get(SHARE_KEY_HEADER)
-                        .and_then(|v| v.to_str().ok())
-                        .unwrap_or("");
-                    if !constant_time_eq(given.as_bytes(), cfg.key.as_bytes()) {
-                        return (
-                            StatusCode::UNAUTHORIZED,
-                            Json(serde_json::json!({"error": "unauthorized"})),
-                        );
-                    }
-                }
+    fn configure_proxy(&mut self, enabled: bool, key: String, allowed_models: BTreeSet<String>) {
+        self.ensure_proxy_started();
+        let Some(proxy) = &self.proxy else {
+            return;
+        };
+        let trusted_certs = self.trusted_peer_certs();
+        self.runtime.block_on(proxy.configure(ProxyConfig {
+            enabled,
+            key,
+            allowed_models,
+            trusted_certs,
+            ..ProxyConfig::default()
+        }));
+    }
 
-                let model = body
-                    .get("model")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("");
-                if model.is_empty() {
-                    return (
-                        StatusCode::BAD_REQUEST,
-                        Json(serde_json::json!({"error": "missing model"})),
-                    );
-                }
-                if !cfg.allowed_models.contains(model) {
-                    return (
-                        StatusCode::FORBIDDEN,
-                        Json(serde_json::json!({"error": "model not allowed"})),
-                    );
-                }
+    /// Name a peer's certificate is pinned under.
+    fn pin_name(peer: &PeerStatus) -> String {
+        match (&peer.name, peer_ip_from_status(peer)) {
+            (Some(name), _) if !name.trim().is_empty() => name.clone(),
+            (_, Some(ip)) => ip.to_string(),
+            _ => peer.id.clone(),
+        }
+    }
 
-                // Forward only /api/chat (stream=false)
-                let url = format!("http://127.0.0.1:{}/api/chat", OLLAMA_PORT);
-                let resp = state.client.post(url).json(&body).send().await;
-                match resp {
-                    Ok(r) => {
-                        let status = StatusCode::from_u16(r.status().as_u16())
-                            .unwrap_or(StatusCode::BAD_GATEWAY);
-                        match r.json::<serde_json::Value>().await {
-                            Ok(v) => (status, Json(v)),
-                            Err(e) => (
-                                StatusCode::BAD_GATEWAY,
-                                Json(serde_json::json!({"error": format!("bad upstream json: {e}")})),
-                            ),
-                        }
-                    }
-                    Err(e) => (
-                        StatusCode::BAD_GATEWAY,
-                        Json(serde_json::json!({"error": format!("upstream error: {e}")})),
-                    ),
+    /// Pin the certificates of members of our groups the first time we see
+    /// them, and let members with a pinned certificate call our proxy
+    /// without the key.
+    fn pin_peer_certs(&mut self) {
+        let mut pinned_new = false;
+        for peer in &self.peers {
+            if !peer.groups.iter().any(|g| self.my_groups.contains(g)) {
+                continue;
+            }
+            let Some(fingerprint) = peer.ollama.as_ref().and_then(|o| o.tls_fingerprint.as_deref()) else {
+                continue;
+            };
+            match self.tls_pins.check(&Self::pin_name(peer), fingerprint) {
+                PinCheck::New => pinned_new = true,
+                PinCheck::Known => {}
+                PinCheck::Changed { .. } => {
+                    tracing::warn!("Certificate of {} changed; not trusting it", Self::pin_name(peer));
                 }
             }
+        }
+        if pinned_new {
+            self.save_persisted_state();
+        }
 
-            let app = Router::new()
-                .route("/api/chat", post(handler))
-                .layer(DefaultBodyLimit::max(256 * 1024))
-                .with_state(state_for_task);
-            let addr: SocketAddr = format!("0.0.0.0:{PROXY_PORT}").parse().expect("proxy addr");
-            let listener = match tokio::net::TcpListener::bind(addr).await {
-                Ok(l) => l,
-                Err(e) => {
-                    tracing::warn!("Failed to bind proxy {}: {e}", addr);
-                    return;
+        if let (Some(proxy), true) = (&self.proxy, self.sharing_active && self.use_tls) {
+            let trusted_certs = self.trusted_peer_certs();
+            self.runtime.block_on(async {
+                let config = proxy.config().await;
+                if config.trusted_certs != trusted_certs {
+                    proxy.configure(ProxyConfig { trusted_certs, ..config }).await;
                 }
-            };
-            let _ = axum::serve(listener, app).await;
-        });
+            });
+        }
+    }
 
-        self.proxy = Some(state);
+    /// Fingerprints of group members whose certificate matches its pin.
+    fn trusted_peer_certs(&self) -> BTreeSet<String> {
+        if !self.use_tls {
+            return BTreeSet::new();
+        }
+        self.peers
+            .iter()
+            .filter(|p| p.groups.iter().any(|g| self.my_groups.contains(g)))
+            .filter_map(|p| {
+                let fingerprint = tls::normalize_fingerprint(p.ollama.as_ref()?.tls_fingerprint.as_deref()?);
+                (self.tls_pins.get(&Self::pin_name(p)) == Some(fingerprint.as_str())).then_some(fingerprint)
+            })
+            .collect()
     }
 
-    fn configure_proxy(&mut self, enabled: bool, key: String, allowed_models: BTreeSet<String>) {
-        self.ensure_proxy_started();
-        let Some(state) = self.proxy.clone() else {
-            return;
+    /// Accept a member's new certificate after it changed (e.g. a reinstall).
+    fn trust_new_cert(&mut self, peer: &PeerStatus) {
+        let name = Self::pin_name(peer);
+        self.tls_pins.forget(&name);
+        if let Some(fingerprint) = peer.ollama.as_ref().and_then(|o| o.tls_fingerprint.as_deref()) {
+            self.tls_pins.check(&name, fingerprint);
+        }
+        self.save_persisted_state();
+    }
+
+    /// Keep the advertised proxy load current while sharing through it.
+    fn refresh_offer_load(&self) {
+        let load = match &self.proxy {
+            Some(proxy) if self.sharing_active && self.shares_through_proxy() => Some(proxy.status().offer_load()),
+            _ => None,
         };
-        self.runtime.block_on(async move {
-            let mut cfg = state.cfg.write().await;
-            cfg.enabled = enabled;
-            cfg.key = key;
-            cfg.allowed_models = allowed_models;
-        });
+        let discovery = self.discovery.clone();
+        self.runtime.block_on(async move { discovery.set_offer_load(load).await });
     }
 
     fn safe_remote_base_url(&self, peer: &PeerStatus) -> String {
@@ -745,13 +806,16 @@ impl OllamaLanShareApp {
         if let (Some(ip), Some(offer)) = (peer_ip, &peer.ollama) {
             if let Some(url) = &offer.base_url {
                 if let Ok(parsed) = url::Url::parse(url) {
-                    if parsed.scheme() == "http" {
+                    // https only with a certificate fingerprint to pin.
+                    let scheme = parsed.scheme();
+                    if scheme == "http" || (scheme == "https" && offer.tls_fingerprint.is_some()) {
                         if let Some(host) = parsed.host_str() {
+                            let host = host.trim_start_matches('[').trim_end_matches(']');
                             let host_ip = host.parse::<IpAddr>().ok();
                             if host_ip == Some(ip) {
                                 let port = parsed.port().unwrap_or(OLLAMA_PORT);
                                 let host_fmt = if host.contains(':') { format!("[{host}]") } else { host.to_string() };
-                                return format!("http://{host_fmt}:{port}");
+                                return format!("{scheme}://{host_fmt}:{port}");
                             }
                         }
                     }
@@ -796,18 +860,36 @@ impl OllamaLanShareApp {
             self.share_key = k.clone();
             self.edit_share_key = self.share_key.clone();
             self.configure_proxy(true, self.share_key.clone(), models.iter().cloned().collect());
+        } else if self.use_tls {
+            self.configure_proxy(true, String::new(), models.iter().cloned().collect());
         } else {
             self.configure_proxy(false, String::new(), BTreeSet::new());
         }
+        let tls_fingerprint = match (&self.proxy, self.use_tls) {
+            (Some(proxy), true) => proxy.tls_fingerprint().map(str::to_string),
+            _ => None,
+        };
+        if self.use_tls && tls_fingerprint.is_none() {
+            self.last_error.get_or_insert_with(|| {
+                tr_lang(self.lang, "TLS 代理未启动", "The TLS proxy did not start").to_string()
+            });
+            return;
+        }
 
-        let base_url = if self.require_share_key {
+        let via_proxy = self.shares_through_proxy();
+        let base_url = if via_proxy {
             self.local_proxy_base_url()
         } else {
             self.local_ollama_base_url()
         };
         let discovery = self.discovery.clone();
+        let require_key = self.require_share_key;
+        let seal_offer = self.seal_offer;
         self.runtime.block_on(async move {
+            discovery.set_seal_offer(seal_offer).await;
             discovery.set_ollama_offer(true, models, Some(base_url)).await;
+            discovery.set_offer_auth(require_key, via_proxy.then_some(PROXY_PORT)).await;
+            discovery.set_offer_tls(tls_fingerprint).await;
         });
         self.discovery.broadcast_presence();
 
@@ -826,6 +908,8 @@ impl OllamaLanShareApp {
         let discovery = self.discovery.clone();
         self.runtime.block_on(async move {
             discovery.set_ollama_offer(false, Vec::new(), Some(base_url)).await;
+            discovery.set_offer_auth(false, None).await;
+            discovery.set_offer_tls(None).await;
         });
         self.discovery.broadcast_presence();
 
@@ -841,7 +925,9 @@ impl OllamaLanShareApp {
 
                     match msg.result {
                         Ok(text) => {
-                            self.chat_transcript.push_str(&format!("\n{}:\n{}\n", msg.who, text));
+                            let session = self.chat_session(&msg.group_id);
+                            session.push_assistant(text, Some(msg.who.clone()));
+                            self.save_chat_session(&msg.group_id);
                         }
                         Err(err) => {
                             self.last_error = Some(format!(
@@ -879,17 +965,37 @@ impl OllamaLanShareApp {
             let url = format!("{}/api/chat", job.base_url);
             let req = serde_json::json!({
                 "model": job.model.clone(),
-                "messages": [{"role": "user", "content": job.prompt.clone()}],
+                "messages": job.messages.clone(),
                 "stream": false
             });
 
-            let client = reqwest::Client::builder()
+            let builder = reqwest::Client::builder()
                 .redirect(reqwest::redirect::Policy::none())
                 .connect_timeout(Duration::from_secs(5))
-                .timeout(Duration::from_secs(300))
-                .build()
-                .unwrap_or_else(|_| reqwest::Client::new());
-            
+                .timeout(Duration::from_secs(300));
+            let client = match &job.tls_pin {
+                // Never fall back to a client that accepts any certificate.
+                Some(pin) => tls::pinned_client(builder, pin, job.identity.as_ref()).and_then(|b| {
+                    b.build().map_err(|e| format!("Failed to create TLS client: {e}"))
+                }),
+                None => Ok(builder.build().unwrap_or_else(|_| reqwest::Client::new())),
+            };
+            let client = match client {
+                Ok(client) => client,
+                Err(err) => {
+                    let _ = tx.send(ChatResult {
+                        job_id: job.job_id,
+                        group_id: job.group_id.clone(),
+                        peer_id: job.peer_id.clone(),
+                        who: job.who.clone(),
+                        model: job.model.clone(),
+                        prompt: job.prompt.clone(),
+                        result: Err(err),
+                    });
+                    return;
+                }
+            };
+
             let mut builder = client.post(url).json(&req);
             if !job.key.is_empty() {
                 builder = builder.header(SHARE_KEY_HEADER, job.key.clone());
@@ -919,6 +1025,7 @@ impl OllamaLanShareApp {
 
             let _ = tx.send(ChatResult {
                 job_id: job.job_id,
+                group_id: job.group_id.clone(),
                 peer_id: job.peer_id.clone(),
                 who: job.who.clone(),
                 model: job.model.clone(),
@@ -928,6 +1035,46 @@ impl OllamaLanShareApp {
         });
     }
 
+    fn chat_session(&mut self, group_id: &str) -> &mut ChatSession {
+        if !self.chat_sessions.contains_key(group_id) {
+            let dir = chat_sessions_dir();
+            let fresh = ChatSession::new(group_id, self.group_display_name(group_id));
+            let session = if fresh.path_in(&dir).exists() {
+                ChatSession::load(&dir, group_id).unwrap_or_else(|err| {
+                    tracing::warn!("Starting a new chat for group {group_id}: {err}");
+                    fresh
+                })
+            } else {
+                fresh
+            };
+            self.chat_sessions.insert(group_id.to_string(), session);
+        }
+        self.chat_sessions.get_mut(group_id).expect("inserted above")
+    }
+
+    fn save_chat_session(&mut self, group_id: &str) {
+        let Some(session) = self.chat_sessions.get(group_id) else {
+            return;
+        };
+        if let Err(err) = session.save(&chat_sessions_dir()) {
+            tracing::warn!("Failed to save chat for group {group_id}: {err}");
+        }
+    }
+
+    /// Write the group's chat next to its JSON as Markdown.
+    fn export_chat_session(&mut self, group_id: &str) {
+        let session = self.chat_session(group_id);
+        let path = session.path_in(&chat_sessions_dir()).with_extension("md");
+        let markdown = session.to_markdown();
+        let result = std::fs::create_dir_all(chat_sessions_dir()).and_then(|_| std::fs::write(&path, markdown));
+        match result {
+            Ok(()) => self.status = format!("{} {}", self.tr("已导出", "Exported"), path.display()),
+            Err(err) => {
+                self.last_error = Some(format!("{}: {err}", self.tr("导出失败", "Export failed")));
+            }
+        }
+    }
+
     fn enqueue_chat_job(&mut self, job: ChatJob) {
         const MAX_QUEUE_PER_PEER: usize = 16;
         let q = self
@@ -942,10 +1089,7 @@ impl OllamaLanShareApp {
         q.push_back(job.clone());
 
         let pending = q.len();
-        self.chat_transcript.push_str(&format!(
-            "\n> queued → {} ({}) [{} pending]\n{}\n",
-            job.who, job.model, pending, job.prompt
-        ));
+        self.status = format!("queued → {} ({}) [{} pending]", job.who, job.model, pending);
 
         self.start_next_chat_job(&job.peer_id);
     }
@@ -995,6 +1139,12 @@ impl OllamaLanShareApp {
         }
 
         let chosen_peer = if self.chat_target_auto {
+            // Prefer the least busy members; peers that don't report load count as idle.
+            let pressure = |p: &PeerStatus| {
+                p.ollama.as_ref().and_then(|o| o.load).map_or(0.0, |l| l.pressure())
+            };
+            let least = candidates.iter().map(pressure).fold(f64::INFINITY, f64::min);
+            candidates.retain(|p| pressure(p) <= least);
             let idx = self
                 .chat_rr_index_by_group
                 .get(&group_id)
@@ -1027,17 +1177,48 @@ impl OllamaLanShareApp {
 
         let base_url = self.safe_remote_base_url(&chosen_peer);
 
-        let needs_key = base_url.ends_with(&format!(":{PROXY_PORT}"));
+        // Talk TLS only to the certificate pinned for this member.
+        let tls_pin = match offer.tls_fingerprint.as_deref() {
+            Some(fingerprint) if base_url.starts_with("https://") => {
+                match self.tls_pins.check(&Self::pin_name(&chosen_peer), fingerprint) {
+                    PinCheck::New => self.save_persisted_state(),
+                    PinCheck::Known => {}
+                    PinCheck::Changed { pinned } => {
+                        self.last_error = Some(format!(
+                            "{} ({} → {})",
+                            self.tr(
+                                "该组员的证书已变更，请确认后在组员列表中重新信任",
+                                "This member's certificate changed; confirm it and trust it again in the member list"
+                            ),
+                            &pinned[..16.min(pinned.len())],
+                            &fingerprint[..16.min(fingerprint.len())],
+                        ));
+                        return;
+                    }
+                }
+                Some(fingerprint.to_string())
+            }
+            _ => None,
+        };
+        let identity = if tls_pin.is_some() { self.tls_identity() } else { None };
+
+        // Older peers don't set `auth_required`; their proxy is on the fixed port.
+        let needs_key = offer.auth_required || base_url.ends_with(&format!(":{PROXY_PORT}"));
         let key = if needs_key {
             let k = self.edit_chat_key.trim().to_string();
             if k.is_empty() {
-                self.last_error = Some(self.tr("该组员需要密码", "This member requires a key").to_string());
-                return;
+                // Over TLS our certificate may be trusted instead of a key.
+                if identity.is_none() {
+                    self.last_error = Some(self.tr("该组员需要密码", "This member requires a key").to_string());
+                    return;
+                }
+                String::new()
+            } else {
+                self.chat_key = k.clone();
+                self.edit_chat_key = k;
+                self.save_persisted_state();
+                self.chat_key.clone()
             }
-            self.chat_key = k.clone();
-            self.edit_chat_key = k;
-            self.save_persisted_state();
-            self.chat_key.clone()
         } else {
             String::new()
         };
@@ -1060,13 +1241,22 @@ impl OllamaLanShareApp {
         self.chat_job_counter = self.chat_job_counter.saturating_add(1);
         let job_id = self.chat_job_counter;
 
+        let session = self.chat_session(&group_id);
+        session.push_user(prompt.clone());
+        let messages = session.context();
+        self.save_chat_session(&group_id);
+
         self.enqueue_chat_job(ChatJob {
             job_id,
+            group_id,
             peer_id: chosen_peer.id.clone(),
             who,
             base_url,
+            tls_pin,
+            identity,
             model,
             prompt: prompt.clone(),
+            messages,
             key,
         });
 
@@ -1134,6 +1324,7 @@ impl eframe::App for OllamaLanShareApp {
         }
 
         if self.last_peer_refresh.elapsed() >= Duration::from_secs(2) {
+            self.refresh_offer_load();
             self.refresh_peers();
         }
 
@@ -1164,7 +1355,7 @@ impl eframe::App for OllamaLanShareApp {
                         ui.close_menu();
                     }
                     ui.separator();
-                    ui.label(tr_lang(lang, "加入 Group ID", "Join Group ID"));
+                    ui.label(tr_lang(lang, "加入（邀请码）", "Join (invite code)"));
                     ui.text_edit_singleline(&mut self.join_group_id);
                     if ui.button(tr_lang(lang, "加入…", "Join…")).clicked() {
                         self.start_join_group();
@@ -1398,15 +1589,42 @@ impl eframe::App for OllamaLanShareApp {
                     {
                         self.send_chat();
                     }
-                    if ui.button(tr_lang(lang, "清空", "Clear")).clicked() {
-                        self.chat_transcript.clear();
+                    let group_id = self.chat_group_id.clone();
+                    if let Some(group_id) = &group_id {
+                        if ui.button(tr_lang(lang, "清空", "Clear")).clicked() {
+                            self.chat_session(group_id).clear();
+                            self.save_chat_session(group_id);
+                        }
+                        if ui.button(tr_lang(lang, "导出 Markdown", "Export Markdown")).clicked() {
+                            self.export_chat_session(group_id);
+                        }
                     }
                 });
 
+                let transcript: Vec<(String, String)> = match self.chat_group_id.clone() {
+                    Some(group_id) => self
+                        .chat_session(&group_id)
+                        .messages
+                        .iter()
+                        .map(|entry| {
+                            let who = match (&entry.author, entry.message.role.as_str()) {
+                                (Some(author), _) => author.clone(),
+                                (None, "user") => tr_lang(lang, "我", "You").to_string(),
+                                (None, role) => role.to_string(),
+                            };
+                            (who, entry.message.content.clone())
+                        })
+                        .collect(),
+                    None => Vec::new(),
+                };
                 egui::ScrollArea::vertical()
                     .id_source("chat_scroll_full")
+                    .stick_to_bottom(true)
                     .show(ui, |ui| {
-                        ui.monospace(&self.chat_transcript);
+                        for (who, text) in &transcript {
+                            ui.strong(who);
+                            ui.monospace(text);
+                        }
                     });
 
                 return;
@@ -1438,6 +1656,7 @@ impl eframe::App for OllamaLanShareApp {
                 ui.add_space(6.0);
                 ui.heading(tr_lang(lang, "组员", "Members"));
                 let members = self.group_members(&gid);
+                let mut trust_new = None;
                 egui::ScrollArea::vertical()
                     .id_source("members_scroll")
                     .max_height(150.0)
@@ -1471,11 +1690,29 @@ impl eframe::App for OllamaLanShareApp {
                                         if o.enabled {
                                             ui.small(tr_lang(lang, "ollama: 已共享", "ollama: shared"));
                                         }
+                                        if let Some(fingerprint) = &o.tls_fingerprint {
+                                            let pinned = self.tls_pins.get(&Self::pin_name(p));
+                                            if pinned.is_some_and(|f| f != tls::normalize_fingerprint(fingerprint)) {
+                                                ui.colored_label(
+                                                    egui::Color32::RED,
+                                                    tr_lang(lang, "证书已变更", "certificate changed"),
+                                                )
+                                                .on_hover_text(fingerprint);
+                                                if ui.small_button(tr_lang(lang, "信任新证书", "Trust new")).clicked() {
+                                                    trust_new = Some(p.clone());
+                                                }
+                                            } else {
+                                                ui.small("🔒 TLS").on_hover_text(fingerprint);
+                                            }
+                                        }
                                     }
                             });
                             ui.small(&p.address);
                         }
                     });
+                if let Some(peer) = trust_new {
+                    self.trust_new_cert(&peer);
+                }
 
                 ui.add_space(8.0);
                 ui.label(tr_lang(
@@ -1499,8 +1736,21 @@ impl eframe::App for OllamaLanShareApp {
                                     .hint_text(tr_lang(lang, "共享密码", "share key")),
                             );
                         }
+                        ui.checkbox(&mut self.use_tls, tr_lang(lang, "TLS 加密", "TLS"));
+                        ui.checkbox(&mut self.seal_offer, tr_lang(lang, "仅组员可见", "Members only"))
+                            .on_hover_text(tr_lang(
+                                lang,
+                                "模型列表和代理端口只加密发给有密钥的组",
+                                "Send models and proxy port only encrypted to keyed groups",
+                            ));
                     });
                 });
+                if let (true, Some(identity)) = (self.sharing_active && self.use_tls, &self.tls_identity) {
+                    ui.horizontal(|ui| {
+                        ui.small(tr_lang(lang, "证书指纹:", "Certificate fingerprint:"));
+                        ui.monospace(identity.fingerprint());
+                    });
+                }
                 ui.horizontal(|ui| {
                     if !self.sharing_active {
                         if ui.button(tr_lang(lang, "确认共享", "Confirm share")).clicked() {
@@ -1633,6 +1883,7 @@ impl eframe::App for OllamaLanShareApp {
                                 if ui.button(tr_lang(lang, "取消", "Cancel")).clicked() {
                                     self.pending_create_group_id = None;
                                     self.pending_create_group_name = None;
+                                    self.pending_create_group_secret = None;
                                 }
                             });
                         }
@@ -1642,7 +1893,7 @@ impl eframe::App for OllamaLanShareApp {
 
                 if self.show_join_group_ui {
                     ui.group(|ui| {
-                        ui.label(tr_lang(lang, "加入 Group ID", "Join Group ID"));
+                        ui.label(tr_lang(lang, "加入（邀请码）", "Join (invite code)"));
                         ui.text_edit_singleline(&mut self.join_group_id);
                         ui.horizontal(|ui| {
                             if ui.button(tr_lang(lang, "加入…", "Join…")).clicked() {
@@ -1660,12 +1911,20 @@ impl eframe::App for OllamaLanShareApp {
                                 ui.label(tr_lang(lang, "Group ID:", "Group ID:"));
                                 ui.monospace(&id);
                             });
+                            if self.pending_join_group_secret.is_none() {
+                                ui.small(tr_lang(
+                                    lang,
+                                    "没有密钥：这是公开组，局域网内任何人都能声称是组员",
+                                    "No secret: an open group anyone on the LAN can claim to be in",
+                                ));
+                            }
                             ui.horizontal(|ui| {
                                 if ui.button(tr_lang(lang, "确认加入", "Confirm join")).clicked() {
                                     self.confirm_join_group();
                                 }
                                 if ui.button(tr_lang(lang, "取消", "Cancel")).clicked() {
                                     self.pending_join_group_id = None;
+                                    self.pending_join_group_secret = None;
                                 }
                             });
                         }
@@ -1719,6 +1978,22 @@ impl eframe::App for OllamaLanShareApp {
                                         ui.label(tr_lang(lang, "Group ID", "Group ID"));
                                         ui.monospace(&gid);
                                     });
+                                    ui.horizontal(|ui| match self.group_keys.get(&gid) {
+                                        Some(secret) => {
+                                            ui.label(tr_lang(lang, "邀请码", "Invite code"));
+                                            if ui.button(tr_lang(lang, "复制", "Copy")).clicked() {
+                                                ui.output_mut(|o| o.copied_text = group_invite(&gid, secret));
+                                            }
+                                            ui.small(tr_lang(lang, "（含密钥，只发给组员）", "(contains the secret; share with members only)"));
+                                        }
+                                        None => {
+                                            ui.small(tr_lang(
+                                                lang,
+                                                "公开组：没有密钥，任何人都能声称是组员",
+                                                "Open group: no secret, anyone can claim to be in it",
+                                            ));
+                                        }
+                                    });
                                 });
 
                                 if save_now {
@@ -1829,9 +2104,3 @@ impl eframe::App for OllamaLanShareApp {
     }
 }
 
-async fn get_local_ip_best_effort() -> Option<String> {
-    let socket = tokio::net::UdpSocket::bind("0.0.0.0:0").await.ok()?;
-    socket.connect("8.8.8.8:80").await.ok()?;
-    let addr = socket.local_addr().ok()?;
-    Some(addr.ip().to_string())
-}
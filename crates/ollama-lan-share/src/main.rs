@@ -1,9 +1,7 @@
 use std::time::Duration;
 
-mod shared;
-mod backend;
-
-use crate::backend::{NetworkDiscovery, OllamaManager};
+use ollama_lan_share::backend::headless::{split_list, ShareArgs};
+use ollama_lan_share::backend::OllamaManager;
 
 fn main() {
     tracing_subscriber::fmt()
@@ -19,74 +17,59 @@ fn main() {
         print_usage_and_exit();
     }
 
+    let setup = match args.share.clone().load() {
+        Ok(setup) => setup,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+
     let rt = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .expect("create tokio runtime");
 
     rt.block_on(async move {
-        let discovery = NetworkDiscovery::new().await;
-
-
-        if let Some(name) = args.name.clone() {
-            discovery.set_local_node_name(name).await;
-        }
-
-        if !args.groups.is_empty() {
-            discovery.set_local_groups(args.groups.clone()).await;
-        }
-
-        let local_name = discovery.local_node_name().await;
-
         // Only touch Ollama if we actually plan to share something.
-        let mut share_enabled = false;
-        let mut models = Vec::new();
+        let mut models = None;
         if args.share_all || args.models_csv.is_some() || args.models_file.is_some() {
-            let ollama = OllamaManager::new(args.ollama_base_url.clone()).await;
-            (share_enabled, models) = resolve_models(&ollama, &args).await;
+            let ollama = OllamaManager::new(args.share.ollama_base_url.clone()).await;
+            let (share_enabled, selected) = resolve_models(&ollama, &args).await;
+            models = share_enabled.then_some(selected);
         }
 
-        discovery
-            .set_ollama_offer(share_enabled, models.clone(), args.ollama_base_url.clone())
-            .await;
+        let keyed_groups: Vec<String> = setup.keyed_groups().into_iter().map(str::to_string).collect();
+        let node = match setup.start(models).await {
+            Ok(node) => node,
+            Err(err) => {
+                tracing::error!("{err}");
+                std::process::exit(1);
+            }
+        };
 
-        tracing::info!("local_name={}", local_name);
-        tracing::info!("groups={:?}", args.groups);
-        if share_enabled {
-            tracing::info!("ollama_share=enabled models={:?}", models);
-        } else {
-            tracing::info!("ollama_share=disabled");
+        tracing::info!("local_name={}", node.discovery.local_node_name().await);
+        tracing::info!("groups={:?} keyed_groups={:?}", args.share.groups, keyed_groups);
+        match &node.models {
+            Some(models) => {
+                tracing::info!("ollama_share=enabled models={:?} base_url={:?}", models, node.base_url)
+            }
+            None => tracing::info!("ollama_share=disabled"),
         }
 
-        discovery.broadcast_presence();
-
         if args.once {
             // Give the background send task a brief moment to run before we exit.
             tokio::time::sleep(Duration::from_millis(200)).await;
             return;
         }
 
-        let mut interval = tokio::time::interval(Duration::from_secs(5));
-        loop {
-            interval.tick().await;
-            let d = discovery.debug_stats().await;
-            tracing::info!(
-                "discovery: bound={} tx(a/q)={}/{} rx(a/q)={}/{} last_rx={:?} {:?}",
-                d.socket_bound,
-                d.sent_announces,
-                d.sent_queries,
-                d.received_announces,
-                d.received_queries,
-                d.last_received_kind,
-                d.last_received_from
-            );
-        }
+        node.run(Duration::from_secs(5)).await;
     });
 }
 
 fn print_usage_and_exit() -> ! {
     eprintln!(
-        "ollama-lan-share\n\nUSAGE:\n  ollama-lan-share [options]\n\nOPTIONS:\n  --help                 Show this help\n  --once                 Broadcast once and exit\n  --name <name>           Override machine name\n  --group <groupId>       Join/advertise a group (repeatable)\n  --groups <csv>          Comma-separated groups\n  --models <csv>          Share subset of local Ollama models\n  --models-file <path>    Share subset listed in a file\n  --share-all             Share all local Ollama models\n  --ollama <base_url>     Ollama base URL (default: http://localhost:11434)\n"
+        "ollama-lan-share\n\nUSAGE:\n  ollama-lan-share [options]\n\nOPTIONS:\n  --help                 Show this help\n  --once                 Broadcast once and exit\n  --name <name>           Override machine name\n  --group <groupId>       Join/advertise a group (repeatable)\n  --groups <csv>          Comma-separated groups\n  --group-keys-file <path> Keyed groups, one <groupId>:<secret> per line\n  --seal-offer            Only show the offer to members of the keyed groups\n  --models <csv>          Share subset of local Ollama models\n  --models-file <path>    Share subset listed in a file\n  --share-all             Share all local Ollama models\n  --ollama <base_url>     Ollama base URL (default: http://localhost:11434)\n  --proxy-port <port>     Serve the shared models through the authenticating proxy\n  --share-key-file <path> Require the key in this file (needs --proxy-port)\n  --proxy-endpoints <csv> Ollama endpoints to forward (default: chat,generate,embed,embeddings,show,tags)\n  --clients-file <path>   Per-client tokens and limits (JSON; reloaded on change)\n  --audit-log <path>      Append every proxied request to this JSON-lines file\n  --tls-dir <dir>         Serve the proxy over HTTPS with the certificate kept here (created on first run)\n  --discovery-port <port> UDP port for discovery (default: 45555)\n  --no-broadcast          Don't announce to 255.255.255.255\n  --multicast             Also announce to the IPv4 multicast group 239.255.45.55\n  --multicast6            Also announce to the IPv6 link-local group ff02::4555\n  --seed <host[:port]>    Announce to this node directly, e.g. across subnets or a VPN (repeatable)\n  --no-gossip             Don't pass on or follow the addresses of peers seen by others\n"
     );
     std::process::exit(0)
 }
@@ -164,50 +147,39 @@ async fn resolve_models(ollama: &OllamaManager, args: &Args) -> (bool, Vec<Strin
 
 #[derive(Debug, Default, Clone)]
 struct Args {
-    name: Option<String>,
-    groups: Vec<String>,
+    share: ShareArgs,
     models_csv: Option<String>,
     models_file: Option<String>,
     share_all: bool,
-    ollama_base_url: Option<String>,
     once: bool,
     help: bool,
 }
 
 impl Args {
     fn from_env() -> Self {
-        // Minimal parsing:
-        //   --name <name>
-        //   --group <groupId>   (repeatable)
-        //   --groups <csv>
+        // Minimal parsing; the discovery, group and proxy flags are
+        // ShareArgs', the rest are:
         //   --models <csv>
         //   --models-file <path>
         //   --share-all
-        //   --ollama <base_url>
         //   --once
         //   --help
         let mut out = Args::default();
         let mut it = std::env::args().skip(1);
         while let Some(a) = it.next() {
+            match out.share.parse_flag(&a, &mut it) {
+                Ok(true) => continue,
+                Ok(false) => {}
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(2);
+                }
+            }
             match a.as_str() {
                 "--help" | "-h" => out.help = true,
-                "--name" => out.name = it.next(),
-                "--group" => {
-                    if let Some(g) = it.next() {
-                        if !g.trim().is_empty() {
-                            out.groups.push(g);
-                        }
-                    }
-                }
-                "--groups" => {
-                    if let Some(csv) = it.next() {
-                        out.groups.extend(split_list(&csv));
-                    }
-                }
                 "--models" => out.models_csv = it.next(),
                 "--models-file" => out.models_file = it.next(),
                 "--share-all" => out.share_all = true,
-                "--ollama" => out.ollama_base_url = it.next(),
                 "--once" => out.once = true,
                 _ => {}
             }
@@ -216,11 +188,3 @@ impl Args {
         out
     }
 }
-
-fn split_list(s: &str) -> Vec<String> {
-    s.split(',')
-        .map(|x| x.trim())
-        .filter(|x| !x.is_empty())
-        .map(|x| x.to_string())
-        .collect()
-}
@@ -34,4 +34,29 @@ pub struct OllamaOfferStatus {
     pub auth_required: bool,
     #[serde(default)]
     pub proxy_port: Option<u16>,
+    // Left out when unset so nodes that don't know the field still verify
+    // the discovery MAC.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load: Option<OfferLoad>,
+    /// SHA-256 of the proxy's TLS certificate when `base_url` is `https`;
+    /// callers pin it on first use.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_fingerprint: Option<String>,
+}
+
+/// How busy the share proxy behind an offer is, from its `/status`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct OfferLoad {
+    pub running: usize,
+    pub capacity: usize,
+    pub queued: usize,
+    #[serde(default)]
+    pub avg_wait_ms: u64,
+}
+
+impl OfferLoad {
+    /// Requests ahead of a new one per upstream slot; lower is less busy.
+    pub fn pressure(&self) -> f64 {
+        (self.running + self.queued) as f64 / self.capacity.max(1) as f64
+    }
 }
@@ -0,0 +1,163 @@
+//! Discovery between several nodes on loopback, each on its own port and
+//! reaching the others only through seeds and gossip.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::json;
+use tokio::net::UdpSocket;
+
+use ollama_lan_share::backend::{DiscoveryOptions, NetworkDiscovery};
+use ollama_lan_share::shared::models::PeerStatus;
+
+/// A node on a port of its own that doesn't broadcast.
+async fn node(seeds: &[&NetworkDiscovery], gossip: bool) -> NetworkDiscovery {
+    NetworkDiscovery::with_options(DiscoveryOptions {
+        port: 0,
+        broadcast: false,
+        seeds: seeds.iter().map(|s| format!("127.0.0.1:{}", s.port())).collect(),
+        gossip,
+        announce_interval: Duration::from_millis(200),
+        ..DiscoveryOptions::default()
+    })
+    .await
+}
+
+async fn peer_ids(node: &NetworkDiscovery) -> Vec<String> {
+    let mut ids: Vec<String> = node
+        .discover_peers()
+        .await
+        .expect("peers")
+        .into_iter()
+        .map(|p| p.id)
+        .collect();
+    ids.sort();
+    ids
+}
+
+async fn peer(node: &NetworkDiscovery, id: &str) -> Option<PeerStatus> {
+    node.discover_peers().await.expect("peers").into_iter().find(|p| p.id == id)
+}
+
+/// Whether `node` sees every one of `others` within a few seconds.
+async fn sees(node: &NetworkDiscovery, others: &[&NetworkDiscovery]) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        let ids = peer_ids(node).await;
+        if others.iter().all(|o| ids.iter().any(|id| id == o.local_node_id())) {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn seeded_nodes_find_each_other() {
+    let a = node(&[], true).await;
+    let b = node(&[&a], true).await;
+    assert_ne!(a.port(), b.port());
+
+    assert!(sees(&a, &[&b]).await, "a sees {:?}", peer_ids(&a).await);
+    assert!(sees(&b, &[&a]).await, "b sees {:?}", peer_ids(&b).await);
+
+    // Reachable at the port the peer listens on, not the default one.
+    let peers = b.discover_peers().await.expect("peers");
+    assert_eq!(peers[0].address, format!("127.0.0.1:{}", a.port()));
+}
+
+#[tokio::test]
+async fn gossip_introduces_nodes_sharing_a_rendezvous() {
+    let rendezvous = node(&[], true).await;
+    let b = node(&[&rendezvous], true).await;
+    let c = node(&[&rendezvous], true).await;
+
+    assert!(sees(&b, &[&rendezvous, &c]).await, "b sees {:?}", peer_ids(&b).await);
+    assert!(sees(&c, &[&rendezvous, &b]).await, "c sees {:?}", peer_ids(&c).await);
+    assert!(b.debug_stats().await.received_gossip > 0);
+}
+
+#[tokio::test]
+async fn without_gossip_only_seeds_are_known() {
+    let rendezvous = node(&[], false).await;
+    let b = node(&[&rendezvous], false).await;
+    let c = node(&[&rendezvous], false).await;
+
+    assert!(sees(&c, &[&rendezvous]).await);
+    assert!(sees(&rendezvous, &[&b, &c]).await);
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(peer_ids(&c).await, vec![rendezvous.local_node_id().to_string()]);
+    assert_eq!(c.debug_stats().await.received_gossip, 0);
+}
+
+#[tokio::test]
+async fn nodes_with_different_keys_stay_apart() {
+    let a = node(&[], true).await;
+    a.set_auth_key(Some("one".to_string())).await;
+    let b = node(&[&a], true).await;
+    b.set_auth_key(Some("two".to_string())).await;
+    let c = node(&[&a], true).await;
+    c.set_auth_key(Some("one".to_string())).await;
+
+    assert!(sees(&a, &[&c]).await);
+    assert!(sees(&c, &[&a]).await);
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(peer_ids(&a).await, vec![c.local_node_id().to_string()]);
+    assert!(peer_ids(&b).await.is_empty());
+}
+
+#[tokio::test]
+async fn unauthenticated_announces_cannot_replace_a_group_member() {
+    let keys = BTreeMap::from([("team".to_string(), "s3cret".to_string())]);
+    let a = node(&[], true).await;
+    a.set_group_keys(keys.clone()).await;
+    // Announces only when told to, so nothing it sends covers up the forgery.
+    let b = NetworkDiscovery::with_options(DiscoveryOptions {
+        port: 0,
+        broadcast: false,
+        seeds: vec![format!("127.0.0.1:{}", a.port())],
+        announce_interval: Duration::from_secs(600),
+        ..DiscoveryOptions::default()
+    })
+    .await;
+    b.set_group_keys(keys).await;
+    b.broadcast_presence();
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !peer(&a, b.local_node_id()).await.is_some_and(|p| p.groups.contains(&"team".to_string())) {
+        assert!(Instant::now() < deadline, "a never saw b in the group");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    // Anyone can read b's id off the wire and announce under it, unsigned.
+    let sent_at_ms = SystemTime::now().duration_since(UNIX_EPOCH).expect("clock").as_millis() as u64;
+    let forged = json!({
+        "version": 2,
+        "kind": "Announce",
+        "sent_at_ms": sent_at_ms,
+        "seq": 1_000_000,
+        "nonce": "00",
+        "status": {
+            "id": b.local_node_id(),
+            "address": "0.0.0.0:1",
+            "mode": "Master",
+            "latency": null,
+            "groups": [],
+            "ollama": { "enabled": true, "base_url": "http://attacker:11434", "models": ["llama3"] },
+        },
+    });
+    let attacker = UdpSocket::bind("127.0.0.1:0").await.expect("bind");
+    for _ in 0..5 {
+        attacker
+            .send_to(forged.to_string().as_bytes(), ("127.0.0.1", a.port()))
+            .await
+            .expect("send");
+    }
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let seen = peer(&a, b.local_node_id()).await.expect("b");
+    assert!(seen.groups.contains(&"team".to_string()), "{seen:?}");
+    assert_eq!(seen.address, format!("127.0.0.1:{}", b.port()));
+    let base_url = seen.ollama.and_then(|o| o.base_url).unwrap_or_default();
+    assert!(!base_url.contains("attacker"), "{base_url}");
+}
@@ -0,0 +1,1037 @@
+//! The share proxy in front of a local stand-in for the Ollama HTTP API.
+
+use std::collections::BTreeSet;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::header,
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use base64::Engine as _;
+use futures_util::StreamExt;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+
+use ollama_lan_share::backend::share_proxy::audit::AuditEntry;
+use ollama_lan_share::backend::share_proxy::tls::{self, PinCheck, TlsIdentity, TofuPins};
+use ollama_lan_share::backend::share_proxy::{parse_endpoints, ClientToken, SHARE_KEY_HEADER};
+use ollama_lan_share::backend::{ProxyConfig, ProxyOptions, ShareProxy};
+
+const KEY: &str = "s3cret";
+
+/// Bodies the stand-in received for chat and embeddings.
+type Recorded = Arc<Mutex<Vec<Value>>>;
+
+struct Upstream {
+    url: String,
+    requests: Recorded,
+}
+
+impl Upstream {
+    fn last_request(&self) -> Value {
+        self.requests.lock().expect("lock").last().cloned().expect("a request")
+    }
+}
+
+/// Echoes requests back; streamed chat and generate requests get three
+/// NDJSON frames, the last one after a pause.
+async fn stand_in() -> Upstream {
+    fn frames(
+        body: &Value,
+        piece: impl Fn(&str) -> Value + Send + Sync + 'static,
+    ) -> Option<axum::response::Response> {
+        if !body["stream"].as_bool().unwrap_or(true) {
+            return None;
+        }
+        let stream = futures_util::stream::iter(["Hel", "lo", ""]).then(move |part| {
+            let mut frame = piece(part);
+            frame["done"] = json!(part.is_empty());
+            if part.is_empty() {
+                frame["done_reason"] = json!("stop");
+                frame["prompt_eval_count"] = json!(7);
+                frame["eval_count"] = json!(3);
+            }
+            async move {
+                if part.is_empty() {
+                    tokio::time::sleep(Duration::from_millis(300)).await;
+                }
+                Ok::<_, std::io::Error>(format!("{frame}\n"))
+            }
+        });
+        Some(([(header::CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(stream)).into_response())
+    }
+
+    async fn chat(State(requests): State<Recorded>, Json(body): Json<Value>) -> axum::response::Response {
+        requests.lock().expect("lock").push(body.clone());
+        if let Some(reply) = frames(&body, |part| json!({"message": {"role": "assistant", "content": part}})) {
+            return reply;
+        }
+        let mut message = json!({"role": "assistant", "content": "ok"});
+        if body.get("tools").is_some() {
+            message["content"] = json!("");
+            message["tool_calls"] = json!([{"function": {"name": "weather", "arguments": {"city": "Paris"}}}]);
+        }
+        Json(json!({
+            "message": message,
+            "done": true,
+            "done_reason": "stop",
+            "prompt_eval_count": 7,
+            "eval_count": 3,
+            "echo": body,
+        }))
+        .into_response()
+    }
+
+    async fn generate(Json(body): Json<Value>) -> axum::response::Response {
+        frames(&body, |part| json!({"response": part}))
+            .unwrap_or_else(|| Json(json!({"response": "ok", "done": true, "echo": body})).into_response())
+    }
+
+    async fn echo(State(requests): State<Recorded>, Json(body): Json<Value>) -> Json<Value> {
+        requests.lock().expect("lock").push(body.clone());
+        Json(json!({"embeddings": [[0.5, 0.25]], "prompt_eval_count": 2, "echo": body}))
+    }
+
+    async fn tags() -> Json<Value> {
+        Json(json!({"models": [
+            {"name": "llama3", "model": "llama3", "size": 1},
+            {"name": "qwen2", "model": "qwen2", "size": 2},
+            {"name": "secret:7b", "model": "secret:7b", "size": 3},
+        ]}))
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let url = format!("http://{}", listener.local_addr().expect("addr"));
+    let requests = Recorded::default();
+    let app = Router::new()
+        .route("/api/chat", post(chat))
+        .route("/api/generate", post(generate))
+        .route("/api/embed", post(echo))
+        .route("/api/show", post(echo))
+        .route("/api/tags", get(tags))
+        .with_state(requests.clone());
+    tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+    Upstream { url, requests }
+}
+
+async fn proxy(key: &str, models: &[&str]) -> ShareProxy {
+    proxy_with_upstream(key, models).await.0
+}
+
+async fn proxy_with_upstream(key: &str, models: &[&str]) -> (ShareProxy, Upstream) {
+    start(ProxyOptions::default(), config(key, models)).await
+}
+
+/// Start a proxy on a free local port in front of a fresh stand-in.
+async fn start(options: ProxyOptions, config: ProxyConfig) -> (ShareProxy, Upstream) {
+    let upstream = stand_in().await;
+    let options = ProxyOptions {
+        bind: SocketAddr::from(([127, 0, 0, 1], 0)),
+        upstream: upstream.url.clone(),
+        ..options
+    };
+    let proxy = ShareProxy::start(options, config).await.expect("start proxy");
+    (proxy, upstream)
+}
+
+fn config(key: &str, models: &[&str]) -> ProxyConfig {
+    ProxyConfig {
+        enabled: true,
+        key: key.to_string(),
+        allowed_models: models.iter().map(|m| m.to_string()).collect::<BTreeSet<_>>(),
+        ..ProxyConfig::default()
+    }
+}
+
+async fn chat(proxy: &ShareProxy, key: Option<&str>, body: Value) -> (u16, String) {
+    call(proxy, "/api/chat", key, body).await
+}
+
+async fn call(proxy: &ShareProxy, path: &str, key: Option<&str>, body: Value) -> (u16, String) {
+    let mut request = reqwest::Client::new()
+        .post(format!("http://{}{path}", proxy.local_addr()))
+        .json(&body);
+    if let Some(key) = key {
+        request = request.header(SHARE_KEY_HEADER, key);
+    }
+    let response = request.send().await.expect("send");
+    let status = response.status().as_u16();
+    (status, response.text().await.expect("body"))
+}
+
+/// An OpenAI-style call with the key as a bearer token.
+async fn openai(proxy: &ShareProxy, path: &str, key: &str, body: Option<Value>) -> (u16, String) {
+    let url = format!("http://{}{path}", proxy.local_addr());
+    let client = reqwest::Client::new();
+    let request = match body {
+        Some(body) => client.post(url).json(&body),
+        None => client.get(url),
+    };
+    let response = request.bearer_auth(key).send().await.expect("send");
+    let status = response.status().as_u16();
+    (status, response.text().await.expect("body"))
+}
+
+fn request(model: &str) -> Value {
+    json!({"model": model, "messages": [{"role": "user", "content": "hi"}], "stream": false})
+}
+
+#[tokio::test]
+async fn rejects_missing_or_wrong_key() {
+    let proxy = proxy(KEY, &["llama3"]).await;
+
+    let (status, _) = chat(&proxy, None, request("llama3")).await;
+    assert_eq!(status, 401);
+    let (status, _) = chat(&proxy, Some("wrong"), request("llama3")).await;
+    assert_eq!(status, 401);
+}
+
+#[tokio::test]
+async fn forwards_with_the_right_key() {
+    let proxy = proxy(KEY, &["llama3"]).await;
+
+    let (status, body) = chat(&proxy, Some(KEY), request("llama3")).await;
+    assert_eq!(status, 200, "{body}");
+    let reply: Value = serde_json::from_str(&body).expect("json");
+    assert_eq!(reply["message"]["content"], "ok");
+    assert_eq!(reply["echo"]["model"], "llama3");
+    assert_eq!(reply["echo"]["messages"][0]["content"], "hi");
+}
+
+#[tokio::test]
+async fn no_key_configured_means_open() {
+    let proxy = proxy("", &["llama3"]).await;
+
+    let (status, _) = chat(&proxy, None, request("llama3")).await;
+    assert_eq!(status, 200);
+}
+
+#[tokio::test]
+async fn enforces_the_model_list() {
+    let proxy = proxy(KEY, &["llama3"]).await;
+
+    let (status, _) = chat(&proxy, Some(KEY), request("qwen2")).await;
+    assert_eq!(status, 403);
+    let (status, _) = chat(&proxy, Some(KEY), json!({"messages": [], "stream": false})).await;
+    assert_eq!(status, 400);
+}
+
+#[tokio::test]
+async fn disabled_proxy_answers_not_found() {
+    let proxy = proxy(KEY, &["llama3"]).await;
+    proxy
+        .configure(ProxyConfig { enabled: false, ..config(KEY, &["llama3"]) })
+        .await;
+
+    let (status, _) = chat(&proxy, Some(KEY), request("llama3")).await;
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn streams_ndjson_frames_through() {
+    let proxy = proxy(KEY, &["llama3"]).await;
+
+    let mut body = request("llama3");
+    body["stream"] = json!(true);
+    let (status, text) = chat(&proxy, Some(KEY), body).await;
+    assert_eq!(status, 200, "{text}");
+
+    let frames: Vec<Value> = text
+        .lines()
+        .map(|l| serde_json::from_str(l).expect("frame"))
+        .collect();
+    assert_eq!(frames.len(), 3);
+    let content: String = frames
+        .iter()
+        .filter_map(|f| f["message"]["content"].as_str())
+        .collect();
+    assert_eq!(content, "Hello");
+    assert_eq!(frames[2]["done"], true);
+}
+
+#[tokio::test]
+async fn reconfiguring_revokes_the_old_key() {
+    let proxy = proxy(KEY, &["llama3"]).await;
+    let (status, _) = chat(&proxy, Some(KEY), request("llama3")).await;
+    assert_eq!(status, 200);
+
+    proxy.configure(config("rotated", &["llama3", "qwen2"])).await;
+
+    let (status, _) = chat(&proxy, Some(KEY), request("llama3")).await;
+    assert_eq!(status, 401);
+    let (status, _) = chat(&proxy, Some("rotated"), request("qwen2")).await;
+    assert_eq!(status, 200);
+    assert!(proxy.config().await.allowed_models.contains("qwen2"));
+}
+
+#[tokio::test]
+async fn forwards_generate_embed_and_show() {
+    let proxy = proxy(KEY, &["llama3"]).await;
+
+    let body = json!({"model": "llama3", "prompt": "hi", "stream": false});
+    let (status, text) = call(&proxy, "/api/generate", Some(KEY), body).await;
+    assert_eq!(status, 200, "{text}");
+    assert_eq!(serde_json::from_str::<Value>(&text).expect("json")["echo"]["prompt"], "hi");
+
+    let body = json!({"model": "llama3", "input": ["a"]});
+    let (status, text) = call(&proxy, "/api/embed", Some(KEY), body).await;
+    assert_eq!(status, 200, "{text}");
+    assert_eq!(serde_json::from_str::<Value>(&text).expect("json")["embeddings"][0][0], 0.5);
+
+    // `/api/show` still accepts the older `name` field.
+    let (status, _) = call(&proxy, "/api/show", Some(KEY), json!({"name": "llama3"})).await;
+    assert_eq!(status, 200);
+}
+
+#[tokio::test]
+async fn every_model_endpoint_enforces_the_model_list() {
+    let proxy = proxy(KEY, &["llama3"]).await;
+
+    for path in ["/api/generate", "/api/embed", "/api/embeddings", "/api/show"] {
+        let (status, _) = call(&proxy, path, Some(KEY), json!({"model": "secret:7b", "prompt": "x"})).await;
+        assert_eq!(status, 403, "{path}");
+        let (status, _) = call(&proxy, path, Some(KEY), json!({"prompt": "x"})).await;
+        assert_eq!(status, 400, "{path}");
+    }
+}
+
+#[tokio::test]
+async fn tags_lists_only_shared_models() {
+    let proxy = proxy(KEY, &["llama3", "qwen2"]).await;
+    let url = format!("http://{}/api/tags", proxy.local_addr());
+
+    let status = reqwest::get(&url).await.expect("send").status();
+    assert_eq!(status, 401);
+
+    let tags: Value = reqwest::Client::new()
+        .get(&url)
+        .header(SHARE_KEY_HEADER, KEY)
+        .send()
+        .await
+        .expect("send")
+        .json()
+        .await
+        .expect("json");
+    let names: Vec<&str> = tags["models"]
+        .as_array()
+        .expect("models")
+        .iter()
+        .filter_map(|m| m["name"].as_str())
+        .collect();
+    assert_eq!(names, ["llama3", "qwen2"]);
+}
+
+#[tokio::test]
+async fn streams_generate_without_buffering() {
+    let proxy = proxy(KEY, &["llama3"]).await;
+
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/api/generate", proxy.local_addr()))
+        .header(SHARE_KEY_HEADER, KEY)
+        .json(&json!({"model": "llama3", "prompt": "hi"}))
+        .send()
+        .await
+        .expect("send");
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The first frames arrive before the stand-in sends its last one.
+    let started = Instant::now();
+    let mut stream = response.bytes_stream();
+    let first = stream.next().await.expect("first chunk").expect("bytes");
+    assert!(started.elapsed() < Duration::from_millis(250), "first chunk was buffered");
+    assert!(String::from_utf8_lossy(&first).contains("Hel"));
+
+    let mut rest = Vec::from(first);
+    while let Some(chunk) = stream.next().await {
+        rest.extend_from_slice(&chunk.expect("bytes"));
+    }
+    let text = String::from_utf8(rest).expect("utf8");
+    let content: String = text
+        .lines()
+        .map(|l| serde_json::from_str::<Value>(l).expect("frame"))
+        .filter_map(|f| f["response"].as_str().map(str::to_string))
+        .collect();
+    assert_eq!(content, "Hello");
+}
+
+#[tokio::test]
+async fn endpoints_outside_the_allow_list_are_refused() {
+    let proxy = proxy(KEY, &["llama3"]).await;
+    proxy
+        .configure(ProxyConfig {
+            endpoints: parse_endpoints("chat, tags").expect("endpoints"),
+            ..config(KEY, &["llama3"])
+        })
+        .await;
+
+    let (status, _) = call(&proxy, "/api/generate", Some(KEY), json!({"model": "llama3"})).await;
+    assert_eq!(status, 403);
+    let (status, _) = chat(&proxy, Some(KEY), request("llama3")).await;
+    assert_eq!(status, 200);
+    let (status, _) = call(&proxy, "/api/pull", Some(KEY), json!({"model": "llama3"})).await;
+    assert_eq!(status, 404);
+    assert!(parse_endpoints("chat,pull").is_err());
+}
+
+#[tokio::test]
+async fn openai_chat_completion_is_translated() {
+    let (proxy, upstream) = proxy_with_upstream(KEY, &["llama3"]).await;
+
+    let body = json!({
+        "model": "llama3",
+        "messages": [
+            {"role": "developer", "content": "Be brief."},
+            {"role": "user", "content": [{"type": "text", "text": "hi"}]},
+        ],
+        "max_tokens": 5,
+        "temperature": 0.2,
+        "stop": "END",
+        "response_format": {"type": "json_object"},
+    });
+    let (status, text) = openai(&proxy, "/v1/chat/completions", KEY, Some(body)).await;
+    assert_eq!(status, 200, "{text}");
+
+    let sent = upstream.last_request();
+    assert_eq!(sent["stream"], false);
+    assert_eq!(sent["messages"][0], json!({"role": "system", "content": "Be brief."}));
+    assert_eq!(sent["messages"][1]["content"], "hi");
+    assert_eq!(sent["options"]["num_predict"], 5);
+    assert_eq!(sent["options"]["temperature"], 0.2);
+    assert_eq!(sent["options"]["stop"], json!(["END"]));
+    assert_eq!(sent["format"], "json");
+
+    let reply: Value = serde_json::from_str(&text).expect("json");
+    assert_eq!(reply["object"], "chat.completion");
+    assert_eq!(reply["model"], "llama3");
+    assert!(reply["id"].as_str().expect("id").starts_with("chatcmpl-"));
+    assert_eq!(reply["choices"][0]["message"], json!({"role": "assistant", "content": "ok"}));
+    assert_eq!(reply["choices"][0]["finish_reason"], "stop");
+    assert_eq!(reply["usage"], json!({"prompt_tokens": 7, "completion_tokens": 3, "total_tokens": 10}));
+}
+
+#[tokio::test]
+async fn openai_tool_calls_round_trip() {
+    let (proxy, upstream) = proxy_with_upstream(KEY, &["llama3"]).await;
+
+    let body = json!({
+        "model": "llama3",
+        "messages": [
+            {"role": "user", "content": "weather?"},
+            {"role": "assistant", "content": null, "tool_calls": [
+                {
+                    "id": "call_1",
+                    "type": "function",
+                    "function": {"name": "weather", "arguments": "{\"city\":\"Oslo\"}"},
+                },
+            ]},
+            {"role": "tool", "tool_call_id": "call_1", "content": "cold"},
+        ],
+        "tools": [{"type": "function", "function": {"name": "weather", "parameters": {"type": "object"}}}],
+    });
+    let (status, text) = openai(&proxy, "/v1/chat/completions", KEY, Some(body)).await;
+    assert_eq!(status, 200, "{text}");
+
+    let sent = upstream.last_request();
+    assert_eq!(sent["messages"][1]["tool_calls"][0]["function"]["arguments"], json!({"city": "Oslo"}));
+    assert_eq!(sent["messages"][2]["role"], "tool");
+    assert_eq!(sent["tools"][0]["function"]["name"], "weather");
+
+    let reply: Value = serde_json::from_str(&text).expect("json");
+    let choice = &reply["choices"][0];
+    assert_eq!(choice["finish_reason"], "tool_calls");
+    let call = &choice["message"]["tool_calls"][0];
+    assert_eq!(call["type"], "function");
+    assert_eq!(call["function"]["name"], "weather");
+    let arguments = call["function"]["arguments"].as_str().expect("string");
+    let arguments: Value = serde_json::from_str(arguments).expect("json");
+    assert_eq!(arguments, json!({"city": "Paris"}));
+}
+
+#[tokio::test]
+async fn openai_chat_streams_server_sent_events() {
+    let proxy = proxy(KEY, &["llama3"]).await;
+
+    let body = json!({
+        "model": "llama3",
+        "messages": [{"role": "user", "content": "hi"}],
+        "stream": true,
+        "stream_options": {"include_usage": true},
+    });
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/v1/chat/completions", proxy.local_addr()))
+        .bearer_auth(KEY)
+        .json(&body)
+        .send()
+        .await
+        .expect("send");
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    let text = response.text().await.expect("body");
+
+    let events: Vec<&str> = text
+        .split("\n\n")
+        .filter(|e| !e.is_empty())
+        .map(|e| e.strip_prefix("data: ").expect("data event"))
+        .collect();
+    assert_eq!(events.last(), Some(&"[DONE]"));
+    let chunks: Vec<Value> = events[..events.len() - 1]
+        .iter()
+        .map(|e| serde_json::from_str(e).expect("chunk"))
+        .collect();
+
+    assert!(chunks.iter().all(|c| c["object"] == "chat.completion.chunk"));
+    assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+    let content: String = chunks
+        .iter()
+        .filter_map(|c| c["choices"][0]["delta"]["content"].as_str())
+        .collect();
+    assert_eq!(content, "Hello");
+    let finish: Vec<&str> = chunks
+        .iter()
+        .filter_map(|c| c["choices"][0]["finish_reason"].as_str())
+        .collect();
+    assert_eq!(finish, ["stop"]);
+    let usage = &chunks.last().expect("usage chunk")["usage"];
+    assert_eq!(usage["total_tokens"], 10);
+}
+
+#[tokio::test]
+async fn openai_models_lists_only_shared_models() {
+    let proxy = proxy(KEY, &["qwen2"]).await;
+
+    let (status, text) = openai(&proxy, "/v1/models", KEY, None).await;
+    assert_eq!(status, 200, "{text}");
+    let list: Value = serde_json::from_str(&text).expect("json");
+    assert_eq!(list["object"], "list");
+    let ids: Vec<&str> = list["data"]
+        .as_array()
+        .expect("data")
+        .iter()
+        .filter_map(|m| m["id"].as_str())
+        .collect();
+    assert_eq!(ids, ["qwen2"]);
+}
+
+#[tokio::test]
+async fn openai_embeddings_as_floats_and_base64() {
+    let (proxy, upstream) = proxy_with_upstream(KEY, &["llama3"]).await;
+
+    let body = json!({"model": "llama3", "input": "hello"});
+    let (status, text) = openai(&proxy, "/v1/embeddings", KEY, Some(body)).await;
+    assert_eq!(status, 200, "{text}");
+    assert_eq!(upstream.last_request()["input"], json!(["hello"]));
+    let reply: Value = serde_json::from_str(&text).expect("json");
+    assert_eq!(reply["data"][0]["object"], "embedding");
+    assert_eq!(reply["data"][0]["embedding"], json!([0.5, 0.25]));
+    assert_eq!(reply["usage"]["prompt_tokens"], 2);
+
+    let body = json!({"model": "llama3", "input": ["hello"], "encoding_format": "base64"});
+    let (status, text) = openai(&proxy, "/v1/embeddings", KEY, Some(body)).await;
+    assert_eq!(status, 200, "{text}");
+    let reply: Value = serde_json::from_str(&text).expect("json");
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(reply["data"][0]["embedding"].as_str().expect("base64"))
+        .expect("decode");
+    let floats: Vec<f32> = bytes
+        .chunks(4)
+        .map(|b| f32::from_le_bytes(b.try_into().expect("4 bytes")))
+        .collect();
+    assert_eq!(floats, [0.5, 0.25]);
+}
+
+#[tokio::test]
+async fn openai_endpoints_share_auth_and_model_list() {
+    let proxy = proxy(KEY, &["llama3"]).await;
+    let body = json!({"model": "llama3", "messages": [{"role": "user", "content": "hi"}]});
+
+    let (status, text) = openai(&proxy, "/v1/chat/completions", "wrong", Some(body.clone())).await;
+    assert_eq!(status, 401);
+    let reply: Value = serde_json::from_str(&text).expect("json");
+    assert_eq!(reply["error"]["type"], "authentication_error");
+
+    let mut other = body.clone();
+    other["model"] = json!("secret:7b");
+    let (status, text) = openai(&proxy, "/v1/chat/completions", KEY, Some(other)).await;
+    assert_eq!(status, 403);
+    assert_eq!(serde_json::from_str::<Value>(&text).expect("json")["error"]["message"], "model not allowed");
+
+    let embed = json!({"model": "secret:7b", "input": "x"});
+    let (status, _) = openai(&proxy, "/v1/embeddings", KEY, Some(embed)).await;
+    assert_eq!(status, 403);
+
+    // The share key header works as well as a bearer token.
+    let (status, _) = call(&proxy, "/v1/chat/completions", Some(KEY), body).await;
+    assert_eq!(status, 200);
+}
+
+/// A proxy sharing llama3 and qwen2 with the share key and these clients.
+async fn proxy_with_clients(clients: Vec<ClientToken>) -> ShareProxy {
+    let config = ProxyConfig { clients, ..config(KEY, &["llama3", "qwen2"]) };
+    start(ProxyOptions::default(), config).await.0
+}
+
+fn client(name: &str) -> ClientToken {
+    ClientToken::new(name, format!("{name}-token"))
+}
+
+#[tokio::test]
+async fn client_tokens_narrow_the_shared_models() {
+    let alice = ClientToken { allowed_models: ["llama3".to_string()].into(), ..client("alice") };
+    let proxy = proxy_with_clients(vec![alice, client("bob")]).await;
+
+    let (status, _) = chat(&proxy, Some("alice-token"), request("llama3")).await;
+    assert_eq!(status, 200);
+    let (status, _) = chat(&proxy, Some("alice-token"), request("qwen2")).await;
+    assert_eq!(status, 403);
+    let (status, _) = chat(&proxy, Some("bob-token"), request("qwen2")).await;
+    assert_eq!(status, 200);
+    // The share key still works next to the client tokens.
+    let (status, _) = chat(&proxy, Some(KEY), request("qwen2")).await;
+    assert_eq!(status, 200);
+    let (status, _) = chat(&proxy, Some("carol-token"), request("llama3")).await;
+    assert_eq!(status, 401);
+
+    let (status, text) = openai(&proxy, "/v1/models", "alice-token", None).await;
+    assert_eq!(status, 200);
+    let list: Value = serde_json::from_str(&text).expect("json");
+    assert_eq!(list["data"].as_array().expect("data").len(), 1);
+    assert_eq!(list["data"][0]["id"], "llama3");
+}
+
+#[tokio::test]
+async fn daily_request_quota_answers_429() {
+    let proxy = proxy_with_clients(vec![ClientToken { daily_requests: Some(2), ..client("alice") }]).await;
+
+    for _ in 0..2 {
+        let (status, _) = chat(&proxy, Some("alice-token"), request("llama3")).await;
+        assert_eq!(status, 200);
+    }
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/api/chat", proxy.local_addr()))
+        .header(SHARE_KEY_HEADER, "alice-token")
+        .json(&request("llama3"))
+        .send()
+        .await
+        .expect("send");
+    assert_eq!(response.status().as_u16(), 429);
+    let retry_after: u64 = response.headers()["retry-after"]
+        .to_str()
+        .expect("ascii")
+        .parse()
+        .expect("seconds");
+    assert!((1..=86_400).contains(&retry_after), "{retry_after}");
+
+    let usage = &proxy.client_usage()["alice"];
+    assert_eq!((usage.requests, usage.in_flight), (2, 0));
+}
+
+#[tokio::test]
+async fn daily_token_quota_counts_reply_tokens() {
+    let proxy = proxy_with_clients(vec![ClientToken { daily_tokens: Some(10), ..client("alice") }]).await;
+
+    // The stand-in reports 7 prompt and 3 completion tokens per reply.
+    let (status, _) = chat(&proxy, Some("alice-token"), request("llama3")).await;
+    assert_eq!(status, 200);
+    assert_eq!(proxy.client_usage()["alice"].tokens, 10);
+
+    let (status, text) = chat(&proxy, Some("alice-token"), request("llama3")).await;
+    assert_eq!(status, 429);
+    assert!(text.contains("token quota"), "{text}");
+}
+
+#[tokio::test]
+async fn client_concurrency_cap_holds_for_the_whole_stream() {
+    let proxy = proxy_with_clients(vec![ClientToken { max_concurrency: Some(1), ..client("alice") }]).await;
+
+    let mut streamed = request("llama3");
+    streamed["stream"] = json!(true);
+    let first = reqwest::Client::new()
+        .post(format!("http://{}/api/chat", proxy.local_addr()))
+        .header(SHARE_KEY_HEADER, "alice-token")
+        .json(&streamed)
+        .send()
+        .await
+        .expect("send");
+    assert_eq!(first.status().as_u16(), 200);
+
+    // The stand-in holds the last frame back, so the first stream is still open.
+    let (status, _) = chat(&proxy, Some("alice-token"), request("llama3")).await;
+    assert_eq!(status, 429);
+    let (status, _) = chat(&proxy, Some(KEY), request("llama3")).await;
+    assert_eq!(status, 200);
+
+    first.text().await.expect("body");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let (status, _) = chat(&proxy, Some("alice-token"), request("llama3")).await;
+    assert_eq!(status, 200);
+    let usage = &proxy.client_usage()["alice"];
+    assert_eq!((usage.requests, usage.tokens, usage.in_flight), (2, 20, 0));
+}
+
+#[tokio::test]
+async fn expired_and_revoked_tokens_are_refused() {
+    let an_hour_ago = chrono::Utc::now() - chrono::Duration::hours(1);
+    let expired = ClientToken { expires_at: Some(an_hour_ago), ..client("old") };
+    let proxy = proxy_with_clients(vec![expired, client("alice")]).await;
+
+    let (status, text) = chat(&proxy, Some("old-token"), request("llama3")).await;
+    assert_eq!(status, 401);
+    assert!(text.contains("expired"), "{text}");
+
+    let (status, _) = chat(&proxy, Some("alice-token"), request("llama3")).await;
+    assert_eq!(status, 200);
+    assert!(proxy.revoke("alice").await);
+    assert!(!proxy.revoke("nobody").await);
+    let (status, text) = chat(&proxy, Some("alice-token"), request("llama3")).await;
+    assert_eq!(status, 401);
+    assert!(text.contains("revoked"), "{text}");
+}
+
+#[tokio::test]
+async fn clients_file_changes_apply_without_restart() {
+    let path = std::env::temp_dir().join(format!("share-proxy-clients-{}.json", uuid::Uuid::new_v4()));
+    std::fs::write(&path, json!([{"name": "alice", "token": "alice-token"}]).to_string()).expect("write");
+    let clients = ollama_lan_share::backend::share_proxy::load_clients_file(&path).expect("load");
+    let proxy = proxy_with_clients(clients).await;
+    let watcher = proxy.watch_clients_file(path.clone(), Duration::from_millis(20));
+
+    let (status, _) = chat(&proxy, Some("alice-token"), request("llama3")).await;
+    assert_eq!(status, 200);
+
+    let revoked = json!([
+        {"name": "alice", "token": "alice-token", "revoked": true},
+        {"name": "bob", "token": "bob-token"},
+    ]);
+    std::fs::write(&path, revoked.to_string()).expect("write");
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let (status, _) = chat(&proxy, Some("alice-token"), request("llama3")).await;
+    assert_eq!(status, 401);
+    let (status, _) = chat(&proxy, Some("bob-token"), request("llama3")).await;
+    assert_eq!(status, 200);
+
+    // A broken file keeps the tokens that were loaded last.
+    std::fs::write(&path, "not json").expect("write");
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let (status, _) = chat(&proxy, Some("bob-token"), request("llama3")).await;
+    assert_eq!(status, 200);
+
+    watcher.abort();
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn every_request_is_audited() {
+    let path = std::env::temp_dir().join(format!("share-proxy-audit-{}.jsonl", uuid::Uuid::new_v4()));
+    let options = ProxyOptions { audit_log: Some(path.clone()), ..ProxyOptions::default() };
+    let config = ProxyConfig { clients: vec![client("alice")], ..config(KEY, &["llama3"]) };
+    let (proxy, _upstream) = start(options, config).await;
+
+    let (status, _) = chat(&proxy, Some("alice-token"), request("llama3")).await;
+    assert_eq!(status, 200);
+    let mut streamed = request("llama3");
+    streamed["stream"] = json!(true);
+    let (status, _) = chat(&proxy, Some(KEY), streamed).await;
+    assert_eq!(status, 200);
+    let (status, _) = chat(&proxy, Some("nope"), request("llama3")).await;
+    assert_eq!(status, 401);
+    let (status, _) = chat(&proxy, Some("alice-token"), request("qwen2")).await;
+    assert_eq!(status, 403);
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let entries: Vec<AuditEntry> = std::fs::read_to_string(&path)
+        .expect("audit log")
+        .lines()
+        .map(|l| serde_json::from_str(l).expect("entry"))
+        .collect();
+    let _ = std::fs::remove_file(&path);
+
+    let summary: Vec<(&str, Option<&str>, u16, u64)> = entries
+        .iter()
+        .map(|e| (e.client.as_str(), e.model.as_deref(), e.status, e.prompt_tokens + e.completion_tokens))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("alice", Some("llama3"), 200, 10),
+            ("shared", Some("llama3"), 200, 10),
+            ("unknown", None, 401, 0),
+            ("alice", Some("qwen2"), 403, 0),
+        ]
+    );
+    assert!(entries.iter().all(|e| e.path == "/api/chat" && e.remote.as_deref() == Some("127.0.0.1")));
+    // The streamed reply is logged when it ends, after the stand-in's pause.
+    assert!(entries[1].latency_ms >= 250, "{}", entries[1].latency_ms);
+}
+
+/// Open a streamed chat with the share key; it holds a slot for the stand-in's pause.
+async fn hold_slot(proxy: &ShareProxy) -> reqwest::Response {
+    let mut streamed = request("llama3");
+    streamed["stream"] = json!(true);
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/api/chat", proxy.local_addr()))
+        .header(SHARE_KEY_HEADER, KEY)
+        .json(&streamed)
+        .send()
+        .await
+        .expect("send");
+    assert_eq!(response.status().as_u16(), 200);
+    response
+}
+
+async fn status(proxy: &ShareProxy) -> Value {
+    let url = format!("http://{}/status", proxy.local_addr());
+    reqwest::get(url).await.expect("send").json().await.expect("json")
+}
+
+fn one_at_a_time(options: ProxyOptions) -> ProxyOptions {
+    ProxyOptions { max_concurrency: 1, ..options }
+}
+
+#[tokio::test]
+async fn queued_requests_are_served_fairly_by_weight() {
+    let carol = ClientToken { weight: Some(3), ..client("carol") };
+    let config = ProxyConfig { clients: vec![client("alice"), carol], ..config(KEY, &["llama3"]) };
+    let (proxy, upstream) = start(one_at_a_time(ProxyOptions::default()), config).await;
+    let proxy = Arc::new(proxy);
+
+    let held = hold_slot(&proxy).await;
+    // Alice queues a burst first, then carol (weight 3) queues hers.
+    let mut waiting = Vec::new();
+    for who in ["alice", "alice", "alice", "carol", "carol", "carol"] {
+        let proxy = proxy.clone();
+        let mut body = request("llama3");
+        body["who"] = json!(who);
+        waiting.push(tokio::spawn(async move { chat(&proxy, Some(&format!("{who}-token")), body).await }));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(status(&proxy).await["queued"], 6);
+
+    held.text().await.expect("body");
+    for call in waiting {
+        assert_eq!(call.await.expect("join").0, 200);
+    }
+    let order: Vec<String> = upstream
+        .requests
+        .lock()
+        .expect("lock")
+        .iter()
+        .filter_map(|r| r["who"].as_str().map(str::to_string))
+        .collect();
+    assert_eq!(order, ["alice", "carol", "carol", "carol", "alice", "alice"]);
+}
+
+#[tokio::test]
+async fn full_queue_answers_429_with_retry_after() {
+    let options = ProxyOptions { max_queued: 1, ..one_at_a_time(ProxyOptions::default()) };
+    let (proxy, _upstream) = start(options, config(KEY, &["llama3"])).await;
+    let proxy = Arc::new(proxy);
+
+    let held = hold_slot(&proxy).await;
+    let queued = {
+        let proxy = proxy.clone();
+        tokio::spawn(async move { chat(&proxy, Some(KEY), request("llama3")).await })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/api/chat", proxy.local_addr()))
+        .header(SHARE_KEY_HEADER, KEY)
+        .json(&request("llama3"))
+        .send()
+        .await
+        .expect("send");
+    assert_eq!(response.status().as_u16(), 429);
+    let retry_after: u64 = response.headers()["retry-after"]
+        .to_str()
+        .expect("ascii")
+        .parse()
+        .expect("seconds");
+    assert!((1..=60).contains(&retry_after), "{retry_after}");
+    assert!(response.text().await.expect("body").contains("queue full"));
+
+    // `/status` needs no key.
+    let busy = status(&proxy).await;
+    assert_eq!((busy["running"].as_u64(), busy["capacity"].as_u64()), (Some(1), Some(1)));
+    assert_eq!((busy["queued"].as_u64(), busy["max_queued"].as_u64()), (Some(1), Some(1)));
+    assert_eq!(busy["rejected"], 1);
+    assert!(busy["oldest_wait_ms"].as_u64().expect("wait") >= 40, "{busy}");
+
+    held.text().await.expect("body");
+    assert_eq!(queued.await.expect("join").0, 200);
+    let idle = status(&proxy).await;
+    assert_eq!((idle["running"].as_u64(), idle["queued"].as_u64()), (Some(0), Some(0)));
+    assert_eq!(idle["served"], 2);
+    assert!(idle["avg_wait_ms"].as_u64().expect("wait") > 0, "{idle}");
+    assert_eq!(proxy.status().served, 2);
+}
+
+#[tokio::test]
+async fn one_caller_cannot_fill_the_queue() {
+    let options = ProxyOptions { max_queued_per_caller: 1, ..one_at_a_time(ProxyOptions::default()) };
+    let config = ProxyConfig { clients: vec![client("alice"), client("bob")], ..config(KEY, &["llama3"]) };
+    let (proxy, _upstream) = start(options, config).await;
+    let proxy = Arc::new(proxy);
+
+    let held = hold_slot(&proxy).await;
+    let mut waiting = Vec::new();
+    for who in ["alice", "bob"] {
+        let proxy = proxy.clone();
+        waiting.push(tokio::spawn(async move { chat(&proxy, Some(&format!("{who}-token")), request("llama3")).await }));
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let (status, text) = chat(&proxy, Some("alice-token"), request("llama3")).await;
+    assert_eq!(status, 429);
+    assert!(text.contains("too many queued"), "{text}");
+
+    held.text().await.expect("body");
+    for call in waiting {
+        assert_eq!(call.await.expect("join").0, 200);
+    }
+    // The refused request does not count against alice's quota.
+    let usage = &proxy.client_usage()["alice"];
+    assert_eq!((usage.requests, usage.in_flight), (1, 0));
+}
+
+#[tokio::test]
+async fn waiting_too_long_answers_429() {
+    let options = ProxyOptions {
+        max_queue_wait: Duration::from_millis(100),
+        ..one_at_a_time(ProxyOptions::default())
+    };
+    let (proxy, _upstream) = start(options, config(KEY, &["llama3"])).await;
+
+    let held = hold_slot(&proxy).await;
+    let started = Instant::now();
+    let (status, text) = chat(&proxy, Some(KEY), request("llama3")).await;
+    assert_eq!(status, 429);
+    assert!(text.contains("timed out"), "{text}");
+    assert!(started.elapsed() < Duration::from_millis(250));
+    assert_eq!(proxy.status().queued, 0);
+
+    held.text().await.expect("body");
+    let (status, _) = chat(&proxy, Some(KEY), request("llama3")).await;
+    assert_eq!(status, 200);
+}
+
+/// A proxy serving HTTPS with a fresh certificate.
+async fn tls_proxy(config: ProxyConfig) -> (ShareProxy, TlsIdentity) {
+    let identity = TlsIdentity::generate().expect("certificate");
+    let options = ProxyOptions { tls: Some(identity.clone()), ..ProxyOptions::default() };
+    (start(options, config).await.0, identity)
+}
+
+/// POST `/api/chat` over TLS, trusting only `pin` and presenting `cert`.
+async fn tls_chat(
+    proxy: &ShareProxy,
+    pin: &str,
+    cert: Option<&TlsIdentity>,
+    key: Option<&str>,
+) -> Result<(u16, String), String> {
+    let client = tls::pinned_client(reqwest::Client::builder(), pin, cert)?
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut request = client
+        .post(format!("https://127.0.0.1:{}/api/chat", proxy.local_addr().port()))
+        .json(&request("llama3"));
+    if let Some(key) = key {
+        request = request.header(SHARE_KEY_HEADER, key);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    let status = response.status().as_u16();
+    Ok((status, response.text().await.map_err(|e| e.to_string())?))
+}
+
+#[tokio::test]
+async fn tls_proxy_only_talks_to_clients_that_pinned_its_certificate() {
+    let (proxy, identity) = tls_proxy(config(KEY, &["llama3"])).await;
+    assert_eq!(proxy.tls_fingerprint(), Some(identity.fingerprint()));
+    assert!(proxy.advertised_base_url().await.starts_with("https://"));
+
+    let (status, _) = tls_chat(&proxy, identity.fingerprint(), None, Some(KEY)).await.expect("https");
+    assert_eq!(status, 200);
+    let (status, _) = tls_chat(&proxy, identity.fingerprint(), None, None).await.expect("https");
+    assert_eq!(status, 401);
+
+    // Pins are compared however they were typed.
+    let typed = identity.fingerprint().to_uppercase();
+    assert!(tls_chat(&proxy, &typed, None, Some(KEY)).await.is_ok());
+
+    let other = TlsIdentity::generate().expect("certificate");
+    assert!(tls_chat(&proxy, other.fingerprint(), None, Some(KEY)).await.is_err());
+    let plain = reqwest::Client::new()
+        .post(format!("http://{}/api/chat", proxy.local_addr()))
+        .header(SHARE_KEY_HEADER, KEY)
+        .json(&request("llama3"))
+        .send()
+        .await;
+    assert!(plain.is_err() || !plain.expect("response").status().is_success());
+}
+
+#[tokio::test]
+async fn client_certificates_stand_in_for_the_key() {
+    let alice = TlsIdentity::generate().expect("certificate");
+    let peer = TlsIdentity::generate().expect("certificate");
+    let stranger = TlsIdentity::generate().expect("certificate");
+    let alice_client = ClientToken {
+        cert_fingerprint: Some(alice.fingerprint().to_uppercase()),
+        ..ClientToken::new("alice", "")
+    };
+    let config = ProxyConfig {
+        clients: vec![alice_client],
+        trusted_certs: [peer.fingerprint().to_string()].into(),
+        ..config(KEY, &["llama3"])
+    };
+    let (proxy, identity) = tls_proxy(config).await;
+    let pin = identity.fingerprint();
+
+    let (status, _) = tls_chat(&proxy, pin, Some(&alice), None).await.expect("https");
+    assert_eq!(status, 200);
+    assert_eq!(proxy.client_usage()["alice"].requests, 1);
+    let (status, _) = tls_chat(&proxy, pin, Some(&peer), None).await.expect("https");
+    assert_eq!(status, 200);
+    let (status, _) = tls_chat(&proxy, pin, Some(&stranger), None).await.expect("https");
+    assert_eq!(status, 401);
+    // A certificate doesn't get in the way of the key.
+    let (status, _) = tls_chat(&proxy, pin, Some(&stranger), Some(KEY)).await.expect("https");
+    assert_eq!(status, 200);
+
+    assert!(proxy.revoke("alice").await);
+    let (status, text) = tls_chat(&proxy, pin, Some(&alice), None).await.expect("https");
+    assert_eq!(status, 401);
+    assert!(text.contains("revoked"), "{text}");
+}
+
+#[tokio::test]
+async fn certificate_is_generated_once_and_reused() {
+    let dir = std::env::temp_dir().join(format!("share-proxy-tls-{}", uuid::Uuid::new_v4()));
+    let first = TlsIdentity::load_or_generate(&dir).expect("generate");
+    let again = TlsIdentity::load_or_generate(&dir).expect("load");
+    assert_eq!(first.fingerprint(), again.fingerprint());
+    assert_eq!(first.fingerprint().len(), 64);
+    assert!(dir.join(tls::CERT_FILE).exists() && dir.join(tls::KEY_FILE).exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn pins_are_kept_until_forgotten() {
+    let mut pins = TofuPins::default();
+    assert_eq!(pins.check("alice", "AB:CD"), PinCheck::New);
+    assert_eq!(pins.check("alice", "abcd"), PinCheck::Known);
+    assert_eq!(pins.check("alice", "ef01"), PinCheck::Changed { pinned: "abcd".to_string() });
+    assert_eq!(pins.get("alice"), Some("abcd"));
+
+    assert!(pins.forget("alice"));
+    assert_eq!(pins.check("alice", "ef01"), PinCheck::New);
+    let saved: TofuPins = serde_json::from_value(serde_json::to_value(&pins).expect("json")).expect("pins");
+    assert_eq!(saved.get("alice"), Some("ef01"));
+}
@@ -0,0 +1,371 @@
+//! Streaming model pulls, pushes and creates, `/api/ps` and batch embeddings
+//! against a local stand-in for the Ollama HTTP API.
+
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+
+use vas_core::backend::ollama_client::stream::LineDecoder;
+use vas_core::backend::ollama_client::{CreateRequest, OllamaClient, PullProgress};
+use vas_core::shared::models::LoadedModel;
+
+/// What the stand-in sends after the response headers.
+#[derive(Clone)]
+enum Script {
+    /// Send these lines (one chunk each, with a short pause), then end the body.
+    Frames(Vec<String>),
+    /// Reply with this status and no body.
+    Status(u16),
+    /// Send a progress frame every 20 ms until the client goes away.
+    Endless,
+}
+
+struct StandIn {
+    url: String,
+    /// Body of the first request received (`null` if it had none).
+    request: oneshot::Receiver<serde_json::Value>,
+    /// Fires once the client has closed the connection.
+    disconnected: oneshot::Receiver<()>,
+}
+
+async fn stand_in(script: Script) -> StandIn {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let url = format!("http://{}", listener.local_addr().expect("addr"));
+    let (request_tx, request) = oneshot::channel();
+    let (disconnected_tx, disconnected) = oneshot::channel();
+
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.expect("accept");
+        let body = read_request(&mut socket).await;
+        let _ = request_tx.send(body);
+        serve(&mut socket, script).await;
+        let _ = disconnected_tx.send(());
+    });
+
+    StandIn { url, request, disconnected }
+}
+
+async fn read_request(socket: &mut TcpStream) -> serde_json::Value {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let n = socket.read(&mut chunk).await.expect("read");
+        buf.extend_from_slice(&chunk[..n]);
+        let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") else { continue };
+        let head = String::from_utf8_lossy(&buf[..end]).to_lowercase();
+        let length: usize = head
+            .lines()
+            .find_map(|l| l.strip_prefix("content-length:"))
+            .map(|v| v.trim().parse().expect("content-length"))
+            .unwrap_or(0);
+        while buf.len() < end + 4 + length {
+            let n = socket.read(&mut chunk).await.expect("read body");
+            buf.extend_from_slice(&chunk[..n]);
+        }
+        if length == 0 {
+            return serde_json::Value::Null;
+        }
+        return serde_json::from_slice(&buf[end + 4..end + 4 + length]).expect("json body");
+    }
+}
+
+async fn serve(socket: &mut TcpStream, script: Script) {
+    let lines = match script {
+        Script::Status(code) => {
+            let head = format!("HTTP/1.1 {code} Error\r\ncontent-length: 0\r\nconnection: close\r\n\r\n");
+            let _ = socket.write_all(head.as_bytes()).await;
+            return;
+        }
+        Script::Frames(lines) => lines,
+        Script::Endless => Vec::new(),
+    };
+    let head = "HTTP/1.1 200 OK\r\ncontent-type: application/x-ndjson\r\ntransfer-encoding: chunked\r\n\r\n";
+    if socket.write_all(head.as_bytes()).await.is_err() {
+        return;
+    }
+
+    if lines.is_empty() {
+        for completed in 0u64.. {
+            let line = frame("pulling aaa", Some(1 << 30), Some(completed * 1024));
+            if write_chunk(socket, line.as_bytes()).await.is_err() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    for line in lines {
+        // Split every frame across two chunks to exercise line reassembly.
+        let bytes = line.as_bytes();
+        let (a, b) = bytes.split_at(bytes.len() / 2);
+        if write_chunk(socket, a).await.is_err() || write_chunk(socket, b).await.is_err() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let _ = socket.write_all(b"0\r\n\r\n").await;
+    // Wait for the client to hang up.
+    let _ = socket.read(&mut [0u8; 16]).await;
+}
+
+async fn write_chunk(socket: &mut TcpStream, data: &[u8]) -> std::io::Result<()> {
+    socket.write_all(format!("{:x}\r\n", data.len()).as_bytes()).await?;
+    socket.write_all(data).await?;
+    socket.write_all(b"\r\n").await?;
+    socket.flush().await
+}
+
+fn frame(status: &str, total: Option<u64>, completed: Option<u64>) -> String {
+    let mut value = json!({ "status": status });
+    if let (Some(total), Some(completed)) = (total, completed) {
+        value["digest"] = json!("sha256:aaa");
+        value["total"] = json!(total);
+        value["completed"] = json!(completed);
+    }
+    format!("{value}\n")
+}
+
+fn scripted_pull() -> Vec<String> {
+    vec![
+        frame("pulling manifest", None, None),
+        frame("pulling aaa", Some(1000), Some(0)),
+        frame("pulling aaa", Some(1000), Some(400)),
+        frame("pulling aaa", Some(1000), Some(1000)),
+        frame("verifying sha256 digest", None, None),
+        frame("writing manifest", None, None),
+        frame("success", None, None),
+    ]
+}
+
+#[tokio::test]
+async fn pull_stream_reports_layer_progress_in_order() {
+    let server = stand_in(Script::Frames(scripted_pull())).await;
+    let client = OllamaClient::new(Some(server.url.clone()));
+
+    let mut progress = client.pull_model_stream("llama3").await.expect("start pull");
+    let mut frames = Vec::new();
+    while let Some(frame) = progress.recv().await {
+        frames.push(frame.expect("frame"));
+    }
+
+    let request = server.request.await.expect("request body");
+    assert_eq!(request["name"], "llama3");
+    assert_eq!(request["stream"], true);
+
+    let statuses: Vec<&str> = frames.iter().map(|f| f.status.as_str()).collect();
+    assert_eq!(
+        statuses,
+        [
+            "pulling manifest",
+            "pulling aaa",
+            "pulling aaa",
+            "pulling aaa",
+            "verifying sha256 digest",
+            "writing manifest",
+            "success"
+        ]
+    );
+    assert_eq!(frames[0].fraction(), None);
+    assert_eq!(frames[2].digest.as_deref(), Some("sha256:aaa"));
+    assert_eq!((frames[2].completed, frames[2].total), (Some(400), Some(1000)));
+    assert_eq!(frames[2].fraction(), Some(0.4));
+    assert!(frames.last().expect("last frame").is_success());
+}
+
+#[tokio::test]
+async fn pull_model_waits_for_success() {
+    let server = stand_in(Script::Frames(scripted_pull())).await;
+    let client = OllamaClient::new(Some(server.url.clone()));
+
+    let message = client.pull_model("llama3").await.expect("pull");
+    assert!(message.contains("llama3"), "{message}");
+}
+
+#[tokio::test]
+async fn pull_model_fails_when_stream_ends_early() {
+    let lines = scripted_pull()[..3].to_vec();
+    let server = stand_in(Script::Frames(lines)).await;
+    let client = OllamaClient::new(Some(server.url.clone()));
+
+    let err = client.pull_model("llama3").await.expect_err("truncated pull");
+    assert!(err.contains("ended before"), "{err}");
+}
+
+#[tokio::test]
+async fn error_frame_ends_the_stream() {
+    let lines = vec![
+        frame("pulling manifest", None, None),
+        format!("{}\n", json!({ "error": "pull model manifest: file does not exist" })),
+        frame("success", None, None),
+    ];
+    let server = stand_in(Script::Frames(lines)).await;
+    let client = OllamaClient::new(Some(server.url.clone()));
+
+    let mut progress = client.pull_model_stream("nope").await.expect("start pull");
+    assert_eq!(progress.recv().await.expect("first").expect("ok").status, "pulling manifest");
+    let err = progress.recv().await.expect("second").expect_err("error frame");
+    assert!(err.contains("file does not exist"), "{err}");
+    assert!(progress.recv().await.is_none(), "nothing after the error");
+}
+
+#[tokio::test]
+async fn http_error_is_reported_before_streaming() {
+    let server = stand_in(Script::Status(404)).await;
+    let client = OllamaClient::new(Some(server.url.clone()));
+
+    let err = client.pull_model_stream("llama3").await.expect_err("404");
+    assert!(err.contains("404"), "{err}");
+}
+
+#[tokio::test]
+async fn dropping_the_stream_cancels_the_pull() {
+    let server = stand_in(Script::Endless).await;
+    let client = OllamaClient::new(Some(server.url.clone()));
+
+    let mut progress = client.pull_model_stream("llama3").await.expect("start pull");
+    for _ in 0..3 {
+        let frame: PullProgress = progress.recv().await.expect("frame").expect("ok");
+        assert_eq!(frame.status, "pulling aaa");
+    }
+    drop(progress);
+
+    tokio::time::timeout(Duration::from_secs(5), server.disconnected)
+        .await
+        .expect("connection closed after cancel")
+        .expect("stand-in finished");
+}
+
+#[tokio::test]
+async fn push_stream_reports_layer_progress() {
+    let lines = vec![
+        frame("retrieving manifest", None, None),
+        frame("pushing aaa", Some(1000), Some(0)),
+        frame("pushing aaa", Some(1000), Some(1000)),
+        frame("pushing manifest", None, None),
+        frame("success", None, None),
+    ];
+    let server = stand_in(Script::Frames(lines)).await;
+    let client = OllamaClient::new(Some(server.url.clone()));
+
+    let mut progress = client.push_model_stream("me/llama3", true).await.expect("start push");
+    let mut frames = Vec::new();
+    while let Some(frame) = progress.recv().await {
+        frames.push(frame.expect("frame"));
+    }
+
+    let request = server.request.await.expect("request body");
+    assert_eq!(request["model"], "me/llama3");
+    assert_eq!(request["insecure"], true);
+    assert_eq!(request["stream"], true);
+
+    assert_eq!(frames.len(), 5);
+    assert_eq!(frames[2].fraction(), Some(1.0));
+    assert!(frames.last().expect("last frame").is_success());
+}
+
+#[tokio::test]
+async fn create_model_waits_for_success() {
+    let lines = vec![
+        frame("reading model metadata", None, None),
+        frame("creating system layer", None, None),
+        frame("writing manifest", None, None),
+        frame("success", None, None),
+    ];
+    let server = stand_in(Script::Frames(lines)).await;
+    let client = OllamaClient::new(Some(server.url.clone()));
+
+    let request = CreateRequest::from_modelfile("terse", "FROM llama3\nSYSTEM Answer briefly.").expect("modelfile");
+    let message = client.create_model(request).await.expect("create");
+    assert!(message.contains("terse"), "{message}");
+
+    let request = server.request.await.expect("request body");
+    assert_eq!(request["model"], "terse");
+    assert_eq!(request["from"], "llama3");
+    assert_eq!(request["stream"], true);
+}
+
+#[tokio::test]
+async fn create_model_reports_an_error_frame() {
+    let lines = vec![
+        frame("reading model metadata", None, None),
+        format!("{}\n", json!({ "error": "pull model manifest: file does not exist" })),
+    ];
+    let server = stand_in(Script::Frames(lines)).await;
+    let client = OllamaClient::new(Some(server.url.clone()));
+
+    let request = CreateRequest::from_modelfile("terse", "FROM nope").expect("modelfile");
+    let err = client.create_model(request).await.expect_err("error frame");
+    assert!(err.contains("file does not exist"), "{err}");
+}
+
+#[tokio::test]
+async fn running_models_are_parsed_from_ps() {
+    let body = json!({
+        "models": [
+            {
+                "name": "llama3:latest",
+                "model": "llama3:latest",
+                "size": 6_u64 << 30,
+                "size_vram": 4_u64 << 30,
+                "digest": "sha256:aaa",
+                "details": { "format": "gguf", "family": "llama", "parameter_size": "8B", "quantization_level": "Q4_0" },
+                "expires_at": "2024-06-04T14:38:31.83753-07:00"
+            },
+            { "name": "all-minilm", "size": 50_u64 << 20 }
+        ]
+    });
+    let server = stand_in(Script::Frames(vec![format!("{body}\n")])).await;
+    let client = OllamaClient::new(Some(server.url.clone()));
+
+    let running = client.list_running().await.expect("ps");
+    assert_eq!(server.request.await.expect("request"), serde_json::Value::Null);
+    assert_eq!(running.len(), 2);
+    assert_eq!(running[0].name, "llama3:latest");
+    assert_eq!(running[0].digest, "sha256:aaa");
+
+    let loaded: Vec<LoadedModel> = running.iter().map(LoadedModel::from).collect();
+    assert_eq!((loaded[0].size_mb, loaded[0].vram_mb), (6 * 1024, 4 * 1024));
+    let expires = loaded[0].expires_at.expect("expiry");
+    assert_eq!(expires.to_rfc3339(), "2024-06-04T21:38:31.837530+00:00");
+    assert_eq!((loaded[1].size_mb, loaded[1].vram_mb), (50, 0));
+    assert_eq!(loaded[1].expires_at, None);
+}
+
+#[tokio::test]
+async fn embed_batch_rejects_a_count_mismatch() {
+    let body = json!({ "model": "all-minilm", "embeddings": [[0.1, 0.2, 0.3]] });
+    let server = stand_in(Script::Frames(vec![format!("{body}\n")])).await;
+    let client = OllamaClient::new(Some(server.url.clone()));
+
+    let inputs = vec!["first".to_string(), "second".to_string()];
+    let err = client.embed_batch("all-minilm", &inputs).await.expect_err("one vector for two inputs");
+    assert!(err.contains("Expected 2 embeddings, got 1"), "{err}");
+
+    let request = server.request.await.expect("request body");
+    assert_eq!(request["input"], json!(["first", "second"]));
+}
+
+#[tokio::test]
+async fn embed_batch_keeps_input_order() {
+    let body = json!({ "embeddings": [[1.0, 0.0], [0.0, 1.0]] });
+    let server = stand_in(Script::Frames(vec![format!("{body}\n")])).await;
+    let client = OllamaClient::new(Some(server.url.clone()));
+
+    let inputs = vec!["first".to_string(), "second".to_string()];
+    let vectors = client.embed_batch("all-minilm", &inputs).await.expect("embed");
+    assert_eq!(vectors, [vec![1.0, 0.0], vec![0.0, 1.0]]);
+}
+
+#[test]
+fn line_decoder_reassembles_split_lines() {
+    let mut decoder = LineDecoder::default();
+    assert!(decoder.push(b"{\"status\":").is_empty());
+    assert_eq!(decoder.push(b"\"a\"}\r\n{\"sta"), vec!["{\"status\":\"a\"}"]);
+    assert_eq!(decoder.push(b"tus\":\"b\"}\n\n"), vec!["{\"status\":\"b\"}", ""]);
+    assert_eq!(decoder.push(b"tail"), Vec::<String>::new());
+    assert_eq!(decoder.finish().as_deref(), Some("tail"));
+    assert_eq!(decoder.finish(), None);
+}
@@ -0,0 +1,108 @@
+//! Resource nodes found through discovery: two resource managers on loopback,
+//! each on its own port, the second seeded with the first.
+
+use std::time::{Duration, Instant};
+
+use vas_core::backend::{DiscoveryOptions, NetworkDiscovery, ResourceManager};
+use vas_core::shared::models::{NodeInfo, NodeResources, NodeStatus};
+
+/// A discovery node on a port of its own that doesn't broadcast.
+async fn discovery(seeds: &[u16]) -> NetworkDiscovery {
+    NetworkDiscovery::with_options(DiscoveryOptions {
+        port: 0,
+        broadcast: false,
+        seeds: seeds.iter().map(|port| format!("127.0.0.1:{port}")).collect(),
+        announce_interval: Duration::from_millis(200),
+        ..DiscoveryOptions::default()
+    })
+    .await
+}
+
+/// Two managers that see each other; the second is seeded with the first.
+async fn pair() -> (ResourceManager, ResourceManager) {
+    let a = discovery(&[]).await;
+    let b = discovery(&[a.port()]).await;
+    let a = ResourceManager::new(a, true).await.expect("manager a");
+    let b = ResourceManager::new(b, true).await.expect("manager b");
+    (a, b)
+}
+
+fn resources() -> NodeResources {
+    NodeResources {
+        cpu_cores: 12,
+        total_memory_mb: 32 * 1024,
+        available_memory_mb: 24 * 1024,
+        gpus: Vec::new(),
+        supported_models: vec!["llama3:latest".to_string()],
+        current_load: 0.25,
+        loaded_models: Vec::new(),
+    }
+}
+
+async fn node(manager: &ResourceManager, id: &str) -> Option<NodeInfo> {
+    manager.list_discovered_nodes().await.into_iter().find(|n| n.id == id)
+}
+
+/// Wait up to a few seconds for `manager` to list (or stop listing) `id`.
+async fn wait_for_node(manager: &ResourceManager, id: &str, listed: bool) -> Option<NodeInfo> {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let found = node(manager, id).await;
+        if found.is_some() == listed || Instant::now() >= deadline {
+            return found;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+#[tokio::test]
+async fn published_resources_show_up_on_the_other_node() {
+    let (a, b) = pair().await;
+    let a_id = a.local_node_id().await;
+    a.start_discovery().await.expect("start");
+    a.update_node_resources(resources()).await;
+
+    let seen = wait_for_node(&b, &a_id, true).await.expect("b lists a");
+    assert_eq!(seen.resources.cpu_cores, 12);
+    assert_eq!(seen.resources.available_memory_mb, 24 * 1024);
+    assert_eq!(seen.resources.supported_models, ["llama3:latest"]);
+    assert_eq!(seen.address, "127.0.0.1");
+    assert_ne!(seen.port, 0);
+    assert_eq!(seen.status, NodeStatus::Online);
+    assert!(seen.allow_remote_access);
+}
+
+#[tokio::test]
+async fn turning_off_remote_access_withdraws_the_resources() {
+    let (a, b) = pair().await;
+    let a_id = a.local_node_id().await;
+    a.start_discovery().await.expect("start");
+    a.update_node_resources(resources()).await;
+    assert!(wait_for_node(&b, &a_id, true).await.is_some(), "b never listed a");
+
+    a.set_remote_access(false).await;
+    assert!(wait_for_node(&b, &a_id, false).await.is_none(), "b still lists a");
+
+    a.set_remote_access(true).await;
+    assert!(wait_for_node(&b, &a_id, true).await.is_some(), "b did not list a again");
+}
+
+#[tokio::test]
+async fn a_peer_without_resources_is_not_a_node() {
+    let a_discovery = discovery(&[]).await;
+    let b_discovery = discovery(&[a_discovery.port()]).await;
+    let a = ResourceManager::new(a_discovery.clone(), true).await.expect("manager a");
+    let b = ResourceManager::new(b_discovery.clone(), true).await.expect("manager b");
+    let b_id = b.local_node_id().await;
+    // Only b publishes; a is still announced, but without resources.
+    b.start_discovery().await.expect("start");
+    b.update_node_resources(resources()).await;
+
+    assert!(wait_for_node(&a, &b_id, true).await.is_some(), "a never listed b");
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !b_discovery.discover_peers().await.expect("peers").iter().any(|p| p.id == a_discovery.local_node_id()) {
+        assert!(Instant::now() < deadline, "b never saw a");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(b.list_discovered_nodes().await.is_empty());
+}
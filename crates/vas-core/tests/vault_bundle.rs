@@ -0,0 +1,217 @@
+//! Round-trip tests for vault export/import bundles.
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use vas_core::backend::vault_bundle::{self, BundlePayload};
+use vas_core::backend::ApiKeyManager;
+use vas_core::shared::models::{AiEntity, ImportConflictPolicy, VaultOp, VaultResult};
+
+struct TestVault {
+    _runtime: tokio::runtime::Runtime,
+    dir: PathBuf,
+    manager: ApiKeyManager,
+}
+
+impl TestVault {
+    fn new(password: &str) -> Self {
+        let runtime = tokio::runtime::Runtime::new().expect("runtime");
+        let dir = std::env::temp_dir().join(format!("vas-vault-test-{}", uuid::Uuid::new_v4()));
+        let manager = runtime.block_on(ApiKeyManager::with_vault_dir(&dir));
+        manager.vault_initialize(password).expect("initialize vault");
+        Self { _runtime: runtime, dir, manager }
+    }
+
+    fn store(&self, name: &str, key: &str, provider: &str) {
+        self.manager
+            .vault_operation(VaultOp::Store { provider: name.to_string(), key: key.into() })
+            .expect("store");
+        self.manager
+            .vault_operation(VaultOp::SetEntryMeta {
+                provider: name.to_string(),
+                provider_id: Some(provider.to_string()),
+                notes: format!("{name} notes"),
+            })
+            .expect("set meta");
+    }
+
+    fn key(&self, name: &str) -> String {
+        self.manager
+            .get_decrypted_key(name)
+            .expect("retrieve")
+            .expose_secret()
+            .to_string()
+    }
+
+    fn names(&self) -> Vec<String> {
+        match self.manager.vault_operation(VaultOp::List).expect("list") {
+            VaultResult::Providers(mut names) => {
+                names.sort();
+                names
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+}
+
+impl Drop for TestVault {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn entity(name: &str, provider: &str) -> AiEntity {
+    AiEntity {
+        name: name.to_string(),
+        provider: provider.to_string(),
+        model: "test-model".to_string(),
+        note: String::new(),
+        custom_base_url: None,
+        key_header: None,
+        key_prefix: None,
+    }
+}
+
+fn export_source() -> (TestVault, String) {
+    let source = TestVault::new("source-password");
+    source.store("openai-main", "sk-source-openai", "openai");
+    source.store("deepseek", "sk-source-deepseek", "deepseek");
+    let bundle = source
+        .manager
+        .export_bundle(
+            &["openai-main".to_string(), "deepseek".to_string()],
+            &[entity("openai-main", "openai")],
+            "bundle-passphrase",
+        )
+        .expect("export");
+    (source, bundle)
+}
+
+#[test]
+fn round_trip_preserves_secrets_metadata_and_entities() {
+    let (_source, bundle) = export_source();
+    let target = TestVault::new("target-password");
+
+    let report = target
+        .manager
+        .import_bundle(&bundle, "bundle-passphrase", ImportConflictPolicy::Skip)
+        .expect("import");
+
+    assert_eq!(report.imported.len(), 2);
+    assert!(report.skipped.is_empty());
+    assert_eq!(target.names(), vec!["deepseek".to_string(), "openai-main".to_string()]);
+    assert_eq!(target.key("openai-main"), "sk-source-openai");
+    assert_eq!(target.key("deepseek"), "sk-source-deepseek");
+
+    let entries = match target.manager.vault_operation(VaultOp::ListEntries).expect("entries") {
+        VaultResult::Entries(entries) => entries,
+        other => panic!("unexpected result: {other:?}"),
+    };
+    let openai = entries.iter().find(|e| e.name == "openai-main").expect("entry");
+    assert_eq!(openai.provider_id.as_deref(), Some("openai"));
+    assert_eq!(openai.notes, "openai-main notes");
+
+    assert_eq!(report.entities, vec![entity("openai-main", "openai")]);
+}
+
+#[test]
+fn skip_keeps_local_entry() {
+    let (_source, bundle) = export_source();
+    let target = TestVault::new("target-password");
+    target.store("openai-main", "sk-local", "openai");
+
+    let report = target
+        .manager
+        .import_bundle(&bundle, "bundle-passphrase", ImportConflictPolicy::Skip)
+        .expect("import");
+
+    assert_eq!(report.skipped, vec!["openai-main".to_string()]);
+    assert_eq!(report.imported, vec!["deepseek".to_string()]);
+    assert_eq!(target.key("openai-main"), "sk-local");
+    assert!(report.entities.is_empty());
+}
+
+#[test]
+fn overwrite_replaces_local_entry() {
+    let (_source, bundle) = export_source();
+    let target = TestVault::new("target-password");
+    target.store("openai-main", "sk-local", "openai");
+
+    let report = target
+        .manager
+        .import_bundle(&bundle, "bundle-passphrase", ImportConflictPolicy::Overwrite)
+        .expect("import");
+
+    assert_eq!(report.overwritten, vec!["openai-main".to_string()]);
+    assert_eq!(target.key("openai-main"), "sk-source-openai");
+    assert_eq!(report.entities, vec![entity("openai-main", "openai")]);
+}
+
+#[test]
+fn rename_imports_alongside_local_entry() {
+    let (_source, bundle) = export_source();
+    let target = TestVault::new("target-password");
+    target.store("openai-main", "sk-local", "openai");
+
+    let report = target
+        .manager
+        .import_bundle(&bundle, "bundle-passphrase", ImportConflictPolicy::Rename)
+        .expect("import");
+
+    assert_eq!(
+        report.renamed,
+        vec![("openai-main".to_string(), "openai-main-imported".to_string())]
+    );
+    assert_eq!(target.key("openai-main"), "sk-local");
+    assert_eq!(target.key("openai-main-imported"), "sk-source-openai");
+    assert_eq!(report.entities, vec![entity("openai-main-imported", "openai")]);
+}
+
+#[test]
+fn wrong_passphrase_is_rejected() {
+    let (_source, bundle) = export_source();
+    let target = TestVault::new("target-password");
+
+    assert!(target
+        .manager
+        .import_bundle(&bundle, "not-the-passphrase", ImportConflictPolicy::Skip)
+        .is_err());
+    assert!(target.names().is_empty());
+}
+
+fn edit(bundle: &str, change: impl FnOnce(&mut serde_json::Value)) -> String {
+    let mut json: serde_json::Value = serde_json::from_str(bundle).expect("bundle json");
+    change(&mut json);
+    json.to_string()
+}
+
+#[test]
+fn inflated_kdf_parameters_are_refused_before_deriving() {
+    let bundle = vault_bundle::seal(&BundlePayload::default(), "bundle-passphrase").expect("seal");
+    let inflated = [
+        ("memory_kib", u32::MAX),
+        ("iterations", u32::MAX),
+        ("parallelism", 1024),
+    ];
+    for (field, value) in inflated {
+        let bundle = edit(&bundle, |json| json["kdf"][field] = value.into());
+        // Deriving with any of these would take minutes or exhaust memory.
+        let started = Instant::now();
+        let err = vault_bundle::open(&bundle, "bundle-passphrase").expect_err(field);
+        assert!(started.elapsed() < Duration::from_secs(1), "{field} ran the KDF");
+        assert!(format!("{err:?}").contains("exceed the limits"), "{field}: {err:?}");
+    }
+
+    let bundle = edit(&bundle, |json| json["kdf"]["algorithm"] = "argon2d".into());
+    let err = vault_bundle::open(&bundle, "bundle-passphrase").expect_err("algorithm");
+    assert!(format!("{err:?}").contains("Unsupported bundle KDF"), "{err:?}");
+}
+
+#[test]
+fn an_edited_header_fails_decryption() {
+    let bundle = vault_bundle::seal(&BundlePayload::default(), "bundle-passphrase").expect("seal");
+    assert!(vault_bundle::open(&bundle, "bundle-passphrase").is_ok());
+
+    let older = edit(&bundle, |json| json["format_version"] = 0.into());
+    assert!(vault_bundle::open(&older, "bundle-passphrase").is_err());
+}
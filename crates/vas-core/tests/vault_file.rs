@@ -0,0 +1,260 @@
+//! Vault container tests: legacy migration, password checks, tamper detection
+//! and the idle auto-lock.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use rand::RngCore;
+use vas_core::backend::vault_file::{self, KdfParams, VaultFile};
+use vas_core::backend::ApiKeyManager;
+use vas_core::shared::models::{KeyValidationStatus, VaultOp, VgaError};
+
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> Self {
+        Self(std::env::temp_dir().join(format!("vas-vault-file-{}", uuid::Uuid::new_v4())))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Write the pre-`vault.json` layout: `salt.bin`, `vault_check.enc` and one
+/// `<name>.enc` per key, all under `Argon2::default()` with no associated data.
+fn write_legacy_layout(dir: &Path, password: &str, keys: &[(&str, &str)]) {
+    fs::create_dir_all(dir).expect("create dir");
+    let mut salt = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut salt);
+    let key = KdfParams::default().derive_key(password, &salt).expect("derive");
+
+    fs::write(dir.join("salt.bin"), salt).expect("write salt");
+    let check = vault_file::encrypt(&key, b"vas-vault-ok", b"").expect("encrypt check");
+    fs::write(dir.join("vault_check.enc"), check).expect("write check");
+    for (name, secret) in keys {
+        let payload = vault_file::encrypt(&key, secret.as_bytes(), b"").expect("encrypt key");
+        fs::write(dir.join(format!("{name}.enc")), payload).expect("write key");
+    }
+}
+
+fn legacy_backups(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .expect("read dir")
+        .map(|e| e.expect("entry").path())
+        .filter(|p| p.is_dir() && p.file_name().is_some_and(|n| n.to_string_lossy().starts_with("legacy-")))
+        .collect()
+}
+
+/// Vault operations block on the manager's own locks, so they run outside the runtime.
+fn manager(dir: &Path) -> (tokio::runtime::Runtime, ApiKeyManager) {
+    let runtime = tokio::runtime::Runtime::new().expect("runtime");
+    let manager = runtime.block_on(ApiKeyManager::with_vault_dir(dir));
+    (runtime, manager)
+}
+
+/// A sealed vault holding one `openai` secret, saved to `<dir>/vault.json`.
+fn sealed_vault(dir: &Path, password: &str) -> (VaultFile, vault_file::MasterKey) {
+    fs::create_dir_all(dir).expect("create dir");
+    let (mut file, key) = VaultFile::create(password).expect("create");
+    file.put_secret(&key, "openai", b"sk-openai").expect("put");
+    file.seal(&key).expect("seal");
+    file.save(&dir.join("vault.json")).expect("save");
+    (file, key)
+}
+
+fn assert_integrity_error(result: Result<(), VgaError>) {
+    match result {
+        Err(VgaError::AuthVaultError(message)) => {
+            assert!(message.contains("integrity"), "{message}")
+        }
+        other => panic!("expected an integrity error, got {other:?}"),
+    }
+}
+
+#[test]
+fn legacy_layout_is_migrated_on_unlock() {
+    let dir = TempDir::new();
+    write_legacy_layout(&dir.0, "legacy-password", &[("openai", "sk-openai"), ("deepseek", "sk-deepseek")]);
+
+    let (_runtime, manager) = manager(&dir.0);
+    assert!(manager.vault_is_initialized());
+    manager.vault_unlock("legacy-password").expect("unlock");
+
+    for (name, secret) in [("openai", "sk-openai"), ("deepseek", "sk-deepseek")] {
+        let key = manager.get_decrypted_key(name).expect("retrieve");
+        assert_eq!(key.expose_secret(), secret);
+    }
+
+    // The old files are kept, but out of the way.
+    assert!(dir.0.join("vault.json").exists());
+    for name in ["salt.bin", "vault_check.enc", "openai.enc", "deepseek.enc"] {
+        assert!(!dir.0.join(name).exists(), "{name} left in place");
+    }
+    let backups = legacy_backups(&dir.0);
+    assert_eq!(backups.len(), 1);
+    assert!(backups[0].join("openai.enc").exists());
+
+    // Later unlocks read vault.json with the same password.
+    manager.vault_lock();
+    manager.vault_unlock("legacy-password").expect("unlock again");
+    assert_eq!(manager.get_decrypted_key("openai").expect("retrieve").expose_secret(), "sk-openai");
+}
+
+#[test]
+fn wrong_password_does_not_migrate_the_legacy_layout() {
+    let dir = TempDir::new();
+    write_legacy_layout(&dir.0, "legacy-password", &[("openai", "sk-openai")]);
+
+    let (_runtime, manager) = manager(&dir.0);
+    assert!(manager.vault_unlock("not-the-password").is_err());
+    assert!(!manager.vault_is_unlocked());
+
+    assert!(!dir.0.join("vault.json").exists());
+    assert!(dir.0.join("openai.enc").exists());
+    assert!(legacy_backups(&dir.0).is_empty());
+}
+
+#[test]
+fn wrong_password_is_rejected() {
+    let dir = TempDir::new();
+    let (_runtime, manager) = manager(&dir.0);
+    manager.vault_initialize("right-password").expect("initialize");
+    manager.vault_lock();
+
+    assert!(manager.vault_unlock("wrong-password").is_err());
+    assert!(!manager.vault_is_unlocked());
+    assert!(manager.get_decrypted_key("openai").is_err());
+    manager.vault_unlock("right-password").expect("unlock");
+
+    let file = VaultFile::load(&dir.0.join("vault.json")).expect("load");
+    assert!(file.unlock("wrong-password").is_err());
+    assert!(file.unlock("right-password").is_ok());
+}
+
+#[test]
+fn tampered_mac_fails_verification() {
+    let dir = TempDir::new();
+    let (mut file, key) = sealed_vault(&dir.0, "password");
+    file.verify(&key).expect("untouched vault verifies");
+
+    let flipped = if file.mac.starts_with('0') { "1" } else { "0" };
+    file.mac.replace_range(0..1, flipped);
+    assert_integrity_error(file.verify(&key));
+
+    file.mac = "not hex".to_string();
+    assert_integrity_error(file.verify(&key));
+}
+
+#[test]
+fn tampered_entries_fail_verification() {
+    let dir = TempDir::new();
+    let (file, key) = sealed_vault(&dir.0, "password");
+
+    let mut renamed = file.clone();
+    renamed.entries[0].notes = "edited by hand".to_string();
+    assert_integrity_error(renamed.verify(&key));
+
+    let mut reencrypted = file.clone();
+    let payload = vault_file::encrypt(&key, b"sk-attacker", b"openai").expect("encrypt");
+    reencrypted.entries[0].ciphertext = hex::encode(payload);
+    assert_integrity_error(reencrypted.verify(&key));
+
+    let mut dropped = file.clone();
+    dropped.entries.clear();
+    assert_integrity_error(dropped.verify(&key));
+}
+
+#[test]
+fn a_tampered_vault_file_refuses_to_unlock_or_read() {
+    let dir = TempDir::new();
+    let (_runtime, manager) = manager(&dir.0);
+    manager.vault_initialize("password").expect("initialize");
+    manager
+        .vault_operation(VaultOp::Store {
+            provider: "openai".to_string(),
+            key: "sk-openai".into(),
+        })
+        .expect("store");
+
+    let path = dir.0.join("vault.json");
+    let mut json: serde_json::Value = serde_json::from_slice(&fs::read(&path).expect("read")).expect("parse");
+    json["entries"][0]["notes"] = "edited by hand".into();
+    fs::write(&path, serde_json::to_vec_pretty(&json).expect("serialize")).expect("write");
+
+    assert!(manager.get_decrypted_key("openai").is_err());
+    manager.vault_lock();
+    assert!(manager.vault_unlock("password").is_err());
+}
+
+#[test]
+fn unset_optional_fields_keep_old_macs_valid() {
+    let dir = TempDir::new();
+    let (mut file, key) = sealed_vault(&dir.0, "password");
+    let original_mac = file.mac.clone();
+
+    // A file written before these fields existed has no keys for them at all.
+    let json: serde_json::Value =
+        serde_json::from_slice(&fs::read(dir.0.join("vault.json")).expect("read")).expect("parse");
+    let entry = json["entries"][0].as_object().expect("entry");
+    for field in ["last_validated_at", "last_validation", "pool"] {
+        assert!(!entry.contains_key(field), "{field} serialized while unset");
+    }
+    VaultFile::load(&dir.0.join("vault.json")).expect("load").verify(&key).expect("verify");
+
+    // Setting them changes the MAC; clearing them again restores the old one.
+    let entry = file.entry_mut("openai").expect("entry");
+    entry.pool = Some("primary".to_string());
+    entry.last_validated_at = Some(chrono::Utc::now());
+    entry.last_validation = Some(KeyValidationStatus::Valid);
+    file.seal(&key).expect("seal");
+    assert_ne!(file.mac, original_mac);
+
+    let entry = file.entry_mut("openai").expect("entry");
+    entry.pool = None;
+    entry.last_validated_at = None;
+    entry.last_validation = None;
+    file.seal(&key).expect("seal");
+    assert_eq!(file.mac, original_mac);
+}
+
+#[test]
+fn an_idle_vault_locks_after_the_timeout() {
+    let dir = TempDir::new();
+    let (_runtime, manager) = manager(&dir.0);
+    manager.vault_initialize("password").expect("initialize");
+    manager.set_auto_lock(Some(Duration::from_millis(300)));
+    assert!(manager.vault_is_unlocked());
+
+    std::thread::sleep(Duration::from_millis(400));
+    assert!(!manager.vault_is_unlocked());
+    assert!(manager.get_decrypted_key("openai").is_err());
+}
+
+#[test]
+fn vault_activity_resets_the_idle_timer() {
+    let dir = TempDir::new();
+    let (_runtime, manager) = manager(&dir.0);
+    manager.vault_initialize("password").expect("initialize");
+    manager
+        .vault_operation(VaultOp::Store {
+            provider: "openai".to_string(),
+            key: "sk-openai".into(),
+        })
+        .expect("store");
+    manager.set_auto_lock(Some(Duration::from_millis(600)));
+
+    // Each read lands inside the timeout, though together they outlast it.
+    for _ in 0..3 {
+        std::thread::sleep(Duration::from_millis(300));
+        manager.get_decrypted_key("openai").expect("still unlocked");
+    }
+    assert!(manager.vault_is_unlocked());
+
+    manager.set_auto_lock(None);
+    std::thread::sleep(Duration::from_millis(700));
+    assert!(manager.vault_is_unlocked());
+}
@@ -1,6 +1,7 @@
 use std::time::Duration;
 
-use vas_core::backend::{NetworkDiscovery, OllamaManager};
+use ollama_lan_share::backend::headless::{split_list, ShareArgs};
+use vas_core::backend::OllamaManager;
 
 fn main() {
     tracing_subscriber::fmt()
@@ -12,23 +13,21 @@ fn main() {
 
     let args = Args::from_env();
 
+    let setup = match args.share.clone().load() {
+        Ok(setup) => setup,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+
     let rt = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .expect("create tokio runtime");
 
     rt.block_on(async move {
-        let discovery = NetworkDiscovery::new().await;
-
-        if let Some(name) = args.name {
-            discovery.set_local_node_name(name).await;
-        }
-        let groups = args.groups.clone();
-        if !groups.is_empty() {
-            discovery.set_local_groups(groups.clone()).await;
-        }
-
-        let ollama = OllamaManager::new(args.ollama_base_url.clone()).await;
+        let ollama = OllamaManager::new(args.share.ollama_base_url.clone()).await;
 
         // Load models once at startup.
         let models = match args.models {
@@ -47,89 +46,58 @@ fn main() {
             },
         };
 
-        discovery
-            .set_ollama_offer(true, models.clone(), args.ollama_base_url.clone())
-            .await;
-        discovery.broadcast_presence();
+        let keyed_groups: Vec<String> = setup.keyed_groups().into_iter().map(str::to_string).collect();
+        let node = match setup.start(Some(models.clone())).await {
+            Ok(node) => node,
+            Err(e) => {
+                tracing::error!("{e}");
+                std::process::exit(1);
+            }
+        };
 
         tracing::info!(
-            "vas-ollama-share started. groups={:?} models={:?} base_url={:?}",
-            groups,
+            "vas-ollama-share started. groups={:?} keyed_groups={:?} models={:?} base_url={:?}",
+            args.share.groups,
+            keyed_groups,
             models,
-            args.ollama_base_url
+            node.base_url
         );
 
         // Keep announcing periodically via NetworkDiscovery background loop.
         // We also refresh debug stats so users can see progress in logs.
-        let mut interval = tokio::time::interval(Duration::from_secs(5));
-        loop {
-            interval.tick().await;
-            let d = discovery.debug_stats().await;
-            tracing::info!(
-                "discovery: bound={} tx(a/q)={}/{} rx(a/q)={}/{} last_rx={:?} {:?}",
-                d.socket_bound,
-                d.sent_announces,
-                d.sent_queries,
-                d.received_announces,
-                d.received_queries,
-                d.last_received_kind,
-                d.last_received_from
-            );
-        }
+        node.run(Duration::from_secs(5)).await;
     });
 }
 
 #[derive(Debug, Default, Clone)]
 struct Args {
-    name: Option<String>,
-    groups: Vec<String>,
+    share: ShareArgs,
     models: Option<Vec<String>>,
-    ollama_base_url: Option<String>,
 }
 
 impl Args {
     fn from_env() -> Self {
-        // Minimal parsing:
-        //   --name <name>
-        //   --group <groupId>   (repeatable)
-        //   --groups <csv>
+        // Minimal parsing; the discovery, group and proxy flags are
+        // ShareArgs', the rest are:
         //   --models <csv>
-        //   --ollama <base_url>
         let mut out = Args::default();
         let mut it = std::env::args().skip(1);
         while let Some(a) = it.next() {
-            match a.as_str() {
-                "--name" => out.name = it.next(),
-                "--group" => {
-                    if let Some(g) = it.next() {
-                        if !g.trim().is_empty() {
-                            out.groups.push(g);
-                        }
-                    }
-                }
-                "--groups" => {
-                    if let Some(csv) = it.next() {
-                        out.groups.extend(split_csv(&csv));
-                    }
+            match out.share.parse_flag(&a, &mut it) {
+                Ok(true) => continue,
+                Ok(false) => {}
+                Err(e) => {
+                    eprintln!("{e}");
+                    std::process::exit(2);
                 }
-                "--models" => {
-                    if let Some(csv) = it.next() {
-                        out.models = Some(split_csv(&csv));
-                    }
+            }
+            if a == "--models" {
+                if let Some(csv) = it.next() {
+                    out.models = Some(split_list(&csv));
                 }
-                "--ollama" => out.ollama_base_url = it.next(),
-                _ => {}
             }
         }
 
         out
     }
 }
-
-fn split_csv(s: &str) -> Vec<String> {
-    s.split(',')
-        .map(|x| x.trim())
-        .filter(|x| !x.is_empty())
-        .map(|x| x.to_string())
-        .collect()
-}
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 use crate::app_types::{UiLang, ProviderFilter, ActiveView, AiEntity};
-use vangriten_ai_swarm::shared::models::{Priority, BalancingStrategy};
+use vangriten_ai_swarm::shared::models::{Priority, BalancingStrategy, CircuitStatus, EntityGroup, ImportConflictPolicy, KeyPoolStatus, KeyValidationResult, ModelListing, TaskEventKind, TaskId};
 
 /// Root application state for the `vgs` GUI.
 pub struct VgaGuiApp {
@@ -48,6 +48,11 @@ pub struct VgaGuiApp {
     pub api_stored_providers: Vec<String>,
     pub api_usage_json: String,
     pub api_quick_status: String,
+    /// Vault idle auto-lock in minutes (0 = never).
+    pub api_auto_lock_mins: u64,
+    pub api_bundle_passphrase: String,
+    pub api_bundle_include_entities: bool,
+    pub api_bundle_policy: ImportConflictPolicy,
 
     // Named AI entities
     pub ai_entities: Vec<AiEntity>,
@@ -58,8 +63,25 @@ pub struct VgaGuiApp {
     pub entity_key_header_input: String,
     pub entity_key_prefix_input: String,
     pub entity_selected: Option<usize>,
-    /// How many clones to create in burst mode (1–10)
-    pub entity_burst_count: u8,
+    /// Extra keys for the selected entity's pool, one per line
+    pub entity_pool_keys_input: String,
+    /// Rotation state of the selected entity's key pool
+    pub entity_pool_status: Option<KeyPoolStatus>,
+    /// Models discovered for the entity in the editor form.
+    pub entity_model_listing: Option<ModelListing>,
+    /// Latest model listing per entity name, used to flag vanished models.
+    pub entity_model_listings: std::collections::HashMap<String, ModelListing>,
+    /// Latest key validation result per entity name.
+    pub entity_validation: std::collections::HashMap<String, KeyValidationResult>,
+
+    // Fallback groups
+    pub entity_groups: Vec<EntityGroup>,
+    pub group_name_input: String,
+    /// Entity names in fallback order, separated by `,` or `→`
+    pub group_chain_input: String,
+    pub group_note_input: String,
+    /// Circuit breaker state of every entity
+    pub circuit_statuses: Vec<CircuitStatus>,
 
     // Custom / relay provider management
     pub custom_providers: Vec<crate::app_types::CustomProvider>,
@@ -100,6 +122,8 @@ pub struct VgaGuiApp {
     pub projects_json: String,
     pub leases_json: String,
     pub tasks_json: String,
+    /// Text streamed so far by each running task, fed by the scheduler's event subscription.
+    pub live_output: Arc<std::sync::Mutex<std::collections::BTreeMap<TaskId, String>>>,
     pub peers_json: String,
 
     pub providers_json: String,
@@ -121,6 +145,9 @@ impl VgaGuiApp {
         let services = runtime.block_on(async {
             let api_manager = vangriten_ai_swarm::backend::ApiKeyManager::new().await;
             let agent_scheduler = vangriten_ai_swarm::backend::AgentScheduler::new().await;
+            agent_scheduler
+                .attach_llm_router(vangriten_ai_swarm::backend::FailoverRouter::new(api_manager.clone()))
+                .await;
             let network_discovery = vangriten_ai_swarm::backend::NetworkDiscovery::new().await;
             let compilation_scheduler = vangriten_ai_swarm::backend::CompilationScheduler::new().await;
             let ollama_manager = vangriten_ai_swarm::backend::OllamaManager::new(None).await;
@@ -133,12 +160,20 @@ impl VgaGuiApp {
                         .expect("c compiler scheduler")
                 }
             };
-            let resource_manager = match vangriten_ai_swarm::backend::ResourceManager::new(false).await {
+            let resource_manager = match vangriten_ai_swarm::backend::ResourceManager::new(network_discovery.clone(), false).await {
                 Ok(rm) => rm,
-                Err(_) => vangriten_ai_swarm::backend::ResourceManager::new(true)
+                Err(_) => vangriten_ai_swarm::backend::ResourceManager::new(network_discovery.clone(), true)
                     .await
                     .expect("resource manager"),
             };
+            let workspace = std::env::current_dir().unwrap_or_default().join("workspace");
+            agent_scheduler
+                .attach_tools(vangriten_ai_swarm::backend::agents::ToolRegistry::workspace(
+                    workspace,
+                    Some(c_compiler.clone()),
+                    agent_scheduler.llm_router().await,
+                ))
+                .await;
 
             Arc::new(vangriten_ai_swarm::backend::BackendServices {
                 api_manager,
@@ -194,6 +229,10 @@ impl VgaGuiApp {
             api_stored_providers: Vec::new(),
             api_usage_json: "(not loaded)".to_string(),
             api_quick_status: String::new(),
+            api_auto_lock_mins: vangriten_ai_swarm::backend::api_manager::DEFAULT_AUTO_LOCK.as_secs() / 60,
+            api_bundle_passphrase: String::new(),
+            api_bundle_include_entities: true,
+            api_bundle_policy: ImportConflictPolicy::Skip,
 
             ai_entities: Vec::new(),
             entity_name_input: String::new(),
@@ -203,7 +242,17 @@ impl VgaGuiApp {
             entity_key_header_input: String::new(),
             entity_key_prefix_input: String::new(),
             entity_selected: None,
-            entity_burst_count: 3,
+            entity_pool_keys_input: String::new(),
+            entity_pool_status: None,
+            entity_validation: std::collections::HashMap::new(),
+            entity_model_listing: None,
+            entity_model_listings: std::collections::HashMap::new(),
+
+            entity_groups: Vec::new(),
+            group_name_input: String::new(),
+            group_chain_input: String::new(),
+            group_note_input: String::new(),
+            circuit_statuses: Vec::new(),
 
             custom_providers: Vec::new(),
             show_provider_picker: false,
@@ -241,6 +290,7 @@ impl VgaGuiApp {
             projects_json: "(not loaded)".to_string(),
             leases_json: "(not loaded)".to_string(),
             tasks_json: "(not loaded)".to_string(),
+            live_output: Arc::new(std::sync::Mutex::new(std::collections::BTreeMap::new())),
             peers_json: "(not loaded)".to_string(),
 
             providers_json: "(not loaded)".to_string(),
@@ -252,8 +302,10 @@ impl VgaGuiApp {
             last_refresh_instant: std::time::Instant::now(),
         };
 
+        app.follow_task_events();
         app.refresh_all();
         app.load_entities();
+        app.load_entity_groups();
 
         if !app.ai_entities.is_empty() {
             app.show_new_project_wizard = true;
@@ -262,6 +314,32 @@ impl VgaGuiApp {
         app
     }
 
+    /// Keep `live_output` in step with the scheduler: running tasks gain
+    /// text as it streams in and drop out once they finish.
+    fn follow_task_events(&self) {
+        let mut events = self.services.agent_scheduler.subscribe();
+        let live_output = self.live_output.clone();
+        self.runtime.spawn(async move {
+            loop {
+                let event = match events.recv().await {
+                    Ok(event) => event,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                };
+                let Ok(mut live) = live_output.lock() else { break };
+                match event.kind {
+                    TaskEventKind::Started => {
+                        live.insert(event.task_id, String::new());
+                    }
+                    TaskEventKind::Delta(text) => live.entry(event.task_id).or_default().push_str(&text),
+                    TaskEventKind::Completed | TaskEventKind::Failed(_) | TaskEventKind::Cancelled => {
+                        live.remove(&event.task_id);
+                    }
+                }
+            }
+        });
+    }
+
     /// Returns the localized string for the current language.
     pub fn tr(&self, zh: &'static str, en: &'static str) -> &'static str {
         match self.lang {
@@ -153,7 +153,8 @@ impl VgaGuiApp {
                 },
                 Priority::High,
                 std::path::PathBuf::from("snapshots/gui.json"),
-            );
+            )
+            .with_entity(outline_entity.clone());
             if let Ok(id) = services.agent_scheduler.submit_task(outline_task).await {
                 submitted.push(format!("outline:{id}"));
             }
@@ -172,7 +173,8 @@ impl VgaGuiApp {
                     },
                     Priority::Medium,
                     std::path::PathBuf::from("snapshots/gui.json"),
-                );
+                )
+                .with_entity(writer);
                 if let Ok(id) = services.agent_scheduler.submit_task(t).await {
                     submitted.push(format!("group-{group_no}:{id}"));
                 }
@@ -189,7 +191,8 @@ impl VgaGuiApp {
                 },
                 Priority::High,
                 std::path::PathBuf::from("snapshots/gui.json"),
-            );
+            )
+            .with_entity(master_entity.clone());
             if let Ok(id) = services.agent_scheduler.submit_task(merge_task).await {
                 submitted.push(format!("merge:{id}"));
             }
@@ -538,11 +541,7 @@ impl VgaGuiApp {
     pub fn load_stored_keys(&mut self) {
         match self.services.api_manager.vault_operation(VaultOp::List) {
             Ok(VaultResult::Providers(list)) => {
-                // Filter out internal vault metadata entries
-                self.api_stored_providers = list
-                    .into_iter()
-                    .filter(|k| k != "salt" && k != "vault_check")
-                    .collect();
+                self.api_stored_providers = list;
                 self.api_list_json = Self::pretty(&self.api_stored_providers);
             }
             Ok(other) => self.api_quick_status = format!("Unexpected: {other:?}"),
@@ -615,15 +614,21 @@ impl VgaGuiApp {
         }
     }
 
-    /// Persist custom/relay providers to disk.
+    /// Persist custom/relay providers to disk and merge them into the backend registry.
     pub fn save_custom_providers(&mut self) {
         let path = Self::custom_providers_path();
         if let Some(p) = path.parent() { let _ = std::fs::create_dir_all(p); }
         match serde_json::to_string_pretty(&self.custom_providers) {
             Ok(s) => { if let Err(e) = std::fs::write(&path, s) {
                 self.api_quick_status = format!("save custom providers failed: {e}");
+                return;
             }}
-            Err(e) => self.api_quick_status = format!("serialize custom providers failed: {e}"),
+            Err(e) => { self.api_quick_status = format!("serialize custom providers failed: {e}"); return; }
+        }
+        let errors = self.services.api_manager.reload_providers();
+        self.load_providers();
+        if !errors.is_empty() {
+            self.set_error(format!("provider registry: {}", errors.join("\n")));
         }
     }
 
@@ -697,7 +702,7 @@ impl VgaGuiApp {
         let am = self.services.api_manager.clone();
         match am.vault_operation(VaultOp::Store {
             provider: name.clone(),
-            key,
+            key: key.into(),
         }) {
             Ok(_) => {}
             Err(e) => {
@@ -705,6 +710,11 @@ impl VgaGuiApp {
                 return;
             }
         }
+        let _ = am.vault_operation(VaultOp::SetEntryMeta {
+            provider: name.clone(),
+            provider_id: Some(provider.clone()),
+            notes: note.clone(),
+        });
 
         // Upsert entity metadata
         let entity = AiEntity {
@@ -734,60 +744,235 @@ impl VgaGuiApp {
         self.api_quick_status = format!("✅ 已保存: {name}");
     }
 
-    /// Create `entity_burst_count` copies of the current form entity, named
-    /// `{base}-1` … `{base}-N`, all sharing the same API key.
-    pub fn burst_add_entities(&mut self) {
-        let base = self.entity_name_input.trim().to_string();
-        let provider = self.api_provider.trim().to_string();
-        let model = self.entity_model_input.trim().to_string();
-        let note = self.entity_note_input.trim().to_string();
-        let key = self.api_key_input.trim().to_string();
-        let custom_url = self.entity_custom_url_input.trim().to_string();
-        let key_header = self.entity_key_header_input.trim().to_string();
-        let key_prefix = self.entity_key_prefix_input.trim().to_string();
-        let count = self.entity_burst_count.clamp(1, 10) as usize;
+    /// Add the keys in `entity_pool_keys_input` (one per line) to the pool of
+    /// the entity named in the form. Requests made as that entity rotate
+    /// across all of its keys.
+    pub fn add_pool_keys(&mut self) {
+        let pool = self.entity_name_input.trim().to_string();
+        if pool.is_empty() { self.api_quick_status = "❌ 名称不能为空".to_string(); return; }
+        let keys: Vec<String> = self
+            .entity_pool_keys_input
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect();
+        if keys.is_empty() { self.api_quick_status = "❌ API Key 不能为空".to_string(); return; }
+
+        let am = self.services.api_manager.clone();
+        let mut added = 0usize;
+        for key in keys {
+            match am.vault_operation(VaultOp::AddPoolKey { pool: pool.clone(), key: key.into() }) {
+                Ok(_) => added += 1,
+                Err(e) => { self.api_quick_status = format!("❌ pool add {pool}: {e:?}"); break; }
+            }
+        }
+        self.entity_pool_keys_input.clear();
+        self.load_stored_keys();
+        self.load_pool_status();
+        if added > 0 {
+            self.api_quick_status = format!("✅ 已加入密钥池 {pool}: {added} 个");
+        }
+    }
 
-        if base.is_empty() { self.api_quick_status = "❌ 名称不能为空".to_string(); return; }
-        if provider.is_empty() { self.api_quick_status = "❌ Provider 不能为空".to_string(); return; }
-        if key.is_empty() { self.api_quick_status = "❌ API Key 不能为空".to_string(); return; }
+    /// Refresh `entity_pool_status` for the entity named in the form.
+    pub fn load_pool_status(&mut self) {
+        let pool = self.entity_name_input.trim().to_string();
+        if pool.is_empty() {
+            self.entity_pool_status = None;
+            return;
+        }
+        match self.services.api_manager.vault_operation(VaultOp::PoolStatus { pool }) {
+            Ok(VaultResult::Pool(status)) => self.entity_pool_status = Some(status),
+            Ok(other) => self.api_quick_status = format!("Unexpected: {other:?}"),
+            Err(e) => {
+                self.entity_pool_status = None;
+                self.api_quick_status = format!("❌ pool status: {e:?}");
+            }
+        }
+    }
 
+    pub fn set_pool_strategy(&mut self, strategy: KeyPoolStrategy) {
+        let pool = self.entity_name_input.trim().to_string();
+        if pool.is_empty() { return; }
+        match self.services.api_manager.vault_operation(VaultOp::SetPoolStrategy { pool, strategy }) {
+            Ok(_) => self.load_pool_status(),
+            Err(e) => self.api_quick_status = format!("❌ pool strategy: {e:?}"),
+        }
+    }
+
+    fn form_entity(&self) -> AiEntity {
+        let optional = |s: &str| if s.trim().is_empty() { None } else { Some(s.trim().to_string()) };
+        AiEntity {
+            name: self.entity_name_input.trim().to_string(),
+            provider: self.api_provider.trim().to_string(),
+            model: self.entity_model_input.trim().to_string(),
+            note: self.entity_note_input.trim().to_string(),
+            custom_base_url: optional(&self.entity_custom_url_input),
+            key_header: optional(&self.entity_key_header_input),
+            key_prefix: optional(&self.entity_key_prefix_input),
+        }
+    }
+
+    /// Query the endpoint of the entity in the editor form for its models.
+    /// Before the entity is saved, the key is looked up under the provider id.
+    pub fn discover_form_models(&mut self) {
+        let mut entity = self.form_entity();
+        if entity.provider.is_empty() {
+            self.api_quick_status = "❌ Provider 不能为空".to_string();
+            return;
+        }
+        if entity.name.is_empty() || !self.ai_entities.iter().any(|e| e.name == entity.name) {
+            entity.name = entity.provider.clone();
+        }
         let am = self.services.api_manager.clone();
-        let mut created = 0usize;
-        for i in 1..=count {
-            let name = format!("{base}-{i}");
-            // Store the same key under each entity name
-            match am.vault_operation(VaultOp::Store { provider: name.clone(), key: key.clone() }) {
-                Ok(_) => {}
-                Err(e) => { self.api_quick_status = format!("❌ vault store {name}: {e:?}"); return; }
+        match self.runtime.block_on(async move { am.list_remote_models(&entity, true).await }) {
+            Ok(listing) => {
+                let remote = listing.models.iter().filter(|m| m.remote).count();
+                self.api_quick_status = match &listing.error {
+                    Some(e) => format!("⚠ 模型列表获取失败，仅显示目录: {e}"),
+                    None => format!("✅ {remote} models @ {}", listing.endpoint),
+                };
+                self.entity_model_listing = Some(listing);
             }
-            let entity = AiEntity {
-                name: name.clone(), provider: provider.clone(), model: model.clone(), note: note.clone(),
-                custom_base_url: if custom_url.is_empty() { None } else { Some(custom_url.clone()) },
-                key_header: if key_header.is_empty() { None } else { Some(key_header.clone()) },
-                key_prefix: if key_prefix.is_empty() { None } else { Some(key_prefix.clone()) },
-            };
-            if let Some(pos) = self.ai_entities.iter().position(|e| e.name == name) {
+            Err(e) => self.api_quick_status = format!("❌ list models failed: {e:?}"),
+        }
+    }
+
+    /// List models for every entity (cached) so vanished models can be flagged.
+    pub fn check_entity_models(&mut self) {
+        let am = self.services.api_manager.clone();
+        let entities = self.ai_entities.clone();
+        let listings = self.runtime.block_on(async move {
+            let mut tasks = tokio::task::JoinSet::new();
+            for entity in entities {
+                let am = am.clone();
+                tasks.spawn(async move { am.list_remote_models(&entity, false).await });
+            }
+            let mut listings = Vec::new();
+            while let Some(joined) = tasks.join_next().await {
+                if let Ok(Ok(listing)) = joined {
+                    listings.push(listing);
+                }
+            }
+            listings
+        });
+        for listing in listings {
+            self.entity_model_listings.insert(listing.target.clone(), listing);
+        }
+        let missing = self
+            .ai_entities
+            .iter()
+            .filter(|e| self.entity_model_listings.get(&e.name).is_some_and(|l| !l.offers(&e.model)))
+            .count();
+        self.api_quick_status = if missing == 0 {
+            "✅ 所有个体的模型均可用".to_string()
+        } else {
+            format!("⚠ {missing} 个个体的模型已不在服务端列表中")
+        };
+    }
+
+    /// Probe one entity's key against its provider.
+    pub fn validate_entity(&mut self, name: &str) {
+        let Some(entity) = self.find_entity_by_name(name) else { return; };
+        let am = self.services.api_manager.clone();
+        match self.runtime.block_on(async move { am.validate_key(&entity).await }) {
+            Ok(result) => {
+                self.api_quick_status = format!("{}: {}", result.entity, Self::validation_label(&result));
+                self.entity_validation.insert(result.entity.clone(), result);
+            }
+            Err(e) => self.api_quick_status = format!("❌ validate failed: {e:?}"),
+        }
+    }
+
+    /// Probe every entity's key concurrently.
+    pub fn validate_all_entities(&mut self) {
+        let am = self.services.api_manager.clone();
+        let entities = self.ai_entities.clone();
+        match self.runtime.block_on(async move { am.validate_all(&entities).await }) {
+            Ok(results) => {
+                let ok = results.iter().filter(|r| r.status == KeyValidationStatus::Valid).count();
+                self.api_quick_status = format!("✅ {ok}/{} valid", results.len());
+                for result in results {
+                    self.entity_validation.insert(result.entity.clone(), result);
+                }
+            }
+            Err(e) => self.api_quick_status = format!("❌ validate failed: {e:?}"),
+        }
+    }
+
+    pub fn validation_label(result: &KeyValidationResult) -> String {
+        let status = match result.status {
+            KeyValidationStatus::Valid => "✅ valid",
+            KeyValidationStatus::InvalidKey => "❌ invalid key",
+            KeyValidationStatus::QuotaExhausted => "⚠ quota exhausted",
+            KeyValidationStatus::Unreachable => "⚠ unreachable",
+            KeyValidationStatus::MissingKey => "— no key",
+            KeyValidationStatus::Unexpected => "⚠ unexpected response",
+        };
+        match (&result.http_status, &result.detail) {
+            (Some(code), _) if result.status != KeyValidationStatus::Valid => format!("{status} (HTTP {code})"),
+            (None, Some(detail)) => format!("{status}: {detail}"),
+            _ => status.to_string(),
+        }
+    }
+
+    /// Export every stored key (and optionally the entity list) to an encrypted bundle file.
+    pub fn export_vault_bundle(&mut self, path: std::path::PathBuf) {
+        let am = self.services.api_manager.clone();
+        let names = match am.vault_operation(VaultOp::List) {
+            Ok(VaultResult::Providers(list)) => list,
+            Ok(other) => { self.api_status = format!("Unexpected: {other:?}"); return; }
+            Err(e) => { self.api_status = format!("❌ export failed: {e:?}"); return; }
+        };
+        let entities = if self.api_bundle_include_entities { self.ai_entities.clone() } else { Vec::new() };
+        match am.export_bundle(&names, &entities, &self.api_bundle_passphrase) {
+            Ok(bundle) => match std::fs::write(&path, bundle) {
+                Ok(()) => {
+                    self.api_bundle_passphrase.clear();
+                    self.api_status = format!("✅ 已导出 {} 个密钥 → {}", names.len(), path.display());
+                }
+                Err(e) => self.api_status = format!("❌ write bundle failed: {e}"),
+            },
+            Err(e) => self.api_status = format!("❌ export failed: {e:?}"),
+        }
+    }
+
+    /// Merge an encrypted bundle file into the vault and the entity list.
+    pub fn import_vault_bundle(&mut self, path: std::path::PathBuf) {
+        let bundle = match std::fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(e) => { self.api_status = format!("❌ read bundle failed: {e}"); return; }
+        };
+        let am = self.services.api_manager.clone();
+        let report = match am.import_bundle(&bundle, &self.api_bundle_passphrase, self.api_bundle_policy) {
+            Ok(r) => r,
+            Err(e) => { self.api_status = format!("❌ import failed: {e:?}"); return; }
+        };
+        for entity in report.entities.iter().cloned() {
+            if let Some(pos) = self.ai_entities.iter().position(|e| e.name == entity.name) {
                 self.ai_entities[pos] = entity;
             } else {
                 self.ai_entities.push(entity);
             }
-            created += 1;
         }
         self.save_entities();
-        self.api_key_input.clear();
-        self.entity_name_input.clear();
-        self.entity_model_input.clear();
-        self.entity_note_input.clear();
-        self.entity_custom_url_input.clear();
-        self.entity_key_header_input.clear();
-        self.entity_key_prefix_input.clear();
         self.load_stored_keys();
-        self.api_quick_status = format!("✅ 裂变创建 {created} 个: {base}-1 … {base}-{count}");
+        self.api_bundle_passphrase.clear();
+        self.api_status = format!(
+            "✅ imported {}, overwritten {}, renamed {}, skipped {}",
+            report.imported.len(),
+            report.overwritten.len(),
+            report.renamed.len(),
+            report.skipped.len()
+        );
     }
 
     /// Delete a named AI entity and remove its key from the vault.
     pub fn delete_entity(&mut self, name: &str) {
         let am = self.services.api_manager.clone();
+        for member in am.pool_members(name).unwrap_or_default() {
+            let _ = am.vault_operation(VaultOp::Delete { provider: member });
+        }
         let _ = am.vault_operation(VaultOp::Delete { provider: name.to_string() });
         self.ai_entities.retain(|e| e.name != name);
         self.save_entities();
@@ -795,6 +980,73 @@ impl VgaGuiApp {
         self.api_quick_status = format!("🗑 已删除: {name}");
     }
 
+    // ── Fallback groups ───────────────────────────────────────────────────────
+
+    fn llm_router(&self) -> Option<vangriten_ai_swarm::backend::FailoverRouter> {
+        let services = self.services.clone();
+        self.runtime.block_on(async move { services.agent_scheduler.llm_router().await })
+    }
+
+    pub fn load_entity_groups(&mut self) {
+        if let Some(router) = self.llm_router() {
+            self.entity_groups = router.groups();
+            self.circuit_statuses = router.circuit_status(None).unwrap_or_default();
+        }
+    }
+
+    /// Add or replace the group described by the group form.
+    pub fn save_entity_group(&mut self) {
+        let name = self.group_name_input.trim().to_string();
+        let chain: Vec<String> = self
+            .group_chain_input
+            .split([',', '→', '\n'])
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        if name.is_empty() { self.api_quick_status = "❌ 组名不能为空".to_string(); return; }
+        if chain.is_empty() { self.api_quick_status = "❌ 故障转移链不能为空".to_string(); return; }
+        let unknown: Vec<&str> = chain
+            .iter()
+            .filter(|n| !self.ai_entities.iter().any(|e| &e.name == *n))
+            .map(String::as_str)
+            .collect();
+        let warning = if unknown.is_empty() { String::new() } else { format!(" (⚠ 未知个体: {})", unknown.join(", ")) };
+
+        let group = EntityGroup { name: name.clone(), chain, note: self.group_note_input.trim().to_string() };
+        let mut groups = self.entity_groups.clone();
+        match groups.iter_mut().find(|g| g.name == name) {
+            Some(existing) => *existing = group,
+            None => groups.push(group),
+        }
+        self.store_entity_groups(groups, format!("✅ 已保存故障转移组: {name}{warning}"));
+    }
+
+    pub fn delete_entity_group(&mut self, name: &str) {
+        let groups = self.entity_groups.iter().filter(|g| g.name != name).cloned().collect();
+        self.store_entity_groups(groups, format!("🗑 已删除故障转移组: {name}"));
+    }
+
+    fn store_entity_groups(&mut self, groups: Vec<EntityGroup>, done: String) {
+        let Some(router) = self.llm_router() else {
+            self.api_quick_status = "❌ LLM router not attached".to_string();
+            return;
+        };
+        match router.save_groups(&groups) {
+            Ok(()) => {
+                self.entity_groups = groups;
+                self.api_quick_status = done;
+            }
+            Err(e) => self.api_quick_status = format!("❌ save groups: {e:?}"),
+        }
+    }
+
+    pub fn reset_circuit(&mut self, entity: &str) {
+        if let Some(router) = self.llm_router() {
+            router.reset_circuit(entity);
+            self.circuit_statuses = router.circuit_status(None).unwrap_or_default();
+        }
+    }
+
     pub fn discover_nodes(&mut self) {
         self.clear_error();
         let services = self.services.clone();
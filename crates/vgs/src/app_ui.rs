@@ -1,6 +1,6 @@
 use crate::app::VgaGuiApp;
 use crate::app_types::{UiLang, ProviderFilter, ActiveView};
-use vangriten_ai_swarm::shared::models::{VaultOp, VaultResult};
+use vangriten_ai_swarm::shared::models::{CircuitState, CircuitStatus, ImportConflictPolicy, KeyPoolStrategy, TaskId, VaultOp, VaultResult};
 
 impl VgaGuiApp {
     pub fn render_api_manager_window(&mut self, ctx: &eframe::egui::Context) {
@@ -25,10 +25,18 @@ impl VgaGuiApp {
         let label_plain = self.tr("显示明文", "Show plaintext");
         let label_revealed = self.tr("已读取的 APIKey", "Revealed API Key");
         let label_local_keys = self.tr("本地存储的 keys", "Local keys");
+        let label_include_entities = self.tr("包含 AI 实体", "Include AI entities");
+        let label_skip = self.tr("跳过", "Skip");
+        let label_overwrite = self.tr("覆盖", "Overwrite");
+        let label_rename = self.tr("重命名", "Rename");
 
         let api_manager = self.services.api_manager.clone();
         let initialized = api_manager.vault_is_initialized();
         let unlocked = api_manager.vault_is_unlocked();
+        if !unlocked {
+            // The vault may have auto-locked; don't keep showing a revealed key.
+            self.api_revealed_key.clear();
+        }
 
         let mut open = self.show_api_manager;
         eframe::egui::Window::new(title)
@@ -94,6 +102,17 @@ impl VgaGuiApp {
                             self.api_status = self.tr("已锁定", "Locked").to_string();
                         }
                     });
+                    ui.horizontal(|ui| {
+                        ui.label(self.tr("空闲自动锁定（分钟，0=从不）", "Auto-lock when idle (min, 0 = never)"));
+                        let changed = ui
+                            .add(eframe::egui::DragValue::new(&mut self.api_auto_lock_mins).clamp_range(0..=24 * 60))
+                            .changed();
+                        if changed {
+                            let timeout = (self.api_auto_lock_mins > 0)
+                                .then(|| std::time::Duration::from_secs(self.api_auto_lock_mins * 60));
+                            api_manager.set_auto_lock(timeout);
+                        }
+                    });
                 }
 
                 ui.separator();
@@ -114,7 +133,7 @@ impl VgaGuiApp {
                     if ui.button(label_store).clicked() {
                         let op = VaultOp::Store {
                             provider: self.api_provider.clone(),
-                            key: self.api_key_input.clone(),
+                            key: self.api_key_input.clone().into(),
                         };
                         match api_manager.vault_operation(op) {
                             Ok(_) => {
@@ -145,7 +164,7 @@ impl VgaGuiApp {
                         };
                         match api_manager.vault_operation(op) {
                             Ok(VaultResult::Key(k)) => {
-                                self.api_revealed_key = k;
+                                self.api_revealed_key = k.expose_secret().to_string();
                                 self.api_status = self.tr("已读取", "Retrieved").to_string();
                             }
                             Ok(v) => self.api_status = format!("unexpected: {v:?}"),
@@ -179,6 +198,36 @@ impl VgaGuiApp {
                         ui.monospace(&self.api_list_json);
                     });
 
+                ui.separator();
+                ui.collapsing(self.tr("导出 / 导入", "Export / Import"), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(self.tr("导出口令", "Bundle passphrase"));
+                        ui.add(eframe::egui::TextEdit::singleline(&mut self.api_bundle_passphrase).password(true));
+                    });
+                    ui.checkbox(&mut self.api_bundle_include_entities, label_include_entities);
+                    ui.horizontal(|ui| {
+                        ui.label(self.tr("冲突处理", "On conflict"));
+                        ui.radio_value(&mut self.api_bundle_policy, ImportConflictPolicy::Skip, label_skip);
+                        ui.radio_value(&mut self.api_bundle_policy, ImportConflictPolicy::Overwrite, label_overwrite);
+                        ui.radio_value(&mut self.api_bundle_policy, ImportConflictPolicy::Rename, label_rename);
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button(self.tr("导出…", "Export…")).clicked() {
+                            if let Some(path) = rfd::FileDialog::new()
+                                .set_file_name("vault-export.json")
+                                .save_file()
+                            {
+                                self.export_vault_bundle(path);
+                            }
+                        }
+                        if ui.button(self.tr("导入…", "Import…")).clicked() {
+                            if let Some(path) = rfd::FileDialog::new().add_filter("json", &["json"]).pick_file() {
+                                self.import_vault_bundle(path);
+                            }
+                        }
+                    });
+                });
+
                 if !self.api_status.trim().is_empty() {
                     ui.separator();
                     ui.monospace(&self.api_status);
@@ -645,6 +694,24 @@ impl eframe::App for VgaGuiApp {
                                 ui.monospace(&self.leases_json);
                             });
                             cols[1].add_space(8.0);
+                            let live: Vec<(TaskId, String)> = self
+                                .live_output
+                                .lock()
+                                .map(|l| l.iter().map(|(id, text)| (*id, text.clone())).collect())
+                                .unwrap_or_default();
+                            if !live.is_empty() {
+                                cols[1].group(|ui| {
+                                    ui.heading(self.tr("实时输出", "Live Output"));
+                                    for (id, text) in &live {
+                                        let short_id: String = id.to_string().chars().take(8).collect();
+                                        ui.label(format!("▶ {short_id} ({} chars)", text.chars().count()));
+                                        // Only the tail; the full text lands in the task list when done.
+                                        let tail_start = text.char_indices().rev().nth(599).map_or(0, |(i, _)| i);
+                                        ui.monospace(&text[tail_start..]);
+                                    }
+                                });
+                                cols[1].add_space(8.0);
+                            }
                             cols[1].group(|ui| {
                                 ui.heading(self.tr("任务列表", "Tasks"));
                                 ui.monospace(&self.tasks_json);
@@ -755,6 +822,12 @@ impl eframe::App for VgaGuiApp {
                                             self.load_entities();
                                             self.load_stored_keys();
                                         }
+                                        if ui.small_button(self.tr("🩺 验证全部", "🩺 Validate all")).clicked() {
+                                            self.validate_all_entities();
+                                        }
+                                        if ui.small_button(self.tr("🔍 检查模型", "🔍 Check models")).clicked() {
+                                            self.check_entity_models();
+                                        }
                                     });
                                     ui.separator();
 
@@ -795,10 +868,17 @@ impl eframe::App for VgaGuiApp {
                                                             } else {
                                                                 format!("— {}", entity.name)
                                                             };
-                                                            let resp = ui.selectable_label(
+                                                            let mut resp = ui.selectable_label(
                                                                 is_selected,
                                                                 &name_label,
                                                             );
+                                                            if let Some(result) = self.entity_validation.get(&entity.name) {
+                                                                resp = resp.on_hover_text(format!(
+                                                                    "{} @ {}",
+                                                                    Self::validation_label(result),
+                                                                    result.checked_at.format("%Y-%m-%d %H:%M:%S")
+                                                                ));
+                                                            }
                                                             if resp.clicked() {
                                                                 if is_selected {
                                                                     self.entity_selected = None;
@@ -822,11 +902,29 @@ impl eframe::App for VgaGuiApp {
                                                                         entity.key_prefix.clone().unwrap_or_default();
                                                                     self.api_key_input.clear();
                                                                     self.api_revealed_key.clear();
+                                                                    self.load_pool_status();
+                                                                    self.entity_model_listing =
+                                                                        self.entity_model_listings.get(&entity.name).cloned();
                                                                 }
                                                             }
 
                                                             ui.monospace(&entity.provider);
-                                                            ui.monospace(&entity.model);
+                                                            match self.entity_model_listings.get(&entity.name) {
+                                                                Some(listing) if !listing.offers(&entity.model) => {
+                                                                    ui.colored_label(
+                                                                        eframe::egui::Color32::from_rgb(220, 150, 40),
+                                                                        format!("⚠ {}", entity.model),
+                                                                    )
+                                                                    .on_hover_text(format!(
+                                                                        "{} {}",
+                                                                        self.tr("服务端已不再提供此模型:", "Model no longer offered by"),
+                                                                        listing.endpoint
+                                                                    ));
+                                                                }
+                                                                _ => {
+                                                                    ui.monospace(&entity.model);
+                                                                }
+                                                            }
                                                             ui.label(if entity.note.is_empty() {
                                                                 "—"
                                                             } else {
@@ -859,7 +957,7 @@ impl eframe::App for VgaGuiApp {
                                                                                 self.entity_name_input = entity.name.clone();
                                                                                 self.entity_model_input = entity.model.clone();
                                                                                 self.entity_note_input = entity.note.clone();
-                                                                                self.api_revealed_key = k;
+                                                                                self.api_revealed_key = k.expose_secret().to_string();
                                                                                 self.api_quick_status = format!(
                                                                                     "{}: {}",
                                                                                     entity.name,
@@ -874,6 +972,14 @@ impl eframe::App for VgaGuiApp {
                                                                         }
                                                                     }
                                                                 }
+                                                                if ui
+                                                                    .small_button("🩺")
+                                                                    .on_hover_text(self.tr("验证密钥", "Validate key"))
+                                                                    .clicked()
+                                                                {
+                                                                    let name = entity.name.clone();
+                                                                    self.validate_entity(&name);
+                                                                }
                                                                 if ui
                                                                     .small_button("🗑")
                                                                     .on_hover_text(self.tr("删除此个体", "Delete entity"))
@@ -971,7 +1077,8 @@ impl eframe::App for VgaGuiApp {
                                                                         ui.strong(self.tr("地址", "Endpoint"));
                                                                         ui.strong("");
                                                                         ui.end_row();
-                                                                        for item in &builtin {
+                                                                        // Custom relays are listed separately below
+                                                                        for item in builtin.iter().filter(|i| i["source"] != "Custom") {
                                                                             let id = item["id"].as_str().unwrap_or("");
                                                                             let name = item["name"].as_str().unwrap_or(id);
                                                                             let ep = item["api_endpoint"].as_str().unwrap_or("");
@@ -1072,13 +1179,46 @@ impl eframe::App for VgaGuiApp {
                                             }
 
                                             ui.label(self.tr("模型", "Model"));
-                                            ui.add(
-                                                eframe::egui::TextEdit::singleline(
-                                                    &mut self.entity_model_input,
-                                                )
-                                                .desired_width(220.0)
-                                                .hint_text("gpt-4o / deepseek-chat / ..."),
+                                            let discover_tip = self.tr(
+                                                "从服务端 /v1/models（Ollama: /api/tags）获取可用模型",
+                                                "Fetch available models from /v1/models (Ollama: /api/tags)",
                                             );
+                                            let catalog_only_tip = self.tr("仅在目录中，服务端未列出", "Catalog only, not listed by the endpoint");
+                                            ui.horizontal(|ui| {
+                                                ui.add(
+                                                    eframe::egui::TextEdit::singleline(
+                                                        &mut self.entity_model_input,
+                                                    )
+                                                    .desired_width(220.0)
+                                                    .hint_text("gpt-4o / deepseek-chat / ..."),
+                                                );
+                                                if ui.small_button("🔍").on_hover_text(discover_tip).clicked() {
+                                                    self.discover_form_models();
+                                                }
+                                                if let Some(listing) = &self.entity_model_listing {
+                                                    let mut chosen: Option<String> = None;
+                                                    eframe::egui::ComboBox::from_id_source("entity_model_combo")
+                                                        .selected_text(format!("{} ▾", listing.models.len()))
+                                                        .width(60.0)
+                                                        .show_ui(ui, |ui| {
+                                                            for model in &listing.models {
+                                                                let label = if model.remote {
+                                                                    model.id.clone()
+                                                                } else {
+                                                                    format!("○ {}", model.id)
+                                                                };
+                                                                let resp = ui.selectable_label(self.entity_model_input == model.id, label);
+                                                                let resp = if model.remote { resp } else { resp.on_hover_text(catalog_only_tip) };
+                                                                if resp.clicked() {
+                                                                    chosen = Some(model.id.clone());
+                                                                }
+                                                            }
+                                                        });
+                                                    if let Some(model) = chosen {
+                                                        self.entity_model_input = model;
+                                                    }
+                                                }
+                                            });
                                             ui.end_row();
 
                                             ui.label(self.tr("备注", "Note"));
@@ -1211,43 +1351,151 @@ impl eframe::App for VgaGuiApp {
                                             self.api_key_input.clear();
                                             self.api_revealed_key.clear();
                                             self.api_quick_status.clear();
+                                            self.entity_model_listing = None;
                                         }
                                     });
 
-                                    // ── 裂变模式 ────────────────────────────────────
+                                    // ── 密钥池 ──────────────────────────────────────
                                     ui.separator();
-                                    let burst_tip = self.tr(
-                                        "创建 N 个编号副本，共享同一 Key。适合单个 API Key 最高 N 路并发",
-                                        "Create N numbered clones sharing the same key for max concurrent calls",
+                                    let pool_tip = self.tr(
+                                        "为同一个体添加多个真实 Key，请求按策略轮换；鉴权/额度错误的 Key 会被暂时停用",
+                                        "Attach several real keys to this entity; requests rotate across them and keys failing auth/quota are benched for a while",
                                     ).to_string();
-                                    let burst_sfx = self.tr(" 个", " clones").to_string();
+                                    let label_round_robin = self.tr("轮询", "Round-robin");
+                                    let label_least_used = self.tr("最少使用", "Least used");
+                                    let hint_pool_keys = self.tr("每行一个 Key", "One key per line");
+                                    ui.horizontal(|ui| {
+                                        ui.label(self.tr("🔑 密钥池:", "🔑 Key pool:")).on_hover_text(&pool_tip);
+                                        let current = self.entity_pool_status.as_ref().map(|s| s.strategy);
+                                        if ui.radio(current == Some(KeyPoolStrategy::RoundRobin), label_round_robin).clicked() {
+                                            self.set_pool_strategy(KeyPoolStrategy::RoundRobin);
+                                        }
+                                        if ui.radio(current == Some(KeyPoolStrategy::LeastUsed), label_least_used).clicked() {
+                                            self.set_pool_strategy(KeyPoolStrategy::LeastUsed);
+                                        }
+                                        if ui.small_button("🔄").on_hover_text(self.tr("刷新池状态", "Refresh pool status")).clicked() {
+                                            self.load_pool_status();
+                                        }
+                                    });
                                     ui.horizontal(|ui| {
-                                        ui.label(self.tr("🔀 裂变并发:", "🔀 Burst:"));
                                         ui.add(
-                                            eframe::egui::DragValue::new(&mut self.entity_burst_count)
-                                                .clamp_range(1u8..=10u8)
-                                                .suffix(&burst_sfx)
-                                                .speed(0.1),
-                                        ).on_hover_text(&burst_tip);
-                                        let burst_btn_label = format!(
-                                            "{}-1 … {}-{}",
-                                            self.entity_name_input.trim(),
-                                            self.entity_name_input.trim(),
-                                            self.entity_burst_count,
+                                            eframe::egui::TextEdit::multiline(&mut self.entity_pool_keys_input)
+                                                .password(!self.api_show_plaintext)
+                                                .desired_rows(2)
+                                                .desired_width(280.0)
+                                                .hint_text(hint_pool_keys),
                                         );
-                                        if ui.button(
-                                            self.tr("🔀 裂变创建", "🔀 Burst Create")
-                                        ).on_hover_text(format!("{burst_tip}\n{burst_btn_label}"))
-                                        .clicked() {
-                                            self.burst_add_entities();
-                                            self.entity_selected = None;
+                                        if ui.button(self.tr("➕ 加入池", "➕ Add to pool"))
+                                            .on_hover_text(&pool_tip)
+                                            .clicked()
+                                        {
+                                            self.add_pool_keys();
+                                        }
+                                    });
+                                    if let Some(status) = &self.entity_pool_status {
+                                        for member in &status.members {
+                                            let state = match member.benched_until {
+                                                Some(until) => format!("⏸ {}", until.with_timezone(&chrono::Local).format("%H:%M:%S")),
+                                                None => "▶".to_string(),
+                                            };
+                                            ui.monospace(format!(
+                                                "{state} {}  req {}  tok {}  err {}",
+                                                member.entry, member.requests_made, member.tokens_used, member.failures
+                                            ));
+                                        }
+                                    }
+                                });
+
+                                ui.add_space(4.0);
+
+                                // ── 4. 故障转移组（折叠）────────────────────────────────
+                                eframe::egui::CollapsingHeader::new(
+                                    self.tr("🔀 故障转移组", "🔀 Fallback Groups"),
+                                )
+                                .id_source("entity_groups_header")
+                                .default_open(false)
+                                .show(ui, |ui| {
+                                    ui.label(self.tr(
+                                        "按顺序尝试链中的个体；可重试错误（限流、额度、服务不可用）时自动切换到下一个。连续失败的个体会被熔断一段时间。",
+                                        "Entities are tried in chain order; retryable errors (rate limit, quota, outage) move on to the next. Entities that keep failing are skipped until a cool-down expires.",
+                                    ));
+                                    let hint_group_note = self.tr("可选说明", "optional note");
+                                    eframe::egui::Grid::new("entity_group_form_grid")
+                                        .num_columns(2)
+                                        .spacing([8.0, 4.0])
+                                        .show(ui, |ui| {
+                                            ui.label(self.tr("组名 *", "Group *"));
+                                            ui.add(eframe::egui::TextEdit::singleline(&mut self.group_name_input)
+                                                .desired_width(200.0).hint_text("coder"));
+                                            ui.end_row();
+
+                                            ui.label(self.tr("转移链 *", "Chain *"))
+                                                .on_hover_text(self.tr("个体名称，用逗号或 → 分隔", "Entity names separated by , or →"));
+                                            ui.add(eframe::egui::TextEdit::singleline(&mut self.group_chain_input)
+                                                .desired_width(340.0).hint_text("deepseek-main, moonshot-backup, llama3-local"));
+                                            ui.end_row();
+
+                                            ui.label(self.tr("备注", "Note"));
+                                            ui.add(eframe::egui::TextEdit::singleline(&mut self.group_note_input)
+                                                .desired_width(280.0).hint_text(hint_group_note));
+                                            ui.end_row();
+                                        });
+                                    ui.horizontal(|ui| {
+                                        if ui.button(self.tr("💾 保存组", "💾 Save Group")).clicked() {
+                                            self.save_entity_group();
+                                        }
+                                        if ui.small_button("🔄").on_hover_text(self.tr("刷新熔断状态", "Refresh circuit status")).clicked() {
+                                            self.load_entity_groups();
                                         }
                                     });
+
+                                    let groups = self.entity_groups.clone();
+                                    if !groups.is_empty() {
+                                        ui.separator();
+                                        for group in &groups {
+                                            ui.horizontal(|ui| {
+                                                ui.label(eframe::egui::RichText::new(&group.name).strong());
+                                                ui.monospace(group.chain.join(" → "));
+                                                if ui.small_button("🗑").on_hover_text(self.tr("删除此组", "Delete")).clicked() {
+                                                    self.delete_entity_group(&group.name);
+                                                }
+                                                if ui.small_button(self.tr("编辑", "Edit")).clicked() {
+                                                    self.group_name_input = group.name.clone();
+                                                    self.group_chain_input = group.chain.join(", ");
+                                                    self.group_note_input = group.note.clone();
+                                                }
+                                            });
+                                        }
+                                    }
+
+                                    let tripped: Vec<CircuitStatus> = self
+                                        .circuit_statuses
+                                        .iter()
+                                        .filter(|c| c.state != CircuitState::Closed)
+                                        .cloned()
+                                        .collect();
+                                    if !tripped.is_empty() {
+                                        ui.separator();
+                                        ui.strong(self.tr("⚡ 已熔断的个体", "⚡ Tripped Entities"));
+                                        for circuit in &tripped {
+                                            ui.horizontal(|ui| {
+                                                let state = match circuit.open_until {
+                                                    Some(until) => format!("⏸ {}", until.with_timezone(&chrono::Local).format("%H:%M:%S")),
+                                                    None => "◐".to_string(),
+                                                };
+                                                ui.monospace(format!("{state} {}", circuit.entity))
+                                                    .on_hover_text(circuit.last_error.clone().unwrap_or_default());
+                                                if ui.small_button(self.tr("恢复", "Reset")).clicked() {
+                                                    self.reset_circuit(&circuit.entity);
+                                                }
+                                            });
+                                        }
+                                    }
                                 });
 
                                 ui.add_space(4.0);
 
-                                // ── 4. 已知供应商快速参考（折叠）────────────────────────
+                                // ── 5. 已知供应商快速参考（折叠）────────────────────────
                                 eframe::egui::CollapsingHeader::new(
                                     self.tr("📖 供应商参考列表", "📖 Provider Reference"),
                                 )
@@ -1419,7 +1667,7 @@ impl eframe::App for VgaGuiApp {
                                     });
                                 });
 
-                                // ── 5. 使用统计（折叠）───────────────────────────────────
+                                // ── 6. 使用统计（折叠）───────────────────────────────────
                                 eframe::egui::CollapsingHeader::new(
                                     self.tr("📊 使用统计", "📊 Usage Stats"),
                                 )
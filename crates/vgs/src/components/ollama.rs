@@ -1,14 +1,27 @@
 use eframe::egui;
 use std::collections::BTreeSet;
+use std::path::PathBuf;
+use vangriten_ai_swarm::backend::chat_session::{
+    ChatSession, SessionTarget, CHAT_SESSIONS_DIR, DEFAULT_KEEP_RECENT,
+};
+use vangriten_ai_swarm::backend::ollama_client::RunningModel;
 
 #[derive(Debug, Default)]
 pub struct OllamaComponent {
     status_line: String,
     version_line: String,
     models: Vec<String>,
+    /// Models Ollama currently holds in memory (`/api/ps`).
+    running: Vec<RunningModel>,
     selected: BTreeSet<String>,
     share_enabled: bool,
     last_error: Option<String>,
+
+    // Chat
+    chat_model: String,
+    chat_system: String,
+    chat_prompt: String,
+    chat_session: Option<ChatSession>,
 }
 
 impl OllamaComponent {
@@ -43,6 +56,20 @@ impl OllamaComponent {
             ui.colored_label(egui::Color32::RED, err);
         }
 
+        if !self.running.is_empty() {
+            ui.add_space(8.0);
+            ui.label(app.tr("内存中的模型", "Loaded in memory"));
+            for model in &self.running {
+                const MB: u64 = 1024 * 1024;
+                ui.monospace(format!(
+                    "{}  {} MB (VRAM {} MB)",
+                    model.name,
+                    model.size / MB,
+                    model.size_vram / MB
+                ));
+            }
+        }
+
         ui.add_space(8.0);
         ui.checkbox(
             &mut self.share_enabled,
@@ -87,6 +114,131 @@ impl OllamaComponent {
                     }
                 }
             });
+
+        ui.add_space(8.0);
+        egui::CollapsingHeader::new(app.tr("对话", "Chat"))
+            .id_source("ollama_chat")
+            .show(ui, |ui| self.chat_ui(ui, app));
+    }
+
+    fn chat_ui(&mut self, ui: &mut egui::Ui, app: &mut crate::app::VgaGuiApp) {
+        ui.horizontal_wrapped(|ui| {
+            ui.label(app.tr("模型", "Model"));
+            egui::ComboBox::from_id_source("ollama_chat_model")
+                .selected_text(if self.chat_model.is_empty() { "-" } else { self.chat_model.as_str() })
+                .show_ui(ui, |ui| {
+                    for m in &self.models {
+                        ui.selectable_value(&mut self.chat_model, m.clone(), m);
+                    }
+                });
+            if ui.button(app.tr("新对话", "New chat")).clicked() {
+                self.chat_session = None;
+            }
+            if ui.button(app.tr("打开上次对话", "Open last chat")).clicked() {
+                self.open_last_session(app);
+            }
+            if ui.button(app.tr("导出 Markdown", "Export Markdown")).clicked() {
+                self.export_session(app);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label(app.tr("系统提示", "System prompt"));
+            ui.text_edit_singleline(&mut self.chat_system);
+        });
+
+        egui::ScrollArea::vertical()
+            .id_source("ollama_chat_scroll")
+            .max_height(300.0)
+            .stick_to_bottom(true)
+            .show(ui, |ui| match &self.chat_session {
+                Some(session) => {
+                    if session.summary.is_some() {
+                        ui.small(app.tr("（较早的对话已摘要）", "(earlier turns summarized)"));
+                    }
+                    for entry in &session.messages {
+                        ui.strong(&entry.message.role);
+                        ui.monospace(&entry.message.content);
+                    }
+                }
+                None => {
+                    ui.label(app.tr("(无对话)", "(no conversation)"));
+                }
+            });
+
+        ui.add(
+            egui::TextEdit::multiline(&mut self.chat_prompt)
+                .desired_rows(3)
+                .hint_text(app.tr("输入要发送的内容…", "Type your message…")),
+        );
+        let enabled = !self.chat_model.is_empty() && !self.chat_prompt.trim().is_empty();
+        if ui.add_enabled(enabled, egui::Button::new(app.tr("发送", "Send"))).clicked() {
+            self.send_chat(app);
+        }
+    }
+
+    fn sessions_dir(app: &crate::app::VgaGuiApp) -> PathBuf {
+        app.services.api_manager.vault_dir().join(CHAT_SESSIONS_DIR)
+    }
+
+    fn send_chat(&mut self, app: &mut crate::app::VgaGuiApp) {
+        self.last_error = None;
+        let model = self.chat_model.clone();
+        let mut session = match self.chat_session.take() {
+            Some(s) if s.target.name() == model => s,
+            _ => {
+                let mut s = ChatSession::ollama(model.clone());
+                if !self.chat_system.trim().is_empty() {
+                    s = s.with_system(self.chat_system.trim());
+                }
+                s
+            }
+        };
+        let text = self.chat_prompt.trim().to_string();
+
+        let services = app.services.clone();
+        let (session, res) = app.runtime.block_on(async move {
+            if let Err(e) = session.summarize(&services.ollama_manager, None, DEFAULT_KEEP_RECENT).await {
+                tracing::warn!("Chat summary failed, truncating instead: {e}");
+            }
+            let res = session.send_ollama(&services.ollama_manager, &model, &text).await;
+            (session, res)
+        });
+        match res {
+            Ok(_) => {
+                self.chat_prompt.clear();
+                if let Err(e) = session.save(&Self::sessions_dir(app)) {
+                    self.last_error = Some(e);
+                }
+            }
+            Err(e) => self.last_error = Some(e),
+        }
+        self.chat_session = Some(session);
+    }
+
+    fn open_last_session(&mut self, app: &mut crate::app::VgaGuiApp) {
+        let last = ChatSession::list(&Self::sessions_dir(app))
+            .into_iter()
+            .find(|s| matches!(s.target, SessionTarget::Ollama { .. }));
+        match last {
+            Some(session) => {
+                self.chat_model = session.target.name().to_string();
+                self.chat_system = session.system.clone().unwrap_or_default();
+                self.chat_session = Some(session);
+            }
+            None => self.status_line = app.tr("没有保存的对话", "No saved chats").to_string(),
+        }
+    }
+
+    fn export_session(&mut self, app: &mut crate::app::VgaGuiApp) {
+        let Some(session) = &self.chat_session else { return };
+        let dir = Self::sessions_dir(app);
+        let path = dir.join(format!("{}.md", session.id));
+        match std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(&path, session.to_markdown())) {
+            Ok(()) => {
+                self.status_line = format!("{}{}", app.tr("已导出：", "Exported: "), path.display());
+            }
+            Err(e) => self.last_error = Some(format!("export failed: {e}")),
+        }
     }
 
     fn check_connection(&mut self, app: &mut crate::app::VgaGuiApp) {
@@ -132,8 +284,19 @@ impl OllamaComponent {
             }
             Err(e) => {
                 self.last_error = Some(e);
+                return;
             }
         }
+
+        // Loaded models also go into this node's resource report.
+        let services = app.services.clone();
+        let res = app.runtime.block_on(async move {
+            services.ollama_manager.report_resources(&services.resource_manager).await
+        });
+        match res {
+            Ok(running) => self.running = running,
+            Err(e) => self.last_error = Some(e),
+        }
     }
 
     fn apply_sharing(&mut self, app: &mut crate::app::VgaGuiApp) {
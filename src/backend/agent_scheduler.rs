@@ -8,16 +8,21 @@ use crate::shared::models::{
     Task,
     TaskId,
     TaskOutput,
+    TaskEvent,
+    TaskEventKind,
     TaskSpec,
     VgaError,
     SwarmPulse,
     TaskStatus,
 };
 use chrono::Utc;
-use crate::backend::agents::{ArchitectAgent, EnvironmentAgent, ProgrammerAgent};
+use crate::backend::agents::tools::{run_tool_loop, DEFAULT_MAX_TOOL_ROUNDS};
+use crate::backend::agents::{ArchitectAgent, EnvironmentAgent, ProgrammerAgent, ToolRegistry};
+use crate::backend::failover::FailoverRouter;
+use crate::backend::llm_backend::LlmRequest;
 use crate::shared::models::AgentTrait;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, mpsc, RwLock};
 use std::sync::atomic::Ordering;
 use std::collections::HashMap;
 
@@ -29,8 +34,16 @@ pub struct AgentScheduler {
     waiting_queue: Arc<RwLock<Vec<TaskId>>>,
     task_store: Arc<RwLock<HashMap<TaskId, Task>>>,
     active_tasks: Arc<RwLock<HashMap<TaskId, tokio::task::JoinHandle<Result<TaskOutput, VgaError>>>>>,
+    /// Serves tasks that name an entity or entity group.
+    llm_router: Arc<RwLock<Option<FailoverRouter>>>,
+    /// Tools entity tasks may ask for through `Task.tools`.
+    tools: Arc<RwLock<ToolRegistry>>,
+    events: broadcast::Sender<TaskEvent>,
 }
 
+/// Events buffered per subscriber before the slowest one starts missing some.
+const TASK_EVENT_CAPACITY: usize = 1024;
+
 impl AgentScheduler {
     pub async fn new() -> Self {
         let scheduler = Self {
@@ -41,6 +54,9 @@ impl AgentScheduler {
             waiting_queue: Arc::new(RwLock::new(vec![])),
             task_store: Arc::new(RwLock::new(HashMap::new())),
             active_tasks: Arc::new(RwLock::new(HashMap::new())),
+            llm_router: Arc::new(RwLock::new(None)),
+            tools: Arc::new(RwLock::new(ToolRegistry::new())),
+            events: broadcast::channel(TASK_EVENT_CAPACITY).0,
         };
 
         // Seed a few default agents so the app has something to show.
@@ -91,6 +107,30 @@ impl AgentScheduler {
         Ok(())
     }
 
+    /// Let tasks with `Task.entity` set be answered through `router`.
+    pub async fn attach_llm_router(&self, router: FailoverRouter) {
+        *self.llm_router.write().await = Some(router);
+    }
+
+    pub async fn llm_router(&self) -> Option<FailoverRouter> {
+        self.llm_router.read().await.clone()
+    }
+
+    /// Replace the tools entity tasks can call.
+    pub async fn attach_tools(&self, tools: ToolRegistry) {
+        *self.tools.write().await = tools;
+    }
+
+    pub async fn tool_names(&self) -> Vec<String> {
+        self.tools.read().await.names()
+    }
+
+    /// Task lifecycle events and streamed reply text. A subscriber that
+    /// falls behind gets `RecvError::Lagged` and can catch up from `get_task`.
+    pub fn subscribe(&self) -> broadcast::Receiver<TaskEvent> {
+        self.events.subscribe()
+    }
+
     pub async fn list_agents(&self) -> Vec<Agent> {
         self.agents.read().await.clone()
     }
@@ -169,11 +209,8 @@ impl AgentScheduler {
 
     pub async fn submit_task(&self, task: Task) -> Result<TaskId, VgaError> {
         let task_id = task.id;
-        let mut task_store = self.task_store.write().await;
-        task_store.insert(task_id, task);
-
-        let mut queue = self.waiting_queue.write().await;
-        queue.push(task_id);
+        self.task_store.write().await.insert(task_id, task);
+        self.waiting_queue.write().await.push(task_id);
 
         // Try to dispatch immediately if agents are available
         self.try_dispatch_next().await;
@@ -208,6 +245,7 @@ impl AgentScheduler {
             handle.abort();
         }
 
+        let _ = self.events.send(TaskEvent { task_id, kind: TaskEventKind::Cancelled });
         Ok(())
     }
 
@@ -266,9 +304,28 @@ impl AgentScheduler {
         if let Ok(agent) = self.gatling_rotate_next() {
             let task_store = self.task_store.clone();
             let active_tasks = self.active_tasks.clone();
+            let router = self.llm_router.read().await.clone();
+            let tools = self.tools.read().await.clone();
+            let events = self.events.clone();
+            let _ = events.send(TaskEvent { task_id: next_task_id, kind: TaskEventKind::Started });
 
             let handle = tokio::spawn(async move {
-                let result = agent.execute_block(task.spec).await;
+                let result = match task.entity.as_deref() {
+                    Some(target) => {
+                        let (deltas, received) = mpsc::unbounded_channel();
+                        let progress = tokio::spawn(record_progress(
+                            next_task_id,
+                            received,
+                            task_store.clone(),
+                            events.clone(),
+                        ));
+                        let result = run_on_entity(router, target, &task, &tools, deltas).await;
+                        // The sender is gone, so this returns once every delta is recorded.
+                        let _ = progress.await;
+                        result
+                    }
+                    None => agent.execute_block(task.spec).await,
+                };
                 {
                     let mut task_store = task_store.write().await;
                     if let Some(task) = task_store.get_mut(&next_task_id) {
@@ -287,6 +344,11 @@ impl AgentScheduler {
                     let mut active_tasks = active_tasks.write().await;
                     active_tasks.remove(&next_task_id);
                 }
+                let kind = match &result {
+                    Ok(_) => TaskEventKind::Completed,
+                    Err(e) => TaskEventKind::Failed(format!("{:?}", e)),
+                };
+                let _ = events.send(TaskEvent { task_id: next_task_id, kind });
                 result
             });
 
@@ -299,4 +361,57 @@ impl AgentScheduler {
         // Clean up completed tasks and try to dispatch more
         self.try_dispatch_next().await;
     }
-}
\ No newline at end of file
+}
+
+/// Answer a task through the failover router, streaming reply text to
+/// `deltas`; the serving entity and the attempts before it end up in the
+/// output metadata. The entity may call the tools the task names.
+async fn run_on_entity(
+    router: Option<FailoverRouter>,
+    target: &str,
+    task: &Task,
+    registry: &ToolRegistry,
+    deltas: mpsc::UnboundedSender<String>,
+) -> Result<TaskOutput, VgaError> {
+    let spec = &task.spec;
+    let tools = registry.select(&task.tools)?;
+    let router = router.ok_or_else(|| {
+        VgaError::InvalidConfig(format!("No LLM router attached; cannot run task on {target}"))
+    })?;
+    let system = format!(
+        "You are a {} agent in a software swarm. Task target: {}.",
+        spec.language, spec.target
+    );
+    let request = LlmRequest {
+        cache: task.cache,
+        ..LlmRequest::prompt(Some(&system), &spec.context_range)
+    };
+    if !tools.is_empty() {
+        let answer = run_tool_loop(&router, target, request, &tools, DEFAULT_MAX_TOOL_ROUNDS, deltas).await?;
+        return Ok(TaskOutput {
+            metadata: answer.metadata(),
+            content: answer.served.reply.content,
+        });
+    }
+    let served = router.chat_stream(target, &request, deltas).await?;
+    Ok(TaskOutput {
+        metadata: served.metadata(),
+        content: served.reply.content,
+    })
+}
+
+/// Append streamed text to the task's `partial_output` and publish it.
+async fn record_progress(
+    task_id: TaskId,
+    mut received: mpsc::UnboundedReceiver<String>,
+    task_store: Arc<RwLock<HashMap<TaskId, Task>>>,
+    events: broadcast::Sender<TaskEvent>,
+) {
+    while let Some(text) = received.recv().await {
+        if let Some(task) = task_store.write().await.get_mut(&task_id) {
+            task.partial_output.push_str(&text);
+            task.updated_at = Utc::now();
+        }
+        let _ = events.send(TaskEvent { task_id, kind: TaskEventKind::Delta(text) });
+    }
+}
@@ -1,4 +1,9 @@
 use crate::shared::models::{AgentTrait, TaskOutput, VgaError, TaskSpec, ContextManager, PerfMetrics};
+use crate::backend::failover::FailoverRouter;
+use crate::backend::llm_backend::LlmRequest;
+use crate::backend::structured::{self, StructuredOutput, DEFAULT_STRUCTURED_ATTEMPTS};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 
 #[derive(Clone)]
 pub struct ArchitectAgent {
@@ -15,6 +20,22 @@ impl ArchitectAgent {
         }
     }
 
+    /// Have an entity or entity group design the blueprint instead of the
+    /// keyword heuristics. The reply is validated against
+    /// [`ArchitectureBlueprint::schema`].
+    pub async fn blueprint_from_entity(
+        router: &FailoverRouter,
+        target: &str,
+        requirements: &str,
+    ) -> Result<ArchitectureBlueprint, VgaError> {
+        let request = LlmRequest::prompt(
+            Some("You are a software architect. Answer with a JSON architecture blueprint for the requirements."),
+            requirements,
+        );
+        let reply = structured::chat_typed(router, target, request, DEFAULT_STRUCTURED_ATTEMPTS).await?;
+        Ok(reply.value)
+    }
+
     /// Analyze project requirements and generate architecture blueprint
     fn analyze_requirements(&self, requirements: &str) -> Result<ArchitectureBlueprint, VgaError> {
         // Parse requirements and extract key components
@@ -207,7 +228,7 @@ impl ArchitectAgent {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ArchitectureBlueprint {
     pub components: Vec<Component>,
     pub patterns: Vec<ArchitecturePattern>,
@@ -216,7 +237,7 @@ pub struct ArchitectureBlueprint {
     pub data_flow: Vec<DataFlow>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Component {
     pub name: String,
     pub component_type: ComponentType,
@@ -224,7 +245,7 @@ pub struct Component {
     pub dependencies: Vec<String>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ComponentType {
     Service,
     DataStore,
@@ -232,7 +253,7 @@ pub enum ComponentType {
     Worker,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ArchitecturePattern {
     Monolithic,
     Microservices,
@@ -240,7 +261,7 @@ pub enum ArchitecturePattern {
     EventDriven,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Interface {
     pub name: String,
     pub component: String,
@@ -248,7 +269,7 @@ pub struct Interface {
     pub protocol: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DataFlow {
     pub from: String,
     pub to: String,
@@ -256,6 +277,40 @@ pub struct DataFlow {
     pub protocol: String,
 }
 
+impl StructuredOutput for ArchitectureBlueprint {
+    const NAME: &'static str = "architecture_blueprint";
+
+    fn schema() -> Value {
+        let strings = json!({ "type": "array", "items": { "type": "string" } });
+        let object = |properties: Value| {
+            let required: Vec<&String> = properties.as_object().map(|p| p.keys().collect()).unwrap_or_default();
+            json!({ "type": "object", "properties": properties, "required": required, "additionalProperties": false })
+        };
+        object(json!({
+            "components": { "type": "array", "items": object(json!({
+                "name": { "type": "string" },
+                "component_type": { "enum": ["Service", "DataStore", "UI", "Worker"] },
+                "technologies": strings,
+                "dependencies": strings,
+            })) },
+            "patterns": { "type": "array", "items": { "enum": ["Monolithic", "Microservices", "Layered", "EventDriven"] } },
+            "tech_stack": strings,
+            "interfaces": { "type": "array", "items": object(json!({
+                "name": { "type": "string" },
+                "component": { "type": "string" },
+                "methods": strings,
+                "protocol": { "type": "string" },
+            })) },
+            "data_flow": { "type": "array", "items": object(json!({
+                "from": { "type": "string" },
+                "to": { "type": "string" },
+                "data_type": { "type": "string" },
+                "protocol": { "type": "string" },
+            })) },
+        }))
+    }
+}
+
 #[async_trait::async_trait]
 impl AgentTrait for ArchitectAgent {
     async fn execute_instruction(&self, instr: String) -> Result<TaskOutput, VgaError> {
@@ -1,7 +1,9 @@
 pub mod architect;
 pub mod programmer;
 pub mod environment;
+pub mod tools;
 
 pub use architect::ArchitectAgent;
 pub use programmer::ProgrammerAgent;
-pub use environment::EnvironmentAgent;
\ No newline at end of file
+pub use environment::EnvironmentAgent;
+pub use tools::{AgentTool, ToolRegistry};
\ No newline at end of file
@@ -0,0 +1,566 @@
+//! Tools an LLM-backed agent can call, and the loop that runs them.
+//!
+//! The registry's definitions go out with the request. When the reply asks
+//! for tool calls, each one is run and its result appended as a `tool`
+//! message, and the conversation goes back to the model. The loop ends with
+//! the first reply that calls no tools, or fails after `max_rounds` rounds.
+//!
+//! File tools are confined to a workspace directory: absolute paths and `..`
+//! are refused.
+
+use crate::backend::c_compiler::{CCompilationScheduler, CCompilationTask};
+use crate::backend::failover::{FailoverReply, FailoverRouter};
+use crate::backend::llm_backend::LlmRequest;
+use crate::backend::ollama_client::{ChatMessage, Tool, ToolCall};
+use crate::shared::models::VgaError;
+use crate::shared::utils::truncate;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Rounds of tool calls allowed before a task is failed.
+pub const DEFAULT_MAX_TOOL_ROUNDS: usize = 8;
+
+/// Longest tool result passed back to the model.
+const MAX_RESULT_BYTES: usize = 32 * 1024;
+
+/// Compiler flags a model may pass to `compile_c`. `-W` flags with a comma
+/// (`-Wl,…`, `-Wa,…`) hand options to other tools and are refused.
+const ALLOWED_FLAG_PREFIXES: &[&str] = &["-W", "-std=", "-D", "-g", "-l"];
+
+#[async_trait::async_trait]
+pub trait AgentTool: Send + Sync {
+    fn definition(&self) -> Tool;
+
+    /// Run the tool. An `Err` is passed back to the model as the result so it
+    /// can correct itself; it does not fail the task.
+    async fn call(&self, arguments: &Value) -> Result<String, String>;
+}
+
+/// Tools by name.
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    tools: BTreeMap<String, Arc<dyn AgentTool>>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `read_file`, `write_file` and `list_files` under `root`, `compile_c`
+    /// if a compiler is given, and `ask_agent` if a router is given.
+    pub fn workspace(
+        root: impl Into<PathBuf>,
+        compiler: Option<CCompilationScheduler>,
+        router: Option<FailoverRouter>,
+    ) -> Self {
+        let root = root.into();
+        let mut registry = Self::new();
+        registry.register(ReadFileTool { root: root.clone() });
+        registry.register(WriteFileTool { root: root.clone() });
+        registry.register(ListFilesTool { root: root.clone() });
+        if let Some(compiler) = compiler {
+            registry.register(CompileTool { root, compiler });
+        }
+        if let Some(router) = router {
+            registry.register(AskAgentTool { router });
+        }
+        registry
+    }
+
+    /// Add `tool`, replacing any tool of the same name.
+    pub fn register(&mut self, tool: impl AgentTool + 'static) {
+        let name = tool.definition().function.name;
+        self.tools.insert(name, Arc::new(tool));
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.tools.keys().cloned().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    pub fn definitions(&self) -> Vec<Tool> {
+        self.tools.values().map(|tool| tool.definition()).collect()
+    }
+
+    /// The tools named in `names`, failing on any unknown name.
+    pub fn select(&self, names: &[String]) -> Result<Self, VgaError> {
+        let mut selected = Self::new();
+        for name in names {
+            let tool = self
+                .tools
+                .get(name)
+                .ok_or_else(|| VgaError::InvalidConfig(format!("Unknown tool {name}")))?;
+            selected.tools.insert(name.clone(), tool.clone());
+        }
+        Ok(selected)
+    }
+
+    /// Run `call` and return the text to send back to the model.
+    pub async fn call(&self, call: &ToolCall) -> String {
+        let name = &call.function.name;
+        let Some(tool) = self.tools.get(name) else {
+            return format!("Error: unknown tool {name}. Available: {}", self.names().join(", "));
+        };
+        match tool.call(&call.function.arguments).await {
+            Ok(output) => truncate(&output, MAX_RESULT_BYTES),
+            Err(e) => format!("Error: {e}"),
+        }
+    }
+}
+
+/// The final answer of a tool loop.
+#[derive(Debug, Clone)]
+pub struct ToolLoopReply {
+    /// Last round's reply, with token counts summed over all rounds.
+    pub served: FailoverReply,
+    pub rounds: usize,
+    /// Names of the tools called, in order.
+    pub calls: Vec<String>,
+}
+
+impl ToolLoopReply {
+    /// [`FailoverReply::metadata`] plus `tool_rounds` and `tool_calls`.
+    pub fn metadata(&self) -> std::collections::HashMap<String, String> {
+        let mut metadata = self.served.metadata();
+        metadata.insert("tool_rounds".to_string(), self.rounds.to_string());
+        metadata.insert("tool_calls".to_string(), self.calls.join(","));
+        metadata
+    }
+}
+
+/// Send `request` to `target` with `tools` offered, running the calls the
+/// model makes until it answers without any. Reply text of every round is
+/// forwarded to `deltas`.
+pub async fn run_tool_loop(
+    router: &FailoverRouter,
+    target: &str,
+    mut request: LlmRequest,
+    tools: &ToolRegistry,
+    max_rounds: usize,
+    deltas: mpsc::UnboundedSender<String>,
+) -> Result<ToolLoopReply, VgaError> {
+    request.tools = tools.definitions();
+    let mut calls = Vec::new();
+    let (mut prompt_tokens, mut completion_tokens) = (0, 0);
+
+    for round in 1..=max_rounds {
+        let mut served = router.chat_stream(target, &request, deltas.clone()).await?;
+        prompt_tokens += served.reply.prompt_tokens.unwrap_or(0);
+        completion_tokens += served.reply.completion_tokens.unwrap_or(0);
+
+        if served.reply.tool_calls.is_empty() {
+            served.reply.prompt_tokens = Some(prompt_tokens);
+            served.reply.completion_tokens = Some(completion_tokens);
+            return Ok(ToolLoopReply { served, rounds: round, calls });
+        }
+
+        // Results are matched to calls by id, so give Ollama's calls one.
+        let mut requested = std::mem::take(&mut served.reply.tool_calls);
+        for (idx, call) in requested.iter_mut().enumerate() {
+            call.id.get_or_insert_with(|| format!("call_{round}_{idx}"));
+        }
+        request.messages.push(ChatMessage {
+            tool_calls: Some(requested.clone()),
+            ..ChatMessage::new("assistant", served.reply.content)
+        });
+        for call in &requested {
+            tracing::debug!("{target} round {round}: calling {}", call.function.name);
+            let output = tools.call(call).await;
+            request.messages.push(ChatMessage::tool_result(call, output));
+            calls.push(call.function.name.clone());
+        }
+    }
+
+    Err(VgaError::LlmFailure(format!(
+        "{target} was still calling tools after {max_rounds} rounds"
+    )))
+}
+
+// ─── Built-in tools ───────────────────────────────────────────────────────────
+
+fn str_arg<'a>(arguments: &'a Value, name: &str) -> Result<&'a str, String> {
+    arguments
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("missing string argument '{name}'"))
+}
+
+/// `relative` joined to `root`, refusing paths that could leave it.
+fn resolve_in(root: &Path, relative: &str) -> Result<PathBuf, String> {
+    let path = Path::new(relative);
+    if path
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(format!("path '{relative}' must be relative to the workspace and stay inside it"));
+    }
+    Ok(root.join(path))
+}
+
+struct ReadFileTool {
+    root: PathBuf,
+}
+
+#[async_trait::async_trait]
+impl AgentTool for ReadFileTool {
+    fn definition(&self) -> Tool {
+        Tool::function(
+            "read_file",
+            "Read a text file from the workspace.",
+            json!({
+                "type": "object",
+                "properties": { "path": { "type": "string", "description": "Path relative to the workspace" } },
+                "required": ["path"],
+            }),
+        )
+    }
+
+    async fn call(&self, arguments: &Value) -> Result<String, String> {
+        let path = resolve_in(&self.root, str_arg(arguments, "path")?)?;
+        tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| format!("cannot read {}: {e}", path.display()))
+    }
+}
+
+struct WriteFileTool {
+    root: PathBuf,
+}
+
+#[async_trait::async_trait]
+impl AgentTool for WriteFileTool {
+    fn definition(&self) -> Tool {
+        Tool::function(
+            "write_file",
+            "Create or overwrite a text file in the workspace.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Path relative to the workspace" },
+                    "content": { "type": "string" },
+                },
+                "required": ["path", "content"],
+            }),
+        )
+    }
+
+    async fn call(&self, arguments: &Value) -> Result<String, String> {
+        let relative = str_arg(arguments, "path")?;
+        let content = str_arg(arguments, "content")?;
+        let path = resolve_in(&self.root, relative)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| format!("cannot create {}: {e}", parent.display()))?;
+        }
+        tokio::fs::write(&path, content)
+            .await
+            .map_err(|e| format!("cannot write {}: {e}", path.display()))?;
+        Ok(format!("Wrote {} bytes to {relative}", content.len()))
+    }
+}
+
+struct ListFilesTool {
+    root: PathBuf,
+}
+
+#[async_trait::async_trait]
+impl AgentTool for ListFilesTool {
+    fn definition(&self) -> Tool {
+        Tool::function(
+            "list_files",
+            "List the entries of a workspace directory.",
+            json!({
+                "type": "object",
+                "properties": { "path": { "type": "string", "description": "Directory relative to the workspace; defaults to its root" } },
+            }),
+        )
+    }
+
+    async fn call(&self, arguments: &Value) -> Result<String, String> {
+        let relative = arguments.get("path").and_then(Value::as_str).unwrap_or(".");
+        let dir = resolve_in(&self.root, relative)?;
+        let mut entries = tokio::fs::read_dir(&dir)
+            .await
+            .map_err(|e| format!("cannot list {}: {e}", dir.display()))?;
+        let mut names = Vec::new();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let mut name = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type().await.is_ok_and(|t| t.is_dir()) {
+                name.push('/');
+            }
+            names.push(name);
+        }
+        names.sort();
+        Ok(names.join("\n"))
+    }
+}
+
+struct CompileTool {
+    root: PathBuf,
+    compiler: CCompilationScheduler,
+}
+
+#[async_trait::async_trait]
+impl AgentTool for CompileTool {
+    fn definition(&self) -> Tool {
+        Tool::function(
+            "compile_c",
+            "Compile C sources from the workspace with GCC and report the compiler output.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "sources": { "type": "array", "items": { "type": "string" } },
+                    "output": { "type": "string", "description": "Binary path relative to the workspace" },
+                    "flags": { "type": "array", "items": { "type": "string" }, "description": "Extra flags such as -Wall or -std=c11" },
+                },
+                "required": ["sources", "output"],
+            }),
+        )
+    }
+
+    async fn call(&self, arguments: &Value) -> Result<String, String> {
+        let strings = |name: &str| -> Vec<String> {
+            arguments
+                .get(name)
+                .and_then(Value::as_array)
+                .map(|items| items.iter().filter_map(Value::as_str).map(str::to_string).collect())
+                .unwrap_or_default()
+        };
+        let sources = strings("sources")
+            .iter()
+            .map(|s| resolve_in(&self.root, s))
+            .collect::<Result<Vec<_>, _>>()?;
+        if sources.is_empty() {
+            return Err("no sources given".to_string());
+        }
+        let flags = strings("flags");
+        let allowed = |f: &String| ALLOWED_FLAG_PREFIXES.iter().any(|p| f.starts_with(p)) && !f.contains(',');
+        if let Some(flag) = flags.iter().find(|f| !allowed(f)) {
+            return Err(format!("flag '{flag}' is not allowed"));
+        }
+        let task = CCompilationTask {
+            task_id: uuid::Uuid::new_v4().to_string(),
+            source_files: sources,
+            output_path: resolve_in(&self.root, str_arg(arguments, "output")?)?,
+            compiler_flags: flags,
+            include_paths: vec![self.root.clone()],
+            optimization_level: "2".to_string(),
+        };
+        let result = self
+            .compiler
+            .compile_round_robin(task)
+            .await
+            .map_err(|e| format!("{e:?}"))?;
+        let verdict = if result.success { "Compilation succeeded" } else { "Compilation failed" };
+        Ok(format!("{verdict} in {}ms\n{}{}", result.compilation_time_ms, result.output, result.error_output))
+    }
+}
+
+struct AskAgentTool {
+    router: FailoverRouter,
+}
+
+#[async_trait::async_trait]
+impl AgentTool for AskAgentTool {
+    fn definition(&self) -> Tool {
+        Tool::function(
+            "ask_agent",
+            "Ask another AI entity or entity group a question and get its answer.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "target": { "type": "string", "description": "Entity or entity group name" },
+                    "question": { "type": "string" },
+                },
+                "required": ["target", "question"],
+            }),
+        )
+    }
+
+    async fn call(&self, arguments: &Value) -> Result<String, String> {
+        let target = str_arg(arguments, "target")?;
+        let request = LlmRequest::prompt(None, str_arg(arguments, "question")?);
+        self.router
+            .chat(target, &request)
+            .await
+            .map(|served| served.reply.content)
+            .map_err(|e| format!("{e:?}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::api_manager::ApiKeyManager;
+    use crate::backend::failover::ENTITIES_FILE;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::task::JoinHandle;
+
+    /// Answers each `/api/chat` call with the next of `messages` as a single
+    /// final Ollama frame, and hands back the request bodies it got.
+    async fn ollama_stub(messages: Vec<Value>) -> (String, JoinHandle<Vec<Value>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let base = format!("http://{}", listener.local_addr().expect("addr"));
+        let server = tokio::spawn(async move {
+            let mut bodies = Vec::new();
+            for message in messages {
+                let (mut conn, _) = listener.accept().await.expect("accept");
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                let body_start = loop {
+                    let n = conn.read(&mut buf).await.expect("read");
+                    request.extend_from_slice(&buf[..n]);
+                    if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break end + 4;
+                    }
+                };
+                let head = String::from_utf8_lossy(&request[..body_start]).to_lowercase();
+                let length: usize = head
+                    .lines()
+                    .find_map(|l| l.strip_prefix("content-length:"))
+                    .and_then(|v| v.trim().parse().ok())
+                    .expect("content-length");
+                while request.len() < body_start + length {
+                    let n = conn.read(&mut buf).await.expect("read");
+                    request.extend_from_slice(&buf[..n]);
+                }
+                bodies.push(serde_json::from_slice(&request[body_start..]).expect("json body"));
+                let reply = format!("{}\n", json!({ "model": "llama3", "message": message, "done": true }));
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/x-ndjson\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{reply}",
+                    reply.len()
+                );
+                conn.write_all(response.as_bytes()).await.expect("write");
+            }
+            bodies
+        });
+        (base, server)
+    }
+
+    async fn router(base: &str) -> FailoverRouter {
+        let dir = std::env::temp_dir().join(format!("vas-tools-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("vault dir");
+        let entities = json!([{
+            "name": "local",
+            "provider": "ollama",
+            "model": "llama3",
+            "note": "",
+            "custom_base_url": base,
+        }]);
+        std::fs::write(dir.join(ENTITIES_FILE), entities.to_string()).expect("entities");
+        FailoverRouter::new(ApiKeyManager::with_vault_dir(dir).await)
+    }
+
+    fn calls_tool(name: &str) -> Value {
+        json!({ "role": "assistant", "content": "", "tool_calls": [{ "function": { "name": name, "arguments": {} } }] })
+    }
+
+    fn workspace() -> ToolRegistry {
+        let root = std::env::temp_dir().join(format!("vas-tools-ws-{}", uuid::Uuid::new_v4()));
+        ToolRegistry::workspace(root, None, None)
+    }
+
+    #[test]
+    fn paths_must_stay_inside_the_workspace() {
+        let root = Path::new("/work");
+        assert_eq!(resolve_in(root, "src/main.c"), Ok(PathBuf::from("/work/src/main.c")));
+        assert_eq!(resolve_in(root, "./notes.txt"), Ok(PathBuf::from("/work/notes.txt")));
+
+        for escape in ["../secret", "src/../../secret", "/etc/passwd", "..", "src/.."] {
+            let err = resolve_in(root, escape).expect_err(escape);
+            assert!(err.contains("stay inside"), "{escape}: {err}");
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_prefixes_are_refused() {
+        for escape in [r"C:\secret", r"C:secret", r"\\server\share\secret"] {
+            assert!(resolve_in(Path::new(r"C:\work"), escape).is_err(), "{escape}");
+        }
+    }
+
+    #[tokio::test]
+    async fn compile_c_refuses_unlisted_flags() {
+        let compiler = CCompilationScheduler::new(1).await.expect("scheduler");
+        let tool = CompileTool { root: PathBuf::from("/work"), compiler };
+
+        for flag in ["-Wl,-rpath,/tmp", "-Wa,--defsym,x=1", "-D,x", "-fplugin=evil.so", "-o/etc/passwd", "--help", "main.c"] {
+            let arguments = json!({ "sources": ["main.c"], "output": "main", "flags": [flag] });
+            let err = tool.call(&arguments).await.expect_err(flag);
+            assert_eq!(err, format!("flag '{flag}' is not allowed"));
+        }
+
+        let arguments = json!({ "sources": ["../main.c"], "output": "main", "flags": ["-Wall"] });
+        assert!(tool.call(&arguments).await.expect_err("escaping source").contains("stay inside"));
+    }
+
+    #[tokio::test]
+    async fn an_unknown_tool_is_reported_back_to_the_model() {
+        let (base, server) = ollama_stub(vec![
+            calls_tool("peek"),
+            json!({ "role": "assistant", "content": "Nothing to peek at." }),
+        ])
+        .await;
+        let router = router(&base).await;
+        let (deltas, _rx) = mpsc::unbounded_channel();
+        let request = LlmRequest::prompt(None, "Look around.");
+
+        let reply = run_tool_loop(&router, "local", request, &workspace(), DEFAULT_MAX_TOOL_ROUNDS, deltas)
+            .await
+            .expect("answer");
+        assert_eq!(reply.rounds, 2);
+        assert_eq!(reply.calls, ["peek"]);
+        assert_eq!(reply.served.reply.content, "Nothing to peek at.");
+
+        let bodies = server.await.expect("server");
+        let names: Vec<&str> = bodies[0]["tools"]
+            .as_array()
+            .expect("tools")
+            .iter()
+            .filter_map(|t| t.pointer("/function/name")?.as_str())
+            .collect();
+        assert_eq!(names, ["list_files", "read_file", "write_file"]);
+
+        let messages = bodies[1]["messages"].as_array().expect("messages");
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1]["tool_calls"][0]["id"], "call_1_0");
+        assert_eq!(messages[2]["role"], "tool");
+        assert_eq!(messages[2]["tool_call_id"], "call_1_0");
+        assert_eq!(
+            messages[2]["content"],
+            "Error: unknown tool peek. Available: list_files, read_file, write_file"
+        );
+    }
+
+    #[tokio::test]
+    async fn the_loop_gives_up_after_max_rounds() {
+        let (base, server) = ollama_stub(vec![calls_tool("list_files"); DEFAULT_MAX_TOOL_ROUNDS]).await;
+        let router = router(&base).await;
+        let (deltas, _rx) = mpsc::unbounded_channel();
+        let request = LlmRequest::prompt(None, "Look around.");
+
+        let err = run_tool_loop(&router, "local", request, &workspace(), DEFAULT_MAX_TOOL_ROUNDS, deltas)
+            .await
+            .expect_err("never answers");
+        match err {
+            VgaError::LlmFailure(message) => {
+                assert!(message.contains(&format!("after {DEFAULT_MAX_TOOL_ROUNDS} rounds")), "{message}")
+            }
+            other => panic!("expected LlmFailure, got {other:?}"),
+        }
+
+        let bodies = server.await.expect("server");
+        assert_eq!(bodies.len(), DEFAULT_MAX_TOOL_ROUNDS);
+        let last = bodies.last().expect("last round")["messages"].as_array().expect("messages");
+        assert_eq!(last.len(), 1 + 2 * (DEFAULT_MAX_TOOL_ROUNDS - 1));
+    }
+}
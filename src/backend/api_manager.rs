@@ -1,19 +1,55 @@
-use crate::backend::provider_config::{get_predefined_providers, get_provider_by_id};
-use crate::shared::models::{VgaError, VaultOp, VaultResult, VaultUsageEntry};
-use aes_gcm::{aead::Aead, Aes256Gcm, KeyInit, Nonce};
-use argon2::Argon2;
-use rand::RngCore;
+use crate::backend::key_pool::KeyPools;
+use crate::backend::key_validator::{self, ValidationProbe};
+use crate::backend::model_discovery::{self, ModelCache};
+use crate::backend::provider_config::ProviderRegistry;
+use crate::backend::vault_bundle::{self, BundleEntry, BundlePayload};
+use crate::backend::vault_file::{self, KdfParams, MasterKey, VaultFile};
+use crate::shared::models::{
+    AiEntity, ImportConflictPolicy, KeyLease, KeyOutcome, KeyPoolStatus, KeyPoolStrategy,
+    KeyValidationResult, KeyValidationStatus, ModelListing, ProviderConfig, SecretString,
+    VaultImportReport, VaultOp, VaultResult, VaultUsageEntry, VgaError,
+};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 
+/// Idle period after which an unlocked vault locks itself, unless reconfigured.
+pub const DEFAULT_AUTO_LOCK: Duration = Duration::from_secs(15 * 60);
+
+/// How often the background watcher checks for an idle vault.
+const AUTO_LOCK_POLL: Duration = Duration::from_secs(5);
+
 #[derive(Clone)]
 pub struct ApiKeyManager {
     vault_path: PathBuf,
-    derived_key: Arc<Mutex<Option<[u8; 32]>>>,
+    /// Master key while unlocked; `Zeroizing` wipes it on lock and on drop.
+    derived_key: Arc<Mutex<Option<MasterKey>>>,
+    session: Arc<Mutex<VaultSession>>,
+    /// Serializes read-modify-write cycles on the vault file.
+    file_lock: Arc<Mutex<()>>,
     usage_stats: Arc<RwLock<HashMap<String, UsageStats>>>,
+    key_pools: Arc<KeyPools>,
+    model_cache: Arc<ModelCache>,
+    /// Built-in catalog merged with the provider files in the vault directory.
+    providers: Arc<Mutex<ProviderRegistry>>,
+    http: reqwest::Client,
+}
+
+/// Idle-timeout bookkeeping for the unlocked vault.
+#[derive(Debug)]
+struct VaultSession {
+    auto_lock: Option<Duration>,
+    last_activity: Instant,
+}
+
+impl VaultSession {
+    fn is_expired(&self) -> bool {
+        self.auto_lock
+            .is_some_and(|timeout| self.last_activity.elapsed() >= timeout)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -21,28 +57,152 @@ struct UsageStats {
     tokens_used: u64,
     requests_made: u64,
     last_used: chrono::DateTime<chrono::Utc>,
+    cache_hits: u64,
+    cache_misses: u64,
+}
+
+impl UsageStats {
+    fn new() -> Self {
+        Self {
+            tokens_used: 0,
+            requests_made: 0,
+            last_used: chrono::Utc::now(),
+            cache_hits: 0,
+            cache_misses: 0,
+        }
+    }
 }
 
 impl ApiKeyManager {
     pub async fn new() -> Self {
-        let vault_path = PathBuf::from("vault/keys.enc");
-        fs::create_dir_all(&vault_path.parent().unwrap()).unwrap();
+        Self::with_vault_dir("vault").await
+    }
 
-        Self {
+    /// Create a manager whose vault lives in `dir` instead of `./vault`.
+    pub async fn with_vault_dir(dir: impl Into<PathBuf>) -> Self {
+        let vault_path = dir.into().join("vault.json");
+        fs::create_dir_all(vault_path.parent().unwrap()).unwrap();
+        let strategies = fs::read_to_string(vault_path.with_file_name("key_pools.json"))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        let providers = ProviderRegistry::load(vault_path.parent().unwrap());
+
+        let manager = Self {
             vault_path,
             derived_key: Arc::new(Mutex::new(None)),
+            session: Arc::new(Mutex::new(VaultSession {
+                auto_lock: Some(DEFAULT_AUTO_LOCK),
+                last_activity: Instant::now(),
+            })),
+            file_lock: Arc::new(Mutex::new(())),
             usage_stats: Arc::new(RwLock::new(HashMap::new())),
+            key_pools: Arc::new(KeyPools::with_strategies(strategies)),
+            model_cache: Arc::new(ModelCache::default()),
+            providers: Arc::new(Mutex::new(providers)),
+            http: reqwest::Client::new(),
+        };
+        manager.spawn_auto_lock_watcher();
+        manager
+    }
+
+    /// Re-read `providers.json` and `custom_providers.json` from the vault
+    /// directory. Returns one message per rejected entry.
+    pub fn reload_providers(&self) -> Vec<String> {
+        let registry = ProviderRegistry::load(self.vault_dir());
+        let errors = registry.errors().to_vec();
+        if let Ok(mut guard) = self.providers.lock() {
+            *guard = registry;
+        }
+        errors
+    }
+
+    /// Errors from the last provider registry load.
+    pub fn provider_errors(&self) -> Vec<String> {
+        self.providers.lock().map(|r| r.errors().to_vec()).unwrap_or_default()
+    }
+
+    /// Every provider in the registry (built-ins, overrides and custom relays).
+    pub fn providers(&self) -> Vec<ProviderConfig> {
+        self.providers.lock().map(|r| r.providers().to_vec()).unwrap_or_default()
+    }
+
+    pub fn provider(&self, id: &str) -> Option<ProviderConfig> {
+        self.providers.lock().ok().and_then(|r| r.get(id).cloned())
+    }
+
+    /// Configure the idle auto-lock. `None` keeps the vault unlocked until `vault_lock`.
+    pub fn set_auto_lock(&self, timeout: Option<Duration>) {
+        if let Ok(mut session) = self.session.lock() {
+            session.auto_lock = timeout;
+            session.last_activity = Instant::now();
+        }
+    }
+
+    pub fn auto_lock(&self) -> Option<Duration> {
+        self.session.lock().ok().and_then(|s| s.auto_lock)
+    }
+
+    /// Lock the vault if it has been idle for longer than the auto-lock timeout.
+    /// Returns `true` if this call locked it.
+    pub fn enforce_auto_lock(&self) -> bool {
+        Self::enforce_auto_lock_on(&self.derived_key, &self.session)
+    }
+
+    fn enforce_auto_lock_on(
+        derived_key: &Mutex<Option<MasterKey>>,
+        session: &Mutex<VaultSession>,
+    ) -> bool {
+        let expired = session.lock().map(|s| s.is_expired()).unwrap_or(false);
+        if !expired {
+            return false;
+        }
+        match derived_key.lock() {
+            Ok(mut guard) if guard.is_some() => {
+                *guard = None;
+                tracing::info!("Vault auto-locked after idle timeout");
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Background task that locks the vault while nobody touches it. Holds only weak
+    /// references, so it exits once the last manager clone is dropped.
+    fn spawn_auto_lock_watcher(&self) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            tracing::warn!("No async runtime for the vault auto-lock watcher; an idle vault locks on its next use");
+            return;
+        };
+        let derived_key: Weak<_> = Arc::downgrade(&self.derived_key);
+        let session: Weak<_> = Arc::downgrade(&self.session);
+        runtime.spawn(async move {
+            let mut ticker = tokio::time::interval(AUTO_LOCK_POLL);
+            loop {
+                ticker.tick().await;
+                let (Some(derived_key), Some(session)) = (derived_key.upgrade(), session.upgrade())
+                else {
+                    break;
+                };
+                Self::enforce_auto_lock_on(&derived_key, &session);
+            }
+        });
+    }
+
+    fn touch_session(&self) {
+        if let Ok(mut session) = self.session.lock() {
+            session.last_activity = Instant::now();
         }
     }
 
     #[allow(dead_code)]
     pub fn vault_is_initialized(&self) -> bool {
-        let dir = self.vault_dir();
-        dir.join("salt.bin").exists() && dir.join("vault_check.enc").exists()
+        self.vault_path.exists() || self.has_legacy_layout()
     }
 
     #[allow(dead_code)]
     pub fn vault_is_unlocked(&self) -> bool {
+        self.enforce_auto_lock();
         self.derived_key
             .lock()
             .map(|g| g.is_some())
@@ -67,28 +227,24 @@ impl ApiKeyManager {
             ));
         }
 
-        let dir = self.vault_dir();
-        fs::create_dir_all(dir)
+        fs::create_dir_all(self.vault_dir())
             .map_err(|e| VgaError::AuthVaultError(format!("Failed to create vault dir: {e}")))?;
 
-        let mut salt = [0u8; 16];
-        rand::rngs::OsRng.fill_bytes(&mut salt);
-        fs::write(dir.join("salt.bin"), &salt)
-            .map_err(|e| VgaError::AuthVaultError(format!("Failed to write salt: {e}")))?;
-
-        let key = Self::derive_key_from_password(password, &salt)?;
-        let check_plain = b"vas-vault-ok";
-        let check_encrypted = Self::encrypt_with_key(&key, check_plain)?;
-        fs::write(dir.join("vault_check.enc"), check_encrypted)
-            .map_err(|e| VgaError::AuthVaultError(format!("Failed to write vault check: {e}")))?;
+        let (file, key) = VaultFile::create(password)?;
+        {
+            let _io = self.lock_file()?;
+            file.save(&self.vault_path)?;
+        }
 
         if let Ok(mut guard) = self.derived_key.lock() {
             *guard = Some(key);
         }
+        self.touch_session();
 
         Ok(())
     }
 
+    /// Unlock the vault, migrating the legacy one-file-per-key layout on first use.
     #[allow(dead_code)]
     pub fn vault_unlock(&self, password: &str) -> Result<(), VgaError> {
         if password.trim().is_empty() {
@@ -100,55 +256,82 @@ impl ApiKeyManager {
             ));
         }
 
-        let dir = self.vault_dir();
-        let salt = fs::read(dir.join("salt.bin"))
-            .map_err(|e| VgaError::AuthVaultError(format!("Failed to read salt: {e}")))?;
-        if salt.len() != 16 {
-            return Err(VgaError::AuthVaultError("Invalid salt".to_string()));
-        }
-
-        let key = Self::derive_key_from_password(password, &salt)?;
-        let check_encrypted = fs::read(dir.join("vault_check.enc"))
-            .map_err(|e| VgaError::AuthVaultError(format!("Failed to read vault check: {e}")))?;
-        let check_plain = Self::decrypt_with_key(&key, &check_encrypted)?;
-        if check_plain != b"vas-vault-ok" {
-            return Err(VgaError::AuthVaultError("Invalid password".to_string()));
-        }
+        let key = {
+            let _io = self.lock_file()?;
+            if self.vault_path.exists() {
+                VaultFile::load(&self.vault_path)?.unlock(password)?
+            } else {
+                self.migrate_legacy_layout(password)?
+            }
+        };
 
         if let Ok(mut guard) = self.derived_key.lock() {
             *guard = Some(key);
         }
+        self.touch_session();
         Ok(())
     }
 
     pub fn vault_operation(&self, op: VaultOp) -> Result<VaultResult, VgaError> {
         match op {
             VaultOp::Store { provider, key } => {
-                self.require_unlocked()?;
-                let encrypted = self.encrypt_key(&key)?;
-                self.persist_to_disk(&provider, &encrypted)?;
+                self.modify_vault(|file, master| {
+                    file.put_secret(master, &provider, key.expose_secret().as_bytes())
+                })?;
                 Ok(VaultResult::Success)
             }
             VaultOp::Retrieve { provider } => {
-                self.require_unlocked()?;
-                let decrypted = self.decrypt_from_disk(&provider)?;
+                let decrypted = self.read_secret(&provider)?;
                 self.update_usage_stats(&provider);
                 Ok(VaultResult::Key(decrypted))
             }
             VaultOp::Delete { provider } => {
-                self.delete_from_disk(&provider)?;
+                self.modify_vault(|file, _| {
+                    if file.remove(&provider) {
+                        Ok(())
+                    } else {
+                        Err(VgaError::AuthVaultError(format!("No vault entry named {provider}")))
+                    }
+                })?;
+                self.key_pools.forget(&provider);
                 Ok(VaultResult::Success)
             }
             VaultOp::List => {
                 let keys = self.list_providers()?;
                 Ok(VaultResult::Providers(keys))
             }
+            VaultOp::ListEntries => {
+                let entries = match self.load_vault_file()? {
+                    Some(file) => file.entries.iter().map(|e| e.meta()).collect(),
+                    None => vec![],
+                };
+                Ok(VaultResult::Entries(entries))
+            }
+            VaultOp::SetEntryMeta { provider, provider_id, notes } => {
+                self.modify_vault(|file, _| {
+                    let entry = file.entry_mut(&provider).ok_or_else(|| {
+                        VgaError::AuthVaultError(format!("No vault entry named {provider}"))
+                    })?;
+                    entry.provider_id = provider_id;
+                    entry.notes = notes;
+                    Ok(())
+                })?;
+                Ok(VaultResult::Success)
+            }
+            VaultOp::AddPoolKey { pool, key } => {
+                let name = self.add_pool_key(&pool, &key)?;
+                Ok(VaultResult::Providers(vec![name]))
+            }
+            VaultOp::PoolStatus { pool } => Ok(VaultResult::Pool(self.pool_status(&pool)?)),
+            VaultOp::SetPoolStrategy { pool, strategy } => {
+                self.set_pool_strategy(&pool, strategy)?;
+                Ok(VaultResult::Success)
+            }
             VaultOp::GetProviders => {
-                let providers = get_predefined_providers();
-                Ok(VaultResult::ProviderConfigs(providers))
+                Ok(VaultResult::ProviderConfigs(self.providers()))
             }
             VaultOp::GetProviderConfig { provider } => {
-                if let Some(config) = get_provider_by_id(&provider) {
+                if let Some(config) = self.provider(&provider) {
                     Ok(VaultResult::ProviderConfig(config))
                 } else {
                     Err(VgaError::AuthVaultError(format!("Provider not found: {}", provider)))
@@ -162,14 +345,13 @@ impl ApiKeyManager {
     }
 
     fn set_default_provider(&self, provider: &str) -> Result<(), VgaError> {
-        let dir = self.vault_path.parent().unwrap();
-        let file_path = dir.join("default_provider.txt");
+        let file_path = self.vault_dir().join("default_provider.txt");
         fs::write(&file_path, provider.as_bytes())
             .map_err(|e| VgaError::AuthVaultError(format!("Failed to write default provider: {e}")))
     }
 
     #[allow(dead_code)]
-    pub fn get_decrypted_key(&self, provider: &str) -> Result<String, VgaError> {
+    pub fn get_decrypted_key(&self, provider: &str) -> Result<SecretString, VgaError> {
         self.vault_operation(VaultOp::Retrieve { provider: provider.to_string() })
             .and_then(|res| match res {
                 VaultResult::Key(key) => Ok(key),
@@ -179,15 +361,23 @@ impl ApiKeyManager {
 
     pub fn update_usage_stats(&self, provider: &str) {
         let mut stats = self.usage_stats.blocking_write();
-        let entry = stats.entry(provider.to_string()).or_insert(UsageStats {
-            tokens_used: 0,
-            requests_made: 0,
-            last_used: chrono::Utc::now(),
-        });
+        let entry = stats.entry(provider.to_string()).or_insert_with(UsageStats::new);
         entry.requests_made += 1;
         entry.last_used = chrono::Utc::now();
     }
 
+    /// Count a response cache lookup for a request that was eligible for caching.
+    pub async fn record_cache_lookup(&self, provider: &str, hit: bool) {
+        let mut stats = self.usage_stats.write().await;
+        let entry = stats.entry(provider.to_string()).or_insert_with(UsageStats::new);
+        if hit {
+            entry.cache_hits += 1;
+        } else {
+            entry.cache_misses += 1;
+        }
+        entry.last_used = chrono::Utc::now();
+    }
+
     pub async fn get_usage_entries(&self) -> Vec<VaultUsageEntry> {
         let stats = self.usage_stats.read().await;
         let mut out: Vec<VaultUsageEntry> = stats
@@ -196,6 +386,8 @@ impl ApiKeyManager {
                 provider: provider.clone(),
                 requests_made: s.requests_made,
                 last_used: s.last_used,
+                cache_hits: s.cache_hits,
+                cache_misses: s.cache_misses,
             })
             .collect();
         for entry in stats.values() {
@@ -214,25 +406,389 @@ impl ApiKeyManager {
         let _ = self.check_quota_availability("local");
     }
 
-    fn encrypt_key(&self, key: &str) -> Result<Vec<u8>, VgaError> {
-        let unlocked = self.get_unlocked_key()?;
-        Self::encrypt_with_key(&unlocked, key.as_bytes())
+    // ── Key pools ─────────────────────────────────────────────────────────────
+
+    /// Entry names in `pool`: the entry named after the pool first, then the
+    /// added members in creation order.
+    pub fn pool_members(&self, pool: &str) -> Result<Vec<String>, VgaError> {
+        let Some(file) = self.load_vault_file()? else {
+            return Ok(vec![]);
+        };
+        let mut members: Vec<_> = file
+            .entries
+            .iter()
+            .filter(|e| e.name != pool && e.pool.as_deref() == Some(pool))
+            .collect();
+        members.sort_by_key(|e| e.created_at);
+        Ok(file
+            .entry(pool)
+            .into_iter()
+            .chain(members)
+            .map(|e| e.name.clone())
+            .collect())
     }
 
-    fn decrypt_from_disk(&self, provider: &str) -> Result<String, VgaError> {
-        let file_path = self.vault_path.with_file_name(format!("{}.enc", provider));
-        let encrypted = fs::read(&file_path)
-            .map_err(|e| VgaError::AuthVaultError(format!("Failed to read key file: {}", e)))?;
+    /// Store another key for `pool`. Returns the generated entry name.
+    pub fn add_pool_key(&self, pool: &str, key: &SecretString) -> Result<String, VgaError> {
+        if key.expose_secret().trim().is_empty() {
+            return Err(VgaError::AuthVaultError("Key cannot be empty".to_string()));
+        }
+        let mut name = String::new();
+        self.modify_vault(|file, master| {
+            let provider_id = file.entry(pool).and_then(|e| e.provider_id.clone());
+            let mut n = 2;
+            name = format!("{pool}#{n}");
+            while file.entry(&name).is_some() {
+                n += 1;
+                name = format!("{pool}#{n}");
+            }
+            file.put_secret(master, &name, key.expose_secret().as_bytes())?;
+            if let Some(entry) = file.entry_mut(&name) {
+                entry.provider_id = provider_id;
+                entry.pool = Some(pool.to_string());
+            }
+            Ok(())
+        })?;
+        Ok(name)
+    }
 
-        let unlocked = self.get_unlocked_key()?;
-        let decrypted = Self::decrypt_with_key(&unlocked, &encrypted)?;
+    /// Hand out the next key of `pool`, skipping benched keys. A single-key
+    /// entity is a pool of one, so this works for every entity name.
+    pub fn acquire_key(&self, pool: &str) -> Result<KeyLease, VgaError> {
+        self.require_unlocked()?;
+        let members = self.pool_members(pool)?;
+        let entry = self.key_pools.select(pool, &members).map_err(|until| match until {
+            Some(until) => VgaError::ResourceLimit(format!(
+                "All {} keys in pool {pool} are benched until {}",
+                members.len(),
+                until.format("%H:%M:%S UTC")
+            )),
+            None => VgaError::AuthVaultError(format!("No keys stored for pool {pool}")),
+        })?;
+        let key = self.read_secret(&entry)?;
+        Ok(KeyLease { pool: pool.to_string(), entry, key })
+    }
 
-        String::from_utf8(decrypted)
+    /// Feed back the result of a request made with a leased key. Auth and
+    /// quota errors take the key out of rotation for a while.
+    pub fn report_key_outcome(&self, entry: &str, outcome: KeyOutcome) {
+        self.key_pools.report(entry, outcome);
+    }
+
+    pub fn unbench_key(&self, entry: &str) {
+        self.key_pools.unbench(entry);
+    }
+
+    pub fn pool_status(&self, pool: &str) -> Result<KeyPoolStatus, VgaError> {
+        let members = self.pool_members(pool)?;
+        Ok(KeyPoolStatus {
+            pool: pool.to_string(),
+            strategy: self.key_pools.strategy(pool),
+            members: self.key_pools.members_status(&members),
+        })
+    }
+
+    pub fn set_pool_strategy(&self, pool: &str, strategy: KeyPoolStrategy) -> Result<(), VgaError> {
+        self.key_pools.set_strategy(pool, strategy);
+        let json = serde_json::to_string_pretty(&self.key_pools.strategies())
+            .map_err(|e| VgaError::AuthVaultError(format!("Failed to serialize pool strategies: {e}")))?;
+        fs::write(self.vault_dir().join("key_pools.json"), json)
+            .map_err(|e| VgaError::AuthVaultError(format!("Failed to write pool strategies: {e}")))
+    }
+
+    // ── Export / import ───────────────────────────────────────────────────────
+
+    /// Export the named entries (and optionally their `AiEntity` records) as a
+    /// bundle encrypted under `passphrase`. Returns the bundle as JSON text.
+    pub fn export_bundle(
+        &self,
+        names: &[String],
+        entities: &[AiEntity],
+        passphrase: &str,
+    ) -> Result<String, VgaError> {
+        self.require_unlocked()?;
+        let master = self.get_unlocked_key()?;
+        let file = self
+            .load_vault_file()?
+            .ok_or_else(|| VgaError::AuthVaultError("Vault is not initialized".to_string()))?;
+        file.verify(&master)?;
+
+        let mut entries = Vec::with_capacity(names.len());
+        for name in names {
+            let entry = file
+                .entry(name)
+                .ok_or_else(|| VgaError::AuthVaultError(format!("No vault entry named {name}")))?;
+            let plain = file.read_secret(&master, name)?;
+            let secret = std::str::from_utf8(&plain)
+                .map(SecretString::from)
+                .map_err(|e| VgaError::AuthVaultError(format!("Invalid UTF-8: {}", e)))?;
+            entries.push(BundleEntry::from_vault_entry(entry, secret));
+        }
+
+        let payload = BundlePayload {
+            exported_at: Some(chrono::Utc::now()),
+            entries,
+            entities: entities.to_vec(),
+        };
+        vault_bundle::seal(&payload, passphrase)
+    }
+
+    /// Merge a bundle produced by `export_bundle` into this vault.
+    ///
+    /// Entities are not persisted here (they live with the GUI); they are
+    /// returned in the report, renamed to match any renamed entries.
+    pub fn import_bundle(
+        &self,
+        bundle: &str,
+        passphrase: &str,
+        policy: ImportConflictPolicy,
+    ) -> Result<VaultImportReport, VgaError> {
+        self.require_unlocked()?;
+        let payload = vault_bundle::open(bundle, passphrase)?;
+
+        let mut report = VaultImportReport::default();
+        self.modify_vault(|file, master| {
+            for incoming in &payload.entries {
+                let exists = file.entry(&incoming.name).is_some();
+                let target = match (exists, policy) {
+                    (false, _) => incoming.name.clone(),
+                    (true, ImportConflictPolicy::Skip) => {
+                        report.skipped.push(incoming.name.clone());
+                        continue;
+                    }
+                    (true, ImportConflictPolicy::Overwrite) => incoming.name.clone(),
+                    (true, ImportConflictPolicy::Rename) => {
+                        let renamed = Self::unused_entry_name(file, &incoming.name);
+                        report.renamed.push((incoming.name.clone(), renamed.clone()));
+                        renamed
+                    }
+                };
+
+                file.put_secret(master, &target, incoming.secret.expose_secret().as_bytes())?;
+                if let Some(entry) = file.entry_mut(&target) {
+                    entry.provider_id = incoming.provider_id.clone();
+                    entry.notes = incoming.notes.clone();
+                    entry.pool = incoming.pool.clone();
+                    if !exists || policy == ImportConflictPolicy::Rename {
+                        entry.created_at = incoming.created_at;
+                        entry.rotated_at = incoming.rotated_at;
+                    }
+                }
+
+                if exists && policy == ImportConflictPolicy::Overwrite {
+                    report.overwritten.push(target);
+                } else {
+                    report.imported.push(target);
+                }
+            }
+            Ok(())
+        })?;
+
+        report.entities = payload
+            .entities
+            .into_iter()
+            .filter(|entity| !report.skipped.contains(&entity.name))
+            .map(|mut entity| {
+                if let Some((_, renamed)) = report.renamed.iter().find(|(from, _)| *from == entity.name) {
+                    entity.name = renamed.clone();
+                }
+                entity
+            })
+            .collect();
+
+        Ok(report)
+    }
+
+    // ── Key validation ────────────────────────────────────────────────────────
+
+    /// Probe the provider behind `entity` with its stored key and record the
+    /// outcome in the entry metadata. Keyless providers (e.g. Ollama) are
+    /// probed without a key.
+    pub async fn validate_key(&self, entity: &AiEntity) -> Result<KeyValidationResult, VgaError> {
+        self.require_unlocked()?;
+        let provider = self.provider(&entity.provider);
+        let needs_key = provider.as_ref().is_none_or(|p| p.requires_api_key);
+
+        let key = match self.read_secret(&entity.name) {
+            Ok(key) => Some(key),
+            Err(_) if !needs_key => None,
+            Err(_) => {
+                return Ok(KeyValidationResult {
+                    entity: entity.name.clone(),
+                    status: KeyValidationStatus::MissingKey,
+                    checked_at: chrono::Utc::now(),
+                    endpoint: String::new(),
+                    http_status: None,
+                    detail: Some(format!("No vault entry named {}", entity.name)),
+                });
+            }
+        };
+
+        let result = key_validator::validate(&self.http, entity, provider.as_ref(), key.as_ref()).await;
+        if key.is_some() {
+            self.record_validation(&result)?;
+        }
+        Ok(result)
+    }
+
+    /// Validate every entity concurrently. Per-entity failures are reported in
+    /// the results; only a locked vault fails the whole call.
+    pub async fn validate_all(
+        &self,
+        entities: &[AiEntity],
+    ) -> Result<Vec<KeyValidationResult>, VgaError> {
+        self.require_unlocked()?;
+        let mut tasks = tokio::task::JoinSet::new();
+        for (idx, entity) in entities.iter().cloned().enumerate() {
+            let manager = self.clone();
+            tasks.spawn(async move { (idx, manager.validate_key(&entity).await) });
+        }
+
+        let mut results = Vec::with_capacity(entities.len());
+        while let Some(joined) = tasks.join_next().await {
+            let (idx, result) =
+                joined.map_err(|e| VgaError::AuthVaultError(format!("Validation task failed: {e}")))?;
+            results.push((idx, result?));
+        }
+        results.sort_by_key(|(idx, _)| *idx);
+        Ok(results.into_iter().map(|(_, r)| r).collect())
+    }
+
+    // ── Model discovery ───────────────────────────────────────────────────────
+
+    /// Models the entity's endpoint offers, merged with the catalog. Answers
+    /// are cached for `MODEL_CACHE_TTL` unless `refresh` is set. A failed query
+    /// still returns the catalog models, with `error` set.
+    pub async fn list_remote_models(
+        &self,
+        entity: &AiEntity,
+        refresh: bool,
+    ) -> Result<ModelListing, VgaError> {
+        let provider = self.provider(&entity.provider);
+        let catalog = provider.as_ref().map(|p| p.models.clone()).unwrap_or_default();
+        let needs_key = provider.as_ref().is_none_or(|p| p.requires_api_key);
+        let catalog_only = |endpoint: String, error: String| ModelListing {
+            target: entity.name.clone(),
+            endpoint,
+            fetched_at: chrono::Utc::now(),
+            from_cache: false,
+            models: model_discovery::merge_with_catalog(&[], &catalog),
+            error: Some(error),
+        };
+
+        let key = match self.read_secret(&entity.name) {
+            Ok(key) => Some(key),
+            Err(_) if !needs_key => None,
+            Err(e) => return Ok(catalog_only(String::new(), format!("{e:?}"))),
+        };
+        let probe = match ValidationProbe::for_entity(entity, provider.as_ref(), key.as_ref()) {
+            Ok(probe) => probe,
+            Err(e) => return Ok(catalog_only(String::new(), e)),
+        };
+
+        let (cached, from_cache) = match self.model_cache.get(&entity.name, &probe.url) {
+            Some(cached) if !refresh => (cached, true),
+            _ => match model_discovery::fetch_remote_models(&self.http, &probe).await {
+                Ok(remote) => (self.model_cache.put(&entity.name, &probe.url, remote), false),
+                Err(e) => return Ok(catalog_only(probe.url, e)),
+            },
+        };
+
+        Ok(ModelListing {
+            target: entity.name.clone(),
+            endpoint: cached.endpoint,
+            fetched_at: cached.fetched_at,
+            from_cache,
+            models: model_discovery::merge_with_catalog(&cached.remote, &catalog),
+            error: None,
+        })
+    }
+
+    /// Like `list_remote_models` for a bare provider id, using the key stored
+    /// under that id (if any) and the catalog endpoint.
+    pub async fn list_provider_models(
+        &self,
+        provider_id: &str,
+        refresh: bool,
+    ) -> Result<ModelListing, VgaError> {
+        let entity = AiEntity {
+            name: provider_id.to_string(),
+            provider: provider_id.to_string(),
+            model: String::new(),
+            note: String::new(),
+            custom_base_url: None,
+            key_header: None,
+            key_prefix: None,
+        };
+        self.list_remote_models(&entity, refresh).await
+    }
+
+    fn record_validation(&self, result: &KeyValidationResult) -> Result<(), VgaError> {
+        self.modify_vault(|file, _| {
+            if let Some(entry) = file.entry_mut(&result.entity) {
+                entry.last_validated_at = Some(result.checked_at);
+                entry.last_validation = Some(result.status);
+            }
+            Ok(())
+        })
+    }
+
+    fn unused_entry_name(file: &VaultFile, name: &str) -> String {
+        let mut candidate = format!("{name}-imported");
+        let mut n = 2;
+        while file.entry(&candidate).is_some() {
+            candidate = format!("{name}-imported-{n}");
+            n += 1;
+        }
+        candidate
+    }
+
+    fn read_secret(&self, provider: &str) -> Result<SecretString, VgaError> {
+        self.require_unlocked()?;
+        let master = self.get_unlocked_key()?;
+        let file = self
+            .load_vault_file()?
+            .ok_or_else(|| VgaError::AuthVaultError("Vault is not initialized".to_string()))?;
+        file.verify(&master)?;
+        let decrypted = file.read_secret(&master, provider)?;
+
+        std::str::from_utf8(&decrypted)
+            .map(SecretString::from)
             .map_err(|e| VgaError::AuthVaultError(format!("Invalid UTF-8: {}", e)))
     }
 
-    #[allow(dead_code)]
-    fn vault_dir(&self) -> &std::path::Path {
+    /// Load, verify, mutate, re-seal and save the vault under the file lock.
+    fn modify_vault<F>(&self, f: F) -> Result<(), VgaError>
+    where
+        F: FnOnce(&mut VaultFile, &[u8; 32]) -> Result<(), VgaError>,
+    {
+        self.require_unlocked()?;
+        let master = self.get_unlocked_key()?;
+        let _io = self.lock_file()?;
+        let mut file = VaultFile::load(&self.vault_path)?;
+        file.verify(&master)?;
+        f(&mut file, &master)?;
+        file.seal(&master)?;
+        file.save(&self.vault_path)
+    }
+
+    fn load_vault_file(&self) -> Result<Option<VaultFile>, VgaError> {
+        let _io = self.lock_file()?;
+        if !self.vault_path.exists() {
+            return Ok(None);
+        }
+        VaultFile::load(&self.vault_path).map(Some)
+    }
+
+    fn lock_file(&self) -> Result<std::sync::MutexGuard<'_, ()>, VgaError> {
+        self.file_lock
+            .lock()
+            .map_err(|_| VgaError::AuthVaultError("Vault file mutex poisoned".to_string()))
+    }
+
+    /// Directory holding the vault file and its side files (`key_pools.json`,
+    /// `entities.json`, …).
+    pub fn vault_dir(&self) -> &Path {
         self.vault_path.parent().unwrap()
     }
 
@@ -246,86 +802,99 @@ impl ApiKeyManager {
         }
     }
 
-    fn get_unlocked_key(&self) -> Result<[u8; 32], VgaError> {
+    /// Copy of the master key for one operation; counts as vault activity.
+    fn get_unlocked_key(&self) -> Result<MasterKey, VgaError> {
+        self.enforce_auto_lock();
         let guard = self
             .derived_key
             .lock()
             .map_err(|_| VgaError::AuthVaultError("Vault key mutex poisoned".to_string()))?;
-        guard
-            .ok_or_else(|| VgaError::AuthVaultError("Vault is locked".to_string()))
+        let key = guard
+            .clone()
+            .ok_or_else(|| VgaError::AuthVaultError("Vault is locked".to_string()))?;
+        drop(guard);
+        self.touch_session();
+        Ok(key)
     }
 
-    #[allow(dead_code)]
-    fn derive_key_from_password(password: &str, salt: &[u8]) -> Result<[u8; 32], VgaError> {
-        let mut out = [0u8; 32];
-        Argon2::default()
-            .hash_password_into(password.as_bytes(), salt, &mut out)
-            .map_err(|e| VgaError::AuthVaultError(format!("KDF failed: {e}")))?;
-        Ok(out)
+    fn list_providers(&self) -> Result<Vec<String>, VgaError> {
+        Ok(match self.load_vault_file()? {
+            Some(file) => file.entries.into_iter().map(|e| e.name).collect(),
+            None => vec![],
+        })
     }
 
-    fn encrypt_with_key(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>, VgaError> {
-        let cipher = Aes256Gcm::new_from_slice(key)
-            .map_err(|e| VgaError::AuthVaultError(format!("Invalid key: {e}")))?;
+    // ── Legacy layout (`salt.bin`, `vault_check.enc`, `<name>.enc`) ───────────
 
-        let mut nonce_bytes = [0u8; 12];
-        rand::rngs::OsRng.fill_bytes(&mut nonce_bytes);
-        let nonce = Nonce::from_slice(&nonce_bytes);
-
-        let ciphertext = cipher
-            .encrypt(nonce, plaintext)
-            .map_err(|e| VgaError::AuthVaultError(e.to_string()))?;
-
-        let mut out = Vec::with_capacity(nonce_bytes.len() + ciphertext.len());
-        out.extend_from_slice(&nonce_bytes);
-        out.extend_from_slice(&ciphertext);
-        Ok(out)
+    fn has_legacy_layout(&self) -> bool {
+        let dir = self.vault_dir();
+        dir.join("salt.bin").exists() && dir.join("vault_check.enc").exists()
     }
 
-    fn decrypt_with_key(key: &[u8; 32], payload: &[u8]) -> Result<Vec<u8>, VgaError> {
-        if payload.len() < 12 {
-            return Err(VgaError::AuthVaultError("Encrypted payload too short".to_string()));
+    /// Re-encrypt every legacy `<name>.enc` file into `vault.json` and move the
+    /// old files into a `legacy-<timestamp>/` backup directory.
+    fn migrate_legacy_layout(&self, password: &str) -> Result<MasterKey, VgaError> {
+        let dir = self.vault_dir();
+        let salt = fs::read(dir.join("salt.bin"))
+            .map_err(|e| VgaError::AuthVaultError(format!("Failed to read salt: {e}")))?;
+        if salt.len() != 16 {
+            return Err(VgaError::AuthVaultError("Invalid salt".to_string()));
         }
-        let (nonce_bytes, ciphertext) = payload.split_at(12);
-
-        let cipher = Aes256Gcm::new_from_slice(key)
-            .map_err(|e| VgaError::AuthVaultError(format!("Invalid key: {e}")))?;
-        let nonce = Nonce::from_slice(nonce_bytes);
-        cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|e| VgaError::AuthVaultError(format!("Decryption failed: {e}")))
-    }
 
-    fn persist_to_disk(&self, provider: &str, encrypted: &[u8]) -> Result<(), VgaError> {
-        let file_path = self.vault_path.with_file_name(format!("{}.enc", provider));
-        fs::write(&file_path, encrypted)
-            .map_err(|e| VgaError::AuthVaultError(format!("Failed to write key file: {}", e)))
-    }
-
-    fn delete_from_disk(&self, provider: &str) -> Result<(), VgaError> {
-        let file_path = self.vault_path.with_file_name(format!("{}.enc", provider));
-        fs::remove_file(&file_path)
-            .map_err(|e| VgaError::AuthVaultError(format!("Failed to delete key file: {}", e)))
-    }
-
-    fn list_providers(&self) -> Result<Vec<String>, VgaError> {
-        let dir = self.vault_path.parent().unwrap();
-        if !dir.exists() {
-            return Ok(vec![]);
+        // The legacy layout always used `Argon2::default()`, which the default params mirror.
+        let kdf = KdfParams::default();
+        let key = kdf.derive_key(password, &salt)?;
+        let check_encrypted = fs::read(dir.join("vault_check.enc"))
+            .map_err(|e| VgaError::AuthVaultError(format!("Failed to read vault check: {e}")))?;
+        match vault_file::decrypt(&key, &check_encrypted, b"") {
+            Ok(plain) if plain == b"vas-vault-ok" => {}
+            _ => return Err(VgaError::AuthVaultError("Invalid password".to_string())),
         }
+
+        let mut file = VaultFile::with_key(kdf, &salt, &key)?;
+        let mut legacy_files = vec![dir.join("salt.bin"), dir.join("vault_check.enc")];
         let entries = fs::read_dir(dir)
             .map_err(|e| VgaError::AuthVaultError(format!("Failed to read vault directory: {}", e)))?;
-        let mut providers = vec![];
         for entry in entries {
-            let entry = entry.map_err(|e| VgaError::AuthVaultError(e.to_string()))?;
-            if let Some(ext) = entry.path().extension() {
-                if ext == "enc" {
-                    if let Some(stem) = entry.path().file_stem() {
-                        providers.push(stem.to_string_lossy().to_string());
-                    }
+            let path = entry.map_err(|e| VgaError::AuthVaultError(e.to_string()))?.path();
+            if path.extension() != Some(std::ffi::OsStr::new("enc")) {
+                continue;
+            }
+            let name = match path.file_stem() {
+                Some(stem) if stem != "vault_check" => stem.to_string_lossy().to_string(),
+                _ => continue,
+            };
+            let payload = fs::read(&path)
+                .map_err(|e| VgaError::AuthVaultError(format!("Failed to read key file: {}", e)))?;
+            let plain = zeroize::Zeroizing::new(vault_file::decrypt(&key, &payload, b"")?);
+            file.put_secret(&key, &name, &plain)?;
+            if let (Some(entry), Ok(modified)) = (
+                file.entry_mut(&name),
+                fs::metadata(&path).and_then(|m| m.modified()),
+            ) {
+                entry.created_at = modified.into();
+            }
+            legacy_files.push(path);
+        }
+        file.seal(&key)?;
+        file.save(&self.vault_path)?;
+
+        let backup_dir = dir.join(format!("legacy-{}", chrono::Utc::now().format("%Y%m%d%H%M%S")));
+        fs::create_dir_all(&backup_dir)
+            .map_err(|e| VgaError::AuthVaultError(format!("Failed to create backup dir: {e}")))?;
+        for path in &legacy_files {
+            if let Some(name) = path.file_name() {
+                if let Err(e) = fs::rename(path, backup_dir.join(name)) {
+                    tracing::warn!("Failed to move legacy vault file {}: {e}", path.display());
                 }
             }
         }
-        Ok(providers)
+        tracing::info!(
+            "Migrated {} legacy vault entries into {}",
+            file.entries.len(),
+            self.vault_path.display()
+        );
+
+        Ok(key)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,514 @@
+//! Multi-turn conversations with history, persistence and export.
+//!
+//! A [`ChatSession`] keeps the full transcript and builds each request from
+//! the system prompt, a running summary of folded-away turns and as many
+//! recent messages as fit `context_tokens`. Token counts are estimated at
+//! four characters per token, which errs on the safe side for most models.
+//!
+//! Sessions are sent either straight to a local Ollama model or to an entity
+//! (group) through the [`FailoverRouter`], and are stored as
+//! `<dir>/<id>.json`. The LAN share crate keeps a copy without the routing
+//! (`ollama_client/session.rs`); keep the two in step.
+
+use crate::backend::failover::{FailoverReply, FailoverRouter};
+use crate::backend::llm_backend::LlmRequest;
+use crate::backend::ollama_client::{ChatMessage, ChatOptions, ChatRequest, OllamaManager};
+use crate::shared::models::VgaError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Context budget when none is set; fits the default window of most local models.
+pub const DEFAULT_CONTEXT_TOKENS: usize = 4096;
+/// Recent messages [`ChatSession::summarize`] leaves untouched.
+pub const DEFAULT_KEEP_RECENT: usize = 6;
+/// Directory under the vault that the Tauri commands keep sessions in.
+pub const CHAT_SESSIONS_DIR: &str = "chat_sessions";
+
+/// Where a session's requests go.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SessionTarget {
+    /// A model on the local Ollama server.
+    Ollama { model: String },
+    /// An entity or entity group, with failover.
+    Entity { target: String },
+}
+
+impl SessionTarget {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Ollama { model } => model,
+            Self::Entity { target } => target,
+        }
+    }
+}
+
+/// One transcript entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionMessage {
+    #[serde(flatten)]
+    pub message: ChatMessage,
+    /// Who produced it when that is not obvious from the role: the serving
+    /// entity, or the peer in a LAN group chat.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatSession {
+    pub id: String,
+    pub title: String,
+    pub target: SessionTarget,
+    pub system: Option<String>,
+    pub options: Option<ChatOptions>,
+    pub messages: Vec<SessionMessage>,
+    /// Summary of the turns before `summarized`; sent after the system prompt.
+    #[serde(default)]
+    pub summary: Option<String>,
+    /// Messages before this index are covered by `summary` and no longer sent.
+    #[serde(default)]
+    pub summarized: usize,
+    pub context_tokens: usize,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl ChatSession {
+    pub fn new(target: SessionTarget) -> Self {
+        let now = Utc::now();
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            title: target.name().to_string(),
+            target,
+            system: None,
+            options: None,
+            messages: Vec::new(),
+            summary: None,
+            summarized: 0,
+            context_tokens: DEFAULT_CONTEXT_TOKENS,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn ollama(model: impl Into<String>) -> Self {
+        Self::new(SessionTarget::Ollama { model: model.into() })
+    }
+
+    pub fn entity(target: impl Into<String>) -> Self {
+        Self::new(SessionTarget::Entity { target: target.into() })
+    }
+
+    pub fn with_system(mut self, system: impl Into<String>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    pub fn with_options(mut self, options: ChatOptions) -> Self {
+        self.options = Some(options);
+        self
+    }
+
+    pub fn with_context_tokens(mut self, tokens: usize) -> Self {
+        self.context_tokens = tokens;
+        self
+    }
+
+    pub fn push(&mut self, message: ChatMessage, author: Option<String>) {
+        self.messages.push(SessionMessage { message, author, at: Utc::now() });
+        self.updated_at = Utc::now();
+    }
+
+    pub fn push_user(&mut self, content: impl Into<String>) {
+        self.push(ChatMessage::new("user", content), None);
+    }
+
+    pub fn push_assistant(&mut self, content: impl Into<String>, author: Option<String>) {
+        self.push(ChatMessage::new("assistant", content), author);
+    }
+
+    /// Forget every turn, keeping the target, system prompt and options.
+    pub fn clear(&mut self) {
+        self.messages.clear();
+        self.summary = None;
+        self.summarized = 0;
+        self.updated_at = Utc::now();
+    }
+
+    /// Messages to send: system prompt, summary, then the newest messages
+    /// that fit `context_tokens`. The newest message is always included, and
+    /// the history never starts with a tool result whose call was cut off.
+    pub fn context(&self) -> Vec<ChatMessage> {
+        let mut head = Vec::new();
+        if let Some(system) = &self.system {
+            head.push(ChatMessage::new("system", system.as_str()));
+        }
+        if let Some(summary) = &self.summary {
+            head.push(ChatMessage::new("system", format!("Summary of the earlier conversation:\n{summary}")));
+        }
+        let mut budget = self.context_tokens.saturating_sub(head.iter().map(estimate_tokens).sum());
+
+        let history = &self.messages[self.summarized.min(self.messages.len())..];
+        let mut start = history.len();
+        for (idx, entry) in history.iter().enumerate().rev() {
+            let cost = estimate_tokens(&entry.message);
+            if cost > budget && start < history.len() {
+                break;
+            }
+            budget = budget.saturating_sub(cost);
+            start = idx;
+        }
+        while start + 1 < history.len() && history[start].message.role == "tool" {
+            start += 1;
+        }
+        head.extend(history[start..].iter().map(|e| e.message.clone()));
+        head
+    }
+
+    pub fn chat_request(&self, model: &str) -> ChatRequest {
+        ChatRequest {
+            model: model.to_string(),
+            messages: self.context(),
+            stream: Some(false),
+            format: None,
+            options: self.options.clone(),
+            tools: None,
+        }
+    }
+
+    pub fn llm_request(&self) -> LlmRequest {
+        LlmRequest {
+            messages: self.context(),
+            options: self.options.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Add `text` as the user's turn, ask the target and record the answer.
+    /// On failure the user's turn is taken back so it can be retried.
+    pub async fn send(
+        &mut self,
+        ollama: &OllamaManager,
+        router: Option<&FailoverRouter>,
+        text: &str,
+    ) -> Result<String, String> {
+        match self.target.clone() {
+            SessionTarget::Ollama { model } => self.send_ollama(ollama, &model, text).await,
+            SessionTarget::Entity { target } => {
+                let router = router.ok_or_else(|| "No LLM router attached".to_string())?;
+                self.send_via(router, &target, text)
+                    .await
+                    .map(|served| served.reply.content)
+                    .map_err(|e| format!("{e:?}"))
+            }
+        }
+    }
+
+    pub async fn send_ollama(&mut self, ollama: &OllamaManager, model: &str, text: &str) -> Result<String, String> {
+        self.push_user(text);
+        match ollama.chat(self.chat_request(model)).await {
+            Ok(response) => {
+                let content = response.message.content.clone();
+                self.push(response.message, None);
+                Ok(content)
+            }
+            Err(e) => {
+                self.messages.pop();
+                Err(e)
+            }
+        }
+    }
+
+    pub async fn send_via(
+        &mut self,
+        router: &FailoverRouter,
+        target: &str,
+        text: &str,
+    ) -> Result<FailoverReply, VgaError> {
+        self.push_user(text);
+        match router.chat(target, &self.llm_request()).await {
+            Ok(served) => {
+                self.push_assistant(served.reply.content.clone(), Some(served.served_by.clone()));
+                Ok(served)
+            }
+            Err(e) => {
+                self.messages.pop();
+                Err(e)
+            }
+        }
+    }
+
+    /// The prompt asking for a summary of everything but the last
+    /// `keep_recent` messages, or `None` if there is nothing new to fold.
+    pub fn summary_prompt(&self, keep_recent: usize) -> Option<String> {
+        let end = self.messages.len().saturating_sub(keep_recent);
+        if end <= self.summarized {
+            return None;
+        }
+        let mut prompt = String::from(
+            "Summarize the conversation below in a few sentences. Keep names, decisions, \
+             open questions and facts the assistant will need later.\n\n",
+        );
+        if let Some(summary) = &self.summary {
+            let _ = writeln!(prompt, "Earlier summary:\n{summary}\n");
+        }
+        for entry in &self.messages[self.summarized..end] {
+            let _ = writeln!(prompt, "{}: {}", entry.message.role, entry.message.content);
+        }
+        Some(prompt)
+    }
+
+    /// Replace everything but the last `keep_recent` messages with `summary`.
+    /// The transcript itself is kept for export.
+    pub fn apply_summary(&mut self, keep_recent: usize, summary: impl Into<String>) {
+        self.summarized = self.messages.len().saturating_sub(keep_recent).max(self.summarized);
+        self.summary = Some(summary.into());
+        self.updated_at = Utc::now();
+    }
+
+    /// Have the session's own target summarize older turns when the history
+    /// no longer fits `context_tokens`. Returns whether a summary was made.
+    pub async fn summarize(
+        &mut self,
+        ollama: &OllamaManager,
+        router: Option<&FailoverRouter>,
+        keep_recent: usize,
+    ) -> Result<bool, String> {
+        if self.fits() {
+            return Ok(false);
+        }
+        let Some(prompt) = self.summary_prompt(keep_recent) else {
+            return Ok(false);
+        };
+        let summary = match &self.target {
+            SessionTarget::Ollama { model } => ollama.chat_simple(model, &prompt).await?,
+            SessionTarget::Entity { target } => {
+                let router = router.ok_or_else(|| "No LLM router attached".to_string())?;
+                router
+                    .chat(target, &LlmRequest::prompt(None, &prompt))
+                    .await
+                    .map_err(|e| format!("{e:?}"))?
+                    .reply
+                    .content
+            }
+        };
+        self.apply_summary(keep_recent, summary.trim());
+        Ok(true)
+    }
+
+    /// Whether every unsummarized message fits the context budget.
+    pub fn fits(&self) -> bool {
+        let pending = self.messages.len() - self.summarized.min(self.messages.len());
+        let sent = self.context().len() - usize::from(self.system.is_some()) - usize::from(self.summary.is_some());
+        sent >= pending
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Failed to serialize session: {e}"))
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# {}\n\n", self.title);
+        let _ = writeln!(out, "- Target: `{}`", self.target.name());
+        let _ = writeln!(out, "- Started: {}", self.created_at.format("%Y-%m-%d %H:%M UTC"));
+        if let Some(system) = &self.system {
+            let _ = write!(out, "\n## System\n\n{system}\n");
+        }
+        for entry in &self.messages {
+            let role = match entry.message.role.as_str() {
+                "user" => "User",
+                "assistant" => "Assistant",
+                "system" => "System",
+                "tool" => "Tool",
+                other => other,
+            };
+            let _ = write!(out, "\n## {role}");
+            if let Some(author) = entry.author.as_deref().or(entry.message.tool_name.as_deref()) {
+                let _ = write!(out, " ({author})");
+            }
+            let _ = write!(out, " · {}\n\n{}\n", entry.at.format("%H:%M:%S"), entry.message.content);
+        }
+        out
+    }
+
+    pub fn path_in(&self, dir: &Path) -> PathBuf {
+        dir.join(format!("{}.json", self.id))
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<PathBuf, String> {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+        let path = self.path_in(dir);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, self.to_json()?)
+            .and_then(|_| fs::rename(&tmp, &path))
+            .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+        Ok(path)
+    }
+
+    pub fn load(dir: &Path, id: &str) -> Result<Self, String> {
+        if id.contains(['/', '\\']) || id.starts_with('.') {
+            return Err(format!("Invalid session id {id}"));
+        }
+        let path = dir.join(format!("{id}.json"));
+        let text = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        serde_json::from_str(&text).map_err(|e| format!("Failed to parse {}: {e}", path.display()))
+    }
+
+    /// Every session stored in `dir`, most recently updated first.
+    pub fn list(dir: &Path) -> Vec<Self> {
+        let Ok(files) = fs::read_dir(dir) else { return Vec::new() };
+        let mut sessions: Vec<Self> = files
+            .flatten()
+            .filter(|f| f.path().extension().and_then(|e| e.to_str()) == Some("json"))
+            .filter_map(|f| {
+                let text = fs::read_to_string(f.path()).ok()?;
+                serde_json::from_str(&text)
+                    .map_err(|e| tracing::warn!("Ignoring unreadable session {}: {e}", f.path().display()))
+                    .ok()
+            })
+            .collect();
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+        sessions
+    }
+
+    pub fn delete(dir: &Path, id: &str) -> Result<(), String> {
+        if id.contains(['/', '\\']) || id.starts_with('.') {
+            return Err(format!("Invalid session id {id}"));
+        }
+        fs::remove_file(dir.join(format!("{id}.json"))).map_err(|e| format!("Failed to delete session {id}: {e}"))
+    }
+}
+
+/// Rough token count: four characters per token plus per-message framing.
+pub fn estimate_tokens(message: &ChatMessage) -> usize {
+    message.content.chars().count().div_ceil(4) + 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 36 characters: 9 tokens plus 4 of framing.
+    fn turn(tag: char) -> String {
+        std::iter::repeat_n(tag, 36).collect()
+    }
+
+    /// Room for the system prompt and two turns.
+    fn session() -> ChatSession {
+        let system = ChatMessage::new("system", "be brief");
+        ChatSession::ollama("llama3")
+            .with_system("be brief")
+            .with_context_tokens(estimate_tokens(&system) + 2 * 13)
+    }
+
+    fn contents(messages: &[ChatMessage]) -> Vec<&str> {
+        messages.iter().map(|m| m.content.as_str()).collect()
+    }
+
+    #[test]
+    fn context_keeps_the_newest_turns_that_fit() {
+        let mut session = session();
+        for tag in ['a', 'b', 'c', 'd'] {
+            session.push_user(turn(tag));
+        }
+        let context = session.context();
+        assert_eq!(contents(&context), ["be brief", &turn('c'), &turn('d')]);
+        assert!(!session.fits());
+
+        session.messages.truncate(2);
+        assert!(session.fits());
+        assert_eq!(session.context().len(), 3);
+    }
+
+    #[test]
+    fn the_newest_turn_is_sent_even_when_it_is_too_long() {
+        let mut session = session();
+        session.push_user(turn('a'));
+        session.push_user("x".repeat(1000));
+        assert_eq!(contents(&session.context()), ["be brief", "x".repeat(1000).as_str()]);
+        assert!(!session.fits());
+    }
+
+    #[test]
+    fn context_never_starts_with_an_orphaned_tool_result() {
+        let mut session = session();
+        session.push_user(turn('a'));
+        session.push(ChatMessage::new("assistant", turn('b')), None);
+        session.push(ChatMessage::new("tool", turn('c')), None);
+        session.push_assistant(turn('d'), None);
+        // The window fits `c` and `d`, but `c` answers a call that was cut.
+        let context = session.context();
+        assert_eq!(contents(&context), ["be brief", &turn('d')]);
+
+        let mut only_tool = ChatSession::ollama("llama3");
+        only_tool.push(ChatMessage::new("tool", "42"), None);
+        assert_eq!(contents(&only_tool.context()), ["42"]);
+    }
+
+    #[test]
+    fn a_summary_replaces_the_turns_it_covers() {
+        let mut session = session();
+        for tag in ['a', 'b', 'c', 'd'] {
+            session.push_user(turn(tag));
+        }
+        assert!(session.summary_prompt(4).is_none());
+        let prompt = session.summary_prompt(2).expect("prompt");
+        assert!(prompt.contains(&turn('a')) && prompt.contains(&turn('b')));
+        assert!(!prompt.contains(&turn('c')));
+
+        session.context_tokens = 1000;
+        session.apply_summary(2, "they said a and b");
+        assert_eq!(session.summarized, 2);
+        let context = session.context();
+        assert_eq!(context.len(), 4);
+        assert_eq!(context[1].role, "system");
+        assert!(context[1].content.ends_with("they said a and b"));
+        assert_eq!(contents(&context[2..]), [&turn('c'), &turn('d')]);
+        assert!(session.summary_prompt(2).is_none());
+
+        // The next summary builds on the last and only covers what is new.
+        session.push_user(turn('e'));
+        let prompt = session.summary_prompt(2).expect("prompt");
+        assert!(prompt.contains("Earlier summary:\nthey said a and b"));
+        assert!(prompt.contains(&turn('c')) && !prompt.contains(&turn('a')));
+        session.apply_summary(2, "a to c");
+        assert_eq!(session.summarized, 3);
+        // Never unfolds turns already summarized.
+        session.apply_summary(10, "same");
+        assert_eq!(session.summarized, 3);
+
+        assert_eq!(session.messages.len(), 5, "the transcript is kept for export");
+        session.clear();
+        assert!(session.summary.is_none() && session.summarized == 0);
+    }
+
+    #[test]
+    fn sessions_survive_a_save_and_load() {
+        let dir = std::env::temp_dir().join(format!("vas-chat-session-{}", uuid::Uuid::new_v4()));
+        let mut session = session().with_title("Plans");
+        session.push_user(turn('a'));
+        session.push_assistant(turn('b'), Some("gpt4-main".to_string()));
+        session.apply_summary(1, "a was asked");
+
+        let path = session.save(&dir).expect("saved");
+        assert_eq!(path, session.path_in(&dir));
+        let loaded = ChatSession::load(&dir, &session.id).expect("loaded");
+        assert_eq!(loaded.to_json(), session.to_json());
+        assert_eq!(ChatSession::list(&dir).len(), 1);
+
+        assert!(ChatSession::load(&dir, "../secrets").is_err());
+        assert!(ChatSession::load(&dir, "missing").is_err());
+        ChatSession::delete(&dir, &session.id).expect("deleted");
+        assert!(ChatSession::list(&dir).is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,745 @@
+//! Fallback chains and circuit breakers for AI entities.
+//!
+//! A request target is either an entity group from `<vault>/entity_groups.json`
+//! or a single entity from `<vault>/entities.json`. Entities are tried in chain
+//! order; retryable errors move on to the next one and a non-retryable error
+//! (the request itself was refused) stops the chain. Each entity has a circuit
+//! breaker: after [`CIRCUIT_FAILURE_THRESHOLD`] consecutive failures it is
+//! skipped until its cool-down expires, then a single trial request decides
+//! whether it is closed again or re-opened with a longer cool-down.
+//!
+//! Streamed requests fail over the same way until the first delta has been
+//! forwarded; an error after that ends the request, since the caller has
+//! already seen part of one entity's answer.
+//!
+//! Breaker state lives in memory and resets on restart.
+//!
+//! Cacheable requests (see [`ResponseCache::is_cacheable`]) are looked up per
+//! entity before it is called; a hit is returned without touching the
+//! provider, its key pool or its breaker.
+
+use crate::backend::api_manager::ApiKeyManager;
+use crate::backend::llm_backend::{self, LlmError, LlmReply, LlmRequest, LlmStreamEvent};
+use crate::backend::response_cache::{
+    ResponseCache, DEFAULT_CACHE_MAX_BYTES, DEFAULT_CACHE_TTL, RESPONSE_CACHE_DIR,
+};
+use crate::shared::models::{
+    AiEntity, CircuitState, CircuitStatus, EntityGroup, FailoverAttempt, KeyOutcome, LlmErrorKind,
+    VgaError,
+};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+pub const ENTITIES_FILE: &str = "entities.json";
+pub const ENTITY_GROUPS_FILE: &str = "entity_groups.json";
+
+/// Consecutive failures that open an entity's circuit.
+pub const CIRCUIT_FAILURE_THRESHOLD: u32 = 3;
+/// First cool-down; doubles each time the circuit re-opens without a success in between.
+pub const CIRCUIT_COOL_DOWN: Duration = Duration::from_secs(60);
+pub const CIRCUIT_COOL_DOWN_MAX: Duration = Duration::from_secs(15 * 60);
+
+// ─── Circuit breaker ──────────────────────────────────────────────────────────
+
+#[derive(Debug, Default)]
+pub struct CircuitBreaker {
+    circuits: Mutex<HashMap<String, Circuit>>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Circuit {
+    consecutive_failures: u32,
+    /// Times the circuit opened since the last success; drives the cool-down.
+    trips: u32,
+    open_until: Option<(Instant, DateTime<Utc>)>,
+    /// A half-open trial request is running; others keep skipping the entity.
+    trial_in_flight: bool,
+    last_error: Option<String>,
+    last_success: Option<DateTime<Utc>>,
+}
+
+impl Circuit {
+    fn state(&self, now: Instant) -> CircuitState {
+        match self.open_until {
+            Some((until, _)) if until > now => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+            None => CircuitState::Closed,
+        }
+    }
+}
+
+/// Leave to send one request to an entity, from [`CircuitBreaker::allow`].
+/// Report how it went with [`success`](Self::success),
+/// [`failure`](Self::failure) or [`release`](Self::release); dropped without
+/// any of them (the call was cancelled) it ends the half-open trial it holds,
+/// so the entity isn't skipped forever.
+#[must_use]
+pub struct CircuitPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    entity: String,
+    trial: bool,
+    reported: bool,
+}
+
+impl CircuitPermit<'_> {
+    pub fn success(mut self) {
+        self.reported = true;
+        self.breaker.record_success(&self.entity);
+    }
+
+    /// A failure that reflects on the entity's health.
+    pub fn failure(mut self, detail: &str) {
+        self.reported = true;
+        self.breaker.record_failure(&self.entity, detail);
+    }
+
+    /// An outcome that says nothing about the entity (e.g. a refused request).
+    pub fn release(self) {}
+}
+
+impl Drop for CircuitPermit<'_> {
+    fn drop(&mut self) {
+        if self.trial && !self.reported {
+            self.breaker.release(&self.entity);
+        }
+    }
+}
+
+impl CircuitBreaker {
+    /// Whether a request may go to `entity` now. Once the cool-down has
+    /// expired exactly one trial request is let through. `Err` says why not.
+    pub fn allow(&self, entity: &str) -> Result<CircuitPermit<'_>, String> {
+        let permit = |trial| CircuitPermit {
+            breaker: self,
+            entity: entity.to_string(),
+            trial,
+            reported: false,
+        };
+        let Ok(mut circuits) = self.circuits.lock() else { return Ok(permit(false)) };
+        let circuit = circuits.entry(entity.to_string()).or_default();
+        match (circuit.state(Instant::now()), circuit.open_until) {
+            (CircuitState::Closed, _) => Ok(permit(false)),
+            (CircuitState::Open, Some((_, until))) => {
+                Err(format!("circuit open until {}", until.format("%H:%M:%S UTC")))
+            }
+            (CircuitState::HalfOpen, _) if circuit.trial_in_flight => {
+                Err("circuit half-open, trial request in progress".to_string())
+            }
+            _ => {
+                circuit.trial_in_flight = true;
+                Ok(permit(true))
+            }
+        }
+    }
+
+    pub fn record_success(&self, entity: &str) {
+        let Ok(mut circuits) = self.circuits.lock() else { return };
+        let circuit = circuits.entry(entity.to_string()).or_default();
+        if circuit.open_until.is_some() {
+            tracing::info!("Circuit for {entity} closed after a successful trial");
+        }
+        *circuit = Circuit {
+            last_error: circuit.last_error.take(),
+            last_success: Some(Utc::now()),
+            ..Default::default()
+        };
+    }
+
+    /// Count a failure that reflects on the entity's health.
+    pub fn record_failure(&self, entity: &str, detail: &str) {
+        let Ok(mut circuits) = self.circuits.lock() else { return };
+        let circuit = circuits.entry(entity.to_string()).or_default();
+        circuit.consecutive_failures += 1;
+        circuit.last_error = Some(detail.to_string());
+        let failed_trial = circuit.trial_in_flight;
+        circuit.trial_in_flight = false;
+
+        if failed_trial || circuit.consecutive_failures >= CIRCUIT_FAILURE_THRESHOLD {
+            let cool_down = CIRCUIT_COOL_DOWN
+                .saturating_mul(1 << circuit.trips.min(6))
+                .min(CIRCUIT_COOL_DOWN_MAX);
+            circuit.trips += 1;
+            circuit.open_until = Some((
+                Instant::now() + cool_down,
+                Utc::now() + chrono::Duration::from_std(cool_down).unwrap_or_default(),
+            ));
+            tracing::warn!(
+                "Circuit for {entity} opened for {}s after {} consecutive failures",
+                cool_down.as_secs(),
+                circuit.consecutive_failures
+            );
+        }
+    }
+
+    /// End a trial whose outcome says nothing about the entity (e.g. a refused request).
+    pub fn release(&self, entity: &str) {
+        if let Ok(mut circuits) = self.circuits.lock() {
+            if let Some(circuit) = circuits.get_mut(entity) {
+                circuit.trial_in_flight = false;
+            }
+        }
+    }
+
+    /// Close the circuit by hand, e.g. after fixing a key.
+    pub fn reset(&self, entity: &str) {
+        if let Ok(mut circuits) = self.circuits.lock() {
+            circuits.remove(entity);
+        }
+    }
+
+    pub fn status(&self, entity: &str) -> CircuitStatus {
+        let circuit = self
+            .circuits
+            .lock()
+            .ok()
+            .and_then(|c| c.get(entity).cloned())
+            .unwrap_or_default();
+        let state = circuit.state(Instant::now());
+        CircuitStatus {
+            entity: entity.to_string(),
+            state,
+            consecutive_failures: circuit.consecutive_failures,
+            open_until: circuit
+                .open_until
+                .filter(|_| state == CircuitState::Open)
+                .map(|(_, utc)| utc),
+            last_error: circuit.last_error,
+            last_success: circuit.last_success,
+        }
+    }
+}
+
+// ─── Router ───────────────────────────────────────────────────────────────────
+
+/// A successful answer and how it was obtained.
+#[derive(Debug, Clone)]
+pub struct FailoverReply {
+    pub reply: LlmReply,
+    /// Entity that produced `reply`.
+    pub served_by: String,
+    /// Group the target named, if it was a group.
+    pub group: Option<String>,
+    /// Every entity tried, in order, ending with `served_by`.
+    pub attempts: Vec<FailoverAttempt>,
+    /// `reply` came from the response cache.
+    pub cached: bool,
+}
+
+impl FailoverReply {
+    /// `served_by`, `served_model`, `entity_group`, `failover_attempts`,
+    /// `failover_trail`, `tokens` and `cached`, for `TaskOutput.metadata`.
+    pub fn metadata(&self) -> HashMap<String, String> {
+        let mut metadata = HashMap::from([
+            ("served_by".to_string(), self.served_by.clone()),
+            ("served_model".to_string(), self.reply.model.clone()),
+            ("failover_attempts".to_string(), self.attempts.len().to_string()),
+            ("failover_trail".to_string(), trail(&self.attempts)),
+            ("tokens".to_string(), self.reply.total_tokens().to_string()),
+            ("cached".to_string(), self.cached.to_string()),
+        ]);
+        if let Some(group) = &self.group {
+            metadata.insert("entity_group".to_string(), group.clone());
+        }
+        metadata
+    }
+}
+
+/// Sends requests to entity groups or single entities with failover.
+#[derive(Clone)]
+pub struct FailoverRouter {
+    api_manager: ApiKeyManager,
+    vault_dir: PathBuf,
+    breaker: Arc<CircuitBreaker>,
+    cache: Arc<ResponseCache>,
+    http: reqwest::Client,
+}
+
+impl FailoverRouter {
+    pub fn new(api_manager: ApiKeyManager) -> Self {
+        let vault_dir = api_manager.vault_dir().to_path_buf();
+        let cache = ResponseCache::new(
+            vault_dir.join(RESPONSE_CACHE_DIR),
+            DEFAULT_CACHE_TTL,
+            DEFAULT_CACHE_MAX_BYTES,
+        );
+        Self {
+            api_manager,
+            vault_dir,
+            breaker: Arc::new(CircuitBreaker::default()),
+            cache: Arc::new(cache),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Entities saved by the GUI. Read on every call so edits apply at once.
+    pub fn entities(&self) -> Vec<AiEntity> {
+        read_json(&self.vault_dir.join(ENTITIES_FILE))
+    }
+
+    pub fn groups(&self) -> Vec<EntityGroup> {
+        read_json(&self.vault_dir.join(ENTITY_GROUPS_FILE))
+    }
+
+    /// Replace all groups. Names must be unique and chains non-empty; chain
+    /// members are not checked against the entity list so that groups can be
+    /// set up before their entities.
+    pub fn save_groups(&self, groups: &[EntityGroup]) -> Result<(), VgaError> {
+        for (idx, group) in groups.iter().enumerate() {
+            if group.name.trim().is_empty() {
+                return Err(VgaError::InvalidConfig(format!("Group {idx} has no name")));
+            }
+            if group.chain.is_empty() {
+                return Err(VgaError::InvalidConfig(format!("Group {} has an empty chain", group.name)));
+            }
+            if groups[..idx].iter().any(|g| g.name == group.name) {
+                return Err(VgaError::InvalidConfig(format!("Duplicate group name {}", group.name)));
+            }
+        }
+        let json = serde_json::to_string_pretty(groups)
+            .map_err(|e| VgaError::Storage(format!("Failed to serialize entity groups: {e}")))?;
+        fs::write(self.vault_dir.join(ENTITY_GROUPS_FILE), json)
+            .map_err(|e| VgaError::Storage(format!("Failed to write entity groups: {e}")))
+    }
+
+    /// The group `target` names (if any) and its entity chain. A group wins
+    /// over an entity of the same name. Unknown chain members are dropped
+    /// with a warning.
+    pub fn resolve(&self, target: &str) -> Result<(Option<String>, Vec<AiEntity>), VgaError> {
+        let entities = self.entities();
+        if let Some(group) = self.groups().into_iter().find(|g| g.name == target) {
+            let chain: Vec<AiEntity> = group
+                .chain
+                .iter()
+                .filter_map(|name| {
+                    let entity = entities.iter().find(|e| &e.name == name).cloned();
+                    if entity.is_none() {
+                        tracing::warn!("Entity group {target}: unknown entity {name}");
+                    }
+                    entity
+                })
+                .collect();
+            if chain.is_empty() {
+                return Err(VgaError::InvalidConfig(format!(
+                    "Entity group {target} has no known entities"
+                )));
+            }
+            return Ok((Some(group.name), chain));
+        }
+        entities
+            .into_iter()
+            .find(|e| e.name == target)
+            .map(|entity| (None, vec![entity]))
+            .ok_or_else(|| VgaError::InvalidConfig(format!("No entity or entity group named {target}")))
+    }
+
+    /// Send `request` to `target`, failing over along its chain.
+    pub async fn chat(&self, target: &str, request: &LlmRequest) -> Result<FailoverReply, VgaError> {
+        self.route(target, request, None).await
+    }
+
+    /// Like [`chat`](Self::chat), forwarding reply text to `deltas` as it is
+    /// generated. The returned reply still holds the complete text.
+    pub async fn chat_stream(
+        &self,
+        target: &str,
+        request: &LlmRequest,
+        deltas: mpsc::UnboundedSender<String>,
+    ) -> Result<FailoverReply, VgaError> {
+        self.route(target, request, Some(&deltas)).await
+    }
+
+    async fn route(
+        &self,
+        target: &str,
+        request: &LlmRequest,
+        deltas: Option<&mpsc::UnboundedSender<String>>,
+    ) -> Result<FailoverReply, VgaError> {
+        let (group, chain) = self.resolve(target)?;
+        let cacheable = ResponseCache::is_cacheable(request);
+        let mut attempts = Vec::new();
+        let mut skipped = Vec::new();
+
+        for entity in &chain {
+            let cache_key = cacheable.then(|| ResponseCache::key(entity, request));
+            if let Some(key) = &cache_key {
+                let hit = self.cache.get(key).await;
+                self.api_manager.record_cache_lookup(&entity.provider, hit.is_some()).await;
+                if let Some(reply) = hit {
+                    if let Some(deltas) = deltas {
+                        let _ = deltas.send(reply.content.clone());
+                    }
+                    attempts.push(FailoverAttempt {
+                        entity: entity.name.clone(),
+                        error: None,
+                        detail: None,
+                        elapsed_ms: 0,
+                    });
+                    return Ok(FailoverReply {
+                        reply,
+                        served_by: entity.name.clone(),
+                        group,
+                        attempts,
+                        cached: true,
+                    });
+                }
+            }
+            let permit = match self.breaker.allow(&entity.name) {
+                Ok(permit) => permit,
+                Err(reason) => {
+                    skipped.push(format!("{} ({reason})", entity.name));
+                    continue;
+                }
+            };
+            let started = Instant::now();
+            let mut forwarded = false;
+            let result = self.call_entity(entity, request, deltas, &mut forwarded).await;
+            let elapsed_ms = started.elapsed().as_millis() as u64;
+
+            match result {
+                Ok(reply) => {
+                    permit.success();
+                    if let Some(key) = &cache_key {
+                        if let Err(e) = self.cache.put(key, &reply).await {
+                            tracing::warn!("Response cache: {e:?}");
+                        }
+                    }
+                    attempts.push(FailoverAttempt {
+                        entity: entity.name.clone(),
+                        error: None,
+                        detail: None,
+                        elapsed_ms,
+                    });
+                    if attempts.len() > 1 {
+                        tracing::info!("{target} served by fallback {} ({})", entity.name, trail(&attempts));
+                    }
+                    return Ok(FailoverReply {
+                        reply,
+                        served_by: entity.name.clone(),
+                        group,
+                        attempts,
+                        cached: false,
+                    });
+                }
+                Err(e) => {
+                    if e.kind.trips_circuit() {
+                        permit.failure(&e.message);
+                    } else {
+                        permit.release();
+                    }
+                    tracing::warn!("{target}: entity {} failed: {e}", entity.name);
+                    attempts.push(FailoverAttempt {
+                        entity: entity.name.clone(),
+                        error: Some(e.kind),
+                        detail: Some(e.message.clone()),
+                        elapsed_ms,
+                    });
+                    if forwarded {
+                        return Err(VgaError::LlmFailure(format!(
+                            "{} failed after streaming part of its reply: {}",
+                            entity.name, e.message
+                        )));
+                    }
+                    if !e.kind.is_retryable() {
+                        return Err(VgaError::LlmFailure(format!(
+                            "{} refused the request: {}",
+                            entity.name, e.message
+                        )));
+                    }
+                }
+            }
+        }
+
+        let mut reasons = Vec::new();
+        if !attempts.is_empty() {
+            reasons.push(trail(&attempts));
+        }
+        if !skipped.is_empty() {
+            reasons.push(format!("skipped {}", skipped.join(", ")));
+        }
+        Err(VgaError::LlmFailure(format!(
+            "No entity in {target} could serve the request: {}",
+            reasons.join("; ")
+        )))
+    }
+
+    /// Breaker state of every entity `target` expands to, or of all entities if `None`.
+    pub fn circuit_status(&self, target: Option<&str>) -> Result<Vec<CircuitStatus>, VgaError> {
+        let entities = match target {
+            Some(target) => self.resolve(target)?.1,
+            None => self.entities(),
+        };
+        Ok(entities.iter().map(|e| self.breaker.status(&e.name)).collect())
+    }
+
+    pub fn reset_circuit(&self, entity: &str) {
+        self.breaker.reset(entity);
+    }
+
+    pub fn response_cache(&self) -> &Arc<ResponseCache> {
+        &self.cache
+    }
+
+    /// One request to one entity, leasing a key from its pool and reporting
+    /// the outcome back to the pool. With `deltas` the reply is streamed and
+    /// `forwarded` is set once any text has been passed on.
+    async fn call_entity(
+        &self,
+        entity: &AiEntity,
+        request: &LlmRequest,
+        deltas: Option<&mpsc::UnboundedSender<String>>,
+        forwarded: &mut bool,
+    ) -> Result<LlmReply, LlmError> {
+        let provider = self.api_manager.provider(&entity.provider);
+        let needs_key = provider.as_ref().is_none_or(|p| p.requires_api_key);
+
+        let lease = match self.api_manager.acquire_key(&entity.name) {
+            Ok(lease) => Some(lease),
+            Err(_) if !needs_key => None,
+            Err(VgaError::ResourceLimit(message)) => {
+                return Err(LlmError::new(LlmErrorKind::QuotaExhausted, message))
+            }
+            Err(e) => return Err(LlmError::new(LlmErrorKind::Config, format!("{e:?}"))),
+        };
+
+        let backend = llm_backend::backend_for(
+            &self.http,
+            entity,
+            provider.as_ref(),
+            lease.as_ref().map(|l| &l.key),
+        )?;
+        let result = match deltas {
+            Some(deltas) => Self::stream_reply(backend.as_ref(), request, deltas, forwarded).await,
+            None => backend.chat(request).await,
+        };
+
+        if let Some(lease) = &lease {
+            let outcome = match &result {
+                Ok(reply) => Some(KeyOutcome::Success { tokens: reply.total_tokens() }),
+                Err(e) => match e.kind {
+                    LlmErrorKind::InvalidKey => Some(KeyOutcome::InvalidKey),
+                    LlmErrorKind::QuotaExhausted => Some(KeyOutcome::QuotaExhausted),
+                    LlmErrorKind::BadRequest | LlmErrorKind::Config => None,
+                    _ => Some(KeyOutcome::Failed),
+                },
+            };
+            if let Some(outcome) = outcome {
+                self.api_manager.report_key_outcome(&lease.entry, outcome);
+            }
+        }
+        result
+    }
+
+    async fn stream_reply(
+        backend: &dyn llm_backend::LlmBackend,
+        request: &LlmRequest,
+        deltas: &mpsc::UnboundedSender<String>,
+        forwarded: &mut bool,
+    ) -> Result<LlmReply, LlmError> {
+        let mut stream = backend.chat_stream(request).await?;
+        while let Some(event) = stream.recv().await {
+            match event? {
+                LlmStreamEvent::Delta(text) => {
+                    *forwarded = true;
+                    // A caller that stopped listening still gets the final reply.
+                    let _ = deltas.send(text);
+                }
+                LlmStreamEvent::Done(reply) => return Ok(reply),
+            }
+        }
+        Err(LlmError::new(LlmErrorKind::Unavailable, "Stream closed before the reply was complete"))
+    }
+}
+
+/// `a: QuotaExhausted (HTTP 429: …) → b: ok`
+fn trail(attempts: &[FailoverAttempt]) -> String {
+    attempts
+        .iter()
+        .map(|a| match (&a.error, &a.detail) {
+            (None, _) => format!("{}: ok", a.entity),
+            (Some(kind), Some(detail)) => format!("{}: {kind:?} ({detail})", a.entity),
+            (Some(kind), None) => format!("{}: {kind:?}", a.entity),
+        })
+        .collect::<Vec<_>>()
+        .join(" → ")
+}
+
+fn read_json<T: serde::de::DeserializeOwned + Default>(path: &std::path::Path) -> T {
+    match fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring unreadable {}: {e}", path.display());
+            T::default()
+        }),
+        Err(_) => T::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trip(breaker: &CircuitBreaker, entity: &str) {
+        for _ in 0..CIRCUIT_FAILURE_THRESHOLD {
+            breaker.allow(entity).expect("closed").failure("boom");
+        }
+    }
+
+    /// Move `entity`'s cool-down into the past, as if it had run out.
+    fn expire(breaker: &CircuitBreaker, entity: &str) {
+        let mut circuits = breaker.circuits.lock().expect("lock");
+        let circuit = circuits.get_mut(entity).expect("circuit");
+        let (_, utc) = circuit.open_until.expect("open");
+        circuit.open_until = Some((Instant::now() - Duration::from_secs(1), utc));
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::default();
+        for _ in 1..CIRCUIT_FAILURE_THRESHOLD {
+            breaker.allow("a").expect("closed").failure("boom");
+        }
+        assert_eq!(breaker.status("a").state, CircuitState::Closed);
+
+        breaker.allow("a").expect("closed").failure("boom");
+        let status = breaker.status("a");
+        assert_eq!(status.state, CircuitState::Open);
+        assert_eq!(status.last_error.as_deref(), Some("boom"));
+        assert!(status.open_until.is_some());
+        let reason = breaker.allow("a").err().expect("skipped");
+        assert!(reason.starts_with("circuit open until"), "{reason}");
+        assert!(breaker.allow("b").is_ok());
+    }
+
+    #[test]
+    fn success_resets_the_failure_count() {
+        let breaker = CircuitBreaker::default();
+        for _ in 1..CIRCUIT_FAILURE_THRESHOLD {
+            breaker.allow("a").expect("closed").failure("boom");
+        }
+        breaker.allow("a").expect("closed").success();
+        for _ in 1..CIRCUIT_FAILURE_THRESHOLD {
+            breaker.allow("a").expect("closed").failure("boom");
+        }
+        assert_eq!(breaker.status("a").state, CircuitState::Closed);
+    }
+
+    #[test]
+    fn half_open_lets_a_single_trial_through() {
+        let breaker = CircuitBreaker::default();
+        trip(&breaker, "a");
+        expire(&breaker, "a");
+        assert_eq!(breaker.status("a").state, CircuitState::HalfOpen);
+
+        let trial = breaker.allow("a").expect("trial");
+        let reason = breaker.allow("a").err().expect("second request skipped");
+        assert!(reason.contains("trial request in progress"), "{reason}");
+
+        trial.success();
+        let status = breaker.status("a");
+        assert_eq!(status.state, CircuitState::Closed);
+        assert_eq!(status.consecutive_failures, 0);
+        assert!(status.last_success.is_some());
+        assert!(breaker.allow("a").is_ok());
+    }
+
+    #[test]
+    fn failed_trial_reopens_with_a_longer_cool_down() {
+        let breaker = CircuitBreaker::default();
+        trip(&breaker, "a");
+        expire(&breaker, "a");
+
+        breaker.allow("a").expect("trial").failure("still down");
+        let status = breaker.status("a");
+        assert_eq!(status.state, CircuitState::Open);
+        let left = status.open_until.expect("open") - Utc::now();
+        assert!(left > chrono::Duration::from_std(CIRCUIT_COOL_DOWN).expect("duration"), "{left}");
+    }
+
+    #[test]
+    fn released_trial_lets_the_next_one_through() {
+        let breaker = CircuitBreaker::default();
+        trip(&breaker, "a");
+        expire(&breaker, "a");
+
+        breaker.allow("a").expect("trial").release();
+        assert_eq!(breaker.status("a").state, CircuitState::HalfOpen);
+        assert!(breaker.allow("a").is_ok());
+    }
+
+    #[test]
+    fn dropped_trial_is_released() {
+        let breaker = CircuitBreaker::default();
+        trip(&breaker, "a");
+        expire(&breaker, "a");
+
+        drop(breaker.allow("a").expect("trial"));
+        assert!(breaker.allow("a").is_ok());
+    }
+
+    #[test]
+    fn closed_permit_does_not_end_a_later_trial() {
+        let breaker = CircuitBreaker::default();
+        let early = breaker.allow("a").expect("closed");
+        trip(&breaker, "a");
+        expire(&breaker, "a");
+
+        let _trial = breaker.allow("a").expect("trial");
+        drop(early);
+        assert!(breaker.allow("a").is_err());
+    }
+
+    #[tokio::test]
+    async fn cancelled_trial_is_released() {
+        let breaker = Arc::new(CircuitBreaker::default());
+        trip(&breaker, "a");
+        expire(&breaker, "a");
+
+        let (started, running) = tokio::sync::oneshot::channel();
+        let held = breaker.clone();
+        let call = tokio::spawn(async move {
+            let _permit = held.allow("a").expect("trial");
+            let _ = started.send(());
+            std::future::pending::<()>().await;
+        });
+        running.await.expect("trial started");
+        assert!(breaker.allow("a").is_err());
+
+        call.abort();
+        assert!(call.await.expect_err("aborted").is_cancelled());
+        assert!(breaker.allow("a").is_ok());
+    }
+
+    #[tokio::test]
+    async fn config_and_provider_failures_have_their_own_variants() {
+        let dir = std::env::temp_dir().join(format!("vas-failover-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).expect("vault dir");
+        // Nothing listens on port 1, so every attempt fails to connect.
+        let entities = serde_json::json!([{
+            "name": "local",
+            "provider": "ollama",
+            "model": "llama3",
+            "note": "",
+            "custom_base_url": "http://127.0.0.1:1",
+        }]);
+        fs::write(dir.join(ENTITIES_FILE), entities.to_string()).expect("entities");
+        let router = FailoverRouter::new(ApiKeyManager::with_vault_dir(&dir).await);
+
+        let empty = EntityGroup { name: "g".to_string(), chain: vec![], note: String::new() };
+        assert!(matches!(router.save_groups(&[empty]), Err(VgaError::InvalidConfig(_))));
+        assert!(matches!(router.resolve("missing"), Err(VgaError::InvalidConfig(_))));
+
+        let request = LlmRequest { cache: Some(false), ..LlmRequest::prompt(None, "hi") };
+        match router.chat("local", &request).await {
+            Err(VgaError::LlmFailure(message)) => {
+                assert!(message.starts_with("No entity in local could serve"), "{message}")
+            }
+            other => panic!("expected LlmFailure, got {other:?}"),
+        }
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
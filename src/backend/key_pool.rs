@@ -0,0 +1,323 @@
+//! Rotation state for key pools.
+//!
+//! A pool is the vault entry named after it (if any) plus every entry whose
+//! `pool` field names it. Membership lives in the vault file; the rotation
+//! cursor, per-key counters and bench timers live here and reset on restart.
+
+use crate::shared::models::{KeyOutcome, KeyPoolMember, KeyPoolStrategy};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a key rejected by the provider stays out of rotation.
+pub const INVALID_KEY_BENCH: Duration = Duration::from_secs(30 * 60);
+/// First bench after a quota error; doubles on each consecutive one.
+pub const QUOTA_BENCH_BASE: Duration = Duration::from_secs(60);
+pub const QUOTA_BENCH_MAX: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Default)]
+pub struct KeyPools {
+    inner: Mutex<PoolsInner>,
+}
+
+#[derive(Debug, Default)]
+struct PoolsInner {
+    strategies: HashMap<String, KeyPoolStrategy>,
+    cursors: HashMap<String, usize>,
+    /// Keyed by vault entry name, so a key shared by two pools has one record.
+    keys: HashMap<String, KeyState>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct KeyState {
+    requests_made: u64,
+    tokens_used: u64,
+    failures: u64,
+    consecutive_quota_errors: u32,
+    last_used: Option<DateTime<Utc>>,
+    bench: Option<Bench>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bench {
+    until: Instant,
+    until_utc: DateTime<Utc>,
+    reason: KeyOutcome,
+}
+
+impl KeyState {
+    /// Whether the key may be handed out; clears a bench that has run out.
+    fn available(&mut self, now: Instant) -> bool {
+        match self.bench {
+            Some(bench) if bench.until > now => false,
+            Some(_) => {
+                self.bench = None;
+                true
+            }
+            None => true,
+        }
+    }
+
+    fn bench_for(&mut self, duration: Duration, reason: KeyOutcome) {
+        self.bench = Some(Bench {
+            until: Instant::now() + duration,
+            until_utc: Utc::now() + chrono::Duration::from_std(duration).unwrap_or_default(),
+            reason,
+        });
+    }
+}
+
+impl KeyPools {
+    pub fn with_strategies(strategies: HashMap<String, KeyPoolStrategy>) -> Self {
+        Self {
+            inner: Mutex::new(PoolsInner { strategies, ..Default::default() }),
+        }
+    }
+
+    pub fn strategy(&self, pool: &str) -> KeyPoolStrategy {
+        self.inner
+            .lock()
+            .ok()
+            .and_then(|inner| inner.strategies.get(pool).copied())
+            .unwrap_or_default()
+    }
+
+    pub fn strategies(&self) -> HashMap<String, KeyPoolStrategy> {
+        self.inner.lock().map(|inner| inner.strategies.clone()).unwrap_or_default()
+    }
+
+    pub fn set_strategy(&self, pool: &str, strategy: KeyPoolStrategy) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.strategies.insert(pool.to_string(), strategy);
+        }
+    }
+
+    /// Pick the next available member of `pool` and count a request against it.
+    ///
+    /// `Err` carries the time the earliest benched member becomes available
+    /// again, or `None` if `members` is empty.
+    pub fn select(&self, pool: &str, members: &[String]) -> Result<String, Option<DateTime<Utc>>> {
+        if members.is_empty() {
+            return Err(None);
+        }
+        let mut inner = self.inner.lock().map_err(|_| None)?;
+        let now = Instant::now();
+        let strategy = inner.strategies.get(pool).copied().unwrap_or_default();
+
+        let chosen = match strategy {
+            KeyPoolStrategy::RoundRobin => {
+                let start = inner.cursors.get(pool).copied().unwrap_or(0);
+                let found = (0..members.len())
+                    .map(|offset| (start + offset) % members.len())
+                    .find(|&idx| inner.keys.entry(members[idx].clone()).or_default().available(now));
+                if let Some(idx) = found {
+                    inner.cursors.insert(pool.to_string(), idx + 1);
+                }
+                found.map(|idx| members[idx].clone())
+            }
+            KeyPoolStrategy::LeastUsed => {
+                let mut best: Option<(&String, u64, Option<DateTime<Utc>>)> = None;
+                for member in members {
+                    let state = inner.keys.entry(member.clone()).or_default();
+                    if !state.available(now) {
+                        continue;
+                    }
+                    let candidate = (member, state.requests_made, state.last_used);
+                    if best.is_none_or(|(_, requests, last_used)| {
+                        (candidate.1, candidate.2) < (requests, last_used)
+                    }) {
+                        best = Some(candidate);
+                    }
+                }
+                best.map(|(member, _, _)| member.clone())
+            }
+        };
+
+        match chosen {
+            Some(entry) => {
+                let state = inner.keys.entry(entry.clone()).or_default();
+                state.requests_made += 1;
+                state.last_used = Some(Utc::now());
+                Ok(entry)
+            }
+            None => Err(members
+                .iter()
+                .filter_map(|m| inner.keys.get(m).and_then(|s| s.bench).map(|b| b.until_utc))
+                .min()),
+        }
+    }
+
+    /// Record how a request made with `entry`'s key went.
+    pub fn report(&self, entry: &str, outcome: KeyOutcome) {
+        let Ok(mut inner) = self.inner.lock() else { return };
+        let state = inner.keys.entry(entry.to_string()).or_default();
+        match outcome {
+            KeyOutcome::Success { tokens } => {
+                state.tokens_used += tokens;
+                state.consecutive_quota_errors = 0;
+            }
+            KeyOutcome::InvalidKey => {
+                state.failures += 1;
+                state.bench_for(INVALID_KEY_BENCH, outcome);
+                tracing::warn!("Benched key {entry}: rejected by provider");
+            }
+            KeyOutcome::QuotaExhausted => {
+                state.failures += 1;
+                let backoff = QUOTA_BENCH_BASE
+                    .saturating_mul(1 << state.consecutive_quota_errors.min(6))
+                    .min(QUOTA_BENCH_MAX);
+                state.consecutive_quota_errors += 1;
+                state.bench_for(backoff, outcome);
+                tracing::info!("Benched key {entry} for {}s: quota exhausted", backoff.as_secs());
+            }
+            KeyOutcome::Failed => state.failures += 1,
+        }
+    }
+
+    /// Put a benched key back into rotation.
+    pub fn unbench(&self, entry: &str) {
+        if let Ok(mut inner) = self.inner.lock() {
+            if let Some(state) = inner.keys.get_mut(entry) {
+                state.bench = None;
+                state.consecutive_quota_errors = 0;
+            }
+        }
+    }
+
+    /// Drop all runtime state for a deleted entry.
+    pub fn forget(&self, entry: &str) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.keys.remove(entry);
+        }
+    }
+
+    pub fn members_status(&self, members: &[String]) -> Vec<KeyPoolMember> {
+        let Ok(mut inner) = self.inner.lock() else { return vec![] };
+        let now = Instant::now();
+        members
+            .iter()
+            .map(|entry| {
+                let state = inner.keys.entry(entry.clone()).or_default();
+                state.available(now);
+                KeyPoolMember {
+                    entry: entry.clone(),
+                    requests_made: state.requests_made,
+                    tokens_used: state.tokens_used,
+                    failures: state.failures,
+                    last_used: state.last_used,
+                    benched_until: state.bench.map(|b| b.until_utc),
+                    bench_reason: state.bench.map(|b| b.reason),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn picks(pools: &KeyPools, pool: &str, members: &[String], count: usize) -> Vec<String> {
+        (0..count).map(|_| pools.select(pool, members).expect("a key")).collect()
+    }
+
+    fn status(pools: &KeyPools, entry: &str) -> KeyPoolMember {
+        pools.members_status(&[entry.to_string()]).remove(0)
+    }
+
+    /// Seconds between now and the end of the bench a fresh `outcome` puts `entry` on.
+    fn bench_secs(pools: &KeyPools, entry: &str, outcome: KeyOutcome) -> i64 {
+        let before = Utc::now();
+        pools.report(entry, outcome);
+        let until = status(pools, entry).benched_until.expect("benched");
+        (until - before).num_seconds()
+    }
+
+    #[test]
+    fn round_robin_rotates_in_order_and_skips_benched_keys() {
+        let pools = KeyPools::default();
+        let keys = members(&["a", "b", "c"]);
+        assert_eq!(picks(&pools, "p", &keys, 4), ["a", "b", "c", "a"]);
+
+        pools.report("c", KeyOutcome::InvalidKey);
+        assert_eq!(picks(&pools, "p", &keys, 3), ["b", "a", "b"]);
+
+        // Each pool keeps its own cursor.
+        assert_eq!(pools.select("other", &keys), Ok("a".to_string()));
+    }
+
+    #[test]
+    fn least_used_picks_the_fewest_requests_then_the_oldest_use() {
+        let pools = KeyPools::with_strategies(HashMap::from([(
+            "p".to_string(),
+            KeyPoolStrategy::LeastUsed,
+        )]));
+        let keys = members(&["a", "b", "c"]);
+
+        // Requests made through another pool count too: the state is per key.
+        picks(&pools, "rr", &members(&["a", "b"]), 2);
+        assert_eq!(pools.select("p", &keys), Ok("c".to_string()));
+
+        // All tied on one request: the least recently used goes first.
+        assert_eq!(picks(&pools, "p", &keys, 3), ["a", "b", "c"]);
+
+        pools.report("a", KeyOutcome::QuotaExhausted);
+        assert_eq!(picks(&pools, "p", &keys, 2), ["b", "c"]);
+        assert_eq!(status(&pools, "a").requests_made, 2);
+        assert_eq!(status(&pools, "b").requests_made, 3);
+    }
+
+    #[test]
+    fn quota_benches_double_up_to_the_cap_and_reset_on_success() {
+        let pools = KeyPools::default();
+        let base = QUOTA_BENCH_BASE.as_secs() as i64;
+        let max = QUOTA_BENCH_MAX.as_secs() as i64;
+
+        let mut expected = base;
+        for _ in 0..8 {
+            let secs = bench_secs(&pools, "a", KeyOutcome::QuotaExhausted);
+            assert!((expected - 1..=expected).contains(&secs), "{secs}s, expected {expected}s");
+            expected = (expected * 2).min(max);
+        }
+        assert_eq!(expected, max);
+
+        pools.report("a", KeyOutcome::Success { tokens: 10 });
+        assert!(bench_secs(&pools, "a", KeyOutcome::QuotaExhausted) <= base);
+        let secs = bench_secs(&pools, "a", KeyOutcome::InvalidKey);
+        assert!(secs > INVALID_KEY_BENCH.as_secs() as i64 - 2, "{secs}s");
+        assert_eq!(status(&pools, "a").bench_reason, Some(KeyOutcome::InvalidKey));
+    }
+
+    #[test]
+    fn unbench_returns_a_key_to_rotation_and_resets_its_backoff() {
+        let pools = KeyPools::default();
+        let keys = members(&["a"]);
+        bench_secs(&pools, "a", KeyOutcome::QuotaExhausted);
+        bench_secs(&pools, "a", KeyOutcome::QuotaExhausted);
+        assert!(pools.select("p", &keys).is_err());
+
+        pools.unbench("a");
+        assert_eq!(pools.select("p", &keys), Ok("a".to_string()));
+        assert_eq!(status(&pools, "a").benched_until, None);
+        assert!(bench_secs(&pools, "a", KeyOutcome::QuotaExhausted) <= QUOTA_BENCH_BASE.as_secs() as i64);
+    }
+
+    #[test]
+    fn when_every_key_is_benched_the_earliest_return_is_reported() {
+        let pools = KeyPools::default();
+        let keys = members(&["a", "b", "c"]);
+        pools.report("a", KeyOutcome::InvalidKey);
+        pools.report("b", KeyOutcome::QuotaExhausted);
+        pools.report("c", KeyOutcome::InvalidKey);
+
+        let b_returns = status(&pools, "b").benched_until;
+        assert!(b_returns.is_some());
+        assert_eq!(pools.select("p", &keys), Err(b_returns));
+        assert_eq!(pools.select("p", &[]), Err(None));
+    }
+}
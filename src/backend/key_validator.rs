@@ -0,0 +1,243 @@
+//! Cheap authenticated probes used to check that a stored API key works.
+//!
+//! Each provider is probed with its model-listing endpoint, which is free on
+//! every provider we support and needs nothing but a valid key.
+
+use crate::shared::models::{
+    AiEntity, KeyValidationResult, KeyValidationStatus, ProviderConfig, SecretString,
+};
+use crate::shared::utils::truncate;
+use reqwest::Client;
+use std::time::Duration;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// A single GET request that exercises the key. Safe to log: the key only
+/// ever shows up as `[REDACTED]`.
+#[derive(Debug, Clone)]
+pub struct ValidationProbe {
+    pub url: String,
+    /// `(name, value)` pairs other than the key.
+    headers: Vec<(String, String)>,
+    key: Option<KeyHeader>,
+}
+
+/// The header carrying the key; its value is put together in
+/// [`ValidationProbe::send`] and nowhere else.
+#[derive(Debug, Clone)]
+struct KeyHeader {
+    name: String,
+    prefix: String,
+    key: SecretString,
+}
+
+impl ValidationProbe {
+    /// Build the probe for `entity`. `provider` is the catalog entry for
+    /// `entity.provider`, if there is one; custom relays are treated as
+    /// OpenAI-compatible.
+    pub fn for_entity(
+        entity: &AiEntity,
+        provider: Option<&ProviderConfig>,
+        key: Option<&SecretString>,
+    ) -> Result<Self, String> {
+        let provider_id = entity.provider.as_str();
+        let (url, default_header, default_prefix) = match &entity.custom_base_url {
+            Some(base) if provider_id == "ollama" => (replace_path(base, "/api/tags"), "Authorization", "Bearer "),
+            Some(base) => (models_url(base), "Authorization", "Bearer "),
+            None => {
+                let endpoint = provider
+                    .map(|p| p.api_endpoint.as_str())
+                    .ok_or_else(|| format!("Unknown provider '{provider_id}' and no custom base URL"))?;
+                match provider_id {
+                    "ollama" => (replace_path(endpoint, "/api/tags"), "Authorization", "Bearer "),
+                    "anthropic" => (replace_path(endpoint, "/v1/models"), "x-api-key", ""),
+                    "google" => (replace_path(endpoint, "/v1beta/models"), "x-goog-api-key", ""),
+                    "tongyi" => (
+                        replace_path(endpoint, "/compatible-mode/v1/models"),
+                        "Authorization",
+                        "Bearer ",
+                    ),
+                    _ => (models_url(endpoint), "Authorization", "Bearer "),
+                }
+            }
+        };
+
+        let mut headers = Vec::new();
+        if provider_id == "anthropic" && entity.custom_base_url.is_none() {
+            headers.push(("anthropic-version".to_string(), "2023-06-01".to_string()));
+        }
+        let key = key.map(|key| {
+            let header = entity
+                .key_header
+                .as_deref()
+                .or(provider.and_then(|p| p.key_header.as_deref()))
+                .unwrap_or(default_header);
+            let prefix = entity
+                .key_prefix
+                .as_deref()
+                .or(provider.and_then(|p| p.key_prefix.as_deref()))
+                .unwrap_or(default_prefix);
+            KeyHeader {
+                name: header.to_string(),
+                prefix: prefix.to_string(),
+                key: key.clone(),
+            }
+        });
+
+        Ok(Self { url, headers, key })
+    }
+
+    /// Send the request without interpreting the response.
+    pub async fn send(&self, client: &Client) -> Result<reqwest::Response, reqwest::Error> {
+        let mut request = client.get(&self.url).timeout(PROBE_TIMEOUT);
+        for (name, value) in &self.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        if let Some(key) = &self.key {
+            request = request.header(key.name.as_str(), format!("{}{}", key.prefix, key.key.expose_secret()));
+        }
+        request.send().await
+    }
+
+    /// Run the probe and classify the response.
+    pub async fn run(&self, client: &Client) -> (KeyValidationStatus, Option<u16>, Option<String>) {
+        match self.send(client).await {
+            Ok(response) => {
+                let status = response.status();
+                let classified = classify_status(status.as_u16());
+                let detail = if status.is_success() {
+                    None
+                } else {
+                    let body = response.text().await.unwrap_or_default();
+                    Some(truncate(&body, 300))
+                };
+                (classified, Some(status.as_u16()), detail)
+            }
+            Err(e) => (KeyValidationStatus::Unreachable, None, Some(e.to_string())),
+        }
+    }
+}
+
+/// Map an HTTP status code from a model-listing endpoint to a validation status.
+pub fn classify_status(status: u16) -> KeyValidationStatus {
+    match status {
+        200..=299 => KeyValidationStatus::Valid,
+        401 | 403 => KeyValidationStatus::InvalidKey,
+        402 | 429 => KeyValidationStatus::QuotaExhausted,
+        _ => KeyValidationStatus::Unexpected,
+    }
+}
+
+/// Probe `entity` and build a result. `key` is `None` for keyless providers.
+pub async fn validate(
+    client: &Client,
+    entity: &AiEntity,
+    provider: Option<&ProviderConfig>,
+    key: Option<&SecretString>,
+) -> KeyValidationResult {
+    let checked_at = chrono::Utc::now();
+    match ValidationProbe::for_entity(entity, provider, key) {
+        Ok(probe) => {
+            let (status, http_status, detail) = probe.run(client).await;
+            KeyValidationResult {
+                entity: entity.name.clone(),
+                status,
+                checked_at,
+                endpoint: probe.url,
+                http_status,
+                detail,
+            }
+        }
+        Err(detail) => KeyValidationResult {
+            entity: entity.name.clone(),
+            status: KeyValidationStatus::Unexpected,
+            checked_at,
+            endpoint: String::new(),
+            http_status: None,
+            detail: Some(detail),
+        },
+    }
+}
+
+/// `https://host/v1/chat/completions` → `https://host/v1/models`; a bare base URL
+/// such as `https://relay.example.com/v1` just gets `/models` appended.
+fn models_url(endpoint: &str) -> String {
+    let trimmed = endpoint.trim_end_matches('/');
+    let base = ["/chat/completions", "/text/chatcompletion_v2", "/chat", "/completions"]
+        .iter()
+        .find_map(|suffix| trimmed.strip_suffix(suffix))
+        .unwrap_or(trimmed);
+    format!("{base}/models")
+}
+
+/// Keep scheme and authority of `endpoint` and replace its path.
+pub(crate) fn replace_path(endpoint: &str, path: &str) -> String {
+    let after_scheme = endpoint.find("://").map(|i| i + 3).unwrap_or(0);
+    let authority_end = endpoint[after_scheme..]
+        .find('/')
+        .map(|i| after_scheme + i)
+        .unwrap_or(endpoint.len());
+    format!("{}{}", &endpoint[..authority_end], path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const KEY: &str = "sk-live-0123456789";
+
+    fn relay(base: &str) -> AiEntity {
+        AiEntity {
+            name: "relay".to_string(),
+            provider: "custom".to_string(),
+            model: "m".to_string(),
+            note: String::new(),
+            custom_base_url: Some(base.to_string()),
+            key_header: Some("api-key".to_string()),
+            key_prefix: Some("Key ".to_string()),
+        }
+    }
+
+    #[test]
+    fn debug_redacts_the_key() {
+        let key = SecretString::from(KEY);
+        let probe = ValidationProbe::for_entity(&relay("https://relay.example.com/v1"), None, Some(&key)).expect("probe");
+        let printed = format!("{probe:?}");
+        assert!(!printed.contains(KEY), "{printed}");
+        assert!(printed.contains("REDACTED"), "{printed}");
+        assert!(printed.contains("https://relay.example.com/v1/models"), "{printed}");
+    }
+
+    #[tokio::test]
+    async fn sends_the_key_in_the_configured_header() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let base = format!("http://{}/v1", listener.local_addr().expect("addr"));
+        let server = tokio::spawn(async move {
+            let (mut conn, _) = listener.accept().await.expect("accept");
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                let n = conn.read(&mut buf).await.expect("read");
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            let _ = conn
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{}")
+                .await;
+            String::from_utf8_lossy(&request).to_lowercase()
+        });
+
+        let key = SecretString::from(KEY);
+        let probe = ValidationProbe::for_entity(&relay(&base), None, Some(&key)).expect("probe");
+        let (status, http_status, _) = probe.run(&Client::new()).await;
+        assert_eq!(status, KeyValidationStatus::Valid);
+        assert_eq!(http_status, Some(200));
+
+        let request = server.await.expect("server");
+        assert!(request.starts_with("get /v1/models "), "{request}");
+        assert!(request.contains(&format!("api-key: key {}", KEY.to_lowercase())), "{request}");
+    }
+}
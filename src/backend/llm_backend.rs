@@ -0,0 +1,919 @@
+//! Chat completion backends behind one trait.
+//!
+//! `OllamaBackend` talks to Ollama's `/api/chat`. `OpenAiBackend` covers every
+//! provider with an OpenAI-compatible `/chat/completions` endpoint, including
+//! the compatibility endpoints of Anthropic, Google and Cohere and all custom
+//! relays. Errors carry an [`LlmErrorKind`] so callers can decide whether to
+//! fail over.
+//!
+//! Replies can also be streamed: Ollama sends newline-delimited JSON frames,
+//! OpenAI-style APIs send server-sent events. Both arrive as an [`LlmStream`]
+//! of text deltas ending in the complete [`LlmReply`].
+//!
+//! Requests may offer tools; a reply then carries the calls the model wants
+//! made in `tool_calls`. Ollama's tool format is used throughout and the
+//! OpenAI adapter translates (string-encoded arguments, `tool_call_id`).
+//! Likewise a [`ResponseFormat`] goes to Ollama as `format` and to OpenAI-style
+//! APIs as `response_format`.
+
+use crate::backend::key_validator::replace_path;
+use crate::backend::ollama_client::stream::{spawn_lines, STREAM_IDLE_TIMEOUT};
+use crate::backend::ollama_client::{
+    ChatMessage, ChatOptions, ChatRequest, ChatResponse, OllamaClient, ResponseFormat, Tool, ToolCall,
+    ToolCallFunction,
+};
+use crate::shared::models::{AiEntity, LlmErrorKind, ProviderConfig, SecretString};
+use crate::shared::utils::truncate;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::fmt;
+use tokio::sync::mpsc;
+
+#[derive(Debug, Clone, Default)]
+pub struct LlmRequest {
+    pub messages: Vec<ChatMessage>,
+    pub options: Option<ChatOptions>,
+    /// Tools the model may call; empty sends none.
+    pub tools: Vec<Tool>,
+    /// Ask for JSON, optionally matching a schema. Providers differ in how
+    /// strictly they enforce it, so check the reply anyway.
+    pub format: Option<ResponseFormat>,
+    /// Response cache use: `None` caches only when `temperature == 0`,
+    /// `Some(true)` always, `Some(false)` never.
+    pub cache: Option<bool>,
+}
+
+impl LlmRequest {
+    /// An optional system prompt followed by one user message.
+    pub fn prompt(system: Option<&str>, user: &str) -> Self {
+        Self {
+            messages: system
+                .map(|s| ChatMessage::new("system", s))
+                .into_iter()
+                .chain([ChatMessage::new("user", user)])
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LlmReply {
+    pub content: String,
+    /// Tools the model asked to run; when non-empty `content` is often empty.
+    pub tool_calls: Vec<ToolCall>,
+    /// Model reported by the endpoint (may differ from the requested alias).
+    pub model: String,
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+}
+
+impl LlmReply {
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens.unwrap_or(0) + self.completion_tokens.unwrap_or(0)
+    }
+}
+
+/// One item of a streamed reply.
+#[derive(Debug, Clone)]
+pub enum LlmStreamEvent {
+    /// The next piece of the reply text.
+    Delta(String),
+    /// The stream finished; `content` is the concatenation of all deltas.
+    /// Tool calls only arrive here, never as deltas.
+    Done(LlmReply),
+}
+
+/// Ends after `Done` or the first error. Dropping it cancels the request.
+pub type LlmStream = mpsc::Receiver<Result<LlmStreamEvent, LlmError>>;
+
+#[derive(Debug, Clone)]
+pub struct LlmError {
+    pub kind: LlmErrorKind,
+    pub http_status: Option<u16>,
+    pub message: String,
+}
+
+impl LlmError {
+    pub fn new(kind: LlmErrorKind, message: impl Into<String>) -> Self {
+        Self { kind, http_status: None, message: message.into() }
+    }
+
+    fn from_status(status: u16, body: &str) -> Self {
+        Self {
+            kind: classify_http_status(status),
+            http_status: Some(status),
+            message: format!("HTTP {status}: {}", truncate(body, 300)),
+        }
+    }
+
+    fn from_reqwest(e: reqwest::Error) -> Self {
+        let kind = if e.is_decode() { LlmErrorKind::Protocol } else { LlmErrorKind::Unavailable };
+        Self::new(kind, e.to_string())
+    }
+
+    /// Check the status of a response before reading it.
+    async fn check(response: reqwest::Response) -> Result<reqwest::Response, LlmError> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let text = response.text().await.unwrap_or_default();
+        Err(Self::from_status(status.as_u16(), &text))
+    }
+}
+
+impl fmt::Display for LlmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.kind, self.message)
+    }
+}
+
+/// Map an HTTP status from a chat endpoint to an error kind.
+pub fn classify_http_status(status: u16) -> LlmErrorKind {
+    match status {
+        401 | 403 => LlmErrorKind::InvalidKey,
+        402 | 429 => LlmErrorKind::QuotaExhausted,
+        404 => LlmErrorKind::ModelNotFound,
+        400 | 413 | 422 => LlmErrorKind::BadRequest,
+        408 | 500..=599 => LlmErrorKind::Unavailable,
+        _ => LlmErrorKind::Protocol,
+    }
+}
+
+#[async_trait::async_trait]
+pub trait LlmBackend: Send + Sync {
+    /// Model requests are sent to.
+    fn model(&self) -> &str;
+    async fn chat(&self, request: &LlmRequest) -> Result<LlmReply, LlmError>;
+
+    /// Stream the reply. The default sends the whole `chat` reply as one delta.
+    async fn chat_stream(&self, request: &LlmRequest) -> Result<LlmStream, LlmError> {
+        let reply = self.chat(request).await?;
+        let (tx, rx) = mpsc::channel(2);
+        let _ = tx.send(Ok(LlmStreamEvent::Delta(reply.content.clone()))).await;
+        let _ = tx.send(Ok(LlmStreamEvent::Done(reply))).await;
+        Ok(rx)
+    }
+}
+
+pub struct OllamaBackend {
+    client: OllamaClient,
+    model: String,
+}
+
+impl OllamaBackend {
+    pub fn new(client: OllamaClient, model: impl Into<String>) -> Self {
+        Self { client, model: model.into() }
+    }
+
+    fn body(&self, request: &LlmRequest, stream: bool) -> ChatRequest {
+        ChatRequest {
+            model: self.model.clone(),
+            messages: request.messages.clone(),
+            stream: Some(stream),
+            format: request.format.clone(),
+            options: request.options.clone(),
+            tools: (!request.tools.is_empty()).then(|| request.tools.clone()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl LlmBackend for OllamaBackend {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn chat(&self, request: &LlmRequest) -> Result<LlmReply, LlmError> {
+        let response = self
+            .client
+            .request(reqwest::Method::POST, "/api/chat")
+            .json(&self.body(request, false))
+            .send()
+            .await
+            .map_err(LlmError::from_reqwest)?;
+        let response = LlmError::check(response).await?;
+        let reply: ChatResponse = response.json().await.map_err(LlmError::from_reqwest)?;
+        Ok(LlmReply {
+            content: reply.message.content,
+            tool_calls: reply.message.tool_calls.unwrap_or_default(),
+            model: reply.model,
+            prompt_tokens: reply.prompt_eval_count.map(u64::from),
+            completion_tokens: reply.eval_count.map(u64::from),
+        })
+    }
+
+    async fn chat_stream(&self, request: &LlmRequest) -> Result<LlmStream, LlmError> {
+        let response = self
+            .client
+            .stream_request(reqwest::Method::POST, "/api/chat")
+            .json(&self.body(request, true))
+            .send()
+            .await
+            .map_err(LlmError::from_reqwest)?;
+        let lines = spawn_lines(LlmError::check(response).await?, STREAM_IDLE_TIMEOUT);
+        Ok(drive_stream(lines, OllamaStream::new(self.model.clone())))
+    }
+}
+
+pub struct OpenAiBackend {
+    http: Client,
+    url: String,
+    /// `(name, value)` pairs; values may contain the key, so never log them.
+    headers: Vec<(String, String)>,
+    model: String,
+}
+
+impl OpenAiBackend {
+    pub fn new(http: Client, url: String, headers: Vec<(String, String)>, model: impl Into<String>) -> Self {
+        Self { http, url, headers, model: model.into() }
+    }
+
+    fn body(&self, request: &LlmRequest, stream: bool) -> Value {
+        let messages: Vec<Value> = request.messages.iter().map(openai_message).collect();
+        let mut body = json!({ "model": self.model, "messages": messages, "stream": stream });
+        if stream {
+            // Without this, streamed replies carry no token counts.
+            body["stream_options"] = json!({ "include_usage": true });
+        }
+        if !request.tools.is_empty() {
+            body["tools"] = json!(request.tools);
+        }
+        match &request.format {
+            Some(ResponseFormat::Json) => body["response_format"] = json!({ "type": "json_object" }),
+            Some(ResponseFormat::Schema { name, schema }) => {
+                body["response_format"] = json!({
+                    "type": "json_schema",
+                    "json_schema": { "name": name, "schema": schema },
+                });
+            }
+            None => {}
+        }
+        if let Some(options) = &request.options {
+            let fields = [
+                ("temperature", options.temperature.map(|v| json!(v))),
+                ("top_p", options.top_p.map(|v| json!(v))),
+                ("max_tokens", options.num_predict.map(|v| json!(v))),
+                ("stop", options.stop.as_ref().map(|v| json!(v))),
+            ];
+            for (name, value) in fields {
+                if let Some(value) = value {
+                    body[name] = value;
+                }
+            }
+        }
+        body
+    }
+
+    async fn send(&self, request: &LlmRequest, stream: bool) -> Result<reqwest::Response, LlmError> {
+        let mut builder = self.http.post(&self.url).json(&self.body(request, stream));
+        for (name, value) in &self.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        let response = builder.send().await.map_err(LlmError::from_reqwest)?;
+        LlmError::check(response).await
+    }
+}
+
+#[async_trait::async_trait]
+impl LlmBackend for OpenAiBackend {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn chat(&self, request: &LlmRequest) -> Result<LlmReply, LlmError> {
+        let response = self.send(request, false).await?;
+        let body: Value = response.json().await.map_err(LlmError::from_reqwest)?;
+        let message = body
+            .pointer("/choices/0/message")
+            .ok_or_else(|| LlmError::new(LlmErrorKind::Protocol, "Response has no choices[0].message"))?;
+        let tool_calls = match message.get("tool_calls").and_then(Value::as_array) {
+            Some(calls) => calls.iter().map(openai_tool_call).collect::<Result<Vec<_>, _>>()?,
+            None => Vec::new(),
+        };
+        let content = match message.get("content").and_then(Value::as_str) {
+            Some(content) => content,
+            None if !tool_calls.is_empty() => "",
+            None => {
+                let message = "Response has neither choices[0].message.content nor tool calls";
+                return Err(LlmError::new(LlmErrorKind::Protocol, message));
+            }
+        };
+        Ok(LlmReply {
+            content: content.to_string(),
+            tool_calls,
+            model: body
+                .get("model")
+                .and_then(Value::as_str)
+                .unwrap_or(&self.model)
+                .to_string(),
+            prompt_tokens: body.pointer("/usage/prompt_tokens").and_then(Value::as_u64),
+            completion_tokens: body.pointer("/usage/completion_tokens").and_then(Value::as_u64),
+        })
+    }
+
+    async fn chat_stream(&self, request: &LlmRequest) -> Result<LlmStream, LlmError> {
+        let lines = spawn_lines(self.send(request, true).await?, STREAM_IDLE_TIMEOUT);
+        Ok(drive_stream(lines, OpenAiStream::new(self.model.clone())))
+    }
+}
+
+/// Turns the lines of a streamed reply into [`LlmStreamEvent`]s.
+trait StreamParser: Send + 'static {
+    /// Reads one line; returns the text it adds to the reply, if any.
+    fn line(&mut self, line: &str) -> Result<Option<String>, LlmError>;
+    /// Whether the end of the reply has been read.
+    fn finished(&self) -> bool;
+    /// The whole reply. A stream that stopped before its end is
+    /// `Unavailable`, since the reply may be cut short.
+    fn finish(self) -> Result<LlmReply, LlmError>;
+}
+
+/// Feeds `lines` to `parser` on a background task.
+fn drive_stream<P: StreamParser>(mut lines: mpsc::Receiver<Result<String, String>>, mut parser: P) -> LlmStream {
+    let (tx, rx) = mpsc::channel(64);
+    tokio::spawn(async move {
+        while let Some(line) = lines.recv().await {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    let _ = tx.send(Err(LlmError::new(LlmErrorKind::Unavailable, e))).await;
+                    return;
+                }
+            };
+            match parser.line(&line) {
+                Ok(Some(delta)) => {
+                    if tx.send(Ok(LlmStreamEvent::Delta(delta))).await.is_err() {
+                        return;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    let _ = tx.send(Err(e)).await;
+                    return;
+                }
+            }
+            if parser.finished() {
+                break;
+            }
+        }
+        let _ = tx.send(parser.finish().map(LlmStreamEvent::Done)).await;
+    });
+    rx
+}
+
+/// Ollama's stream: one JSON frame per line, the last with `done: true`.
+struct OllamaStream {
+    model: String,
+    content: String,
+    tool_calls: Vec<ToolCall>,
+    /// `(prompt_tokens, completion_tokens)` from the final frame.
+    done: Option<(Option<u64>, Option<u64>)>,
+}
+
+impl OllamaStream {
+    fn new(model: String) -> Self {
+        Self { model, content: String::new(), tool_calls: Vec::new(), done: None }
+    }
+}
+
+impl StreamParser for OllamaStream {
+    fn line(&mut self, line: &str) -> Result<Option<String>, LlmError> {
+        if line.trim().is_empty() {
+            return Ok(None);
+        }
+        let frame: Value = serde_json::from_str(line)
+            .map_err(|e| LlmError::new(LlmErrorKind::Protocol, format!("Invalid stream frame: {e}")))?;
+        if let Some(error) = frame.get("error").and_then(Value::as_str) {
+            return Err(LlmError::new(LlmErrorKind::Unavailable, error));
+        }
+        // Ollama sends each tool call whole, in a frame of its own.
+        if let Some(calls) = frame.pointer("/message/tool_calls") {
+            let calls = serde_json::from_value::<Vec<ToolCall>>(calls.clone())
+                .map_err(|e| LlmError::new(LlmErrorKind::Protocol, format!("Invalid tool call: {e}")))?;
+            self.tool_calls.extend(calls);
+        }
+        if frame.get("done").and_then(Value::as_bool) == Some(true) {
+            if let Some(name) = frame.get("model").and_then(Value::as_str) {
+                self.model = name.to_string();
+            }
+            self.done = Some((
+                frame.get("prompt_eval_count").and_then(Value::as_u64),
+                frame.get("eval_count").and_then(Value::as_u64),
+            ));
+        }
+        let delta = frame.pointer("/message/content").and_then(Value::as_str).unwrap_or("");
+        if delta.is_empty() {
+            return Ok(None);
+        }
+        self.content.push_str(delta);
+        Ok(Some(delta.to_string()))
+    }
+
+    fn finished(&self) -> bool {
+        self.done.is_some()
+    }
+
+    fn finish(self) -> Result<LlmReply, LlmError> {
+        let Some((prompt_tokens, completion_tokens)) = self.done else {
+            return Err(LlmError::new(LlmErrorKind::Unavailable, "Stream ended without a final frame"));
+        };
+        Ok(LlmReply {
+            content: self.content,
+            tool_calls: self.tool_calls,
+            model: self.model,
+            prompt_tokens,
+            completion_tokens,
+        })
+    }
+}
+
+/// Most tool calls one streamed OpenAI reply may make. Fragments name the
+/// call they belong to by `index`, and calls are kept in a list by it.
+const MAX_STREAMED_TOOL_CALLS: usize = 128;
+
+/// OpenAI's server-sent events: `data:` lines until `data: [DONE]`. Comment
+/// lines (`:`) and other SSE fields are ignored; usage is taken from
+/// whichever event carries it. Tool calls arrive in fragments keyed by
+/// `index` and are joined at the end.
+struct OpenAiStream {
+    model: String,
+    content: String,
+    partial_calls: Vec<PartialToolCall>,
+    prompt_tokens: Option<u64>,
+    completion_tokens: Option<u64>,
+    done: bool,
+}
+
+impl OpenAiStream {
+    fn new(model: String) -> Self {
+        Self {
+            model,
+            content: String::new(),
+            partial_calls: Vec::new(),
+            prompt_tokens: None,
+            completion_tokens: None,
+            done: false,
+        }
+    }
+}
+
+impl StreamParser for OpenAiStream {
+    fn line(&mut self, line: &str) -> Result<Option<String>, LlmError> {
+        let Some(data) = line.strip_prefix("data:") else { return Ok(None) };
+        let data = data.trim();
+        if data == "[DONE]" {
+            self.done = true;
+            return Ok(None);
+        }
+        let event: Value = serde_json::from_str(data)
+            .map_err(|e| LlmError::new(LlmErrorKind::Protocol, format!("Invalid stream event: {e}")))?;
+        if let Some(error) = event.get("error") {
+            let message = error.get("message").and_then(Value::as_str).unwrap_or("stream error");
+            return Err(LlmError::new(LlmErrorKind::Unavailable, message));
+        }
+        if let Some(name) = event.get("model").and_then(Value::as_str) {
+            self.model = name.to_string();
+        }
+        if let Some(usage) = event.get("usage").filter(|u| !u.is_null()) {
+            self.prompt_tokens = usage.get("prompt_tokens").and_then(Value::as_u64);
+            self.completion_tokens = usage.get("completion_tokens").and_then(Value::as_u64);
+        }
+        let fragments = event.pointer("/choices/0/delta/tool_calls").and_then(Value::as_array);
+        for fragment in fragments.into_iter().flatten() {
+            let index = fragment.get("index").and_then(Value::as_u64).unwrap_or(0);
+            let index = usize::try_from(index)
+                .ok()
+                .filter(|i| *i < MAX_STREAMED_TOOL_CALLS)
+                .ok_or_else(|| {
+                    let message = format!("Streamed tool call index {index} is over {MAX_STREAMED_TOOL_CALLS}");
+                    LlmError::new(LlmErrorKind::Protocol, message)
+                })?;
+            if self.partial_calls.len() <= index {
+                self.partial_calls.resize_with(index + 1, PartialToolCall::default);
+            }
+            self.partial_calls[index].push(fragment);
+        }
+        let delta = event
+            .pointer("/choices/0/delta/content")
+            .and_then(Value::as_str)
+            .unwrap_or("");
+        if delta.is_empty() {
+            return Ok(None);
+        }
+        self.content.push_str(delta);
+        Ok(Some(delta.to_string()))
+    }
+
+    fn finished(&self) -> bool {
+        self.done
+    }
+
+    fn finish(self) -> Result<LlmReply, LlmError> {
+        if !self.done {
+            return Err(LlmError::new(LlmErrorKind::Unavailable, "Stream ended before data: [DONE]"));
+        }
+        let tool_calls = self
+            .partial_calls
+            .into_iter()
+            .map(PartialToolCall::finish)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(LlmReply {
+            content: self.content,
+            tool_calls,
+            model: self.model,
+            prompt_tokens: self.prompt_tokens,
+            completion_tokens: self.completion_tokens,
+        })
+    }
+}
+
+/// A message in OpenAI's shape: tool call arguments as a JSON string and
+/// `tool_call_id` on tool results.
+fn openai_message(message: &ChatMessage) -> Value {
+    let mut value = json!({ "role": message.role, "content": message.content });
+    if let Some(calls) = message.tool_calls.as_ref().filter(|c| !c.is_empty()) {
+        let calls: Vec<Value> = calls
+            .iter()
+            .enumerate()
+            .map(|(idx, call)| {
+                json!({
+                    "id": call.id.clone().unwrap_or_else(|| format!("call_{idx}")),
+                    "type": "function",
+                    "function": {
+                        "name": call.function.name,
+                        "arguments": call.function.arguments.to_string(),
+                    },
+                })
+            })
+            .collect();
+        value["tool_calls"] = json!(calls);
+        if message.content.is_empty() {
+            value["content"] = Value::Null;
+        }
+    }
+    if let Some(id) = &message.tool_call_id {
+        value["tool_call_id"] = json!(id);
+    }
+    value
+}
+
+fn openai_tool_call(call: &Value) -> Result<ToolCall, LlmError> {
+    let name = call
+        .pointer("/function/name")
+        .and_then(Value::as_str)
+        .ok_or_else(|| LlmError::new(LlmErrorKind::Protocol, "Tool call has no function name"))?;
+    let arguments = match call.pointer("/function/arguments") {
+        Some(Value::String(text)) => parse_arguments(name, text)?,
+        Some(value) => value.clone(),
+        None => Value::Object(Default::default()),
+    };
+    Ok(ToolCall {
+        id: call.get("id").and_then(Value::as_str).map(str::to_string),
+        function: ToolCallFunction { name: name.to_string(), arguments },
+    })
+}
+
+fn parse_arguments(name: &str, text: &str) -> Result<Value, LlmError> {
+    if text.trim().is_empty() {
+        return Ok(Value::Object(Default::default()));
+    }
+    serde_json::from_str(text).map_err(|e| {
+        LlmError::new(LlmErrorKind::Protocol, format!("Tool call {name} has invalid arguments: {e}"))
+    })
+}
+
+/// A streamed OpenAI tool call being put together from its fragments.
+#[derive(Default)]
+struct PartialToolCall {
+    id: Option<String>,
+    name: String,
+    arguments: String,
+}
+
+impl PartialToolCall {
+    fn push(&mut self, fragment: &Value) {
+        if let Some(id) = fragment.get("id").and_then(Value::as_str) {
+            self.id = Some(id.to_string());
+        }
+        if let Some(name) = fragment.pointer("/function/name").and_then(Value::as_str) {
+            self.name.push_str(name);
+        }
+        if let Some(arguments) = fragment.pointer("/function/arguments").and_then(Value::as_str) {
+            self.arguments.push_str(arguments);
+        }
+    }
+
+    fn finish(self) -> Result<ToolCall, LlmError> {
+        if self.name.is_empty() {
+            return Err(LlmError::new(LlmErrorKind::Protocol, "Streamed tool call has no function name"));
+        }
+        let arguments = parse_arguments(&self.name, &self.arguments)?;
+        Ok(ToolCall { id: self.id, function: ToolCallFunction { name: self.name, arguments } })
+    }
+}
+
+/// Build the backend for `entity`. `provider` is the registry entry for
+/// `entity.provider`, if any; `key` is `None` for keyless providers.
+pub fn backend_for(
+    http: &Client,
+    entity: &AiEntity,
+    provider: Option<&ProviderConfig>,
+    key: Option<&SecretString>,
+) -> Result<Box<dyn LlmBackend>, LlmError> {
+    let endpoint = entity
+        .custom_base_url
+        .as_deref()
+        .or(provider.map(|p| p.api_endpoint.as_str()))
+        .ok_or_else(|| {
+            LlmError::new(
+                LlmErrorKind::Config,
+                format!("Unknown provider '{}' and no custom base URL", entity.provider),
+            )
+        })?;
+
+    if entity.provider == "ollama" {
+        let client = OllamaClient::new(Some(replace_path(endpoint, "")));
+        return Ok(Box::new(OllamaBackend::new(client, entity.model.clone())));
+    }
+
+    let url = match &entity.custom_base_url {
+        Some(base) => chat_completions_url(base),
+        None => match entity.provider.as_str() {
+            "anthropic" => replace_path(endpoint, "/v1/chat/completions"),
+            "google" => replace_path(endpoint, "/v1beta/openai/chat/completions"),
+            "cohere" => replace_path(endpoint, "/compatibility/v1/chat/completions"),
+            "huggingface" => "https://router.huggingface.co/v1/chat/completions".to_string(),
+            _ => chat_completions_url(endpoint),
+        },
+    };
+
+    let mut headers = Vec::new();
+    if let Some(key) = key {
+        let header = entity
+            .key_header
+            .as_deref()
+            .or(provider.and_then(|p| p.key_header.as_deref()))
+            .unwrap_or("Authorization");
+        let prefix = entity
+            .key_prefix
+            .as_deref()
+            .or(provider.and_then(|p| p.key_prefix.as_deref()))
+            .unwrap_or("Bearer ");
+        headers.push((header.to_string(), format!("{prefix}{}", key.expose_secret())));
+    }
+    Ok(Box::new(OpenAiBackend::new(http.clone(), url, headers, entity.model.clone())))
+}
+
+/// A full chat endpoint is kept as is; a base URL such as
+/// `https://relay.example.com/v1` gets `/chat/completions` appended.
+fn chat_completions_url(endpoint: &str) -> String {
+    let trimmed = endpoint.trim_end_matches('/');
+    if trimmed.ends_with("/chat/completions") || trimmed.ends_with("/text/chatcompletion_v2") {
+        trimmed.to_string()
+    } else {
+        format!("{trimmed}/chat/completions")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed<P: StreamParser>(parser: &mut P, lines: &[&str]) -> Result<Vec<String>, LlmError> {
+        let mut deltas = Vec::new();
+        for line in lines {
+            deltas.extend(parser.line(line)?);
+        }
+        Ok(deltas)
+    }
+
+    fn openai(model: &str) -> OpenAiBackend {
+        OpenAiBackend::new(Client::new(), "http://localhost/v1/chat/completions".to_string(), Vec::new(), model)
+    }
+
+    #[test]
+    fn streamed_openai_bodies_ask_for_usage() {
+        let request = LlmRequest::prompt(None, "hi");
+        let body = openai("gpt-4o").body(&request, true);
+        assert_eq!(body["stream"], json!(true));
+        assert_eq!(body["stream_options"], json!({ "include_usage": true }));
+
+        let body = openai("gpt-4o").body(&request, false);
+        assert_eq!(body["stream"], json!(false));
+        assert!(body.get("stream_options").is_none());
+    }
+
+    #[test]
+    fn ndjson_frames_build_the_reply() {
+        let mut stream = OllamaStream::new("llama3".to_string());
+        let deltas = feed(
+            &mut stream,
+            &[
+                r#"{"model":"llama3:8b","message":{"role":"assistant","content":"Hel"},"done":false}"#,
+                "",
+                r#"{"message":{"role":"assistant","content":"","tool_calls":[{"function":{"name":"add","arguments":{"a":1}}}]},"done":false}"#,
+                r#"{"message":{"role":"assistant","content":"lo"},"done":false}"#,
+                r#"{"model":"llama3:8b","message":{"role":"assistant","content":""},"done":true,"prompt_eval_count":7,"eval_count":2}"#,
+            ],
+        )
+        .expect("parsed");
+        assert_eq!(deltas, ["Hel", "lo"]);
+        assert!(stream.finished());
+
+        let reply = stream.finish().expect("reply");
+        assert_eq!(reply.content, "Hello");
+        assert_eq!(reply.model, "llama3:8b");
+        assert_eq!((reply.prompt_tokens, reply.completion_tokens), (Some(7), Some(2)));
+        assert_eq!(reply.tool_calls.len(), 1);
+        assert_eq!(reply.tool_calls[0].function.name, "add");
+        assert_eq!(reply.tool_calls[0].function.arguments, json!({ "a": 1 }));
+    }
+
+    #[test]
+    fn ndjson_without_a_final_frame_is_unavailable() {
+        let mut stream = OllamaStream::new("llama3".to_string());
+        feed(&mut stream, &[r#"{"message":{"content":"Hel"},"done":false}"#]).expect("parsed");
+        assert!(!stream.finished());
+        let err = stream.finish().expect_err("cut short");
+        assert_eq!(err.kind, LlmErrorKind::Unavailable);
+    }
+
+    #[test]
+    fn ndjson_errors_and_garbage_are_reported() {
+        let err = OllamaStream::new("m".to_string())
+            .line(r#"{"error":"model is loading"}"#)
+            .expect_err("error frame");
+        assert_eq!(err.kind, LlmErrorKind::Unavailable);
+        assert_eq!(err.message, "model is loading");
+
+        let err = OllamaStream::new("m".to_string()).line("{not json").expect_err("garbage");
+        assert_eq!(err.kind, LlmErrorKind::Protocol);
+    }
+
+    #[test]
+    fn sse_events_build_the_reply() {
+        let mut stream = OpenAiStream::new("gpt-4o".to_string());
+        let deltas = feed(
+            &mut stream,
+            &[
+                ": keep-alive",
+                "event: message",
+                r#"data: {"model":"gpt-4o-2024-08-06","choices":[{"delta":{"role":"assistant","content":"Hel"}}]}"#,
+                "",
+                r#"data:{"choices":[{"delta":{"content":"lo"}}],"usage":null}"#,
+                r#"data: {"choices":[],"usage":{"prompt_tokens":9,"completion_tokens":2}}"#,
+                "data: [DONE]",
+            ],
+        )
+        .expect("parsed");
+        assert_eq!(deltas, ["Hel", "lo"]);
+        assert!(stream.finished());
+
+        let reply = stream.finish().expect("reply");
+        assert_eq!(reply.content, "Hello");
+        assert_eq!(reply.model, "gpt-4o-2024-08-06");
+        assert_eq!((reply.prompt_tokens, reply.completion_tokens), (Some(9), Some(2)));
+        assert!(reply.tool_calls.is_empty());
+    }
+
+    #[test]
+    fn sse_tool_call_fragments_are_joined_by_index() {
+        let mut stream = OpenAiStream::new("gpt-4o".to_string());
+        feed(
+            &mut stream,
+            &[
+                r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_a","function":{"name":"add","arguments":"{\"a\":"}}]}}]}"#,
+                r#"data: {"choices":[{"delta":{"tool_calls":[{"index":1,"id":"call_b","function":{"name":"now","arguments":""}}]}}]}"#,
+                r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"1}"}}]}}]}"#,
+                "data: [DONE]",
+            ],
+        )
+        .expect("parsed");
+
+        let calls = stream.finish().expect("reply").tool_calls;
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].id.as_deref(), Some("call_a"));
+        assert_eq!(calls[0].function.arguments, json!({ "a": 1 }));
+        assert_eq!(calls[1].function.name, "now");
+        assert_eq!(calls[1].function.arguments, json!({}));
+    }
+
+    #[test]
+    fn sse_without_done_is_unavailable() {
+        let mut stream = OpenAiStream::new("gpt-4o".to_string());
+        feed(&mut stream, &[r#"data: {"choices":[{"delta":{"content":"Hel"}}]}"#]).expect("parsed");
+        assert!(!stream.finished());
+        let err = stream.finish().expect_err("cut short");
+        assert_eq!(err.kind, LlmErrorKind::Unavailable);
+    }
+
+    #[test]
+    fn sse_tool_call_indexes_are_capped() {
+        let mut stream = OpenAiStream::new("gpt-4o".to_string());
+        let line = format!(
+            r#"data: {{"choices":[{{"delta":{{"tool_calls":[{{"index":{},"function":{{"name":"x"}}}}]}}}}]}}"#,
+            u64::MAX
+        );
+        let err = stream.line(&line).expect_err("huge index");
+        assert_eq!(err.kind, LlmErrorKind::Protocol);
+        assert!(stream.partial_calls.is_empty());
+
+        let last = MAX_STREAMED_TOOL_CALLS - 1;
+        let line = format!(r#"data: {{"choices":[{{"delta":{{"tool_calls":[{{"index":{last},"function":{{"name":"x"}}}}]}}}}]}}"#);
+        stream.line(&line).expect("last allowed index");
+        assert_eq!(stream.partial_calls.len(), MAX_STREAMED_TOOL_CALLS);
+    }
+
+    #[test]
+    fn sse_errors_and_garbage_are_reported() {
+        let err = OpenAiStream::new("m".to_string())
+            .line(r#"data: {"error":{"message":"overloaded"}}"#)
+            .expect_err("error event");
+        assert_eq!(err.kind, LlmErrorKind::Unavailable);
+        assert_eq!(err.message, "overloaded");
+
+        let err = OpenAiStream::new("m".to_string()).line("data: {not json").expect_err("garbage");
+        assert_eq!(err.kind, LlmErrorKind::Protocol);
+    }
+
+    #[test]
+    fn tool_calls_go_out_in_openai_form() {
+        let calls = vec![
+            ToolCall {
+                id: Some("call_a".to_string()),
+                function: ToolCallFunction { name: "add".to_string(), arguments: json!({ "a": 1 }) },
+            },
+            ToolCall { id: None, function: ToolCallFunction { name: "now".to_string(), arguments: json!({}) } },
+        ];
+        let assistant = ChatMessage { tool_calls: Some(calls.clone()), ..ChatMessage::new("assistant", "") };
+        let value = openai_message(&assistant);
+        assert_eq!(value["content"], Value::Null);
+        assert_eq!(
+            value["tool_calls"],
+            json!([
+                { "id": "call_a", "type": "function", "function": { "name": "add", "arguments": "{\"a\":1}" } },
+                { "id": "call_1", "type": "function", "function": { "name": "now", "arguments": "{}" } },
+            ])
+        );
+
+        let result = openai_message(&ChatMessage::tool_result(&calls[0], "2"));
+        assert_eq!(result, json!({ "role": "tool", "content": "2", "tool_call_id": "call_a" }));
+        assert!(openai_message(&ChatMessage::new("user", "hi")).get("tool_calls").is_none());
+    }
+
+    #[test]
+    fn non_streamed_openai_tool_calls_are_parsed() {
+        let call = openai_tool_call(&json!({
+            "id": "call_a",
+            "type": "function",
+            "function": { "name": "add", "arguments": "{\"a\":1,\"b\":2}" },
+        }))
+        .expect("string arguments");
+        assert_eq!(call.id.as_deref(), Some("call_a"));
+        assert_eq!(call.function.name, "add");
+        assert_eq!(call.function.arguments, json!({ "a": 1, "b": 2 }));
+
+        // Some relays send the arguments as an object, or leave them out.
+        let call = openai_tool_call(&json!({ "function": { "name": "add", "arguments": { "a": 1 } } })).expect("object");
+        assert_eq!((call.id, call.function.arguments), (None, json!({ "a": 1 })));
+        let call = openai_tool_call(&json!({ "function": { "name": "now", "arguments": "" } })).expect("empty");
+        assert_eq!(call.function.arguments, json!({}));
+        let call = openai_tool_call(&json!({ "function": { "name": "now" } })).expect("missing");
+        assert_eq!(call.function.arguments, json!({}));
+
+        let err = openai_tool_call(&json!({ "function": { "name": "add", "arguments": "{\"a\":" } })).expect_err("cut off");
+        assert_eq!(err.kind, LlmErrorKind::Protocol);
+        assert!(err.message.contains("add"), "{}", err.message);
+        let err = openai_tool_call(&json!({ "function": { "arguments": "{}" } })).expect_err("no name");
+        assert_eq!(err.kind, LlmErrorKind::Protocol);
+    }
+
+    async fn drive(lines: &[&str]) -> Vec<Result<LlmStreamEvent, LlmError>> {
+        let (tx, rx) = mpsc::channel(16);
+        for line in lines {
+            tx.send(Ok(line.to_string())).await.expect("send");
+        }
+        drop(tx);
+        let mut events = drive_stream(rx, OpenAiStream::new("gpt-4o".to_string()));
+        let mut out = Vec::new();
+        while let Some(event) = events.recv().await {
+            out.push(event);
+        }
+        out
+    }
+
+    #[tokio::test]
+    async fn a_stream_ends_with_done_or_an_error() {
+        let delta = r#"data: {"choices":[{"delta":{"content":"Hi"}}]}"#;
+
+        let events = drive(&[delta, "data: [DONE]", delta]).await;
+        assert!(matches!(&events[..], [Ok(LlmStreamEvent::Delta(d)), Ok(LlmStreamEvent::Done(reply))]
+            if d == "Hi" && reply.content == "Hi"));
+
+        let events = drive(&[delta]).await;
+        assert!(matches!(&events[..], [Ok(LlmStreamEvent::Delta(_)), Err(e)]
+            if e.kind == LlmErrorKind::Unavailable));
+    }
+}
@@ -1,4 +1,14 @@
 pub mod api_manager;
+pub mod vault_file;
+pub mod vault_bundle;
+pub mod key_validator;
+pub mod key_pool;
+pub mod model_discovery;
+pub mod llm_backend;
+pub mod failover;
+pub mod response_cache;
+pub mod chat_session;
+pub mod structured;
 pub mod agent_scheduler;
 pub mod compilation_scheduler;
 pub mod network_discovery;
@@ -29,8 +39,9 @@ pub struct BackendServices {
 
 pub use api_manager::ApiKeyManager;
 pub use agent_scheduler::AgentScheduler;
+pub use failover::FailoverRouter;
 pub use compilation_scheduler::CompilationScheduler;
-pub use network_discovery::NetworkDiscovery;
+pub use network_discovery::{DiscoveryOptions, NetworkDiscovery};
 pub use resource_manager::ResourceManager;
 pub use c_compiler::CCompilationScheduler;
 pub use ollama_client::OllamaManager;
@@ -0,0 +1,201 @@
+//! Live model discovery.
+//!
+//! Queries the same model-listing endpoint as key validation (`/v1/models`
+//! for OpenAI-compatible providers and relays, `/api/tags` for Ollama), keeps
+//! the answer for [`MODEL_CACHE_TTL`] and merges it with the catalog models.
+
+use crate::backend::key_validator::ValidationProbe;
+use crate::shared::models::ModelEntry;
+use crate::shared::utils::truncate;
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a successful model list is reused before querying again.
+pub const MODEL_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone)]
+pub struct CachedModels {
+    pub endpoint: String,
+    pub fetched_at: DateTime<Utc>,
+    pub remote: Vec<String>,
+    fetched: Instant,
+}
+
+/// Successful listings keyed by target (entity name or provider id). Failures
+/// are not cached so that a fixed key or a restarted server shows up at once.
+#[derive(Debug)]
+pub struct ModelCache {
+    entries: Mutex<HashMap<String, CachedModels>>,
+    ttl: Duration,
+}
+
+impl Default for ModelCache {
+    fn default() -> Self {
+        Self::with_ttl(MODEL_CACHE_TTL)
+    }
+}
+
+impl ModelCache {
+    /// A cache that keeps listings for `ttl` instead of [`MODEL_CACHE_TTL`].
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            ttl,
+        }
+    }
+
+    pub fn get(&self, target: &str, endpoint: &str) -> Option<CachedModels> {
+        let entries = self.entries.lock().ok()?;
+        entries
+            .get(target)
+            .filter(|c| c.endpoint == endpoint && c.fetched.elapsed() < self.ttl)
+            .cloned()
+    }
+
+    pub fn put(&self, target: &str, endpoint: &str, remote: Vec<String>) -> CachedModels {
+        let cached = CachedModels {
+            endpoint: endpoint.to_string(),
+            fetched_at: Utc::now(),
+            remote,
+            fetched: Instant::now(),
+        };
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(target.to_string(), cached.clone());
+        }
+        cached
+    }
+
+    pub fn invalidate(&self, target: &str) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.remove(target);
+        }
+    }
+}
+
+/// Run `probe` and return the model ids it reports.
+pub async fn fetch_remote_models(client: &Client, probe: &ValidationProbe) -> Result<Vec<String>, String> {
+    let response = probe.send(client).await.map_err(|e| e.to_string())?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("HTTP {}: {}", status.as_u16(), truncate(&body, 300)));
+    }
+    let body: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid model list: {e}"))?;
+    Ok(parse_model_ids(&body))
+}
+
+/// Accepts OpenAI-style `{"data": [{"id": ..}]}`, Ollama `{"models": [{"name": ..}]}`
+/// and Gemini `{"models": [{"name": "models/.."}]}`. Returns sorted, unique ids.
+pub fn parse_model_ids(body: &Value) -> Vec<String> {
+    let items = body
+        .get("data")
+        .or_else(|| body.get("models"))
+        .and_then(Value::as_array)
+        .or_else(|| body.as_array());
+    let mut ids: Vec<String> = items
+        .into_iter()
+        .flatten()
+        .filter_map(|item| {
+            item.as_str()
+                .or_else(|| ["id", "name", "model"].iter().find_map(|k| item.get(k)?.as_str()))
+        })
+        .map(|id| id.strip_prefix("models/").unwrap_or(id).to_string())
+        .filter(|id| !id.is_empty())
+        .collect();
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+/// Remote models first (in their sorted order), then catalog models the endpoint did not list.
+pub fn merge_with_catalog(remote: &[String], catalog: &[String]) -> Vec<ModelEntry> {
+    let mut merged: Vec<ModelEntry> = remote
+        .iter()
+        .map(|id| ModelEntry {
+            id: id.clone(),
+            remote: true,
+            in_catalog: false,
+        })
+        .collect();
+    for id in catalog {
+        match merged.iter_mut().find(|m| m.matches(id)) {
+            Some(entry) => entry.in_catalog = true,
+            None => merged.push(ModelEntry {
+                id: id.clone(),
+                remote: false,
+                in_catalog: true,
+            }),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn cached_listings_expire_after_the_ttl() {
+        let cache = ModelCache::with_ttl(Duration::from_millis(50));
+        cache.put("openai", "https://api.openai.com/v1/models", ids(&["gpt-4o"]));
+        let hit = cache.get("openai", "https://api.openai.com/v1/models").expect("fresh");
+        assert_eq!(hit.remote, ["gpt-4o"]);
+
+        std::thread::sleep(Duration::from_millis(80));
+        assert!(cache.get("openai", "https://api.openai.com/v1/models").is_none());
+    }
+
+    #[test]
+    fn a_changed_endpoint_misses_the_cache() {
+        let cache = ModelCache::default();
+        cache.put("relay", "https://old.example/v1/models", ids(&["gpt-4o"]));
+        assert!(cache.get("relay", "https://new.example/v1/models").is_none());
+        assert!(cache.get("other", "https://old.example/v1/models").is_none());
+        assert!(cache.get("relay", "https://old.example/v1/models").is_some());
+
+        cache.invalidate("relay");
+        assert!(cache.get("relay", "https://old.example/v1/models").is_none());
+    }
+
+    #[test]
+    fn model_ids_are_read_from_each_listing_shape() {
+        let openai = json!({ "object": "list", "data": [{ "id": "gpt-4o" }, { "id": "gpt-4o-mini" }] });
+        assert_eq!(parse_model_ids(&openai), ["gpt-4o", "gpt-4o-mini"]);
+
+        let ollama = json!({ "models": [{ "name": "qwen2:7b", "model": "qwen2:7b" }, { "name": "llama3:latest" }] });
+        assert_eq!(parse_model_ids(&ollama), ["llama3:latest", "qwen2:7b"]);
+
+        let gemini = json!({ "models": [{ "name": "models/gemini-1.5-pro" }, { "name": "models/gemini-1.5-flash" }] });
+        assert_eq!(parse_model_ids(&gemini), ["gemini-1.5-flash", "gemini-1.5-pro"]);
+
+        let duplicates = json!({ "data": [{ "id": "b" }, { "id": "a" }, { "id": "b" }, { "id": "" }] });
+        assert_eq!(parse_model_ids(&duplicates), ["a", "b"]);
+        assert!(parse_model_ids(&json!({ "error": "nope" })).is_empty());
+    }
+
+    #[test]
+    fn latest_tags_match_untagged_catalog_ids() {
+        let merged = merge_with_catalog(&ids(&["llama3:latest", "qwen2:7b"]), &ids(&["llama3", "mistral"]));
+        let summary: Vec<_> = merged.iter().map(|m| (m.id.as_str(), m.remote, m.in_catalog)).collect();
+        assert_eq!(
+            summary,
+            [
+                ("llama3:latest", true, true),
+                ("qwen2:7b", true, false),
+                ("mistral", false, true),
+            ]
+        );
+    }
+}
@@ -2,10 +2,12 @@
 //!
 //! - `types`: API request/response types
 //! - `client`: Low-level HTTP client (OllamaClient)
+//! - `stream`: Line/NDJSON decoding of streamed responses
 //! - `manager`: High-level manager with usage stats (OllamaManager)
 
 pub mod types;
 pub mod client;
+pub mod stream;
 pub mod manager;
 
 pub use types::*;
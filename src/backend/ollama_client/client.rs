@@ -2,12 +2,17 @@
 
 use reqwest::Client;
 use std::time::Duration;
+use tokio::sync::mpsc;
+use super::stream::{spawn_ndjson, PULL_IDLE_TIMEOUT, STREAM_IDLE_TIMEOUT};
 use super::types::*;
 
 #[derive(Debug, Clone)]
 pub struct OllamaClient {
     pub(super) base_url: String,
     pub(super) client: Client,
+    /// Client without an overall timeout, for streamed responses; streams
+    /// only fail when idle (see `stream::STREAM_IDLE_TIMEOUT`).
+    pub(super) stream_client: Client,
     pub(super) connection_timeout: Duration,
 }
 
@@ -20,10 +25,15 @@ impl OllamaClient {
             .connect_timeout(Duration::from_secs(10))
             .build()
             .expect("Failed to create HTTP client");
+        let stream_client = Client::builder()
+            .connect_timeout(Duration::from_secs(10))
+            .build()
+            .expect("Failed to create HTTP client");
 
         Self {
             base_url,
             client,
+            stream_client,
             connection_timeout: Duration::from_secs(5),
         }
     }
@@ -33,6 +43,43 @@ impl OllamaClient {
         self
     }
 
+    /// A request to `path` on this server, for callers that handle the raw response.
+    pub fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.client.request(method, format!("{}{}", self.base_url, path))
+    }
+
+    /// Like [`request`](Self::request) but without an overall timeout, for streamed responses.
+    pub fn stream_request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.stream_client.request(method, format!("{}{}", self.base_url, path))
+    }
+
+    /// POST `body` to `path` with streaming enabled and yield the NDJSON frames.
+    async fn post_stream<B, T>(
+        &self,
+        path: &str,
+        body: &B,
+        idle: Duration,
+    ) -> Result<mpsc::Receiver<Result<T, String>>, String>
+    where
+        B: serde::Serialize,
+        T: serde::de::DeserializeOwned + Send + 'static,
+    {
+        let response = self
+            .stream_request(reqwest::Method::POST, path)
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to send stream request: {}", e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(format!("HTTP error {}: {}", status, error_text));
+        }
+
+        Ok(spawn_ndjson(response, idle))
+    }
+
     pub async fn check_connection(&self) -> OllamaConnectionStatus {
         let url = format!("{}/api/version", self.base_url);
 
@@ -118,9 +165,70 @@ impl OllamaClient {
         Ok(model_info)
     }
 
+    /// Pull a model and wait for it to finish. Runs without an overall
+    /// timeout; see [`pull_model_stream`](Self::pull_model_stream).
     pub async fn pull_model(&self, model_name: &str) -> Result<String, String> {
-        let url = format!("{}/api/pull", self.base_url);
-        let request = PullRequest { name: model_name.to_string(), stream: Some(false) };
+        let progress = self.pull_model_stream(model_name).await?;
+        wait_for_success(progress, "Pull", model_name).await?;
+        Ok(format!("Model '{}' pulled successfully", model_name))
+    }
+
+    /// Pull a model, reporting each status line as it arrives. The stream
+    /// ends after the `"success"` frame or the first error (an `error` frame
+    /// from Ollama becomes `Err`). Dropping the receiver cancels the pull;
+    /// layers already downloaded are kept by Ollama and resumed next time.
+    pub async fn pull_model_stream(
+        &self,
+        model_name: &str,
+    ) -> Result<mpsc::Receiver<Result<PullProgress, String>>, String> {
+        let request = PullRequest { name: model_name.to_string(), stream: Some(true) };
+        self.progress_stream("/api/pull", &request).await
+    }
+
+    /// Push a model to its registry (the name must include the namespace,
+    /// e.g. `user/model:tag`) and wait for it to finish.
+    pub async fn push_model(&self, model_name: &str, insecure: bool) -> Result<String, String> {
+        let progress = self.push_model_stream(model_name, insecure).await?;
+        wait_for_success(progress, "Push", model_name).await?;
+        Ok(format!("Model '{}' pushed successfully", model_name))
+    }
+
+    /// Push with per-layer progress; behaves like [`pull_model_stream`](Self::pull_model_stream).
+    pub async fn push_model_stream(
+        &self,
+        model_name: &str,
+        insecure: bool,
+    ) -> Result<mpsc::Receiver<Result<PullProgress, String>>, String> {
+        let request = PushRequest {
+            model: model_name.to_string(),
+            insecure: insecure.then_some(true),
+            stream: Some(true),
+        };
+        self.progress_stream("/api/push", &request).await
+    }
+
+    /// Create a model (e.g. from [`CreateRequest::from_modelfile`]) and wait
+    /// for it to finish.
+    pub async fn create_model(&self, request: CreateRequest) -> Result<String, String> {
+        let model = request.model.clone();
+        let progress = self.create_model_stream(request).await?;
+        wait_for_success(progress, "Create", &model).await?;
+        Ok(format!("Model '{}' created successfully", model))
+    }
+
+    /// Create a model, reporting each status line as it arrives.
+    pub async fn create_model_stream(
+        &self,
+        mut request: CreateRequest,
+    ) -> Result<mpsc::Receiver<Result<PullProgress, String>>, String> {
+        request.stream = Some(true);
+        self.progress_stream("/api/create", &request).await
+    }
+
+    /// Copy `source` to a new name; both names then share the same blobs.
+    pub async fn copy_model(&self, source: &str, destination: &str) -> Result<String, String> {
+        let url = format!("{}/api/copy", self.base_url);
+        let request = CopyRequest { source: source.to_string(), destination: destination.to_string() };
 
         let response = self
             .client
@@ -128,13 +236,68 @@ impl OllamaClient {
             .json(&request)
             .send()
             .await
-            .map_err(|e| format!("Failed to pull model: {}", e))?;
+            .map_err(|e| format!("Failed to copy model: {}", e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(format!("HTTP error {}: {}", status, error_text));
+        }
+
+        Ok(format!("Model '{}' copied to '{}'", source, destination))
+    }
+
+    /// Models currently loaded in memory, with their VRAM share.
+    pub async fn list_running(&self) -> Result<Vec<RunningModel>, String> {
+        let url = format!("{}/api/ps", self.base_url);
+
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to list running models: {}", e))?;
 
         if !response.status().is_success() {
             return Err(format!("HTTP error: {}", response.status()));
         }
 
-        Ok(format!("Model '{}' pulled successfully", model_name))
+        let running: RunningModelsResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+        Ok(running.models)
+    }
+
+    /// Stream the status lines of a pull, push or create. `error` frames
+    /// become `Err`; the stream ends after `"success"` or the first error.
+    async fn progress_stream<B: serde::Serialize>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<mpsc::Receiver<Result<PullProgress, String>>, String> {
+        let mut frames = self
+            .post_stream::<_, serde_json::Value>(path, body, PULL_IDLE_TIMEOUT)
+            .await?;
+
+        let (tx, rx) = mpsc::channel(64);
+        tokio::spawn(async move {
+            while let Some(frame) = frames.recv().await {
+                let progress = frame.and_then(|frame| {
+                    if let Some(error) = frame.get("error").and_then(|e| e.as_str()) {
+                        return Err(format!("Ollama reported: {}", error));
+                    }
+                    serde_json::from_value::<PullProgress>(frame)
+                        .map_err(|e| format!("Invalid progress frame: {}", e))
+                });
+                let last = progress.as_ref().map_or(true, PullProgress::is_success);
+                if tx.send(progress).await.is_err() || last {
+                    return;
+                }
+            }
+        });
+        Ok(rx)
     }
 
     pub async fn delete_model(&self, model_name: &str) -> Result<String, String> {
@@ -156,8 +319,9 @@ impl OllamaClient {
         Ok(format!("Model '{}' deleted successfully", model_name))
     }
 
-    pub async fn chat(&self, request: ChatRequest) -> Result<ChatResponse, String> {
+    pub async fn chat(&self, mut request: ChatRequest) -> Result<ChatResponse, String> {
         let url = format!("{}/api/chat", self.base_url);
+        request.stream = Some(false);
 
         let response = self
             .client
@@ -181,24 +345,33 @@ impl OllamaClient {
         Ok(chat_response)
     }
 
+    /// Stream a chat reply. Each frame carries the next piece of
+    /// `message.content`; the last one has `done: true` and the token counts.
+    /// Dropping the receiver cancels the generation.
+    pub async fn chat_stream(
+        &self,
+        mut request: ChatRequest,
+    ) -> Result<mpsc::Receiver<Result<ChatResponse, String>>, String> {
+        request.stream = Some(true);
+        self.post_stream("/api/chat", &request, STREAM_IDLE_TIMEOUT).await
+    }
+
     pub async fn chat_simple(&self, model: &str, prompt: &str) -> Result<String, String> {
         let request = ChatRequest {
             model: model.to_string(),
-            messages: vec![ChatMessage {
-                role: "user".to_string(),
-                content: prompt.to_string(),
-                images: None,
-            }],
+            messages: vec![ChatMessage::new("user", prompt)],
             stream: Some(false),
             format: None,
             options: None,
+            tools: None,
         };
         let response = self.chat(request).await?;
         Ok(response.message.content)
     }
 
-    pub async fn generate(&self, request: GenerateRequest) -> Result<GenerateResponse, String> {
+    pub async fn generate(&self, mut request: GenerateRequest) -> Result<GenerateResponse, String> {
         let url = format!("{}/api/generate", self.base_url);
+        request.stream = Some(false);
 
         let response = self
             .client
@@ -222,6 +395,15 @@ impl OllamaClient {
         Ok(generate_response)
     }
 
+    /// Stream a completion; frames carry the next piece of `response`.
+    pub async fn generate_stream(
+        &self,
+        mut request: GenerateRequest,
+    ) -> Result<mpsc::Receiver<Result<GenerateResponse, String>>, String> {
+        request.stream = Some(true);
+        self.post_stream("/api/generate", &request, STREAM_IDLE_TIMEOUT).await
+    }
+
     pub async fn generate_simple(&self, model: &str, prompt: &str) -> Result<String, String> {
         let request = GenerateRequest {
             model: model.to_string(),
@@ -235,8 +417,25 @@ impl OllamaClient {
     }
 
     pub async fn embed(&self, model: &str, input: &str) -> Result<Vec<f32>, String> {
+        let vectors = self.embed_input(model, EmbedInput::Single(input.to_string())).await?;
+        vectors.into_iter().next().ok_or_else(|| "Response has no embedding".to_string())
+    }
+
+    /// Embed several inputs in one request; vectors come back in input order.
+    pub async fn embed_batch(&self, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        if inputs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let vectors = self.embed_input(model, EmbedInput::Batch(inputs.to_vec())).await?;
+        if vectors.len() != inputs.len() {
+            return Err(format!("Expected {} embeddings, got {}", inputs.len(), vectors.len()));
+        }
+        Ok(vectors)
+    }
+
+    async fn embed_input(&self, model: &str, input: EmbedInput) -> Result<Vec<Vec<f32>>, String> {
         let url = format!("{}/api/embed", self.base_url);
-        let request = EmbedRequest { model: model.to_string(), input: input.to_string() };
+        let request = EmbedRequest { model: model.to_string(), input };
 
         let response = self
             .client
@@ -255,7 +454,7 @@ impl OllamaClient {
             .await
             .map_err(|e| format!("Failed to parse response: {}", e))?;
 
-        Ok(embed_response.embedding)
+        Ok(embed_response.into_vectors())
     }
 
     pub async fn get_version(&self) -> Result<String, String> {
@@ -280,3 +479,17 @@ impl OllamaClient {
         Ok(version_response.version)
     }
 }
+
+/// Drain a progress stream, failing unless it ends with `"success"`.
+async fn wait_for_success(
+    mut progress: mpsc::Receiver<Result<PullProgress, String>>,
+    action: &str,
+    model_name: &str,
+) -> Result<(), String> {
+    while let Some(frame) = progress.recv().await {
+        if frame?.is_success() {
+            return Ok(());
+        }
+    }
+    Err(format!("{} of '{}' ended before it succeeded", action, model_name))
+}
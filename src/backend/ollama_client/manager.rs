@@ -1,9 +1,11 @@
 //! High-level manager wrapping OllamaClient with usage tracking.
 
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{mpsc, RwLock};
 use super::types::*;
 use super::client::OllamaClient;
+use crate::backend::resource_manager::ResourceManager;
+use crate::shared::models::LoadedModel;
 
 #[derive(Clone)]
 pub struct OllamaManager {
@@ -39,6 +41,87 @@ impl OllamaManager {
         result
     }
 
+    /// Pull with progress; counts as a request once `"success"` arrives.
+    pub async fn pull_model_stream(
+        &self,
+        model_name: &str,
+    ) -> Result<mpsc::Receiver<Result<PullProgress, String>>, String> {
+        let frames = self.client.read().await.pull_model_stream(model_name).await?;
+        Ok(self.count_on_success(frames))
+    }
+
+    pub async fn push_model(&self, model_name: &str, insecure: bool) -> Result<String, String> {
+        let result = self.client.read().await.push_model(model_name, insecure).await;
+        if result.is_ok() {
+            self.usage_stats.write().await.total_requests += 1;
+        }
+        result
+    }
+
+    pub async fn push_model_stream(
+        &self,
+        model_name: &str,
+        insecure: bool,
+    ) -> Result<mpsc::Receiver<Result<PullProgress, String>>, String> {
+        let frames = self.client.read().await.push_model_stream(model_name, insecure).await?;
+        Ok(self.count_on_success(frames))
+    }
+
+    pub async fn create_model(&self, request: CreateRequest) -> Result<String, String> {
+        let result = self.client.read().await.create_model(request).await;
+        if result.is_ok() {
+            self.usage_stats.write().await.total_requests += 1;
+        }
+        result
+    }
+
+    pub async fn create_model_stream(
+        &self,
+        request: CreateRequest,
+    ) -> Result<mpsc::Receiver<Result<PullProgress, String>>, String> {
+        let frames = self.client.read().await.create_model_stream(request).await?;
+        Ok(self.count_on_success(frames))
+    }
+
+    pub async fn copy_model(&self, source: &str, destination: &str) -> Result<String, String> {
+        self.client.read().await.copy_model(source, destination).await
+    }
+
+    pub async fn list_running(&self) -> Result<Vec<RunningModel>, String> {
+        self.client.read().await.list_running().await
+    }
+
+    /// Publish installed and loaded models through the node's resource
+    /// report; returns the loaded models.
+    pub async fn report_resources(&self, resource_manager: &ResourceManager) -> Result<Vec<RunningModel>, String> {
+        let installed = self.list_models().await?.into_iter().map(|m| m.name).collect();
+        let running = self.list_running().await?;
+        resource_manager
+            .report_ollama_models(installed, running.iter().map(LoadedModel::from).collect())
+            .await;
+        Ok(running)
+    }
+
+    /// Forward progress frames, counting a request once `"success"` arrives.
+    fn count_on_success(
+        &self,
+        mut frames: mpsc::Receiver<Result<PullProgress, String>>,
+    ) -> mpsc::Receiver<Result<PullProgress, String>> {
+        let usage_stats = self.usage_stats.clone();
+        let (tx, rx) = mpsc::channel(64);
+        tokio::spawn(async move {
+            while let Some(frame) = frames.recv().await {
+                if frame.as_ref().is_ok_and(PullProgress::is_success) {
+                    usage_stats.write().await.total_requests += 1;
+                }
+                if tx.send(frame).await.is_err() {
+                    return;
+                }
+            }
+        });
+        rx
+    }
+
     pub async fn delete_model(&self, model_name: &str) -> Result<String, String> {
         self.client.read().await.delete_model(model_name).await
     }
@@ -46,23 +129,34 @@ impl OllamaManager {
     pub async fn chat(&self, request: ChatRequest) -> Result<ChatResponse, String> {
         let model = request.model.clone();
         let response = self.client.read().await.chat(request).await?;
-
-        let mut stats = self.usage_stats.write().await;
-        stats.total_requests += 1;
-
-        if let Some(eval_count) = response.eval_count {
-            stats.total_tokens += eval_count as u64;
-            let model_stats = stats.model_stats.entry(model).or_default();
-            model_stats.requests += 1;
-            model_stats.tokens += eval_count as u64;
-        }
-        if let Some(duration) = response.eval_duration {
-            stats.total_duration_ms += duration / 1_000_000;
-        }
-
+        record_usage(&self.usage_stats, model, response.eval_count, response.eval_duration).await;
         Ok(response)
     }
 
+    /// Stream a chat reply; usage is recorded when the final frame arrives.
+    pub async fn chat_stream(
+        &self,
+        request: ChatRequest,
+    ) -> Result<mpsc::Receiver<Result<ChatResponse, String>>, String> {
+        let model = request.model.clone();
+        let mut frames = self.client.read().await.chat_stream(request).await?;
+        let usage_stats = self.usage_stats.clone();
+        let (tx, rx) = mpsc::channel(64);
+        tokio::spawn(async move {
+            while let Some(frame) = frames.recv().await {
+                if let Ok(frame) = &frame {
+                    if frame.done {
+                        record_usage(&usage_stats, model.clone(), frame.eval_count, frame.eval_duration).await;
+                    }
+                }
+                if tx.send(frame).await.is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(rx)
+    }
+
     pub async fn chat_simple(&self, model: &str, prompt: &str) -> Result<String, String> {
         self.client.read().await.chat_simple(model, prompt).await
     }
@@ -70,23 +164,34 @@ impl OllamaManager {
     pub async fn generate(&self, request: GenerateRequest) -> Result<GenerateResponse, String> {
         let model = request.model.clone();
         let response = self.client.read().await.generate(request).await?;
-
-        let mut stats = self.usage_stats.write().await;
-        stats.total_requests += 1;
-
-        if let Some(eval_count) = response.eval_count {
-            stats.total_tokens += eval_count as u64;
-            let model_stats = stats.model_stats.entry(model).or_default();
-            model_stats.requests += 1;
-            model_stats.tokens += eval_count as u64;
-        }
-        if let Some(duration) = response.eval_duration {
-            stats.total_duration_ms += duration / 1_000_000;
-        }
-
+        record_usage(&self.usage_stats, model, response.eval_count, response.eval_duration).await;
         Ok(response)
     }
 
+    /// Stream a completion; usage is recorded when the final frame arrives.
+    pub async fn generate_stream(
+        &self,
+        request: GenerateRequest,
+    ) -> Result<mpsc::Receiver<Result<GenerateResponse, String>>, String> {
+        let model = request.model.clone();
+        let mut frames = self.client.read().await.generate_stream(request).await?;
+        let usage_stats = self.usage_stats.clone();
+        let (tx, rx) = mpsc::channel(64);
+        tokio::spawn(async move {
+            while let Some(frame) = frames.recv().await {
+                if let Ok(frame) = &frame {
+                    if frame.done {
+                        record_usage(&usage_stats, model.clone(), frame.eval_count, frame.eval_duration).await;
+                    }
+                }
+                if tx.send(frame).await.is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(rx)
+    }
+
     pub async fn generate_simple(&self, model: &str, prompt: &str) -> Result<String, String> {
         self.client.read().await.generate_simple(model, prompt).await
     }
@@ -95,6 +200,10 @@ impl OllamaManager {
         self.client.read().await.embed(model, input).await
     }
 
+    pub async fn embed_batch(&self, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        self.client.read().await.embed_batch(model, inputs).await
+    }
+
     pub async fn get_version(&self) -> Result<String, String> {
         self.client.read().await.get_version().await
     }
@@ -107,3 +216,23 @@ impl OllamaManager {
         *self.usage_stats.write().await = OllamaUsageStats::default();
     }
 }
+
+async fn record_usage(
+    usage_stats: &RwLock<OllamaUsageStats>,
+    model: String,
+    eval_count: Option<u32>,
+    eval_duration: Option<u64>,
+) {
+    let mut stats = usage_stats.write().await;
+    stats.total_requests += 1;
+
+    if let Some(eval_count) = eval_count {
+        stats.total_tokens += eval_count as u64;
+        let model_stats = stats.model_stats.entry(model).or_default();
+        model_stats.requests += 1;
+        model_stats.tokens += eval_count as u64;
+    }
+    if let Some(duration) = eval_duration {
+        stats.total_duration_ms += duration / 1_000_000;
+    }
+}
@@ -0,0 +1,118 @@
+//! Incremental reading of streamed response bodies.
+//!
+//! Ollama streams newline-delimited JSON; OpenAI-style APIs stream SSE, which
+//! is line based as well. Both are read chunk by chunk into complete lines on
+//! a background task; dropping the receiver stops the task and closes the
+//! connection.
+
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// A stream that sends nothing for this long is treated as dead. There is no
+/// overall deadline, so long generations are never cut off while tokens flow.
+pub const STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Idle limit for model pulls: Ollama reports nothing while it verifies the
+/// digest of a multi-gigabyte layer, which can take minutes on slow disks.
+pub const PULL_IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
+const STREAM_BUFFER: usize = 64;
+
+/// Splits a byte stream into `\n`-terminated lines (a trailing `\r` is dropped).
+#[derive(Debug, Default)]
+pub struct LineDecoder {
+    buf: Vec<u8>,
+}
+
+impl LineDecoder {
+    /// Append `chunk` and return every line it completed.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buf.extend_from_slice(chunk);
+        let mut lines = Vec::new();
+        while let Some(pos) = self.buf.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buf.drain(..=pos).collect();
+            lines.push(Self::decode(&line[..line.len() - 1]));
+        }
+        lines
+    }
+
+    /// The unterminated remainder, if any, once the body has ended.
+    pub fn finish(&mut self) -> Option<String> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        let rest = std::mem::take(&mut self.buf);
+        Some(Self::decode(&rest))
+    }
+
+    fn decode(line: &[u8]) -> String {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        String::from_utf8_lossy(line).into_owned()
+    }
+}
+
+/// Read `response` on a background task and send its lines as they arrive.
+/// Fails with an error if no data arrives for `idle`.
+pub fn spawn_lines(mut response: reqwest::Response, idle: Duration) -> mpsc::Receiver<Result<String, String>> {
+    let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+    tokio::spawn(async move {
+        let mut decoder = LineDecoder::default();
+        loop {
+            let chunk = match tokio::time::timeout(idle, response.chunk()).await {
+                Ok(Ok(Some(chunk))) => chunk,
+                Ok(Ok(None)) => break,
+                Ok(Err(e)) => {
+                    let _ = tx.send(Err(format!("Stream interrupted: {e}"))).await;
+                    return;
+                }
+                Err(_) => {
+                    let _ = tx
+                        .send(Err(format!("Stream idle for {}s", idle.as_secs())))
+                        .await;
+                    return;
+                }
+            };
+            for line in decoder.push(&chunk) {
+                if tx.send(Ok(line)).await.is_err() {
+                    return;
+                }
+            }
+        }
+        if let Some(rest) = decoder.finish() {
+            let _ = tx.send(Ok(rest)).await;
+        }
+    });
+    rx
+}
+
+/// Like [`spawn_lines`], parsing each non-empty line as one JSON value.
+pub fn spawn_ndjson<T>(response: reqwest::Response, idle: Duration) -> mpsc::Receiver<Result<T, String>>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    let mut lines = spawn_lines(response, idle);
+    let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+    tokio::spawn(async move {
+        while let Some(line) = lines.recv().await {
+            let item = line.and_then(|line| {
+                if line.trim().is_empty() {
+                    return Ok(None);
+                }
+                serde_json::from_str::<T>(&line)
+                    .map(Some)
+                    .map_err(|e| format!("Invalid stream frame: {e}"))
+            });
+            let item = match item {
+                Ok(None) => continue,
+                Ok(Some(item)) => Ok(item),
+                Err(e) => Err(e),
+            };
+            let failed = item.is_err();
+            if tx.send(item).await.is_err() || failed {
+                return;
+            }
+        }
+    });
+    rx
+}
@@ -35,12 +35,83 @@ pub struct OllamaModelInfo {
     pub details: ModelDetails,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: String,
+    #[serde(default)]
     pub content: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub images: Option<Vec<String>>,
+    /// Tools the model asked to run (`assistant` messages).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    /// Tool whose result this is (`tool` messages).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    /// Call this result answers; OpenAI-style APIs require it on `tool` messages.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    pub fn new(role: impl Into<String>, content: impl Into<String>) -> Self {
+        Self { role: role.into(), content: content.into(), ..Default::default() }
+    }
+
+    /// The `tool` message carrying the result of `call`.
+    pub fn tool_result(call: &ToolCall, content: impl Into<String>) -> Self {
+        Self {
+            role: "tool".to_string(),
+            content: content.into(),
+            tool_name: Some(call.function.name.clone()),
+            tool_call_id: call.id.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// A function the model may call. Serializes to the
+/// `{"type": "function", "function": {...}}` shape both Ollama and
+/// OpenAI-style APIs accept.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tool {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: ToolFunction,
+}
+
+impl Tool {
+    /// `parameters` is a JSON Schema object describing the arguments.
+    pub fn function(name: impl Into<String>, description: impl Into<String>, parameters: serde_json::Value) -> Self {
+        Self {
+            kind: "function".to_string(),
+            function: ToolFunction { name: name.into(), description: description.into(), parameters },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolFunction {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    /// Set by OpenAI-style APIs; Ollama does not number its calls.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub function: ToolCallFunction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallFunction {
+    pub name: String,
+    /// Arguments as a JSON object. OpenAI-style APIs send them as a string;
+    /// the adapter parses it.
+    #[serde(default)]
+    pub arguments: serde_json::Value,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,12 +121,51 @@ pub struct ChatRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub format: Option<String>,
+    pub format: Option<ResponseFormat>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub options: Option<ChatOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Required shape of a reply. Serializes to Ollama's `format`: the string
+/// `"json"` or the JSON Schema object itself.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResponseFormat {
+    /// Any JSON value.
+    Json,
+    /// JSON matching `schema`. OpenAI-style APIs also want a `name` for it.
+    Schema { name: String, schema: serde_json::Value },
+}
+
+impl ResponseFormat {
+    pub fn schema(name: impl Into<String>, schema: serde_json::Value) -> Self {
+        Self::Schema { name: name.into(), schema }
+    }
+}
+
+impl Serialize for ResponseFormat {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Json => serializer.serialize_str("json"),
+            Self::Schema { schema, .. } => schema.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ResponseFormat {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match serde_json::Value::deserialize(deserializer)? {
+            serde_json::Value::String(s) if s == "json" => Ok(Self::Json),
+            schema @ serde_json::Value::Object(_) => Ok(Self::schema("response", schema)),
+            other => Err(serde::de::Error::custom(format!(
+                "format must be \"json\" or a JSON Schema object, got {other}"
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ChatOptions {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub temperature: Option<f32>,
@@ -100,7 +210,7 @@ pub struct GenerateRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub format: Option<String>,
+    pub format: Option<ResponseFormat>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub options: Option<ChatOptions>,
 }
@@ -134,6 +244,34 @@ pub struct PullRequest {
     pub stream: Option<bool>,
 }
 
+/// One status line of a streamed pull, push or create. Layer transfers
+/// carry `digest`, `total` and `completed` (bytes); other steps such as
+/// `"pulling manifest"` or the final `"success"` only have `status`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PullProgress {
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed: Option<u64>,
+}
+
+impl PullProgress {
+    /// Downloaded share of the current layer, if it reports sizes.
+    pub fn fraction(&self) -> Option<f32> {
+        match (self.completed, self.total) {
+            (Some(completed), Some(total)) if total > 0 => Some((completed as f32 / total as f32).min(1.0)),
+            _ => None,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.status == "success"
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeleteRequest {
     pub name: String,
@@ -142,14 +280,199 @@ pub struct DeleteRequest {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbedRequest {
     pub model: String,
-    pub input: String,
+    pub input: EmbedInput,
+}
+
+/// `/api/embed` takes one string or a batch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EmbedInput {
+    Single(String),
+    Batch(Vec<String>),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbedResponse {
+    /// One vector per input, in input order.
+    #[serde(default)]
+    pub embeddings: Vec<Vec<f32>>,
+    /// Single vector returned by servers that predate `/api/embed` batching.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub embedding: Vec<f32>,
 }
 
+impl EmbedResponse {
+    pub fn into_vectors(self) -> Vec<Vec<f32>> {
+        if self.embeddings.is_empty() && !self.embedding.is_empty() {
+            vec![self.embedding]
+        } else {
+            self.embeddings
+        }
+    }
+}
+
+/// A model currently loaded by the server (`/api/ps`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunningModel {
+    pub name: String,
+    #[serde(default)]
+    pub model: String,
+    /// Bytes in memory, including `size_vram`.
+    pub size: u64,
+    /// Bytes held in GPU memory.
+    #[serde(default)]
+    pub size_vram: u64,
+    #[serde(default)]
+    pub digest: String,
+    #[serde(default)]
+    pub details: Option<ModelDetails>,
+    /// When the server unloads the model if it stays idle.
+    #[serde(default)]
+    pub expires_at: Option<String>,
+}
+
+impl From<&RunningModel> for crate::shared::models::LoadedModel {
+    fn from(model: &RunningModel) -> Self {
+        const MB: u64 = 1024 * 1024;
+        Self {
+            name: model.name.clone(),
+            size_mb: model.size / MB,
+            vram_mb: model.size_vram / MB,
+            expires_at: model
+                .expires_at
+                .as_deref()
+                .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+                .map(|t| t.with_timezone(&chrono::Utc)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunningModelsResponse {
+    pub models: Vec<RunningModel>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CopyRequest {
+    pub source: String,
+    pub destination: String,
+}
+
+/// `/api/create`. Current servers read the structured fields; older ones
+/// read `modelfile`. [`CreateRequest::from_modelfile`] fills in both.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CreateRequest {
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<serde_json::Map<String, serde_json::Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantize: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modelfile: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+}
+
+impl CreateRequest {
+    /// Build a request from Modelfile text. `FROM`, `SYSTEM`, `TEMPLATE`,
+    /// `LICENSE` and `PARAMETER` are mapped to fields (`"""` blocks may span
+    /// lines; repeated parameters such as `stop` become arrays); the text is
+    /// also sent verbatim as `modelfile`.
+    pub fn from_modelfile(model: impl Into<String>, modelfile: &str) -> Result<Self, String> {
+        let mut request = Self {
+            model: model.into(),
+            modelfile: Some(modelfile.to_string()),
+            ..Default::default()
+        };
+        let mut parameters = serde_json::Map::new();
+        let mut lines = modelfile.lines();
+
+        while let Some(line) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let rest = rest.trim();
+            let value = match rest.strip_prefix("\"\"\"") {
+                Some(first) => match first.strip_suffix("\"\"\"") {
+                    Some(whole) => whole.to_string(),
+                    None => {
+                        let mut block = vec![first.to_string()];
+                        loop {
+                            let next = lines
+                                .next()
+                                .ok_or_else(|| format!("Unterminated \"\"\" block in {command}"))?;
+                            if let Some(last) = next.strip_suffix("\"\"\"") {
+                                block.push(last.to_string());
+                                break;
+                            }
+                            block.push(next.to_string());
+                        }
+                        block.join("\n")
+                    }
+                },
+                None => rest.trim_matches('"').to_string(),
+            };
+
+            match command.to_ascii_uppercase().as_str() {
+                "FROM" => request.from = Some(value),
+                "SYSTEM" => request.system = Some(value),
+                "TEMPLATE" => request.template = Some(value),
+                "LICENSE" => request.license = Some(value),
+                "PARAMETER" => {
+                    let (name, raw) = value
+                        .split_once(char::is_whitespace)
+                        .ok_or_else(|| format!("PARAMETER without a value: {value}"))?;
+                    let raw = raw.trim().trim_matches('"');
+                    let parsed = serde_json::from_str::<serde_json::Value>(raw)
+                        .ok()
+                        .filter(|v| v.is_number() || v.is_boolean())
+                        .unwrap_or_else(|| serde_json::Value::String(raw.to_string()));
+                    match parameters.get_mut(name) {
+                        Some(serde_json::Value::Array(values)) => values.push(parsed),
+                        Some(existing) => *existing = serde_json::Value::Array(vec![existing.take(), parsed]),
+                        None if name == "stop" => {
+                            parameters.insert(name.to_string(), serde_json::Value::Array(vec![parsed]));
+                        }
+                        None => {
+                            parameters.insert(name.to_string(), parsed);
+                        }
+                    }
+                }
+                // ADAPTER, MESSAGE and REQUIRES are only understood via `modelfile`.
+                _ => {}
+            }
+        }
+
+        if request.from.is_none() {
+            return Err("Modelfile has no FROM line".to_string());
+        }
+        if !parameters.is_empty() {
+            request.parameters = Some(parameters);
+        }
+        Ok(request)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushRequest {
+    pub model: String,
+    /// Allow a registry without TLS.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub insecure: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VersionResponse {
     pub version: String,
@@ -1,334 +1,394 @@
-use crate::shared::models::{ProviderConfig, ProviderRegion, PricingInfo};
-
-pub fn get_predefined_providers() -> Vec<ProviderConfig> {
-    vec![
-        // 中国AI服务提供商
-        ProviderConfig {
-            id: "zhipu-ai".to_string(),
-            name: "智谱AI (Zhipu AI)".to_string(),
-            region: ProviderRegion::China,
-            api_endpoint: "https://open.bigmodel.cn/api/paas/v4/chat/completions".to_string(),
-            models: vec![
-                "glm-4".to_string(),
-                "glm-4-plus".to_string(),
-                "glm-4-0520".to_string(),
-                "glm-4-0916".to_string(),
-                "glm-4-air".to_string(),
-                "glm-4-flash".to_string(),
-                "glm-3-turbo".to_string(),
-            ],
-            pricing: PricingInfo {
-                currency: "CNY".to_string(),
-                input_price_per_1k: 0.1,
-                output_price_per_1k: 0.1,
-                free_tier_limit: Some(100000),
-            },
-            requires_api_key: true,
-            description: "智谱AI是中国领先的大模型研发公司，提供GLM系列大语言模型。".to_string(),
-        },
-        ProviderConfig {
-            id: "baichuan".to_string(),
-            name: "百川智能 (Baichuan)".to_string(),
-            region: ProviderRegion::China,
-            api_endpoint: "https://api.baichuan-ai.com/v1/chat/completions".to_string(),
-            models: vec![
-                "baichuan-7b".to_string(),
-                "baichuan-13b".to_string(),
-                "baichuan-turbo".to_string(),
-            ],
-            pricing: PricingInfo {
-                currency: "CNY".to_string(),
-                input_price_per_1k: 0.05,
-                output_price_per_1k: 0.05,
-                free_tier_limit: Some(200000),
-            },
-            requires_api_key: true,
-            description: "百川智能提供开源大模型，支持多模态交互。".to_string(),
-        },
-        ProviderConfig {
-            id: "minimax".to_string(),
-            name: "MiniMax".to_string(),
-            region: ProviderRegion::China,
-            api_endpoint: "https://api.minimax.chat/v1/text/chatcompletion_v2".to_string(),
-            models: vec![
-                "abab5.5-chat".to_string(),
-                "abab5.5-chat-pro".to_string(),
-                "abab6-chat".to_string(),
-                "abab6-chat-pro".to_string(),
-            ],
-            pricing: PricingInfo {
-                currency: "CNY".to_string(),
-                input_price_per_1k: 0.015,
-                output_price_per_1k: 0.015,
-                free_tier_limit: Some(1000000),
-            },
-            requires_api_key: true,
-            description: "MiniMax提供高性能大语言模型API服务。".to_string(),
-        },
-        ProviderConfig {
-            id: "deepseek".to_string(),
-            name: "深度求索 (DeepSeek)".to_string(),
-            region: ProviderRegion::China,
-            api_endpoint: "https://api.deepseek.com/chat/completions".to_string(),
-            models: vec![
-                "deepseek-chat".to_string(),
-                "deepseek-coder".to_string(),
-            ],
-            pricing: PricingInfo {
-                currency: "CNY".to_string(),
-                input_price_per_1k: 0.001,
-                output_price_per_1k: 0.002,
-                free_tier_limit: Some(5000000),
-            },
-            requires_api_key: true,
-            description: "深度求索提供开源大语言模型，支持代码生成。".to_string(),
-        },
-        ProviderConfig {
-            id: "moonshot".to_string(),
-            name: "月之暗面 (Moonshot AI)".to_string(),
-            region: ProviderRegion::China,
-            api_endpoint: "https://api.moonshot.cn/v1/chat/completions".to_string(),
-            models: vec![
-                "moonshot-v1-8k".to_string(),
-                "moonshot-v1-32k".to_string(),
-                "moonshot-v1-128k".to_string(),
-            ],
-            pricing: PricingInfo {
-                currency: "CNY".to_string(),
-                input_price_per_1k: 0.012,
-                output_price_per_1k: 0.012,
-                free_tier_limit: Some(2000000),
-            },
-            requires_api_key: true,
-            description: "月之暗面提供长上下文大语言模型。".to_string(),
-        },
-        ProviderConfig {
-            id: "tongyi".to_string(),
-            name: "通义千问 (Tongyi Qianwen)".to_string(),
-            region: ProviderRegion::China,
-            api_endpoint: "https://dashscope.aliyuncs.com/api/v1/services/aigc/text-generation/generation".to_string(),
-            models: vec![
-                "qwen-turbo".to_string(),
-                "qwen-plus".to_string(),
-                "qwen-max".to_string(),
-                "qwen-longcontext".to_string(),
-            ],
-            pricing: PricingInfo {
-                currency: "CNY".to_string(),
-                input_price_per_1k: 0.008,
-                output_price_per_1k: 0.008,
-                free_tier_limit: Some(1000000),
-            },
-            requires_api_key: true,
-            description: "通义千问是阿里云推出的大语言模型。".to_string(),
-        },
-        ProviderConfig {
-            id: "ernie-bot".to_string(),
-            name: "文心一言 (ERNIE Bot)".to_string(),
-            region: ProviderRegion::China,
-            api_endpoint: "https://aip.baidubce.com/rpc/2.0/ai_custom/v1/wenxinworkshop/chat/completions".to_string(),
-            models: vec![
-                "ernie-bot-4".to_string(),
-                "ernie-bot-turbo".to_string(),
-                "ernie-bot-pro".to_string(),
-            ],
-            pricing: PricingInfo {
-                currency: "CNY".to_string(),
-                input_price_per_1k: 0.012,
-                output_price_per_1k: 0.012,
-                free_tier_limit: Some(500000),
-            },
-            requires_api_key: true,
-            description: "文心一言是百度推出的大语言模型。".to_string(),
-        },
-        
-        // 美国AI服务提供商
-        ProviderConfig {
-            id: "openai".to_string(),
-            name: "OpenAI".to_string(),
-            region: ProviderRegion::USA,
-            api_endpoint: "https://api.openai.com/v1/chat/completions".to_string(),
-            models: vec![
-                "gpt-4".to_string(),
-                "gpt-4-turbo".to_string(),
-                "gpt-4-turbo-preview".to_string(),
-                "gpt-3.5-turbo".to_string(),
-                "gpt-3.5-turbo-16k".to_string(),
-                "gpt-3.5-turbo-instruct".to_string(),
-            ],
-            pricing: PricingInfo {
-                currency: "USD".to_string(),
-                input_price_per_1k: 0.03,
-                output_price_per_1k: 0.06,
-                free_tier_limit: None,
-            },
-            requires_api_key: true,
-            description: "OpenAI提供GPT系列大语言模型，业界领先。".to_string(),
-        },
-        ProviderConfig {
-            id: "anthropic".to_string(),
-            name: "Anthropic Claude".to_string(),
-            region: ProviderRegion::USA,
-            api_endpoint: "https://api.anthropic.com/v1/messages".to_string(),
-            models: vec![
-                "claude-3-opus".to_string(),
-                "claude-3-sonnet".to_string(),
-                "claude-3-haiku".to_string(),
-            ],
-            pricing: PricingInfo {
-                currency: "USD".to_string(),
-                input_price_per_1k: 0.015,
-                output_price_per_1k: 0.075,
-                free_tier_limit: None,
-            },
-            requires_api_key: true,
-            description: "Anthropic提供Claude系列大语言模型，以安全著称。".to_string(),
-        },
-        ProviderConfig {
-            id: "google".to_string(),
-            name: "Google Gemini".to_string(),
-            region: ProviderRegion::USA,
-            api_endpoint: "https://generativelanguage.googleapis.com/v1beta/models/gemini-pro:generateContent".to_string(),
-            models: vec![
-                "gemini-pro".to_string(),
-                "gemini-pro-vision".to_string(),
-                "gemini-1.5-pro".to_string(),
-                "gemini-1.5-flash".to_string(),
-            ],
-            pricing: PricingInfo {
-                currency: "USD".to_string(),
-                input_price_per_1k: 0.00025,
-                output_price_per_1k: 0.0005,
-                free_tier_limit: Some(15000000),
-            },
-            requires_api_key: true,
-            description: "Google提供Gemini系列多模态大模型。".to_string(),
-        },
-        ProviderConfig {
-            id: "cohere".to_string(),
-            name: "Cohere".to_string(),
-            region: ProviderRegion::USA,
-            api_endpoint: "https://api.cohere.ai/v1/chat".to_string(),
-            models: vec![
-                "command".to_string(),
-                "command-light".to_string(),
-                "command-r".to_string(),
-            ],
-            pricing: PricingInfo {
-                currency: "USD".to_string(),
-                input_price_per_1k: 0.015,
-                output_price_per_1k: 0.03,
-                free_tier_limit: None,
-            },
-            requires_api_key: true,
-            description: "Cohere提供Command系列大语言模型。".to_string(),
-        },
-        ProviderConfig {
-            id: "mistral".to_string(),
-            name: "Mistral AI".to_string(),
-            region: ProviderRegion::USA,
-            api_endpoint: "https://api.mistral.ai/v1/chat/completions".to_string(),
-            models: vec![
-                "mistral-large".to_string(),
-                "mistral-medium".to_string(),
-                "mistral-small".to_string(),
-                "mixtral-8x7b".to_string(),
-                "mixtral-8x22b".to_string(),
-            ],
-            pricing: PricingInfo {
-                currency: "EUR".to_string(),
-                input_price_per_1k: 0.003,
-                output_price_per_1k: 0.006,
-                free_tier_limit: None,
-            },
-            requires_api_key: true,
-            description: "Mistral AI提供开源大语言模型。".to_string(),
-        },
-        ProviderConfig {
-            id: "meta".to_string(),
-            name: "Meta Llama".to_string(),
-            region: ProviderRegion::Global,
-            api_endpoint: "https://api.meta.com/v1/chat/completions".to_string(),
-            models: vec![
-                "llama-3-70b".to_string(),
-                "llama-3-8b".to_string(),
-                "llama-2-70b".to_string(),
-            ],
-            pricing: PricingInfo {
-                currency: "USD".to_string(),
-                input_price_per_1k: 0.0,
-                output_price_per_1k: 0.0,
-                free_tier_limit: None,
-            },
-            requires_api_key: false,
-            description: "Meta提供开源Llama系列大语言模型，可免费使用。".to_string(),
-        },
-        ProviderConfig {
-            id: "huggingface".to_string(),
-            name: "Hugging Face".to_string(),
-            region: ProviderRegion::Global,
-            api_endpoint: "https://api-inference.huggingface.co/models".to_string(),
-            models: vec![
-                "meta-llama-3-70b-instruct".to_string(),
-                "mistralai-mixtral-8x7b".to_string(),
-            ],
-            pricing: PricingInfo {
-                currency: "USD".to_string(),
-                input_price_per_1k: 0.0001,
-                output_price_per_1k: 0.0001,
-                free_tier_limit: Some(1000000),
-            },
-            requires_api_key: true,
-            description: "Hugging Face提供开源模型推理API。".to_string(),
-        },
-        
-        // 本地AI服务提供商
-        ProviderConfig {
-            id: "ollama".to_string(),
-            name: "Ollama".to_string(),
-            region: ProviderRegion::Global,
-            api_endpoint: "http://localhost:11434/api/chat".to_string(),
-            models: vec![
-                "llama3".to_string(),
-                "llama3:8b".to_string(),
-                "llama3:70b".to_string(),
-                "llama2".to_string(),
-                "mistral".to_string(),
-                "mistral:7b".to_string(),
-                "mixtral".to_string(),
-                "mixtral:8x7b".to_string(),
-                "codellama".to_string(),
-                "codellama:7b".to_string(),
-                "codellama:13b".to_string(),
-                "gemma".to_string(),
-                "gemma:2b".to_string(),
-                "gemma:7b".to_string(),
-                "phi3".to_string(),
-                "phi3:mini".to_string(),
-                "qwen".to_string(),
-                "qwen:7b".to_string(),
-                "qwen:14b".to_string(),
-            ],
-            pricing: PricingInfo {
-                currency: "USD".to_string(),
-                input_price_per_1k: 0.0,
-                output_price_per_1k: 0.0,
-                free_tier_limit: None,
-            },
-            requires_api_key: false,
-            description: "Ollama是一个开源的大语言模型运行环境，支持在本地运行多种开源模型，无需API密钥，完全免费。".to_string(),
+//! Provider registry.
+//!
+//! Three layers are merged in order, later layers winning per provider id:
+//!
+//! 1. the catalog embedded from `provider_catalog.json`;
+//! 2. `<vault>/providers.json`, same schema as the catalog. An entry whose id
+//!    matches an existing provider only needs the fields it changes;
+//! 3. `<vault>/custom_providers.json`, the relays defined in the GUI.
+//!
+//! Invalid entries are skipped and reported by name; the rest of the file
+//! still applies.
+
+use crate::shared::models::{
+    CustomProvider, PricingInfo, ProviderConfig, ProviderRegion, ProviderSource,
+};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::Path;
+
+const BUILTIN_CATALOG: &str = include_str!("provider_catalog.json");
+
+/// Highest catalog `format_version` this build understands.
+pub const CATALOG_FORMAT_VERSION: u64 = 1;
+pub const OVERRIDES_FILE: &str = "providers.json";
+pub const CUSTOM_PROVIDERS_FILE: &str = "custom_providers.json";
+
+#[derive(Debug, Clone, Default)]
+pub struct ProviderRegistry {
+    providers: Vec<ProviderConfig>,
+    /// One message per rejected entry or unreadable file.
+    errors: Vec<String>,
+}
+
+impl ProviderRegistry {
+    /// The embedded catalog alone.
+    pub fn builtin() -> Self {
+        let mut registry = Self::default();
+        registry.merge_catalog("embedded provider catalog", BUILTIN_CATALOG, ProviderSource::BuiltIn);
+        debug_assert!(registry.errors.is_empty(), "{:?}", registry.errors);
+        registry
+    }
+
+    /// The embedded catalog plus the user layers found in `vault_dir`.
+    pub fn load(vault_dir: &Path) -> Self {
+        let mut registry = Self::builtin();
+
+        let overrides = vault_dir.join(OVERRIDES_FILE);
+        if let Ok(text) = fs::read_to_string(&overrides) {
+            registry.merge_catalog(&overrides.display().to_string(), &text, ProviderSource::Override);
+        }
+        let custom = vault_dir.join(CUSTOM_PROVIDERS_FILE);
+        if let Ok(text) = fs::read_to_string(&custom) {
+            registry.merge_custom(&custom.display().to_string(), &text);
+        }
+
+        for error in &registry.errors {
+            tracing::warn!("Provider registry: {error}");
+        }
+        registry
+    }
+
+    pub fn providers(&self) -> &[ProviderConfig] {
+        &self.providers
+    }
+
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ProviderConfig> {
+        self.providers.iter().find(|p| p.id == id)
+    }
+
+    #[allow(dead_code)]
+    pub fn by_region(&self, region: ProviderRegion) -> Vec<ProviderConfig> {
+        self.providers.iter().filter(|p| p.region == region).cloned().collect()
+    }
+
+    fn upsert(&mut self, config: ProviderConfig) {
+        match self.providers.iter_mut().find(|p| p.id == config.id) {
+            Some(existing) => *existing = config,
+            None => self.providers.push(config),
+        }
+    }
+
+    fn merge_catalog(&mut self, origin: &str, text: &str, source: ProviderSource) {
+        let root: Value = match serde_json::from_str(text) {
+            Ok(root) => root,
+            Err(e) => {
+                self.errors.push(format!("{origin}: invalid JSON: {e}"));
+                return;
+            }
+        };
+        let version = root.get("format_version").and_then(Value::as_u64).unwrap_or(1);
+        if version > CATALOG_FORMAT_VERSION {
+            self.errors.push(format!(
+                "{origin}: unsupported format_version {version} (this build reads up to {CATALOG_FORMAT_VERSION})"
+            ));
+            return;
+        }
+        let Some(entries) = root.get("providers").and_then(Value::as_array) else {
+            self.errors.push(format!("{origin}: missing `providers` array"));
+            return;
+        };
+
+        for (idx, entry) in entries.iter().enumerate() {
+            match self.merge_entry(entry, source) {
+                Ok(config) => self.upsert(config),
+                Err(e) => self.errors.push(format!("{}: {e}", entry_label(origin, idx, entry))),
+            }
+        }
+    }
+
+    /// Overlay `entry` on the provider with the same id (if any) and validate the result.
+    fn merge_entry(&self, entry: &Value, source: ProviderSource) -> Result<ProviderConfig, String> {
+        if !entry.is_object() {
+            return Err("entry is not an object".to_string());
+        }
+        let id = entry
+            .get("id")
+            .and_then(Value::as_str)
+            .ok_or("missing string field `id`")?;
+
+        let mut merged = match self.get(id) {
+            Some(existing) => serde_json::to_value(existing).map_err(|e| e.to_string())?,
+            None => Value::Object(Map::new()),
+        };
+        merge_json(&mut merged, entry);
+        merged["source"] = serde_json::to_value(source).map_err(|e| e.to_string())?;
+
+        let config: ProviderConfig = serde_json::from_value(merged).map_err(|e| e.to_string())?;
+        validate(&config)?;
+        Ok(config)
+    }
+
+    fn merge_custom(&mut self, origin: &str, text: &str) {
+        let entries: Vec<Value> = match serde_json::from_str(text) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.errors.push(format!("{origin}: expected a JSON array of relays: {e}"));
+                return;
+            }
+        };
+        for (idx, entry) in entries.iter().enumerate() {
+            let config = serde_json::from_value::<CustomProvider>(entry.clone())
+                .map_err(|e| e.to_string())
+                .map(custom_to_config)
+                .and_then(|config| validate(&config).map(|_| config));
+            match config {
+                Ok(config) => self.upsert(config),
+                Err(e) => self.errors.push(format!("{}: {e}", entry_label(origin, idx, entry))),
+            }
+        }
+    }
+}
+
+fn custom_to_config(cp: CustomProvider) -> ProviderConfig {
+    let non_empty = |s: String| if s.trim().is_empty() { None } else { Some(s) };
+    ProviderConfig {
+        models: cp
+            .models_hint
+            .split([',', ' ', '\n'])
+            .filter(|m| !m.is_empty())
+            .map(str::to_string)
+            .collect(),
+        name: if cp.name.trim().is_empty() { cp.id.clone() } else { cp.name },
+        id: cp.id,
+        region: ProviderRegion::Global,
+        api_endpoint: cp.base_url.trim().to_string(),
+        pricing: PricingInfo {
+            currency: "USD".to_string(),
+            input_price_per_1k: 0.0,
+            output_price_per_1k: 0.0,
+            free_tier_limit: None,
         },
-    ]
+        requires_api_key: true,
+        description: cp.description,
+        key_header: non_empty(cp.key_header),
+        key_prefix: non_empty(cp.key_prefix),
+        source: ProviderSource::Custom,
+    }
+}
+
+fn validate(config: &ProviderConfig) -> Result<(), String> {
+    if config.id.is_empty()
+        || !config
+            .id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(format!("invalid id {:?} (use letters, digits, '-', '_' or '.')", config.id));
+    }
+    if config.name.trim().is_empty() {
+        return Err("`name` is empty".to_string());
+    }
+    if !config.api_endpoint.starts_with("http://") && !config.api_endpoint.starts_with("https://") {
+        return Err(format!("`api_endpoint` {:?} is not an http(s) URL", config.api_endpoint));
+    }
+    if config.models.iter().any(|m| m.trim().is_empty()) {
+        return Err("`models` contains an empty model id".to_string());
+    }
+    let prices = [config.pricing.input_price_per_1k, config.pricing.output_price_per_1k];
+    if prices.iter().any(|p| !p.is_finite() || *p < 0.0) {
+        return Err("prices must be finite and non-negative".to_string());
+    }
+    Ok(())
 }
 
-pub fn get_provider_by_id(id: &str) -> Option<ProviderConfig> {
-    get_predefined_providers().into_iter().find(|p| p.id == id)
+/// `<origin>: entry <idx> (id "<id>")`, or without the id if there is none.
+fn entry_label(origin: &str, idx: usize, entry: &Value) -> String {
+    match entry.get("id").and_then(Value::as_str) {
+        Some(id) => format!("{origin}: entry {idx} (id {id:?})"),
+        None => format!("{origin}: entry {idx}"),
+    }
 }
 
-#[allow(dead_code)]
-pub fn get_providers_by_region(region: ProviderRegion) -> Vec<ProviderConfig> {
-    get_predefined_providers()
-        .into_iter()
-        .filter(|p| p.region == region)
-        .collect()
-}
\ No newline at end of file
+/// Recursively overlay `patch` onto `base`; nested objects (e.g. `pricing`) merge key by key.
+fn merge_json(base: &mut Value, patch: &Value) {
+    match (base, patch) {
+        (Value::Object(base), Value::Object(patch)) => {
+            for (key, value) in patch {
+                merge_json(base.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (base, patch) => *base = patch.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::ApiKeyManager;
+    use serde_json::json;
+
+    fn catalog(entries: Value) -> String {
+        json!({ "format_version": 1, "providers": entries }).to_string()
+    }
+
+    fn relay(id: &str, base_url: &str) -> Value {
+        json!({
+            "id": id,
+            "name": "",
+            "base_url": base_url,
+            "key_header": "",
+            "key_prefix": "",
+            "models_hint": "m1, m2",
+            "description": "",
+        })
+    }
+
+    fn merged(text: &str) -> ProviderRegistry {
+        let mut registry = ProviderRegistry::builtin();
+        registry.merge_catalog("providers.json", text, ProviderSource::Override);
+        registry
+    }
+
+    fn temp_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("vas-providers-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).expect("create dir");
+        dir
+    }
+
+    #[test]
+    fn overrides_only_need_the_fields_they_change() {
+        let registry = merged(&catalog(json!([
+            { "id": "zhipu-ai", "pricing": { "input_price_per_1k": 0.01 } },
+        ])));
+        assert!(registry.errors().is_empty(), "{:?}", registry.errors());
+
+        let zhipu = registry.get("zhipu-ai").expect("zhipu");
+        assert_eq!(zhipu.source, ProviderSource::Override);
+        assert_eq!(zhipu.pricing.input_price_per_1k, 0.01);
+        assert_eq!(zhipu.pricing.currency, "CNY");
+        assert!(zhipu.api_endpoint.starts_with("https://open.bigmodel.cn/"));
+    }
+
+    #[test]
+    fn validation_errors_name_the_entry_and_the_rest_still_applies() {
+        let registry = merged(&catalog(json!([
+            {
+                "id": "bad id",
+                "name": "Bad",
+                "region": "Global",
+                "api_endpoint": "https://bad.example.com",
+                "models": [],
+                "pricing": { "currency": "USD", "input_price_per_1k": 0.0, "output_price_per_1k": 0.0 },
+                "requires_api_key": true,
+                "description": "",
+            },
+            { "id": "zhipu-ai", "name": " " },
+            { "id": "zhipu-ai", "api_endpoint": "ftp://example.com" },
+            { "id": "zhipu-ai", "models": ["glm-4", ""] },
+            { "id": "zhipu-ai", "pricing": { "output_price_per_1k": -1.0 } },
+            { "id": "zhipu-ai", "description": "patched" },
+        ])));
+
+        let expected = [
+            "providers.json: entry 0 (id \"bad id\"): invalid id \"bad id\"",
+            "providers.json: entry 1 (id \"zhipu-ai\"): `name` is empty",
+            "providers.json: entry 2 (id \"zhipu-ai\"): `api_endpoint` \"ftp://example.com\" is not an http(s) URL",
+            "providers.json: entry 3 (id \"zhipu-ai\"): `models` contains an empty model id",
+            "providers.json: entry 4 (id \"zhipu-ai\"): prices must be finite and non-negative",
+        ];
+        assert_eq!(registry.errors().len(), expected.len(), "{:?}", registry.errors());
+        for (error, expected) in registry.errors().iter().zip(expected) {
+            assert!(error.starts_with(expected), "{error:?} should start with {expected:?}");
+        }
+        assert_eq!(registry.get("zhipu-ai").expect("zhipu").description, "patched");
+        assert!(registry.get("bad id").is_none());
+    }
+
+    #[test]
+    fn merge_errors_name_the_entry() {
+        let registry = merged(&catalog(json!([
+            "not an object",
+            { "name": "No id" },
+            { "id": "new", "api_endpoint": "https://new.example.com" },
+        ])));
+        assert_eq!(
+            registry.errors()[..2],
+            [
+                "providers.json: entry 0: entry is not an object".to_string(),
+                "providers.json: entry 1: missing string field `id`".to_string(),
+            ]
+        );
+        let missing = &registry.errors()[2];
+        assert!(missing.starts_with("providers.json: entry 2 (id \"new\"): missing field"), "{missing}");
+        assert!(registry.get("new").is_none());
+    }
+
+    #[test]
+    fn unreadable_files_name_the_file() {
+        let registry = merged("{ not json");
+        assert!(registry.errors()[0].starts_with("providers.json: invalid JSON"), "{:?}", registry.errors());
+
+        let registry = merged(&json!({ "format_version": 99, "providers": [] }).to_string());
+        assert_eq!(
+            registry.errors(),
+            ["providers.json: unsupported format_version 99 (this build reads up to 1)".to_string()]
+        );
+
+        let registry = merged("{}");
+        assert_eq!(registry.errors(), ["providers.json: missing `providers` array".to_string()]);
+    }
+
+    #[test]
+    fn custom_relay_errors_name_the_entry() {
+        let mut registry = ProviderRegistry::builtin();
+        let relays = json!([
+            relay("good-relay", " https://relay.example.com/v1 "),
+            relay("bad-relay", "relay.example.com"),
+            { "id": "partial" },
+        ]);
+        registry.merge_custom("custom_providers.json", &relays.to_string());
+
+        let errors = registry.errors();
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert!(
+            errors[0].starts_with("custom_providers.json: entry 1 (id \"bad-relay\"): `api_endpoint`"),
+            "{errors:?}"
+        );
+        assert!(errors[1].starts_with("custom_providers.json: entry 2 (id \"partial\"): missing field"), "{errors:?}");
+
+        let good = registry.get("good-relay").expect("good relay");
+        assert_eq!(good.source, ProviderSource::Custom);
+        assert_eq!(good.name, "good-relay");
+        assert_eq!(good.api_endpoint, "https://relay.example.com/v1");
+        assert_eq!(good.models, ["m1", "m2"]);
+    }
+
+    #[tokio::test]
+    async fn each_manager_holds_the_registry_of_its_own_vault() {
+        let (with_relay, without) = (temp_dir(), temp_dir());
+        let relays = json!([relay("my-relay", "https://relay.example.com/v1")]);
+        fs::write(with_relay.join(CUSTOM_PROVIDERS_FILE), relays.to_string()).expect("write");
+
+        let a = ApiKeyManager::with_vault_dir(&with_relay).await;
+        let b = ApiKeyManager::with_vault_dir(&without).await;
+        assert!(a.provider("my-relay").is_some());
+        assert!(b.provider("my-relay").is_none());
+        assert_eq!(a.providers().len(), b.providers().len() + 1);
+
+        fs::write(without.join(CUSTOM_PROVIDERS_FILE), "[{}]").expect("write");
+        let errors = b.reload_providers();
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(errors[0].contains("custom_providers.json: entry 0: missing field"), "{errors:?}");
+        assert_eq!(b.provider_errors(), errors);
+        assert!(a.provider_errors().is_empty());
+
+        for dir in [with_relay, without] {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
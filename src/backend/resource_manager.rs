@@ -1,43 +1,46 @@
+use crate::backend::network_discovery::NetworkDiscovery;
 use crate::shared::models::{
-    VgaError, ClientMode, NodeInfo, NodeResources, GpuInfo, NodeStatus,
+    VgaError, ClientMode, NodeInfo, NodeResources, GpuInfo, NodeStatus, PeerStatus,
     ResourceRequest, ResourceRequirements, ResourceAllocation, AllocatedResources,
-    AllocatedGpu, AllocationStatus, HealthCheck, LoadBalancingStrategy,
+    AllocatedGpu, AllocationStatus, HealthCheck, LoadBalancingStrategy, LoadedModel,
     BalancingStrategy, DistributedTask, DistributedTaskStatus, SwarmGroup,
     ResourcePool, TaskId, TaskSpec, Priority,
 };
 use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 use chrono::Utc;
 use std::time::Duration;
 use tokio::net::UdpSocket;
-use serde_json;
-use serde::{Deserialize, Serialize};
 
+/// Nodes find each other through [`NetworkDiscovery`]: this node's resources
+/// ride along in its announcements, and other nodes' come out of
+/// `discover_peers`, under the same authentication and staleness rules.
 pub struct ResourceManager {
     node_id: String,
     node_info: Arc<RwLock<NodeInfo>>,
-    discovered_nodes: Arc<RwLock<HashMap<String, NodeInfo>>>,
+    discovery: NetworkDiscovery,
+    /// Set by `start_discovery`; resources are only announced after it.
+    announcing: AtomicBool,
     swarm_groups: Arc<RwLock<HashMap<String, SwarmGroup>>>,
     resource_pools: Arc<RwLock<HashMap<String, ResourcePool>>>,
     allocations: Arc<RwLock<HashMap<String, ResourceAllocation>>>,
     distributed_tasks: Arc<RwLock<HashMap<TaskId, DistributedTask>>>,
     balancing_strategy: Arc<RwLock<LoadBalancingStrategy>>,
     allow_remote_access: Arc<RwLock<bool>>,
-    discovery_socket: Option<Arc<UdpSocket>>,
-    broadcast_port: u16,
 }
 
 impl ResourceManager {
-    pub async fn new(allow_remote: bool) -> Result<Self, VgaError> {
-        let node_id = Uuid::new_v4().to_string();
-        let broadcast_port = Self::find_available_port(8080, 8100).await?;
-        
+    pub async fn new(discovery: NetworkDiscovery, allow_remote: bool) -> Result<Self, VgaError> {
+        let node_id = discovery.local_node_id().to_string();
+
         let node_info = NodeInfo {
             id: node_id.clone(),
             address: Self::get_local_ip().await?,
-            port: broadcast_port,
+            port: discovery.port(),
             mode: ClientMode::Master,
             resources: Self::detect_local_resources().await,
             allow_remote_access: allow_remote,
@@ -45,14 +48,11 @@ impl ResourceManager {
             status: NodeStatus::Online,
         };
 
-        let socket = UdpSocket::bind(format!("0.0.0.0:{}", broadcast_port))
-            .await
-            .map_err(|e| VgaError::ResourceLimit(format!("Failed to bind socket: {}", e)))?;
-
         Ok(Self {
             node_id,
             node_info: Arc::new(RwLock::new(node_info)),
-            discovered_nodes: Arc::new(RwLock::new(HashMap::new())),
+            discovery,
+            announcing: AtomicBool::new(false),
             swarm_groups: Arc::new(RwLock::new(HashMap::new())),
             resource_pools: Arc::new(RwLock::new(HashMap::new())),
             allocations: Arc::new(RwLock::new(HashMap::new())),
@@ -62,8 +62,6 @@ impl ResourceManager {
                 weights: HashMap::new(),
             })),
             allow_remote_access: Arc::new(RwLock::new(allow_remote)),
-            discovery_socket: Some(Arc::new(socket)),
-            broadcast_port,
         })
     }
 
@@ -105,6 +103,7 @@ impl ResourceManager {
                 "claude-3".to_string(),
             ],
             current_load: 0.0,
+            loaded_models: Vec::new(),
         }
     }
 
@@ -118,118 +117,44 @@ impl ResourceManager {
         vec![]
     }
 
-    async fn find_available_port(start: u16, end: u16) -> Result<u16, VgaError> {
-        for port in start..=end {
-            if let Ok(_socket) = UdpSocket::bind(format!("0.0.0.0:{}", port)).await {
-                drop(_socket);
-                return Ok(port);
-            }
-        }
-        Err(VgaError::ResourceLimit(format!("No available port in range {}-{}", start, end)))
-    }
-
     pub async fn start_discovery(&self) -> Result<(), VgaError> {
         tracing::info!(
             "Starting resource discovery for {} on port {}",
             self.node_id,
-            self.broadcast_port
+            self.discovery.port()
         );
-        let socket = self.discovery_socket.clone()
-            .ok_or_else(|| VgaError::ResourceLimit("Discovery socket not initialized".into()))?;
-
-        let node_info = self.node_info.clone();
-        let discovered_nodes = self.discovered_nodes.clone();
-        let allow_remote = self.allow_remote_access.clone();
-        let socket_recv = socket.clone();
-        let node_info_recv = node_info.clone();
-
-        tokio::spawn(async move {
-            let mut buf = [0u8; 65536];
-            
-            loop {
-                match socket_recv.recv_from(&mut buf).await {
-                    Ok((len, addr)) => {
-                        if let Ok(message) = serde_json::from_slice::<DiscoveryMessage>(&buf[..len]) {
-                            match message.message_type {
-                                DiscoveryMessageType::Announce => {
-                                    let allow = allow_remote.read().await;
-                                    if *allow && message.node_info.allow_remote_access {
-                                        let node_id = message.node_info.id.clone();
-                                        let mut nodes = discovered_nodes.write().await;
-                                        nodes.insert(node_id.clone(), message.node_info);
-                                        tracing::info!("Discovered node: {} from {}", node_id, addr);
-                                    }
-                                }
-                                DiscoveryMessageType::Query => {
-                                    let info = node_info_recv.read().await;
-                                    if info.allow_remote_access {
-                                        let response = DiscoveryMessage {
-                                            message_type: DiscoveryMessageType::Announce,
-                                            node_info: info.clone(),
-                                        };
-                                        if let Ok(data) = serde_json::to_vec(&response) {
-                                            let _ = socket_recv.send_to(&data, addr).await;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        tracing::error!("Discovery error: {}", e);
-                    }
-                }
-            }
-        });
-
-        let socket_broadcast = socket.clone();
-        let node_info_broadcast = node_info.clone();
-
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(30));
-            
-            loop {
-                interval.tick().await;
-                let info = node_info_broadcast.read().await;
-                if info.allow_remote_access {
-                    let message = DiscoveryMessage {
-                        message_type: DiscoveryMessageType::Announce,
-                        node_info: info.clone(),
-                    };
-                    if let Ok(data) = serde_json::to_vec(&message) {
-                        let _ = socket_broadcast.send_to(&data, "255.255.255.255:8080").await;
-                    }
-                }
-            }
-        });
-
-        let discovered_nodes_cleanup = self.discovered_nodes.clone();
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(60));
-            
-            loop {
-                interval.tick().await;
-                let now = Utc::now();
-                let mut nodes = discovered_nodes_cleanup.write().await;
-                let mut to_remove = Vec::new();
-                
-                for (node_id, node) in nodes.iter() {
-                    let timeout = chrono::Duration::minutes(5);
-                    if now.signed_duration_since(node.last_seen) > timeout {
-                        to_remove.push(node_id.clone());
-                        tracing::warn!("Removing stale node: {}", node_id);
-                    }
-                }
-                
-                for node_id in to_remove {
-                    nodes.remove(&node_id);
-                }
-            }
-        });
-
+        self.announcing.store(true, Ordering::Relaxed);
+        self.publish_resources().await;
+        self.discovery.broadcast_presence();
         Ok(())
     }
 
+    /// Hand the current resources to discovery for the next announcement,
+    /// or withdraw them if remote access is off.
+    async fn publish_resources(&self) {
+        if !self.announcing.load(Ordering::Relaxed) {
+            return;
+        }
+        let info = self.node_info.read().await;
+        let resources = info.allow_remote_access.then(|| info.resources.clone());
+        drop(info);
+        self.discovery.set_resources(resources).await;
+    }
+
+    /// Discovered peers that offer resources, by node id. Empty while this
+    /// node doesn't allow remote access.
+    async fn discovered_nodes(&self) -> HashMap<String, NodeInfo> {
+        if !*self.allow_remote_access.read().await {
+            return HashMap::new();
+        }
+        let peers = self.discovery.discover_peers().await.unwrap_or_default();
+        peers
+            .into_iter()
+            .filter_map(node_from_peer)
+            .map(|node| (node.id.clone(), node))
+            .collect()
+    }
+
     pub async fn create_swarm_group(&self, name: String, max_members: usize) -> Result<String, VgaError> {
         let group_id = Uuid::new_v4().to_string();
         let node_info = self.node_info.read().await;
@@ -290,7 +215,7 @@ impl ResourceManager {
         let group = groups.get(&group_id)
             .ok_or_else(|| VgaError::ResourceLimit("Group not found".into()))?;
 
-        let nodes = self.discovered_nodes.read().await;
+        let nodes = self.discovered_nodes().await;
         let mut members = Vec::new();
 
         for member_id in &group.members {
@@ -303,11 +228,9 @@ impl ResourceManager {
     }
 
     pub async fn set_remote_access(&self, allow: bool) {
-        let mut allow_remote = self.allow_remote_access.write().await;
-        *allow_remote = allow;
-        
-        let mut node_info = self.node_info.write().await;
-        node_info.allow_remote_access = allow;
+        *self.allow_remote_access.write().await = allow;
+        self.node_info.write().await.allow_remote_access = allow;
+        self.publish_resources().await;
     }
 
     pub async fn get_remote_access_status(&self) -> bool {
@@ -315,29 +238,13 @@ impl ResourceManager {
     }
 
     pub async fn discover_nodes(&self) -> Result<Vec<NodeInfo>, VgaError> {
-        let socket = self.discovery_socket.clone()
-            .ok_or_else(|| VgaError::ResourceLimit("Discovery socket not initialized".into()))?;
-
-        let message = DiscoveryMessage {
-            message_type: DiscoveryMessageType::Query,
-            node_info: self.node_info.read().await.clone(),
-        };
-
-        let data = serde_json::to_vec(&message)
-            .map_err(|e| VgaError::ResourceLimit(format!("Serialization error: {}", e)))?;
-
-        socket.send_to(&data, "255.255.255.255:8080")
-            .await
-            .map_err(|e| VgaError::ResourceLimit(format!("Broadcast error: {}", e)))?;
-
+        self.discovery.query_peers();
         tokio::time::sleep(Duration::from_secs(2)).await;
-
-        let nodes = self.discovered_nodes.read().await;
-        Ok(nodes.values().cloned().collect())
+        Ok(self.list_discovered_nodes().await)
     }
 
     pub async fn list_discovered_nodes(&self) -> Vec<NodeInfo> {
-        self.discovered_nodes.read().await.values().cloned().collect()
+        self.discovered_nodes().await.into_values().collect()
     }
 
     pub async fn request_resources(&self, requirements: ResourceRequirements, task_type: String, priority: Priority) -> Result<ResourceAllocation, VgaError> {
@@ -373,8 +280,8 @@ impl ResourceManager {
     }
 
     async fn select_node_for_allocation(&self, request: &ResourceRequest) -> Result<NodeInfo, VgaError> {
+        let nodes = self.discovered_nodes().await;
         let strategy = self.balancing_strategy.read().await;
-        let nodes = self.discovered_nodes.read().await;
 
         let available_nodes: Vec<NodeInfo> = nodes.values()
             .filter(|node| node.allow_remote_access && node.status == NodeStatus::Online)
@@ -481,7 +388,7 @@ impl ResourceManager {
     pub async fn perform_health_check(&self, node_id: String) -> Result<HealthCheck, VgaError> {
         let start = std::time::Instant::now();
         
-        let nodes = self.discovered_nodes.read().await;
+        let nodes = self.discovered_nodes().await;
         let node = nodes.get(&node_id)
             .ok_or_else(|| VgaError::ResourceLimit("Node not found".into()))?;
 
@@ -504,6 +411,24 @@ impl ResourceManager {
         let mut node_info = self.node_info.write().await;
         node_info.resources = resources;
         node_info.last_seen = Utc::now();
+        drop(node_info);
+        self.publish_resources().await;
+    }
+
+    /// Report what the local Ollama serves: `installed` becomes the node's
+    /// supported models and `loaded` its resident models. Memory held
+    /// outside the GPU by loaded models is subtracted from available memory.
+    /// The result goes out with the next discovery announcement.
+    pub async fn report_ollama_models(&self, installed: Vec<String>, loaded: Vec<LoadedModel>) {
+        let mut node_info = self.node_info.write().await;
+        let resources = &mut node_info.resources;
+        let resident_ram_mb: u64 = loaded.iter().map(|m| m.size_mb.saturating_sub(m.vram_mb)).sum();
+        resources.available_memory_mb = resources.total_memory_mb.saturating_sub(resident_ram_mb);
+        resources.supported_models = installed;
+        resources.loaded_models = loaded;
+        node_info.last_seen = Utc::now();
+        drop(node_info);
+        self.publish_resources().await;
     }
 
     pub async fn set_balancing_strategy(&self, strategy: BalancingStrategy) {
@@ -517,7 +442,7 @@ impl ResourceManager {
 
     pub async fn create_resource_pool(&self, name: String, node_ids: Vec<String>) -> Result<String, VgaError> {
         let pool_id = Uuid::new_v4().to_string();
-        let nodes = self.discovered_nodes.read().await;
+        let nodes = self.discovered_nodes().await;
 
         let mut total_resources = NodeResources {
             cpu_cores: 0,
@@ -526,6 +451,7 @@ impl ResourceManager {
             gpus: vec![],
             supported_models: vec![],
             current_load: 0.0,
+            loaded_models: vec![],
         };
 
         for node_id in &node_ids {
@@ -603,6 +529,7 @@ impl ResourceManager {
             gpus: Vec::new(),
             supported_models: Vec::new(),
             current_load: 0.0,
+            loaded_models: Vec::new(),
         }).await;
 
         self.set_balancing_strategy(BalancingStrategy::LeastLoaded).await;
@@ -613,14 +540,78 @@ impl ResourceManager {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct DiscoveryMessage {
-    message_type: DiscoveryMessageType,
-    node_info: NodeInfo,
+/// A discovered peer as a resource node, if it offers resources. Peers are
+/// dropped from discovery once stale, so anything listed is online.
+fn node_from_peer(peer: PeerStatus) -> Option<NodeInfo> {
+    let resources = peer.resources?;
+    let (address, port) = match peer.address.parse::<SocketAddr>() {
+        Ok(addr) => (addr.ip().to_string(), addr.port()),
+        Err(_) => (peer.address.clone(), 0),
+    };
+    // `latency` is how long ago the peer was last heard from.
+    let age = chrono::Duration::milliseconds(peer.latency.unwrap_or(0) as i64);
+    Some(NodeInfo {
+        id: peer.id,
+        address,
+        port,
+        mode: peer.mode,
+        resources,
+        allow_remote_access: true,
+        last_seen: Utc::now() - age,
+        status: NodeStatus::Online,
+    })
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-enum DiscoveryMessageType {
-    Announce,
-    Query,
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::network_discovery::DiscoveryOptions;
+
+    fn loaded(name: &str, size_mb: u64, vram_mb: u64) -> LoadedModel {
+        LoadedModel { name: name.to_string(), size_mb, vram_mb, expires_at: None }
+    }
+
+    async fn manager_with_memory(total_memory_mb: u64) -> ResourceManager {
+        let discovery = NetworkDiscovery::with_options(DiscoveryOptions {
+            port: 0,
+            broadcast: false,
+            ..DiscoveryOptions::default()
+        })
+        .await;
+        let manager = ResourceManager::new(discovery, true).await.expect("manager");
+        manager.update_node_resources(NodeResources {
+            cpu_cores: 8,
+            total_memory_mb,
+            available_memory_mb: total_memory_mb,
+            gpus: Vec::new(),
+            supported_models: Vec::new(),
+            current_load: 0.0,
+            loaded_models: Vec::new(),
+        }).await;
+        manager
+    }
+
+    #[tokio::test]
+    async fn loaded_models_reduce_available_memory_by_their_ram_share() {
+        let manager = manager_with_memory(16 * 1024).await;
+
+        // 2 GiB of llama3 stays outside the GPU; the embedder is all RAM; a
+        // report with more VRAM than size counts as nothing.
+        manager.report_ollama_models(
+            vec!["llama3:latest".to_string(), "all-minilm:latest".to_string()],
+            vec![loaded("llama3:latest", 6144, 4096), loaded("all-minilm:latest", 512, 0), loaded("odd", 100, 200)],
+        ).await;
+        let resources = manager.local_node_info().await.resources;
+        assert_eq!(resources.total_memory_mb, 16 * 1024);
+        assert_eq!(resources.available_memory_mb, 16 * 1024 - 2048 - 512);
+        assert_eq!(resources.supported_models, ["llama3:latest", "all-minilm:latest"]);
+        assert_eq!(resources.loaded_models.len(), 3);
+
+        // Each report replaces the last rather than adding to it.
+        manager.report_ollama_models(Vec::new(), Vec::new()).await;
+        assert_eq!(manager.local_node_info().await.resources.available_memory_mb, 16 * 1024);
+
+        manager.report_ollama_models(Vec::new(), vec![loaded("huge", 64 * 1024, 0)]).await;
+        assert_eq!(manager.local_node_info().await.resources.available_memory_mb, 0);
+    }
+}
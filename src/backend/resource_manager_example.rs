@@ -1,8 +1,9 @@
+use crate::backend::network_discovery::NetworkDiscovery;
 use crate::backend::resource_manager::ResourceManager;
 use crate::shared::models::{ResourceRequirements, Priority, BalancingStrategy};
 
 pub async fn resource_manager_example() {
-    let resource_manager = ResourceManager::new(true).await.unwrap();
+    let resource_manager = ResourceManager::new(NetworkDiscovery::new().await, true).await.unwrap();
 
     resource_manager.start_discovery().await.unwrap();
 
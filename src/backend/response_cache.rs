@@ -0,0 +1,450 @@
+//! On-disk cache of deterministic LLM replies.
+//!
+//! Entries are addressed by a SHA-256 of everything that shapes the reply:
+//! provider, model, endpoint, messages, options, tools and response format.
+//! Only requests that ask for it are cached: `LlmRequest.cache == Some(true)`,
+//! or `None` with `temperature == 0`. `Some(false)` bypasses the cache.
+//!
+//! Each entry is one JSON file under `<vault>/response_cache/<2 hex>/`.
+//! Expired entries are dropped when read; after each write the oldest entries
+//! (by last use) are removed until the cache fits its size limit. The size and
+//! last use of every entry are kept in memory, read from disk once, so a
+//! write doesn't walk the shards. File access runs on tokio's blocking pool.
+
+use crate::backend::llm_backend::{LlmReply, LlmRequest};
+use crate::backend::ollama_client::ToolCall;
+use crate::shared::models::{AiEntity, VgaError};
+use crate::shared::utils::compute_hash;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime};
+
+pub const RESPONSE_CACHE_DIR: &str = "response_cache";
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(7 * 24 * 3600);
+pub const DEFAULT_CACHE_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedReply {
+    created_at: DateTime<Utc>,
+    content: String,
+    #[serde(default)]
+    tool_calls: Vec<ToolCall>,
+    model: String,
+    prompt_tokens: Option<u64>,
+    completion_tokens: Option<u64>,
+}
+
+/// Entry count and size on disk.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct CacheSummary {
+    pub entries: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug)]
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+    max_bytes: u64,
+    /// Loaded from disk on first use; `None` until then.
+    index: Mutex<Option<CacheIndex>>,
+}
+
+/// Size and last use of every entry, and their total size.
+#[derive(Debug, Default)]
+struct CacheIndex {
+    entries: HashMap<PathBuf, (u64, SystemTime)>,
+    bytes: u64,
+}
+
+impl CacheIndex {
+    fn insert(&mut self, path: PathBuf, size: u64, used: SystemTime) {
+        if let Some((old, _)) = self.entries.insert(path, (size, used)) {
+            self.bytes -= old;
+        }
+        self.bytes += size;
+    }
+
+    fn remove(&mut self, path: &Path) {
+        if let Some((size, _)) = self.entries.remove(path) {
+            self.bytes -= size;
+        }
+    }
+}
+
+impl ResponseCache {
+    pub fn new(dir: impl Into<PathBuf>, ttl: Duration, max_bytes: u64) -> Self {
+        Self { dir: dir.into(), ttl, max_bytes, index: Mutex::new(None) }
+    }
+
+    /// Whether `request` may be answered from, and stored in, the cache.
+    pub fn is_cacheable(request: &LlmRequest) -> bool {
+        match request.cache {
+            Some(enabled) => enabled,
+            None => request
+                .options
+                .as_ref()
+                .and_then(|o| o.temperature)
+                .is_some_and(|t| t == 0.0),
+        }
+    }
+
+    /// Stable key for `request` sent to `entity`.
+    pub fn key(entity: &AiEntity, request: &LlmRequest) -> String {
+        let material = json!({
+            "provider": entity.provider,
+            "model": entity.model,
+            "endpoint": entity.custom_base_url,
+            "messages": request.messages,
+            "options": request.options,
+            "tools": request.tools,
+            "format": request.format,
+        });
+        compute_hash(material.to_string().as_bytes())
+    }
+
+    pub async fn get(self: &Arc<Self>, key: &str) -> Option<LlmReply> {
+        let key = key.to_string();
+        self.blocking(move |cache| cache.get_blocking(&key)).await.flatten()
+    }
+
+    pub async fn put(self: &Arc<Self>, key: &str, reply: &LlmReply) -> Result<(), VgaError> {
+        let (key, reply) = (key.to_string(), reply.clone());
+        self.blocking(move |cache| cache.put_blocking(&key, &reply))
+            .await
+            .unwrap_or_else(|| Err(VgaError::Storage("Cache write was cancelled".to_string())))
+    }
+
+    pub async fn summary(self: &Arc<Self>) -> CacheSummary {
+        self.blocking(|cache| {
+            cache.with_index(|index| CacheSummary {
+                entries: index.entries.len() as u64,
+                bytes: index.bytes,
+            })
+        })
+        .await
+        .unwrap_or_default()
+    }
+
+    /// Remove every entry; returns how many there were.
+    pub async fn clear(self: &Arc<Self>) -> u64 {
+        self.blocking(|cache| {
+            cache.with_index(|index| {
+                for path in index.entries.keys() {
+                    let _ = fs::remove_file(path);
+                }
+                let count = index.entries.len() as u64;
+                *index = CacheIndex::default();
+                count
+            })
+        })
+        .await
+        .unwrap_or_default()
+    }
+
+    /// Runs `f` on the blocking pool; `None` if it panicked or was cancelled.
+    async fn blocking<T: Send + 'static>(
+        self: &Arc<Self>,
+        f: impl FnOnce(&ResponseCache) -> T + Send + 'static,
+    ) -> Option<T> {
+        let cache = Arc::clone(self);
+        tokio::task::spawn_blocking(move || f(&cache)).await.ok()
+    }
+
+    fn get_blocking(&self, key: &str) -> Option<LlmReply> {
+        let path = self.path(key);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(_) => {
+                self.with_index(|index| index.remove(&path));
+                return None;
+            }
+        };
+        let cached: CachedReply = match serde_json::from_str(&text) {
+            Ok(cached) => cached,
+            Err(e) => {
+                tracing::warn!("Dropping unreadable cache entry {}: {e}", path.display());
+                self.remove(&path);
+                return None;
+            }
+        };
+        let age = (Utc::now() - cached.created_at).to_std().unwrap_or_default();
+        if age > self.ttl {
+            self.remove(&path);
+            return None;
+        }
+        // The modification time doubles as "last used" for eviction, so the
+        // order survives a restart.
+        let now = SystemTime::now();
+        if let Ok(file) = fs::File::options().append(true).open(&path) {
+            let _ = file.set_modified(now);
+        }
+        self.with_index(|index| index.insert(path, text.len() as u64, now));
+        Some(LlmReply {
+            content: cached.content,
+            tool_calls: cached.tool_calls,
+            model: cached.model,
+            prompt_tokens: cached.prompt_tokens,
+            completion_tokens: cached.completion_tokens,
+        })
+    }
+
+    fn put_blocking(&self, key: &str, reply: &LlmReply) -> Result<(), VgaError> {
+        let path = self.path(key);
+        let cached = CachedReply {
+            created_at: Utc::now(),
+            content: reply.content.clone(),
+            tool_calls: reply.tool_calls.clone(),
+            model: reply.model.clone(),
+            prompt_tokens: reply.prompt_tokens,
+            completion_tokens: reply.completion_tokens,
+        };
+        let json = serde_json::to_vec(&cached)
+            .map_err(|e| VgaError::Storage(format!("Failed to serialize cache entry: {e}")))?;
+        let size = json.len() as u64;
+        if size > self.max_bytes {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| VgaError::Storage(format!("Failed to create cache directory: {e}")))?;
+        }
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, json)
+            .and_then(|_| fs::rename(&tmp, &path))
+            .map_err(|e| VgaError::Storage(format!("Failed to write cache entry: {e}")))?;
+        self.with_index(|index| {
+            index.insert(path, size, SystemTime::now());
+            self.evict(index);
+        });
+        Ok(())
+    }
+
+    /// Drop entries past their TTL, then the least recently used until the
+    /// total fits `max_bytes`.
+    fn evict(&self, index: &mut CacheIndex) {
+        let now = SystemTime::now();
+        // Reads refresh the last use, so an entry untouched for longer than
+        // the TTL is certainly expired.
+        let stale: Vec<PathBuf> = index
+            .entries
+            .iter()
+            .filter(|(_, (_, used))| now.duration_since(*used).is_ok_and(|age| age > self.ttl))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in stale {
+            let _ = fs::remove_file(&path);
+            index.remove(&path);
+        }
+        if index.bytes <= self.max_bytes {
+            return;
+        }
+        let mut by_use: Vec<(PathBuf, SystemTime)> =
+            index.entries.iter().map(|(path, (_, used))| (path.clone(), *used)).collect();
+        by_use.sort_by_key(|e| e.1);
+        for (path, _) in by_use {
+            if index.bytes <= self.max_bytes {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() || !path.exists() {
+                index.remove(&path);
+            }
+        }
+    }
+
+    fn remove(&self, path: &Path) {
+        let _ = fs::remove_file(path);
+        self.with_index(|index| index.remove(path));
+    }
+
+    /// Runs `f` on the index, reading it from disk first if this is the first use.
+    fn with_index<T>(&self, f: impl FnOnce(&mut CacheIndex) -> T) -> T {
+        let mut guard = self.index.lock().unwrap_or_else(PoisonError::into_inner);
+        let index = guard.get_or_insert_with(|| {
+            let mut index = CacheIndex::default();
+            for (path, size, used) in self.entries() {
+                index.insert(path, size, used);
+            }
+            index
+        });
+        f(index)
+    }
+
+    /// `(path, size, last used)` of every entry.
+    fn entries(&self) -> Vec<(PathBuf, u64, SystemTime)> {
+        let mut out = Vec::new();
+        let Ok(shards) = fs::read_dir(&self.dir) else { return out };
+        for shard in shards.flatten() {
+            let Ok(files) = fs::read_dir(shard.path()) else { continue };
+            for file in files.flatten() {
+                let path = file.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                    continue;
+                }
+                if let Ok(meta) = file.metadata() {
+                    let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                    out.push((path, meta.len(), modified));
+                }
+            }
+        }
+        out
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let shard = key.get(..2).unwrap_or("00");
+        self.dir.join(shard).join(format!("{key}.json"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::ollama_client::ChatOptions;
+
+    fn entity(model: &str) -> AiEntity {
+        AiEntity {
+            name: "main".to_string(),
+            provider: "openai".to_string(),
+            model: model.to_string(),
+            note: String::new(),
+            custom_base_url: None,
+            key_header: None,
+            key_prefix: None,
+        }
+    }
+
+    fn request(user: &str, temperature: Option<f32>, cache: Option<bool>) -> LlmRequest {
+        LlmRequest {
+            options: temperature.map(|t| ChatOptions { temperature: Some(t), ..Default::default() }),
+            cache,
+            ..LlmRequest::prompt(Some("be brief"), user)
+        }
+    }
+
+    fn reply(content: &str) -> LlmReply {
+        LlmReply {
+            content: content.to_string(),
+            tool_calls: Vec::new(),
+            model: "gpt-4o".to_string(),
+            prompt_tokens: Some(3),
+            completion_tokens: Some(5),
+        }
+    }
+
+    fn cache(ttl: Duration, max_bytes: u64) -> Arc<ResponseCache> {
+        let dir = std::env::temp_dir().join(format!("vas-response-cache-{}", uuid::Uuid::new_v4()));
+        Arc::new(ResponseCache::new(dir, ttl, max_bytes))
+    }
+
+    #[test]
+    fn keys_are_stable_and_cover_what_shapes_the_reply() {
+        let key = ResponseCache::key(&entity("gpt-4o"), &request("hi", Some(0.0), None));
+        assert_eq!(key, ResponseCache::key(&entity("gpt-4o"), &request("hi", Some(0.0), None)));
+        // Whether to use the cache isn't part of the request's content.
+        assert_eq!(key, ResponseCache::key(&entity("gpt-4o"), &request("hi", Some(0.0), Some(true))));
+        let mut renamed = entity("gpt-4o");
+        renamed.name = "backup".to_string();
+        assert_eq!(key, ResponseCache::key(&renamed, &request("hi", Some(0.0), None)));
+
+        assert_ne!(key, ResponseCache::key(&entity("gpt-4o-mini"), &request("hi", Some(0.0), None)));
+        assert_ne!(key, ResponseCache::key(&entity("gpt-4o"), &request("hello", Some(0.0), None)));
+        assert_ne!(key, ResponseCache::key(&entity("gpt-4o"), &request("hi", Some(0.5), None)));
+        let mut relayed = entity("gpt-4o");
+        relayed.custom_base_url = Some("https://relay.example.com/v1".to_string());
+        assert_ne!(key, ResponseCache::key(&relayed, &request("hi", Some(0.0), None)));
+    }
+
+    #[test]
+    fn only_deterministic_or_opted_in_requests_are_cached() {
+        assert!(ResponseCache::is_cacheable(&request("hi", Some(0.0), None)));
+        assert!(!ResponseCache::is_cacheable(&request("hi", Some(0.7), None)));
+        assert!(!ResponseCache::is_cacheable(&request("hi", None, None)));
+        assert!(ResponseCache::is_cacheable(&request("hi", Some(0.7), Some(true))));
+        assert!(!ResponseCache::is_cacheable(&request("hi", Some(0.0), Some(false))));
+    }
+
+    #[tokio::test]
+    async fn a_stored_reply_is_returned() {
+        let cache = cache(DEFAULT_CACHE_TTL, DEFAULT_CACHE_MAX_BYTES);
+        assert!(cache.get("ab01").await.is_none());
+        cache.put("ab01", &reply("four")).await.expect("put");
+
+        let hit = cache.get("ab01").await.expect("hit");
+        assert_eq!(hit.content, "four");
+        assert_eq!(hit.total_tokens(), 8);
+        let summary = cache.summary().await;
+        assert_eq!(summary.entries, 1);
+        assert_eq!(summary.bytes, fs::metadata(cache.path("ab01")).expect("entry").len());
+
+        assert_eq!(cache.clear().await, 1);
+        assert!(cache.get("ab01").await.is_none());
+        assert_eq!(cache.summary().await.entries, 0);
+    }
+
+    #[tokio::test]
+    async fn expired_entries_are_dropped() {
+        let cache = cache(Duration::from_millis(50), DEFAULT_CACHE_MAX_BYTES);
+        cache.put("ab01", &reply("four")).await.expect("put");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert!(cache.get("ab01").await.is_none());
+        assert!(!cache.path("ab01").exists());
+        assert_eq!(cache.summary().await.entries, 0);
+    }
+
+    #[tokio::test]
+    async fn the_least_recently_used_entry_is_evicted_first() {
+        let size = serde_json::to_vec(&CachedReply {
+            created_at: Utc::now(),
+            content: "a".to_string(),
+            tool_calls: Vec::new(),
+            model: "gpt-4o".to_string(),
+            prompt_tokens: Some(3),
+            completion_tokens: Some(5),
+        })
+        .expect("json")
+        .len() as u64;
+        // Room for two entries, not three.
+        let cache = cache(DEFAULT_CACHE_TTL, size * 2 + size / 2);
+
+        cache.put("aa01", &reply("a")).await.expect("put");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        cache.put("bb02", &reply("b")).await.expect("put");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        // Reading the first makes the second the oldest.
+        assert!(cache.get("aa01").await.is_some());
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        cache.put("cc03", &reply("c")).await.expect("put");
+
+        assert!(cache.get("bb02").await.is_none());
+        assert!(cache.get("aa01").await.is_some());
+        assert!(cache.get("cc03").await.is_some());
+        assert_eq!(cache.summary().await.entries, 2);
+        cache.clear().await;
+    }
+
+    #[tokio::test]
+    async fn the_size_is_read_from_disk_once() {
+        let first = cache(DEFAULT_CACHE_TTL, DEFAULT_CACHE_MAX_BYTES);
+        first.put("aa01", &reply("a")).await.expect("put");
+        first.put("bb02", &reply("b")).await.expect("put");
+        first.put("bb02", &reply("b")).await.expect("overwrite");
+        let bytes = first.summary().await.bytes;
+
+        // A new process finds what the last one left behind.
+        let reopened = Arc::new(ResponseCache::new(first.dir(), DEFAULT_CACHE_TTL, DEFAULT_CACHE_MAX_BYTES));
+        let summary = reopened.summary().await;
+        assert_eq!(summary.entries, 2);
+        assert_eq!(summary.bytes, bytes);
+        reopened.clear().await;
+    }
+}
@@ -0,0 +1,621 @@
+//! Structured (JSON) replies checked against a JSON Schema.
+//!
+//! The schema goes to the provider as `format` / `response_format`, but not
+//! every model honours it, so the reply is validated here as well. A reply
+//! that does not parse or validate is sent back to the model with the list of
+//! problems, up to `max_attempts` times in total.
+//!
+//! The validator covers the keywords structured-output schemas use: `type`,
+//! `enum`, `const`, `properties`, `required`, `additionalProperties`, `items`,
+//! `minItems`/`maxItems`, `minLength`/`maxLength`, `minimum`/`maximum`,
+//! `anyOf`/`oneOf`/`allOf` and local `$ref`s (`#/$defs/…`). Other keywords,
+//! such as `pattern` and `format`, are ignored.
+
+use crate::backend::failover::{FailoverReply, FailoverRouter};
+use crate::shared::utils::truncate;
+use crate::backend::llm_backend::LlmRequest;
+use crate::backend::ollama_client::{ChatMessage, ResponseFormat};
+use crate::shared::models::{ConflictInfo, VgaError};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// Requests made before giving up on a valid reply.
+pub const DEFAULT_STRUCTURED_ATTEMPTS: usize = 3;
+
+/// A type that can be requested as structured output.
+pub trait StructuredOutput: DeserializeOwned {
+    /// Schema name sent to OpenAI-style APIs (`[a-zA-Z0-9_-]`).
+    const NAME: &'static str;
+
+    /// JSON Schema of the serialized form.
+    fn schema() -> Value;
+}
+
+impl StructuredOutput for ConflictInfo {
+    const NAME: &'static str = "conflict_info";
+
+    fn schema() -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "task_id": { "type": "string", "description": "UUID of the conflicting task" },
+                "conflicts": { "type": "array", "items": { "type": "string" } },
+                "resolution_options": { "type": "array", "items": { "type": "string" } },
+            },
+            "required": ["task_id", "conflicts", "resolution_options"],
+            "additionalProperties": false,
+        })
+    }
+}
+
+/// One way a value fails its schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaViolation {
+    /// Where in the value, e.g. `$.components[2].name`.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StructuredError {
+    /// The request itself failed (no entity could answer).
+    Request(VgaError),
+    /// Every attempt produced a reply that did not match the schema.
+    Invalid {
+        attempts: usize,
+        /// Problems with the last reply.
+        violations: Vec<SchemaViolation>,
+        last_reply: String,
+    },
+}
+
+impl fmt::Display for StructuredError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Request(e) => write!(f, "request failed: {e:?}"),
+            Self::Invalid { attempts, violations, .. } => {
+                let list: Vec<String> = violations.iter().map(ToString::to_string).collect();
+                write!(f, "no valid reply after {attempts} attempts: {}", list.join("; "))
+            }
+        }
+    }
+}
+
+impl From<StructuredError> for VgaError {
+    fn from(e: StructuredError) -> Self {
+        match e {
+            StructuredError::Request(e) => e,
+            invalid => VgaError::InvalidOutput(format!("Structured output: {invalid}")),
+        }
+    }
+}
+
+/// A reply that passed validation.
+#[derive(Debug, Clone)]
+pub struct StructuredReply<T> {
+    pub value: T,
+    /// The last (valid) exchange; `reply.content` is the raw JSON text.
+    pub served: FailoverReply,
+    pub attempts: usize,
+}
+
+/// Ask `target` for JSON matching `schema`, retrying with the validation
+/// errors on a mismatch.
+pub async fn chat_structured(
+    router: &FailoverRouter,
+    target: &str,
+    request: LlmRequest,
+    name: &str,
+    schema: &Value,
+    max_attempts: usize,
+) -> Result<StructuredReply<Value>, StructuredError> {
+    chat_until_valid(router, target, request, name, schema, max_attempts, |value| Ok(value.clone())).await
+}
+
+/// Ask `target` for a `T`, using `T::schema()`.
+pub async fn chat_typed<T: StructuredOutput>(
+    router: &FailoverRouter,
+    target: &str,
+    request: LlmRequest,
+    max_attempts: usize,
+) -> Result<StructuredReply<T>, StructuredError> {
+    let schema = T::schema();
+    chat_until_valid(router, target, request, T::NAME, &schema, max_attempts, |value| {
+        // The schema may be looser than serde (e.g. integer ranges); report
+        // what serde rejects the same way as a schema violation.
+        serde_json::from_value(value.clone()).map_err(|e| {
+            vec![SchemaViolation { path: "$".to_string(), message: e.to_string() }]
+        })
+    })
+    .await
+}
+
+async fn chat_until_valid<T>(
+    router: &FailoverRouter,
+    target: &str,
+    mut request: LlmRequest,
+    name: &str,
+    schema: &Value,
+    max_attempts: usize,
+    convert: impl Fn(&Value) -> Result<T, Vec<SchemaViolation>>,
+) -> Result<StructuredReply<T>, StructuredError> {
+    request.format = Some(ResponseFormat::schema(name, schema.clone()));
+    let mut violations = Vec::new();
+    let mut last_reply = String::new();
+
+    for attempt in 1..=max_attempts.max(1) {
+        let served = router.chat(target, &request).await.map_err(StructuredError::Request)?;
+        let checked = parse_json(&served.reply.content).and_then(|value| {
+            let problems = validate(schema, &value);
+            if problems.is_empty() {
+                convert(&value)
+            } else {
+                Err(problems)
+            }
+        });
+        match checked {
+            Ok(value) => return Ok(StructuredReply { value, served, attempts: attempt }),
+            Err(problems) => {
+                tracing::debug!("{target}: structured reply attempt {attempt} rejected ({} problems)", problems.len());
+                violations = problems;
+                last_reply = served.reply.content;
+                request.messages.push(ChatMessage::new("assistant", last_reply.clone()));
+                request.messages.push(ChatMessage::new("user", correction(&violations)));
+            }
+        }
+    }
+
+    Err(StructuredError::Invalid { attempts: max_attempts.max(1), violations, last_reply })
+}
+
+fn correction(violations: &[SchemaViolation]) -> String {
+    let list: Vec<String> = violations.iter().take(20).map(|v| format!("- {v}")).collect();
+    format!(
+        "Your reply does not match the required JSON schema:\n{}\nReply again with only the corrected JSON.",
+        list.join("\n")
+    )
+}
+
+/// Parse a reply as JSON, tolerating a Markdown code fence around it.
+pub fn parse_json(text: &str) -> Result<Value, Vec<SchemaViolation>> {
+    let trimmed = text.trim();
+    let body = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|rest| rest.trim_end().strip_suffix("```"))
+        .unwrap_or(trimmed);
+    serde_json::from_str(body.trim()).map_err(|e| {
+        vec![SchemaViolation {
+            path: "$".to_string(),
+            message: format!("not valid JSON ({e}): {}", truncate(body, 200)),
+        }]
+    })
+}
+
+/// Every way `value` fails `schema`; empty if it is valid.
+pub fn validate(schema: &Value, value: &Value) -> Vec<SchemaViolation> {
+    let mut violations = Vec::new();
+    check(schema, schema, value, "$", &mut violations);
+    violations
+}
+
+fn check(root: &Value, schema: &Value, value: &Value, path: &str, out: &mut Vec<SchemaViolation>) {
+    let Some(schema) = schema.as_object() else {
+        // `true` accepts everything, `false` nothing.
+        if schema == &Value::Bool(false) {
+            push(out, path, "no value is allowed here".to_string());
+        }
+        return;
+    };
+
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        match resolve_ref(root, reference) {
+            Some(target) => check(root, target, value, path, out),
+            None => push(out, path, format!("schema reference {reference} cannot be resolved")),
+        }
+        return;
+    }
+
+    if let Some(expected) = schema.get("type") {
+        let names: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !names.is_empty() && !names.iter().any(|name| has_type(value, name)) {
+            push(out, path, format!("expected {}, got {}", names.join(" or "), type_name(value)));
+            return;
+        }
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            let options: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            push(out, path, format!("{value} is not one of {}", options.join(", ")));
+        }
+    }
+    if let Some(constant) = schema.get("const") {
+        if constant != value {
+            push(out, path, format!("expected {constant}, got {value}"));
+        }
+    }
+
+    match value {
+        Value::Object(fields) => check_object(root, schema, fields, path, out),
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (idx, item) in items.iter().enumerate() {
+                    check(root, item_schema, item, &format!("{path}[{idx}]"), out);
+                }
+            }
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+                if (items.len() as u64) < min {
+                    push(out, path, format!("needs at least {min} items, has {}", items.len()));
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+                if items.len() as u64 > max {
+                    push(out, path, format!("allows at most {max} items, has {}", items.len()));
+                }
+            }
+        }
+        Value::String(text) => {
+            let len = text.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                if len < min {
+                    push(out, path, format!("needs at least {min} characters, has {len}"));
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                if len > max {
+                    push(out, path, format!("allows at most {max} characters, has {len}"));
+                }
+            }
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+                if number < min {
+                    push(out, path, format!("{number} is below the minimum {min}"));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+                if number > max {
+                    push(out, path, format!("{number} is above the maximum {max}"));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+        for sub in all {
+            check(root, sub, value, path, out);
+        }
+    }
+    for keyword in ["anyOf", "oneOf"] {
+        let Some(options) = schema.get(keyword).and_then(Value::as_array) else { continue };
+        let matching = options
+            .iter()
+            .filter(|sub| {
+                let mut scratch = Vec::new();
+                check(root, sub, value, path, &mut scratch);
+                scratch.is_empty()
+            })
+            .count();
+        let ok = if keyword == "oneOf" { matching == 1 } else { matching > 0 };
+        if !ok {
+            push(out, path, format!("matches {matching} of the {} {keyword} alternatives", options.len()));
+        }
+    }
+}
+
+fn check_object(
+    root: &Value,
+    schema: &Map<String, Value>,
+    fields: &Map<String, Value>,
+    path: &str,
+    out: &mut Vec<SchemaViolation>,
+) {
+    let properties = schema.get("properties").and_then(Value::as_object);
+    for name in schema.get("required").and_then(Value::as_array).into_iter().flatten() {
+        if let Some(name) = name.as_str() {
+            if !fields.contains_key(name) {
+                push(out, path, format!("missing required field '{name}'"));
+            }
+        }
+    }
+    for (name, field) in fields {
+        let field_path = format!("{path}.{name}");
+        match properties.and_then(|p| p.get(name)) {
+            Some(field_schema) => check(root, field_schema, field, &field_path, out),
+            None => match schema.get("additionalProperties") {
+                Some(Value::Bool(false)) => push(out, &field_path, "is not an allowed field".to_string()),
+                Some(extra @ Value::Object(_)) => check(root, extra, field, &field_path, out),
+                _ => {}
+            },
+        }
+    }
+}
+
+fn resolve_ref<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    let pointer = reference.strip_prefix('#')?;
+    root.pointer(pointer)
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "integer" => value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn push(out: &mut Vec<SchemaViolation>, path: &str, message: String) {
+    out.push(SchemaViolation { path: path.to_string(), message });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::api_manager::ApiKeyManager;
+    use crate::backend::failover::ENTITIES_FILE;
+    use serde_json::json;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::task::JoinHandle;
+
+    fn paths(violations: &[SchemaViolation]) -> Vec<&str> {
+        violations.iter().map(|v| v.path.as_str()).collect()
+    }
+
+    #[test]
+    fn types_enums_and_constants() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "minLength": 2 },
+                "count": { "type": "integer", "minimum": 0, "maximum": 10 },
+                "note": { "type": ["string", "null"] },
+                "level": { "enum": ["low", "high"] },
+                "kind": { "const": "task" },
+            },
+        });
+        let valid = json!({ "name": "ab", "count": 3, "note": null, "level": "low", "kind": "task" });
+        assert!(validate(&schema, &valid).is_empty());
+        assert!(validate(&schema, &json!({ "count": 3.0 })).is_empty(), "3.0 is an integer");
+
+        let invalid = json!({ "name": 7, "count": 1.5, "note": 1, "level": "mid", "kind": "job" });
+        let violations = validate(&schema, &invalid);
+        let mut found = paths(&violations);
+        found.sort();
+        assert_eq!(found, ["$.count", "$.kind", "$.level", "$.name", "$.note"]);
+        assert!(violations.iter().any(|v| v.message == "expected string, got number"));
+        assert!(violations.iter().any(|v| v.message == "expected string or null, got number"));
+
+        let out_of_range = validate(&schema, &json!({ "name": "a", "count": 11 }));
+        assert_eq!(paths(&out_of_range), ["$.count", "$.name"]);
+        assert_eq!(validate(&schema, &json!([])).len(), 1, "not an object at all");
+    }
+
+    #[test]
+    fn required_and_additional_properties() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "id": { "type": "string" } },
+            "required": ["id", "title"],
+            "additionalProperties": false,
+        });
+        let violations = validate(&schema, &json!({ "id": "1", "extra": true }));
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations[0].message, "missing required field 'title'");
+        assert_eq!(violations[1].path, "$.extra");
+
+        let open = json!({ "type": "object", "additionalProperties": { "type": "number" } });
+        assert!(validate(&open, &json!({ "a": 1, "b": 2 })).is_empty());
+        assert_eq!(paths(&validate(&open, &json!({ "a": "x" }))), ["$.a"]);
+        let anything = json!({ "type": "object", "properties": {} });
+        assert!(validate(&anything, &json!({ "a": "x" })).is_empty());
+    }
+
+    #[test]
+    fn any_of_needs_one_match_and_one_of_exactly_one() {
+        let any = json!({ "anyOf": [{ "type": "string" }, { "type": "integer" }] });
+        assert!(validate(&any, &json!("x")).is_empty());
+        assert!(validate(&any, &json!(1)).is_empty());
+        let violations = validate(&any, &json!(true));
+        assert_eq!(violations[0].message, "matches 0 of the 2 anyOf alternatives");
+
+        let one = json!({ "oneOf": [{ "type": "number" }, { "type": "integer" }] });
+        assert!(validate(&one, &json!(1.5)).is_empty());
+        let violations = validate(&one, &json!(2));
+        assert_eq!(violations[0].message, "matches 2 of the 2 oneOf alternatives");
+
+        let all = json!({ "allOf": [{ "minimum": 1 }, { "maximum": 3 }] });
+        assert!(validate(&all, &json!(2)).is_empty());
+        assert_eq!(validate(&all, &json!(4)).len(), 1);
+    }
+
+    #[test]
+    fn local_refs_are_followed() {
+        let schema = json!({
+            "$defs": { "step": { "type": "object", "required": ["name"] } },
+            "type": "object",
+            "properties": {
+                "first": { "$ref": "#/$defs/step" },
+                "other": { "$ref": "#/$defs/missing" },
+            },
+        });
+        assert!(validate(&schema, &json!({ "first": { "name": "a" } })).is_empty());
+        let violations = validate(&schema, &json!({ "first": {}, "other": 1 }));
+        assert_eq!(paths(&violations), ["$.first", "$.other"]);
+        assert_eq!(violations[1].message, "schema reference #/$defs/missing cannot be resolved");
+    }
+
+    #[test]
+    fn array_items_are_checked_with_their_index() {
+        let schema = json!({
+            "type": "array",
+            "items": { "type": "object", "properties": { "name": { "type": "string" } } },
+            "minItems": 1,
+            "maxItems": 2,
+        });
+        assert!(validate(&schema, &json!([{ "name": "a" }])).is_empty());
+        assert_eq!(paths(&validate(&schema, &json!([{ "name": "a" }, { "name": 2 }]))), ["$[1].name"]);
+        assert_eq!(validate(&schema, &json!([]))[0].message, "needs at least 1 items, has 0");
+        assert_eq!(validate(&schema, &json!([{}, {}, {}]))[0].message, "allows at most 2 items, has 3");
+        assert_eq!(validate(&json!(false), &json!(1))[0].message, "no value is allowed here");
+    }
+
+    #[test]
+    fn replies_may_be_fenced_but_must_be_json() {
+        assert_eq!(parse_json("```json\n{\"a\": 1}\n```").expect("fenced"), json!({ "a": 1 }));
+        assert_eq!(parse_json(" ```\n[1]\n``` ").expect("bare fence"), json!([1]));
+        let violations = parse_json("Sure! Here it is: {").expect_err("prose");
+        assert_eq!(paths(&violations), ["$"]);
+        assert!(violations[0].message.starts_with("not valid JSON"));
+    }
+
+    #[test]
+    fn invalid_replies_are_not_reported_as_resource_limits() {
+        let invalid = StructuredError::Invalid { attempts: 2, violations: Vec::new(), last_reply: "{}".to_string() };
+        assert!(matches!(VgaError::from(invalid), VgaError::InvalidOutput(_)));
+        let failed = StructuredError::Request(VgaError::NetworkSplit);
+        assert!(matches!(VgaError::from(failed), VgaError::NetworkSplit));
+    }
+
+    /// Answers each `/api/chat` call with the next of `replies` as Ollama
+    /// would, and hands back the request bodies it got.
+    async fn ollama_stub(replies: Vec<&'static str>) -> (String, JoinHandle<Vec<Value>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let base = format!("http://{}", listener.local_addr().expect("addr"));
+        let server = tokio::spawn(async move {
+            let mut bodies = Vec::new();
+            for content in replies {
+                let (mut conn, _) = listener.accept().await.expect("accept");
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                let body_start = loop {
+                    let n = conn.read(&mut buf).await.expect("read");
+                    request.extend_from_slice(&buf[..n]);
+                    if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break end + 4;
+                    }
+                };
+                let head = String::from_utf8_lossy(&request[..body_start]).to_lowercase();
+                let length: usize = head
+                    .lines()
+                    .find_map(|l| l.strip_prefix("content-length:"))
+                    .and_then(|v| v.trim().parse().ok())
+                    .expect("content-length");
+                while request.len() < body_start + length {
+                    let n = conn.read(&mut buf).await.expect("read");
+                    request.extend_from_slice(&buf[..n]);
+                }
+                bodies.push(serde_json::from_slice(&request[body_start..]).expect("json body"));
+                let reply = json!({
+                    "model": "llama3",
+                    "created_at": "2026-01-01T00:00:00Z",
+                    "message": { "role": "assistant", "content": content },
+                    "done": true,
+                })
+                .to_string();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{reply}",
+                    reply.len()
+                );
+                conn.write_all(response.as_bytes()).await.expect("write");
+            }
+            bodies
+        });
+        (base, server)
+    }
+
+    async fn router(base: &str) -> FailoverRouter {
+        let dir = std::env::temp_dir().join(format!("vas-structured-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("vault dir");
+        let entities = json!([{
+            "name": "local",
+            "provider": "ollama",
+            "model": "llama3",
+            "note": "",
+            "custom_base_url": base,
+        }]);
+        std::fs::write(dir.join(ENTITIES_FILE), entities.to_string()).expect("entities");
+        FailoverRouter::new(ApiKeyManager::with_vault_dir(dir).await)
+    }
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": { "answer": { "type": "integer" } },
+            "required": ["answer"],
+            "additionalProperties": false,
+        })
+    }
+
+    #[tokio::test]
+    async fn an_invalid_reply_is_sent_back_with_its_problems() {
+        let (base, server) = ollama_stub(vec![r#"{"answer": "four"}"#, r#"{"answer": 4}"#]).await;
+        let router = router(&base).await;
+        let request = LlmRequest::prompt(None, "2 + 2?");
+
+        let reply = chat_structured(&router, "local", request, "sum", &schema(), 3).await.expect("valid");
+        assert_eq!(reply.value, json!({ "answer": 4 }));
+        assert_eq!(reply.attempts, 2);
+
+        let bodies = server.await.expect("server");
+        assert_eq!(bodies[0]["format"], schema());
+        let retry = bodies[1]["messages"].as_array().expect("messages");
+        assert_eq!(retry.len(), 3);
+        assert_eq!(retry[1]["role"], "assistant");
+        assert_eq!(retry[1]["content"], r#"{"answer": "four"}"#);
+        let correction = retry[2]["content"].as_str().expect("correction");
+        assert!(correction.contains("- $.answer: expected integer, got string"), "{correction}");
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let (base, server) = ollama_stub(vec!["not json", r#"{"answer": 4, "why": "math"}"#]).await;
+        let router = router(&base).await;
+        let request = LlmRequest::prompt(None, "2 + 2?");
+
+        let err = chat_structured(&router, "local", request, "sum", &schema(), 2).await.expect_err("invalid");
+        match err {
+            StructuredError::Invalid { attempts, violations, last_reply } => {
+                assert_eq!(attempts, 2);
+                assert_eq!(paths(&violations), ["$.why"]);
+                assert_eq!(last_reply, r#"{"answer": 4, "why": "math"}"#);
+            }
+            other => panic!("expected Invalid, got {other}"),
+        }
+        assert_eq!(server.await.expect("server").len(), 2);
+    }
+
+    #[tokio::test]
+    async fn typed_replies_are_also_checked_by_serde() {
+        let reply = r#"{"task_id": "not-a-uuid", "conflicts": [], "resolution_options": []}"#;
+        let (base, _server) = ollama_stub(vec![reply]).await;
+        let router = router(&base).await;
+        let request = LlmRequest::prompt(None, "conflicts?");
+
+        let err = chat_typed::<ConflictInfo>(&router, "local", request, 1).await.expect_err("bad uuid");
+        assert!(matches!(err, StructuredError::Invalid { attempts: 1, .. }), "{err}");
+    }
+}
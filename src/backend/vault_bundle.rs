@@ -0,0 +1,182 @@
+//! Passphrase-protected export bundles for moving vault entries between machines.
+//!
+//! A bundle is a small JSON document: the KDF parameters and salt in the clear,
+//! followed by one AES-256-GCM blob holding the selected entries (secrets and
+//! metadata) and, optionally, the matching `AiEntity` records. The clear header
+//! is bound to the blob as associated data. The passphrase is independent of
+//! either vault's master password.
+
+use crate::backend::vault_file::{self, KdfParams, VaultEntry};
+use crate::shared::models::{AiEntity, SecretString, VgaError};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+/// Current bundle format version written by this build.
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+const BUNDLE_KIND: &str = "vas-vault-bundle";
+
+/// Bounds on the KDF parameters a bundle may ask for. A bundle comes from
+/// outside, and its parameters are run before the passphrase is checked.
+const MAX_KDF_MEMORY_KIB: u32 = 256 * 1024;
+const MAX_KDF_ITERATIONS: u32 = 16;
+const MAX_KDF_PARALLELISM: u32 = 8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BundleEnvelope {
+    kind: String,
+    format_version: u32,
+    kdf: KdfParams,
+    /// Hex-encoded KDF salt.
+    salt: String,
+    /// Hex-encoded `nonce || ciphertext` of a [`BundlePayload`].
+    payload: String,
+}
+
+/// The envelope fields covered by the payload's associated data, so that
+/// editing them fails decryption.
+#[derive(Serialize)]
+struct BundleHeader<'a> {
+    kind: &'a str,
+    format_version: u32,
+    kdf: &'a KdfParams,
+    salt: &'a str,
+}
+
+impl BundleHeader<'_> {
+    fn aad(&self) -> Result<Vec<u8>, VgaError> {
+        serde_json::to_vec(self).map_err(|e| VgaError::AuthVaultError(format!("Failed to serialize bundle: {e}")))
+    }
+}
+
+/// Decrypted bundle contents.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct BundlePayload {
+    pub exported_at: Option<DateTime<Utc>>,
+    pub entries: Vec<BundleEntry>,
+    #[serde(default)]
+    pub entities: Vec<AiEntity>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleEntry {
+    pub name: String,
+    #[serde(default)]
+    pub provider_id: Option<String>,
+    #[serde(default)]
+    pub notes: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub rotated_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub pool: Option<String>,
+    pub secret: SecretString,
+}
+
+impl BundleEntry {
+    pub fn from_vault_entry(entry: &VaultEntry, secret: SecretString) -> Self {
+        Self {
+            name: entry.name.clone(),
+            provider_id: entry.provider_id.clone(),
+            notes: entry.notes.clone(),
+            created_at: entry.created_at,
+            rotated_at: entry.rotated_at,
+            pool: entry.pool.clone(),
+            secret,
+        }
+    }
+}
+
+/// Encrypt `payload` under `passphrase` and return the bundle as JSON text.
+pub fn seal(payload: &BundlePayload, passphrase: &str) -> Result<String, VgaError> {
+    if passphrase.trim().is_empty() {
+        return Err(VgaError::AuthVaultError("Bundle passphrase cannot be empty".to_string()));
+    }
+
+    let mut salt = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut salt);
+    let kdf = KdfParams::default();
+    let key = kdf.derive_key(passphrase, &salt)?;
+    let salt = hex::encode(salt);
+    let aad = BundleHeader {
+        kind: BUNDLE_KIND,
+        format_version: BUNDLE_FORMAT_VERSION,
+        kdf: &kdf,
+        salt: &salt,
+    }
+    .aad()?;
+
+    let plain = Zeroizing::new(
+        serde_json::to_vec(payload)
+            .map_err(|e| VgaError::AuthVaultError(format!("Failed to serialize bundle: {e}")))?,
+    );
+    let ciphertext = vault_file::encrypt(&key, &plain, &aad)?;
+
+    let envelope = BundleEnvelope {
+        kind: BUNDLE_KIND.to_string(),
+        format_version: BUNDLE_FORMAT_VERSION,
+        kdf,
+        salt,
+        payload: hex::encode(ciphertext),
+    };
+    serde_json::to_string_pretty(&envelope)
+        .map_err(|e| VgaError::AuthVaultError(format!("Failed to serialize bundle: {e}")))
+}
+
+/// Decrypt a bundle produced by [`seal`].
+pub fn open(bundle: &str, passphrase: &str) -> Result<BundlePayload, VgaError> {
+    let envelope: BundleEnvelope = serde_json::from_str(bundle)
+        .map_err(|e| VgaError::AuthVaultError(format!("Malformed bundle: {e}")))?;
+    if envelope.kind != BUNDLE_KIND {
+        return Err(VgaError::AuthVaultError("Not a vault export bundle".to_string()));
+    }
+    if envelope.format_version > BUNDLE_FORMAT_VERSION {
+        return Err(VgaError::AuthVaultError(format!(
+            "Unsupported bundle format version {} (this build reads up to {})",
+            envelope.format_version, BUNDLE_FORMAT_VERSION
+        )));
+    }
+
+    check_kdf(&envelope.kdf)?;
+
+    let salt = hex::decode(&envelope.salt)
+        .map_err(|_| VgaError::AuthVaultError("Invalid bundle salt".to_string()))?;
+    let payload = hex::decode(&envelope.payload)
+        .map_err(|_| VgaError::AuthVaultError("Invalid bundle payload".to_string()))?;
+    let aad = BundleHeader {
+        kind: &envelope.kind,
+        format_version: envelope.format_version,
+        kdf: &envelope.kdf,
+        salt: &envelope.salt,
+    }
+    .aad()?;
+    let key = envelope.kdf.derive_key(passphrase, &salt)?;
+    let plain = Zeroizing::new(vault_file::decrypt(&key, &payload, &aad).map_err(|_| {
+        VgaError::AuthVaultError("Wrong passphrase or corrupted bundle".to_string())
+    })?);
+
+    serde_json::from_slice(&plain)
+        .map_err(|e| VgaError::AuthVaultError(format!("Malformed bundle payload: {e}")))
+}
+
+/// Refuse a bundle's KDF unless it is argon2id within the limits, before running it.
+fn check_kdf(kdf: &KdfParams) -> Result<(), VgaError> {
+    if kdf.algorithm != KdfParams::default().algorithm {
+        return Err(VgaError::AuthVaultError(format!(
+            "Unsupported bundle KDF algorithm: {}",
+            kdf.algorithm
+        )));
+    }
+    if kdf.memory_kib > MAX_KDF_MEMORY_KIB
+        || kdf.iterations > MAX_KDF_ITERATIONS
+        || kdf.parallelism > MAX_KDF_PARALLELISM
+    {
+        return Err(VgaError::AuthVaultError(format!(
+            "Bundle KDF parameters exceed the limits (memory {} KiB, {} iterations, parallelism {})",
+            kdf.memory_kib, kdf.iterations, kdf.parallelism
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,328 @@
+//! Single-file, versioned on-disk format for the API key vault.
+//!
+//! Layout of `vault/vault.json`:
+//! - `header`: format version, Argon2 KDF parameters, salt and a password check blob
+//! - `entries`: one AES-256-GCM encrypted secret per entry, plus plaintext metadata
+//! - `mac`: HMAC-SHA256 over header + entries, keyed from the master key
+//!
+//! Entry ciphertexts use the entry name as associated data, so an encrypted
+//! value cannot be moved to a different entry without failing decryption.
+
+use crate::shared::models::{KeyValidationStatus, VaultEntryMeta, VgaError};
+use aes_gcm::{
+    aead::{Aead, Payload},
+    Aes256Gcm, KeyInit, Nonce,
+};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fs;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// A derived vault key; wiped from memory when dropped.
+pub type MasterKey = Zeroizing<[u8; 32]>;
+
+/// Current on-disk format version written by this build.
+pub const VAULT_FORMAT_VERSION: u32 = 1;
+
+const CHECK_PLAINTEXT: &[u8] = b"vas-vault-ok";
+const CHECK_AAD: &[u8] = b"vas-vault-check";
+const MAC_CONTEXT: &[u8] = b"vas-vault-mac-v1";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KdfParams {
+    pub algorithm: String,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    /// Matches `Argon2::default()`, which is what the legacy layout used.
+    fn default() -> Self {
+        Self {
+            algorithm: "argon2id".to_string(),
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl KdfParams {
+    pub fn derive_key(&self, password: &str, salt: &[u8]) -> Result<MasterKey, VgaError> {
+        let algorithm = match self.algorithm.as_str() {
+            "argon2id" => Algorithm::Argon2id,
+            "argon2i" => Algorithm::Argon2i,
+            "argon2d" => Algorithm::Argon2d,
+            other => {
+                return Err(VgaError::AuthVaultError(format!(
+                    "Unsupported KDF algorithm: {other}"
+                )))
+            }
+        };
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| VgaError::AuthVaultError(format!("Invalid KDF parameters: {e}")))?;
+
+        let mut out = Zeroizing::new([0u8; 32]);
+        Argon2::new(algorithm, Version::V0x13, params)
+            .hash_password_into(password.as_bytes(), salt, out.as_mut())
+            .map_err(|e| VgaError::AuthVaultError(format!("KDF failed: {e}")))?;
+        Ok(out)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultHeader {
+    pub format_version: u32,
+    pub kdf: KdfParams,
+    /// Hex-encoded KDF salt.
+    pub salt: String,
+    /// Hex-encoded encryption of a fixed marker, used to verify the password.
+    pub check: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultEntry {
+    pub name: String,
+    #[serde(default)]
+    pub provider_id: Option<String>,
+    #[serde(default)]
+    pub notes: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub rotated_at: Option<DateTime<Utc>>,
+    /// Hex-encoded `nonce || ciphertext`, bound to `name` as associated data.
+    pub ciphertext: String,
+    // Fields added after format version 1 are skipped when unset so that the
+    // serialized form (and therefore the MAC) of older entries is unchanged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_validated_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_validation: Option<KeyValidationStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool: Option<String>,
+}
+
+impl VaultEntry {
+    pub fn meta(&self) -> VaultEntryMeta {
+        VaultEntryMeta {
+            name: self.name.clone(),
+            provider_id: self.provider_id.clone(),
+            notes: self.notes.clone(),
+            created_at: self.created_at,
+            rotated_at: self.rotated_at,
+            last_validated_at: self.last_validated_at,
+            last_validation: self.last_validation,
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultFile {
+    pub header: VaultHeader,
+    #[serde(default)]
+    pub entries: Vec<VaultEntry>,
+    /// Hex-encoded HMAC-SHA256 over `header` and `entries`.
+    #[serde(default)]
+    pub mac: String,
+}
+
+impl VaultFile {
+    /// Create an empty vault for `password`, returning the file and its master key.
+    pub fn create(password: &str) -> Result<(Self, MasterKey), VgaError> {
+        let mut salt = [0u8; 16];
+        rand::rngs::OsRng.fill_bytes(&mut salt);
+        let kdf = KdfParams::default();
+        let key = kdf.derive_key(password, &salt)?;
+        Ok((Self::with_key(kdf, &salt, &key)?, key))
+    }
+
+    /// Create an empty, sealed vault around an already-derived master key.
+    pub fn with_key(kdf: KdfParams, salt: &[u8], key: &[u8; 32]) -> Result<Self, VgaError> {
+        let check = encrypt(key, CHECK_PLAINTEXT, CHECK_AAD)?;
+        let mut file = Self {
+            header: VaultHeader {
+                format_version: VAULT_FORMAT_VERSION,
+                kdf,
+                salt: hex::encode(salt),
+                check: hex::encode(check),
+            },
+            entries: Vec::new(),
+            mac: String::new(),
+        };
+        file.seal(key)?;
+        Ok(file)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, VgaError> {
+        let raw = fs::read(path)
+            .map_err(|e| VgaError::AuthVaultError(format!("Failed to read vault file: {e}")))?;
+        let file: Self = serde_json::from_slice(&raw)
+            .map_err(|e| VgaError::AuthVaultError(format!("Malformed vault file: {e}")))?;
+        if file.header.format_version > VAULT_FORMAT_VERSION {
+            return Err(VgaError::AuthVaultError(format!(
+                "Unsupported vault format version {} (this build reads up to {})",
+                file.header.format_version, VAULT_FORMAT_VERSION
+            )));
+        }
+        Ok(file)
+    }
+
+    /// Write the vault atomically (temp file + rename).
+    pub fn save(&self, path: &Path) -> Result<(), VgaError> {
+        let raw = serde_json::to_vec_pretty(self)
+            .map_err(|e| VgaError::AuthVaultError(format!("Failed to serialize vault: {e}")))?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, raw)
+            .map_err(|e| VgaError::AuthVaultError(format!("Failed to write vault file: {e}")))?;
+        fs::rename(&tmp, path)
+            .map_err(|e| VgaError::AuthVaultError(format!("Failed to replace vault file: {e}")))
+    }
+
+    /// Derive the master key for `password` and check it against the header.
+    pub fn unlock(&self, password: &str) -> Result<MasterKey, VgaError> {
+        let salt = hex::decode(&self.header.salt)
+            .map_err(|_| VgaError::AuthVaultError("Invalid salt".to_string()))?;
+        let key = self.header.kdf.derive_key(password, &salt)?;
+
+        let check = hex::decode(&self.header.check)
+            .map_err(|_| VgaError::AuthVaultError("Invalid vault check".to_string()))?;
+        match decrypt(&key, &check, CHECK_AAD) {
+            Ok(plain) if plain == CHECK_PLAINTEXT => {}
+            _ => return Err(VgaError::AuthVaultError("Invalid password".to_string())),
+        }
+
+        self.verify(&key)?;
+        Ok(key)
+    }
+
+    /// Recompute the container MAC after a modification.
+    pub fn seal(&mut self, key: &[u8; 32]) -> Result<(), VgaError> {
+        self.mac = hex::encode(self.compute_mac(key)?);
+        Ok(())
+    }
+
+    /// Fail if the header or any entry was modified outside this process.
+    pub fn verify(&self, key: &[u8; 32]) -> Result<(), VgaError> {
+        let expected = hex::decode(&self.mac).map_err(|_| {
+            VgaError::AuthVaultError("Vault integrity check failed: invalid MAC".to_string())
+        })?;
+        let mut mac = Self::mac_for(key)?;
+        mac.update(&self.mac_input()?);
+        mac.verify_slice(&expected).map_err(|_| {
+            VgaError::AuthVaultError(
+                "Vault integrity check failed: the vault file has been modified".to_string(),
+            )
+        })
+    }
+
+    pub fn entry(&self, name: &str) -> Option<&VaultEntry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    pub fn entry_mut(&mut self, name: &str) -> Option<&mut VaultEntry> {
+        self.entries.iter_mut().find(|e| e.name == name)
+    }
+
+    /// Insert a new entry or rotate the secret of an existing one.
+    pub fn put_secret(&mut self, key: &[u8; 32], name: &str, secret: &[u8]) -> Result<(), VgaError> {
+        let ciphertext = hex::encode(encrypt(key, secret, name.as_bytes())?);
+        let now = Utc::now();
+        match self.entry_mut(name) {
+            Some(entry) => {
+                entry.ciphertext = ciphertext;
+                entry.rotated_at = Some(now);
+            }
+            None => self.entries.push(VaultEntry {
+                name: name.to_string(),
+                provider_id: None,
+                notes: String::new(),
+                created_at: now,
+                rotated_at: None,
+                ciphertext,
+                last_validated_at: None,
+                last_validation: None,
+                pool: None,
+            }),
+        }
+        Ok(())
+    }
+
+    pub fn read_secret(&self, key: &[u8; 32], name: &str) -> Result<Zeroizing<Vec<u8>>, VgaError> {
+        let entry = self
+            .entry(name)
+            .ok_or_else(|| VgaError::AuthVaultError(format!("No vault entry named {name}")))?;
+        let payload = hex::decode(&entry.ciphertext)
+            .map_err(|_| VgaError::AuthVaultError(format!("Corrupt vault entry: {name}")))?;
+        decrypt(key, &payload, name.as_bytes()).map(Zeroizing::new)
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|e| e.name != name);
+        self.entries.len() != before
+    }
+
+    fn mac_input(&self) -> Result<Vec<u8>, VgaError> {
+        serde_json::to_vec(&(&self.header, &self.entries))
+            .map_err(|e| VgaError::AuthVaultError(format!("Failed to serialize vault: {e}")))
+    }
+
+    fn compute_mac(&self, key: &[u8; 32]) -> Result<Vec<u8>, VgaError> {
+        let mut mac = Self::mac_for(key)?;
+        mac.update(&self.mac_input()?);
+        Ok(mac.finalize().into_bytes().to_vec())
+    }
+
+    /// The MAC key is derived from the master key so the two are never reused directly.
+    fn mac_for(key: &[u8; 32]) -> Result<HmacSha256, VgaError> {
+        let mut kdf = <HmacSha256 as Mac>::new_from_slice(key)
+            .map_err(|e| VgaError::AuthVaultError(format!("Invalid key: {e}")))?;
+        kdf.update(MAC_CONTEXT);
+        let mac_key: Zeroizing<[u8; 32]> = Zeroizing::new(kdf.finalize().into_bytes().into());
+        <HmacSha256 as Mac>::new_from_slice(mac_key.as_ref())
+            .map_err(|e| VgaError::AuthVaultError(format!("Invalid key: {e}")))
+    }
+}
+
+/// AES-256-GCM encrypt, returning `nonce || ciphertext`.
+pub fn encrypt(key: &[u8; 32], plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, VgaError> {
+    let cipher = Aes256Gcm::new_from_slice(key)
+        .map_err(|e| VgaError::AuthVaultError(format!("Invalid key: {e}")))?;
+
+    let mut nonce_bytes = [0u8; 12];
+    rand::rngs::OsRng.fill_bytes(&mut nonce_bytes);
+    let nonce = Nonce::from_slice(&nonce_bytes);
+
+    let ciphertext = cipher
+        .encrypt(nonce, Payload { msg: plaintext, aad })
+        .map_err(|e| VgaError::AuthVaultError(e.to_string()))?;
+
+    let mut out = Vec::with_capacity(nonce_bytes.len() + ciphertext.len());
+    out.extend_from_slice(&nonce_bytes);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// Inverse of [`encrypt`].
+pub fn decrypt(key: &[u8; 32], payload: &[u8], aad: &[u8]) -> Result<Vec<u8>, VgaError> {
+    if payload.len() < 12 {
+        return Err(VgaError::AuthVaultError("Encrypted payload too short".to_string()));
+    }
+    let (nonce_bytes, ciphertext) = payload.split_at(12);
+
+    let cipher = Aes256Gcm::new_from_slice(key)
+        .map_err(|e| VgaError::AuthVaultError(format!("Invalid key: {e}")))?;
+    let nonce = Nonce::from_slice(nonce_bytes);
+    cipher
+        .decrypt(nonce, Payload { msg: ciphertext, aad })
+        .map_err(|e| VgaError::AuthVaultError(format!("Decryption failed: {e}")))
+}
@@ -1,7 +1,7 @@
 use std::env;
 use std::io::{self, Write};
 use tokio::time::{sleep, Duration};
-use vangriten_ai_swarm::backend::ResourceManager;
+use vangriten_ai_swarm::backend::{NetworkDiscovery, ResourceManager};
 use vangriten_ai_swarm::shared::models::{ResourceRequirements, Priority, BalancingStrategy};
 
 #[tokio::main]
@@ -47,7 +47,7 @@ async fn handle_command(args: &[String]) {
 }
 
 async fn run_interactive() {
-    let resource_manager = ResourceManager::new(true).await
+    let resource_manager = ResourceManager::new(NetworkDiscovery::new().await, true).await
         .expect("Failed to initialize resource manager");
 
     println!("Starting resource manager...");
@@ -269,7 +269,7 @@ async fn run_interactive() {
 }
 
 async fn run_discovery() {
-    let resource_manager = ResourceManager::new(true).await
+    let resource_manager = ResourceManager::new(NetworkDiscovery::new().await, true).await
         .expect("Failed to initialize resource manager");
 
     resource_manager.start_discovery().await
@@ -294,7 +294,7 @@ async fn run_discovery() {
 }
 
 async fn run_status() {
-    let resource_manager = ResourceManager::new(true).await
+    let resource_manager = ResourceManager::new(NetworkDiscovery::new().await, true).await
         .expect("Failed to initialize resource manager");
 
     println!("Resource Manager Status:");
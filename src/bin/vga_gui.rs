@@ -129,6 +129,9 @@ impl VgaGuiApp {
         let services = runtime.block_on(async {
             let api_manager = vangriten_ai_swarm::backend::ApiKeyManager::new().await;
             let agent_scheduler = vangriten_ai_swarm::backend::AgentScheduler::new().await;
+            agent_scheduler
+                .attach_llm_router(vangriten_ai_swarm::backend::FailoverRouter::new(api_manager.clone()))
+                .await;
             let network_discovery = vangriten_ai_swarm::backend::NetworkDiscovery::new().await;
             let compilation_scheduler = vangriten_ai_swarm::backend::CompilationScheduler::new().await;
             let ollama_manager = vangriten_ai_swarm::backend::OllamaManager::new(None).await;
@@ -141,12 +144,20 @@ impl VgaGuiApp {
                         .expect("c compiler scheduler")
                 }
             };
-            let resource_manager = match vangriten_ai_swarm::backend::ResourceManager::new(false).await {
+            let resource_manager = match vangriten_ai_swarm::backend::ResourceManager::new(network_discovery.clone(), false).await {
                 Ok(rm) => rm,
-                Err(_) => vangriten_ai_swarm::backend::ResourceManager::new(true)
+                Err(_) => vangriten_ai_swarm::backend::ResourceManager::new(network_discovery.clone(), true)
                     .await
                     .expect("resource manager"),
             };
+            let workspace = std::env::current_dir().unwrap_or_default().join("workspace");
+            agent_scheduler
+                .attach_tools(vangriten_ai_swarm::backend::agents::ToolRegistry::workspace(
+                    workspace,
+                    Some(c_compiler.clone()),
+                    agent_scheduler.llm_router().await,
+                ))
+                .await;
 
             std::sync::Arc::new(vangriten_ai_swarm::backend::BackendServices {
                 api_manager,
@@ -358,7 +369,7 @@ impl VgaGuiApp {
                     if ui.button(label_store).clicked() {
                         let op = vangriten_ai_swarm::shared::models::VaultOp::Store {
                             provider: self.api_provider.clone(),
-                            key: self.api_key_input.clone(),
+                            key: self.api_key_input.clone().into(),
                         };
                         match api_manager.vault_operation(op) {
                             Ok(_) => {
@@ -389,7 +400,7 @@ impl VgaGuiApp {
                         };
                         match api_manager.vault_operation(op) {
                             Ok(vangriten_ai_swarm::shared::models::VaultResult::Key(k)) => {
-                                self.api_revealed_key = k;
+                                self.api_revealed_key = k.expose_secret().to_string();
                                 self.api_status = self.tr("已读取", "Retrieved").to_string();
                             }
                             Ok(v) => self.api_status = format!("unexpected: {v:?}"),
@@ -0,0 +1,99 @@
+//! Tauri commands for multi-turn chat sessions.
+
+use crate::backend::chat_session::{ChatSession, SessionTarget, CHAT_SESSIONS_DIR, DEFAULT_KEEP_RECENT};
+use crate::backend::BackendServices;
+use tauri::State;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+fn sessions_dir(state: &BackendServices) -> PathBuf {
+    state.api_manager.vault_dir().join(CHAT_SESSIONS_DIR)
+}
+
+/// Start a session with a local Ollama model (`entity: false`) or an entity
+/// or entity group (`entity: true`).
+#[tauri::command]
+pub async fn cmd_chat_session_create(
+    target: String,
+    entity: bool,
+    system: Option<String>,
+    title: Option<String>,
+    context_tokens: Option<usize>,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<ChatSession, String> {
+    let target = if entity {
+        SessionTarget::Entity { target }
+    } else {
+        SessionTarget::Ollama { model: target }
+    };
+    let mut session = ChatSession::new(target);
+    if let Some(system) = system {
+        session = session.with_system(system);
+    }
+    if let Some(title) = title {
+        session = session.with_title(title);
+    }
+    if let Some(tokens) = context_tokens {
+        session = session.with_context_tokens(tokens);
+    }
+    session.save(&sessions_dir(&state))?;
+    Ok(session)
+}
+
+/// Send the next user turn. Older turns are summarized first if the history
+/// no longer fits the session's context budget.
+#[tauri::command]
+pub async fn cmd_chat_session_send(
+    id: String,
+    text: String,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<ChatSession, String> {
+    let dir = sessions_dir(&state);
+    let mut session = ChatSession::load(&dir, &id)?;
+    let router = state.agent_scheduler.llm_router().await;
+    if let Err(e) = session.summarize(&state.ollama_manager, router.as_ref(), DEFAULT_KEEP_RECENT).await {
+        tracing::warn!("Chat session {id}: summary failed, truncating instead: {e}");
+    }
+    session.send(&state.ollama_manager, router.as_ref(), &text).await?;
+    session.save(&dir)?;
+    Ok(session)
+}
+
+#[tauri::command]
+pub async fn cmd_chat_session_get(
+    id: String,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<ChatSession, String> {
+    ChatSession::load(&sessions_dir(&state), &id)
+}
+
+/// All stored sessions, most recently used first.
+#[tauri::command]
+pub async fn cmd_chat_session_list(
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<Vec<ChatSession>, String> {
+    Ok(ChatSession::list(&sessions_dir(&state)))
+}
+
+#[tauri::command]
+pub async fn cmd_chat_session_delete(
+    id: String,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<(), String> {
+    ChatSession::delete(&sessions_dir(&state), &id)
+}
+
+/// The transcript as `"markdown"` or `"json"`.
+#[tauri::command]
+pub async fn cmd_chat_session_export(
+    id: String,
+    format: String,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<String, String> {
+    let session = ChatSession::load(&sessions_dir(&state), &id)?;
+    match format.as_str() {
+        "markdown" | "md" => Ok(session.to_markdown()),
+        "json" => session.to_json(),
+        other => Err(format!("Unknown export format {other}")),
+    }
+}
@@ -0,0 +1,116 @@
+//! Tauri commands for entity groups, failover and circuit breakers.
+
+use crate::backend::llm_backend::LlmRequest;
+use crate::backend::response_cache::CacheSummary;
+use crate::backend::structured::{self, DEFAULT_STRUCTURED_ATTEMPTS};
+use crate::backend::{BackendServices, FailoverRouter};
+use crate::shared::models::*;
+use tauri::State;
+use std::sync::Arc;
+
+async fn router(state: &BackendServices) -> Result<FailoverRouter, String> {
+    state
+        .agent_scheduler
+        .llm_router()
+        .await
+        .ok_or_else(|| "No LLM router attached".to_string())
+}
+
+#[tauri::command]
+pub async fn cmd_list_entity_groups(
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<Vec<EntityGroup>, String> {
+    Ok(router(&state).await?.groups())
+}
+
+#[tauri::command]
+pub async fn cmd_save_entity_groups(
+    groups: Vec<EntityGroup>,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<(), String> {
+    router(&state)
+        .await?
+        .save_groups(&groups)
+        .map_err(|e| format!("Failed to save entity groups: {:?}", e))
+}
+
+/// Send one prompt to an entity or entity group. The reply metadata names
+/// the entity that served it and whether it came from the response cache.
+#[tauri::command]
+pub async fn cmd_llm_chat(
+    target: String,
+    system: Option<String>,
+    prompt: String,
+    cache: Option<bool>,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<TaskOutput, String> {
+    let request = LlmRequest {
+        cache,
+        ..LlmRequest::prompt(system.as_deref(), &prompt)
+    };
+    let served = router(&state)
+        .await?
+        .chat(&target, &request)
+        .await
+        .map_err(|e| format!("Chat failed: {:?}", e))?;
+    Ok(TaskOutput {
+        metadata: served.metadata(),
+        content: served.reply.content,
+    })
+}
+
+/// Ask for JSON matching `schema` (a JSON Schema object) and return the
+/// validated value. Mismatching replies are retried with the errors listed.
+#[tauri::command]
+pub async fn cmd_llm_chat_structured(
+    target: String,
+    system: Option<String>,
+    prompt: String,
+    schema: serde_json::Value,
+    name: Option<String>,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<serde_json::Value, String> {
+    let request = LlmRequest::prompt(system.as_deref(), &prompt);
+    let router = router(&state).await?;
+    let name = name.unwrap_or_else(|| "response".to_string());
+    structured::chat_structured(&router, &target, request, &name, &schema, DEFAULT_STRUCTURED_ATTEMPTS)
+        .await
+        .map(|reply| reply.value)
+        .map_err(|e| format!("Structured chat failed: {e}"))
+}
+
+/// Breaker state for the entities of `target`, or for every entity.
+#[tauri::command]
+pub async fn cmd_circuit_status(
+    target: Option<String>,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<Vec<CircuitStatus>, String> {
+    router(&state)
+        .await?
+        .circuit_status(target.as_deref())
+        .map_err(|e| format!("Failed to get circuit status: {:?}", e))
+}
+
+#[tauri::command]
+pub async fn cmd_reset_circuit(
+    entity: String,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<(), String> {
+    router(&state).await?.reset_circuit(&entity);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn cmd_response_cache_summary(
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<CacheSummary, String> {
+    Ok(router(&state).await?.response_cache().summary().await)
+}
+
+/// Drop every cached reply; returns how many were removed.
+#[tauri::command]
+pub async fn cmd_clear_response_cache(
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<u64, String> {
+    Ok(router(&state).await?.response_cache().clear().await)
+}
@@ -12,6 +12,8 @@ pub mod task_commands;
 pub mod resource_commands;
 pub mod compiler_commands;
 pub mod ollama_commands;
+pub mod llm_commands;
+pub mod chat_commands;
 
 // Re-export all commands so callers can still use `crate::frontend::cmd_*`.
 pub use vault_commands::*;
@@ -20,6 +22,8 @@ pub use task_commands::*;
 pub use resource_commands::*;
 pub use compiler_commands::*;
 pub use ollama_commands::*;
+pub use llm_commands::*;
+pub use chat_commands::*;
 
 pub async fn prime_frontend_stubs() {
     let _ = app::initialize();
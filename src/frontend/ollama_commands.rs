@@ -2,7 +2,7 @@
 
 use crate::backend::BackendServices;
 use crate::backend::ollama_client::{
-    OllamaConnectionStatus, OllamaModel, OllamaModelInfo,
+    OllamaConnectionStatus, OllamaModel, OllamaModelInfo, RunningModel, CreateRequest,
     ChatRequest, ChatResponse, GenerateRequest, GenerateResponse, OllamaUsageStats,
 };
 use tauri::State;
@@ -38,6 +38,29 @@ pub async fn cmd_ollama_pull_model(
     state.ollama_manager.pull_model(&model_name).await
 }
 
+/// Pull a model, emitting each status line to `window` as
+/// `ollama-pull-progress` (`{ model, progress }`).
+#[tauri::command]
+pub async fn cmd_ollama_pull_model_stream(
+    model_name: String,
+    window: tauri::Window,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<String, String> {
+    let mut progress = state.ollama_manager.pull_model_stream(&model_name).await?;
+    while let Some(frame) = progress.recv().await {
+        let frame = frame?;
+        let payload = serde_json::json!({ "model": model_name, "progress": frame });
+        if window.emit("ollama-pull-progress", payload).is_err() {
+            // Window closed: dropping the stream cancels the pull.
+            return Err("Window closed; pull cancelled".to_string());
+        }
+        if frame.is_success() {
+            return Ok(format!("Model '{}' pulled successfully", model_name));
+        }
+    }
+    Err(format!("Pull of '{}' ended before it succeeded", model_name))
+}
+
 #[tauri::command]
 pub async fn cmd_ollama_delete_model(
     model_name: String,
@@ -46,6 +69,43 @@ pub async fn cmd_ollama_delete_model(
     state.ollama_manager.delete_model(&model_name).await
 }
 
+#[tauri::command]
+pub async fn cmd_ollama_copy_model(
+    source: String,
+    destination: String,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<String, String> {
+    state.ollama_manager.copy_model(&source, &destination).await
+}
+
+/// Create `model_name` from Modelfile text.
+#[tauri::command]
+pub async fn cmd_ollama_create_model(
+    model_name: String,
+    modelfile: String,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<String, String> {
+    let request = CreateRequest::from_modelfile(model_name, &modelfile)?;
+    state.ollama_manager.create_model(request).await
+}
+
+#[tauri::command]
+pub async fn cmd_ollama_push_model(
+    model_name: String,
+    insecure: Option<bool>,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<String, String> {
+    state.ollama_manager.push_model(&model_name, insecure.unwrap_or(false)).await
+}
+
+/// Loaded models and their VRAM use; also refreshes the node's resource report.
+#[tauri::command]
+pub async fn cmd_ollama_list_running(
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<Vec<RunningModel>, String> {
+    state.ollama_manager.report_resources(&state.resource_manager).await
+}
+
 #[tauri::command]
 pub async fn cmd_ollama_chat(
     request: ChatRequest,
@@ -89,6 +149,15 @@ pub async fn cmd_ollama_embed(
     state.ollama_manager.embed(&model, &input).await
 }
 
+#[tauri::command]
+pub async fn cmd_ollama_embed_batch(
+    model: String,
+    inputs: Vec<String>,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<Vec<Vec<f32>>, String> {
+    state.ollama_manager.embed_batch(&model, &inputs).await
+}
+
 #[tauri::command]
 pub async fn cmd_ollama_get_version(
     state: State<'_, Arc<BackendServices>>,
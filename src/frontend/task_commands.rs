@@ -36,9 +36,15 @@ pub async fn cmd_submit_task(
     spec: TaskSpec,
     priority: Priority,
     input_snapshot: String,
+    entity: Option<String>,
+    tools: Option<Vec<String>>,
+    cache: Option<bool>,
     state: State<'_, Arc<BackendServices>>,
 ) -> Result<String, String> {
-    let task = Task::new(spec, priority, std::path::PathBuf::from(input_snapshot));
+    let mut task = Task::new(spec, priority, std::path::PathBuf::from(input_snapshot));
+    task.entity = entity;
+    task.tools = tools.unwrap_or_default();
+    task.cache = cache;
     state
         .agent_scheduler
         .submit_task(task)
@@ -64,6 +70,14 @@ pub async fn cmd_list_tasks(
     Ok(state.agent_scheduler.list_tasks().await)
 }
 
+/// Tools an entity task can be given through `cmd_submit_task`.
+#[tauri::command]
+pub async fn cmd_list_agent_tools(
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<Vec<String>, String> {
+    Ok(state.agent_scheduler.tool_names().await)
+}
+
 #[tauri::command]
 pub async fn cmd_cancel_task(
     task_id: String,
@@ -78,3 +92,29 @@ pub async fn cmd_cancel_task(
         .map(|_| true)
         .map_err(|e| format!("Task cancellation failed: {:?}", e))
 }
+
+/// Forward task events to `window` as `task-event`. Call once per window;
+/// forwarding stops when the window is gone.
+#[tauri::command]
+pub async fn cmd_subscribe_task_events(
+    window: tauri::Window,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<(), String> {
+    let mut events = state.agent_scheduler.subscribe();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if window.emit("task-event", &event).is_err() {
+                        break;
+                    }
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Task event subscriber skipped {skipped} events");
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+    Ok(())
+}
@@ -36,7 +36,7 @@ pub async fn cmd_vault_store(
     key: String,
     state: State<'_, Arc<BackendServices>>,
 ) -> Result<bool, String> {
-    match state.api_manager.vault_operation(VaultOp::Store { provider, key }) {
+    match state.api_manager.vault_operation(VaultOp::Store { provider, key: key.into() }) {
         Ok(VaultResult::Success) => Ok(true),
         Ok(other) => Err(format!("Unexpected vault result: {:?}", other)),
         Err(e) => Err(format!("Vault store failed: {:?}", e)),
@@ -49,7 +49,7 @@ pub async fn cmd_vault_retrieve(
     state: State<'_, Arc<BackendServices>>,
 ) -> Result<String, String> {
     match state.api_manager.vault_operation(VaultOp::Retrieve { provider }) {
-        Ok(VaultResult::Key(key)) => Ok(key),
+        Ok(VaultResult::Key(key)) => Ok(key.expose_secret().to_string()),
         Ok(other) => Err(format!("Unexpected vault result: {:?}", other)),
         Err(e) => Err(format!("Vault retrieve failed: {:?}", e)),
     }
@@ -66,6 +66,17 @@ pub async fn cmd_vault_list(
     }
 }
 
+#[tauri::command]
+pub async fn cmd_vault_entries(
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<Vec<VaultEntryMeta>, String> {
+    match state.api_manager.vault_operation(VaultOp::ListEntries) {
+        Ok(VaultResult::Entries(entries)) => Ok(entries),
+        Ok(other) => Err(format!("Unexpected vault result: {:?}", other)),
+        Err(e) => Err(format!("Vault list entries failed: {:?}", e)),
+    }
+}
+
 #[tauri::command]
 pub async fn cmd_vault_delete(
     provider: String,
@@ -78,6 +89,151 @@ pub async fn cmd_vault_delete(
     }
 }
 
+/// Models offered by the entity's endpoint, merged with the catalog (cached unless `refresh`).
+#[tauri::command]
+pub async fn cmd_list_remote_models(
+    entity: AiEntity,
+    refresh: bool,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<ModelListing, String> {
+    state
+        .api_manager
+        .list_remote_models(&entity, refresh)
+        .await
+        .map_err(|e| format!("Model listing failed: {:?}", e))
+}
+
+#[tauri::command]
+pub async fn cmd_list_provider_models(
+    provider: String,
+    refresh: bool,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<ModelListing, String> {
+    state
+        .api_manager
+        .list_provider_models(&provider, refresh)
+        .await
+        .map_err(|e| format!("Model listing failed: {:?}", e))
+}
+
+/// Re-read provider overrides and custom relays; returns rejected entries.
+#[tauri::command]
+pub async fn cmd_reload_providers(
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<Vec<String>, String> {
+    Ok(state.api_manager.reload_providers())
+}
+
+/// Add another key to `pool`; returns the generated entry name.
+#[tauri::command]
+pub async fn cmd_vault_pool_add_key(
+    pool: String,
+    key: String,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<String, String> {
+    state
+        .api_manager
+        .add_pool_key(&pool, &key.into())
+        .map_err(|e| format!("Vault pool add failed: {:?}", e))
+}
+
+#[tauri::command]
+pub async fn cmd_vault_pool_status(
+    pool: String,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<KeyPoolStatus, String> {
+    match state.api_manager.vault_operation(VaultOp::PoolStatus { pool }) {
+        Ok(VaultResult::Pool(status)) => Ok(status),
+        Ok(other) => Err(format!("Unexpected vault result: {:?}", other)),
+        Err(e) => Err(format!("Vault pool status failed: {:?}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn cmd_vault_pool_set_strategy(
+    pool: String,
+    strategy: KeyPoolStrategy,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<bool, String> {
+    match state.api_manager.vault_operation(VaultOp::SetPoolStrategy { pool, strategy }) {
+        Ok(VaultResult::Success) => Ok(true),
+        Ok(other) => Err(format!("Unexpected vault result: {:?}", other)),
+        Err(e) => Err(format!("Vault pool strategy failed: {:?}", e)),
+    }
+}
+
+/// Configure the vault idle auto-lock; `idle_secs = None` disables it.
+#[tauri::command]
+pub async fn cmd_vault_set_auto_lock(
+    idle_secs: Option<u64>,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<bool, String> {
+    state
+        .api_manager
+        .set_auto_lock(idle_secs.map(std::time::Duration::from_secs));
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn cmd_vault_lock(
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<bool, String> {
+    state.api_manager.vault_lock();
+    Ok(true)
+}
+
+/// Export the named entries (plus optional entity records) as a passphrase-protected bundle.
+#[tauri::command]
+pub async fn cmd_vault_export(
+    names: Vec<String>,
+    entities: Vec<AiEntity>,
+    passphrase: String,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<String, String> {
+    state
+        .api_manager
+        .export_bundle(&names, &entities, &passphrase)
+        .map_err(|e| format!("Vault export failed: {:?}", e))
+}
+
+#[tauri::command]
+pub async fn cmd_vault_import(
+    bundle: String,
+    passphrase: String,
+    policy: ImportConflictPolicy,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<VaultImportReport, String> {
+    state
+        .api_manager
+        .import_bundle(&bundle, &passphrase, policy)
+        .map_err(|e| format!("Vault import failed: {:?}", e))
+}
+
+/// Probe the provider with the entity's stored key and record the outcome.
+#[tauri::command]
+pub async fn cmd_vault_validate_key(
+    entity: AiEntity,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<KeyValidationResult, String> {
+    state
+        .api_manager
+        .validate_key(&entity)
+        .await
+        .map_err(|e| format!("Key validation failed: {:?}", e))
+}
+
+#[tauri::command]
+pub async fn cmd_vault_validate_all(
+    entities: Vec<AiEntity>,
+    state: State<'_, Arc<BackendServices>>,
+) -> Result<Vec<KeyValidationResult>, String> {
+    state
+        .api_manager
+        .validate_all(&entities)
+        .await
+        .map_err(|e| format!("Key validation failed: {:?}", e))
+}
+
 #[tauri::command]
 pub async fn cmd_vault_usage(
     state: State<'_, Arc<BackendServices>>,
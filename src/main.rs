@@ -22,8 +22,29 @@ async fn main() {
             vangriten_ai_swarm::frontend::cmd_vault_store,
             vangriten_ai_swarm::frontend::cmd_vault_retrieve,
             vangriten_ai_swarm::frontend::cmd_vault_list,
+            vangriten_ai_swarm::frontend::cmd_vault_entries,
             vangriten_ai_swarm::frontend::cmd_vault_delete,
+            vangriten_ai_swarm::frontend::cmd_reload_providers,
+            vangriten_ai_swarm::frontend::cmd_list_remote_models,
+            vangriten_ai_swarm::frontend::cmd_list_provider_models,
+            vangriten_ai_swarm::frontend::cmd_vault_pool_add_key,
+            vangriten_ai_swarm::frontend::cmd_vault_pool_status,
+            vangriten_ai_swarm::frontend::cmd_vault_pool_set_strategy,
+            vangriten_ai_swarm::frontend::cmd_list_entity_groups,
+            vangriten_ai_swarm::frontend::cmd_save_entity_groups,
+            vangriten_ai_swarm::frontend::cmd_llm_chat,
+            vangriten_ai_swarm::frontend::cmd_llm_chat_structured,
+            vangriten_ai_swarm::frontend::cmd_circuit_status,
+            vangriten_ai_swarm::frontend::cmd_reset_circuit,
+            vangriten_ai_swarm::frontend::cmd_response_cache_summary,
+            vangriten_ai_swarm::frontend::cmd_clear_response_cache,
             vangriten_ai_swarm::frontend::cmd_vault_usage,
+            vangriten_ai_swarm::frontend::cmd_vault_set_auto_lock,
+            vangriten_ai_swarm::frontend::cmd_vault_lock,
+            vangriten_ai_swarm::frontend::cmd_vault_export,
+            vangriten_ai_swarm::frontend::cmd_vault_import,
+            vangriten_ai_swarm::frontend::cmd_vault_validate_key,
+            vangriten_ai_swarm::frontend::cmd_vault_validate_all,
             vangriten_ai_swarm::frontend::cmd_deploy_project,
             vangriten_ai_swarm::frontend::cmd_node_discovery,
             vangriten_ai_swarm::frontend::cmd_get_all_agents,
@@ -36,7 +57,9 @@ async fn main() {
             vangriten_ai_swarm::frontend::cmd_submit_task,
             vangriten_ai_swarm::frontend::cmd_get_task,
             vangriten_ai_swarm::frontend::cmd_list_tasks,
+            vangriten_ai_swarm::frontend::cmd_list_agent_tools,
             vangriten_ai_swarm::frontend::cmd_cancel_task,
+            vangriten_ai_swarm::frontend::cmd_subscribe_task_events,
             vangriten_ai_swarm::frontend::cmd_get_providers,
             vangriten_ai_swarm::frontend::cmd_get_provider_config,
             vangriten_ai_swarm::frontend::cmd_set_default_provider,
@@ -64,12 +87,24 @@ async fn main() {
             vangriten_ai_swarm::frontend::cmd_ollama_list_models,
             vangriten_ai_swarm::frontend::cmd_ollama_show_model_info,
             vangriten_ai_swarm::frontend::cmd_ollama_pull_model,
+            vangriten_ai_swarm::frontend::cmd_ollama_pull_model_stream,
             vangriten_ai_swarm::frontend::cmd_ollama_delete_model,
+            vangriten_ai_swarm::frontend::cmd_ollama_copy_model,
+            vangriten_ai_swarm::frontend::cmd_ollama_create_model,
+            vangriten_ai_swarm::frontend::cmd_ollama_push_model,
+            vangriten_ai_swarm::frontend::cmd_ollama_list_running,
             vangriten_ai_swarm::frontend::cmd_ollama_chat,
             vangriten_ai_swarm::frontend::cmd_ollama_chat_simple,
+            vangriten_ai_swarm::frontend::cmd_chat_session_create,
+            vangriten_ai_swarm::frontend::cmd_chat_session_send,
+            vangriten_ai_swarm::frontend::cmd_chat_session_get,
+            vangriten_ai_swarm::frontend::cmd_chat_session_list,
+            vangriten_ai_swarm::frontend::cmd_chat_session_delete,
+            vangriten_ai_swarm::frontend::cmd_chat_session_export,
             vangriten_ai_swarm::frontend::cmd_ollama_generate,
             vangriten_ai_swarm::frontend::cmd_ollama_generate_simple,
             vangriten_ai_swarm::frontend::cmd_ollama_embed,
+            vangriten_ai_swarm::frontend::cmd_ollama_embed_batch,
             vangriten_ai_swarm::frontend::cmd_ollama_get_version,
             vangriten_ai_swarm::frontend::cmd_ollama_get_usage_stats,
             vangriten_ai_swarm::frontend::cmd_ollama_reset_usage_stats,
@@ -81,13 +116,24 @@ async fn main() {
 async fn setup_backend_services() -> backend::BackendServices {
     let api_manager = backend::ApiKeyManager::new().await;
     let agent_scheduler = backend::AgentScheduler::new().await;
+    agent_scheduler
+        .attach_llm_router(backend::FailoverRouter::new(api_manager.clone()))
+        .await;
     let network_discovery = backend::NetworkDiscovery::new().await;
     let compilation_scheduler = backend::CompilationScheduler::new().await;
-    let resource_manager = backend::ResourceManager::new(true).await.unwrap();
+    let resource_manager = backend::ResourceManager::new(network_discovery.clone(), true).await.unwrap();
     let c_compiler = backend::CCompilationScheduler::new(4)
         .await
         .expect("Failed to initialize C compiler scheduler");
     let ollama_manager = backend::OllamaManager::new(None).await;
+    let workspace = std::env::current_dir().unwrap_or_default().join("workspace");
+    agent_scheduler
+        .attach_tools(backend::agents::ToolRegistry::workspace(
+            workspace,
+            Some(c_compiler.clone()),
+            agent_scheduler.llm_router().await,
+        ))
+        .await;
 
     compilation_scheduler.prime_environment_cache().await;
     compilation_scheduler.prime_demo_usage().await;
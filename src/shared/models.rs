@@ -4,11 +4,13 @@
 //! this level so existing `use crate::shared::models::*` imports continue to work.
 
 pub mod core;
+pub mod llm;
 pub mod network;
 pub mod resource;
 pub mod vault;
 
 pub use core::*;
+pub use llm::*;
 pub use network::*;
 pub use resource::*;
 pub use vault::*;
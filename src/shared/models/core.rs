@@ -183,6 +183,20 @@ pub struct Task {
     pub retry_count: u32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Entity or entity group that answers this task. `None` runs the
+    /// built-in agent logic without an LLM.
+    #[serde(default)]
+    pub entity: Option<String>,
+    /// Reply text received so far while an entity task is running.
+    #[serde(default)]
+    pub partial_output: String,
+    /// Tools the entity may call, by name, from the scheduler's registry.
+    #[serde(default)]
+    pub tools: Vec<String>,
+    /// Response cache use for the entity's replies; `None` caches only
+    /// deterministic (`temperature == 0`) requests, `Some(false)` bypasses it.
+    #[serde(default)]
+    pub cache: Option<bool>,
 }
 
 impl Task {
@@ -200,9 +214,35 @@ impl Task {
             retry_count: 0,
             created_at: now,
             updated_at: now,
+            entity: None,
+            partial_output: String::new(),
+            tools: Vec::new(),
+            cache: None,
         }
     }
 
+    /// Route the task to an entity or entity group (with failover).
+    pub fn with_entity(mut self, target: impl Into<String>) -> Self {
+        self.entity = Some(target.into());
+        self
+    }
+
+    /// Let the entity call these registered tools while it works.
+    pub fn with_tools<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.tools = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Force (`true`) or bypass (`false`) the response cache for this task.
+    pub fn with_cache(mut self, enabled: bool) -> Self {
+        self.cache = Some(enabled);
+        self
+    }
+
     pub fn finalize_with_result(&mut self, res: TaskResult) {
         self.output = res;
         self.updated_at = Utc::now();
@@ -213,6 +253,23 @@ impl Task {
     }
 }
 
+/// Progress of a task, as published by `AgentScheduler::subscribe`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskEvent {
+    pub task_id: TaskId,
+    pub kind: TaskEventKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TaskEventKind {
+    Started,
+    /// Reply text appended to `Task.partial_output`.
+    Delta(String),
+    Completed,
+    Failed(String),
+    Cancelled,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskSpec {
     pub language: String,
@@ -262,6 +319,14 @@ pub enum VgaError {
     NetworkSplit,
     CompileFailure(String),
     ResourceLimit(String),
+    /// A model's reply did not parse or did not match the requested schema.
+    InvalidOutput(String),
+    /// A user-supplied setting is invalid or names something that does not exist.
+    InvalidConfig(String),
+    /// Reading, writing or (de)serializing a local file failed.
+    Storage(String),
+    /// No model could answer: the providers failed, refused or never finished.
+    LlmFailure(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -0,0 +1,84 @@
+//! LLM routing types: entity groups, failover attempts and circuit breaker state.
+
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+
+// ─── Entity groups ────────────────────────────────────────────────────────────
+
+/// An ordered fallback chain of AI entities, e.g.
+/// `deepseek-main → moonshot-backup → local-llama3`. Requests go to the first
+/// entity whose circuit is closed and move down the chain on retryable errors.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EntityGroup {
+    pub name: String,
+    /// Entity names, most preferred first.
+    pub chain: Vec<String>,
+    #[serde(default)]
+    pub note: String,
+}
+
+/// Why a call to one entity did not produce an answer.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum LlmErrorKind {
+    /// The provider rejected the key (HTTP 401/403).
+    InvalidKey,
+    /// Rate limited or out of credit (HTTP 402/429).
+    QuotaExhausted,
+    /// Connect/timeout failure or HTTP 5xx.
+    Unavailable,
+    /// The endpoint does not serve the configured model (HTTP 404).
+    ModelNotFound,
+    /// The request itself was refused (HTTP 400/413/422); another entity
+    /// would refuse it too.
+    BadRequest,
+    /// Missing key, unknown provider, locked vault and similar local problems.
+    Config,
+    /// The response could not be parsed.
+    Protocol,
+}
+
+impl LlmErrorKind {
+    /// Whether the next entity in a fallback chain should be tried.
+    pub fn is_retryable(self) -> bool {
+        !matches!(self, LlmErrorKind::BadRequest)
+    }
+
+    /// Whether the failure says something about the entity's health and so
+    /// counts towards tripping its circuit breaker.
+    pub fn trips_circuit(self) -> bool {
+        !matches!(self, LlmErrorKind::BadRequest | LlmErrorKind::Config)
+    }
+}
+
+/// One entity tried while serving a request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailoverAttempt {
+    pub entity: String,
+    /// `None` if the entity answered.
+    pub error: Option<LlmErrorKind>,
+    pub detail: Option<String>,
+    pub elapsed_ms: u64,
+}
+
+// ─── Circuit breaker ──────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests flow normally.
+    Closed,
+    /// Skipped until the cool-down expires.
+    Open,
+    /// Cool-down expired; the next request is a trial that closes or re-opens the circuit.
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitStatus {
+    pub entity: String,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    /// Set while the circuit is open.
+    pub open_until: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub last_success: Option<DateTime<Utc>>,
+}
@@ -27,6 +27,20 @@ pub struct NodeResources {
     pub gpus: Vec<GpuInfo>,
     pub supported_models: Vec<String>,
     pub current_load: f64,
+    /// Models the local Ollama currently holds in memory.
+    #[serde(default)]
+    pub loaded_models: Vec<LoadedModel>,
+}
+
+/// A model resident in memory on a node, as reported by Ollama's `/api/ps`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadedModel {
+    pub name: String,
+    /// Total resident size, including `vram_mb`.
+    pub size_mb: u64,
+    pub vram_mb: u64,
+    /// When Ollama unloads it if it stays idle.
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
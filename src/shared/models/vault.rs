@@ -2,28 +2,181 @@
 
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use zeroize::Zeroize;
+
+// ─── Secrets ──────────────────────────────────────────────────────────────────
+
+/// A decrypted secret (API key). `Debug` is redacted and the buffer is wiped on drop,
+/// so `format!("{:?}", ..)` on any type holding one cannot leak the value.
+/// Serialization is transparent so the value can still cross the Tauri boundary.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(value: String) -> Self {
+        Self(value)
+    }
+
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl std::fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecretString([REDACTED])")
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
 
 // ─── Vault operations ─────────────────────────────────────────────────────────
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum VaultOp {
-    Store { provider: String, key: String },
+    Store { provider: String, key: SecretString },
     Retrieve { provider: String },
     Delete { provider: String },
     List,
     GetProviders,
     GetProviderConfig { provider: String },
     SetDefaultProvider { provider: String },
+    /// List entries together with their metadata (no secrets).
+    ListEntries,
+    /// Attach the provider id and free-form notes to an existing entry.
+    SetEntryMeta { provider: String, provider_id: Option<String>, notes: String },
+    /// Store an additional key in `pool` under a generated entry name (`<pool>#2`, …).
+    AddPoolKey { pool: String, key: SecretString },
+    /// Rotation state and per-key usage of `pool`.
+    PoolStatus { pool: String },
+    SetPoolStrategy { pool: String, strategy: KeyPoolStrategy },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum VaultResult {
     Success,
-    Key(String),
+    Key(SecretString),
     Providers(Vec<String>),
     ProviderConfigs(Vec<ProviderConfig>),
     ProviderConfig(ProviderConfig),
     DefaultProvider(String),
+    Entries(Vec<VaultEntryMeta>),
+    Pool(KeyPoolStatus),
+}
+
+/// Plaintext metadata stored next to each encrypted vault entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultEntryMeta {
+    pub name: String,
+    pub provider_id: Option<String>,
+    pub notes: String,
+    pub created_at: DateTime<Utc>,
+    pub rotated_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_validated_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_validation: Option<KeyValidationStatus>,
+    /// Pool this entry belongs to in addition to the one named after it.
+    #[serde(default)]
+    pub pool: Option<String>,
+}
+
+// ─── Key validation ───────────────────────────────────────────────────────────
+
+/// Outcome of probing a provider endpoint with a stored key.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum KeyValidationStatus {
+    Valid,
+    /// The provider rejected the key (HTTP 401/403).
+    InvalidKey,
+    /// The provider accepted the key but refused service (HTTP 402/429).
+    QuotaExhausted,
+    /// DNS, connect, TLS or timeout failure before any HTTP status.
+    Unreachable,
+    /// The provider requires a key but none is stored for the entity.
+    MissingKey,
+    /// Any other response (e.g. 404 from a wrong endpoint, 5xx).
+    Unexpected,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyValidationResult {
+    pub entity: String,
+    pub status: KeyValidationStatus,
+    pub checked_at: DateTime<Utc>,
+    /// URL that was probed (never includes the key).
+    pub endpoint: String,
+    pub http_status: Option<u16>,
+    pub detail: Option<String>,
+}
+
+// ─── Model discovery ──────────────────────────────────────────────────────────
+
+/// Models offered for an entity or provider: what its endpoint reports,
+/// merged with the static catalog.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelListing {
+    /// Entity name, or provider id when listing a provider without an entity.
+    pub target: String,
+    /// URL that was queried (never includes the key).
+    pub endpoint: String,
+    pub fetched_at: DateTime<Utc>,
+    /// Served from the TTL cache instead of a fresh request.
+    pub from_cache: bool,
+    /// Remote models first (sorted), then catalog-only models.
+    pub models: Vec<ModelEntry>,
+    /// Why the remote query failed; `models` then holds only the catalog.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ModelEntry {
+    pub id: String,
+    /// Reported by the live endpoint.
+    pub remote: bool,
+    /// Listed in the provider catalog.
+    pub in_catalog: bool,
+}
+
+impl ModelListing {
+    /// Whether the live endpoint answered. Only then can a model be reported missing.
+    pub fn is_live(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// `false` only if the endpoint answered and did not list `model`.
+    pub fn offers(&self, model: &str) -> bool {
+        !self.is_live() || self.models.iter().any(|m| m.remote && m.matches(model))
+    }
+}
+
+impl ModelEntry {
+    /// Exact match, treating Ollama's implicit `:latest` tag as equal to no tag.
+    pub fn matches(&self, model: &str) -> bool {
+        let strip = |id: &str| id.strip_suffix(":latest").unwrap_or(id).to_string();
+        self.id == model || strip(&self.id) == strip(model)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,6 +184,120 @@ pub struct VaultUsageEntry {
     pub provider: String,
     pub requests_made: u64,
     pub last_used: DateTime<Utc>,
+    /// Replies served from the local response cache.
+    #[serde(default)]
+    pub cache_hits: u64,
+    /// Cacheable requests that had to go to the provider.
+    #[serde(default)]
+    pub cache_misses: u64,
+}
+
+// ─── Key pools ────────────────────────────────────────────────────────────────
+
+/// How the next key is picked from a pool.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum KeyPoolStrategy {
+    #[default]
+    RoundRobin,
+    /// The key with the fewest requests so far (ties: least recently used).
+    LeastUsed,
+}
+
+/// A key handed out by `ApiKeyManager::acquire_key`. Report how the request
+/// went with `report_key_outcome(&lease.entry, ..)`.
+#[derive(Debug, Clone)]
+pub struct KeyLease {
+    pub pool: String,
+    /// Vault entry the key came from.
+    pub entry: String,
+    pub key: SecretString,
+}
+
+/// Result of one request made with a pooled key.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum KeyOutcome {
+    Success { tokens: u64 },
+    /// Auth rejected (HTTP 401/403); the key is benched for a long time.
+    InvalidKey,
+    /// Rate limited or out of credit (HTTP 402/429); benched with backoff.
+    QuotaExhausted,
+    /// Network or server error; counted but not benched.
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyPoolMember {
+    pub entry: String,
+    pub requests_made: u64,
+    pub tokens_used: u64,
+    pub failures: u64,
+    pub last_used: Option<DateTime<Utc>>,
+    /// Set while the key is excluded from rotation.
+    pub benched_until: Option<DateTime<Utc>>,
+    pub bench_reason: Option<KeyOutcome>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyPoolStatus {
+    pub pool: String,
+    pub strategy: KeyPoolStrategy,
+    pub members: Vec<KeyPoolMember>,
+}
+
+// ─── Export / import ──────────────────────────────────────────────────────────
+
+/// What to do when an imported entry name already exists in the vault.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum ImportConflictPolicy {
+    /// Keep the local entry and drop the imported one.
+    #[default]
+    Skip,
+    /// Replace the local secret and metadata with the imported ones.
+    Overwrite,
+    /// Import under a fresh name (`<name>-imported`, `<name>-imported-2`, …).
+    Rename,
+}
+
+/// Outcome of `ApiKeyManager::import_bundle`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct VaultImportReport {
+    pub imported: Vec<String>,
+    pub overwritten: Vec<String>,
+    pub skipped: Vec<String>,
+    /// `(original name, new name)` pairs for entries imported under `Rename`.
+    pub renamed: Vec<(String, String)>,
+    /// Entities carried by the bundle, with names already adjusted for renames
+    /// and skipped entries removed.
+    pub entities: Vec<AiEntity>,
+}
+
+// ─── AI entities ──────────────────────────────────────────────────────────────
+
+/// A named AI entity: one API key + provider + model combination that can be
+/// referenced by name throughout the swarm.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AiEntity {
+    /// User-chosen name, e.g. "gpt4-coder", "deepseek-main"
+    pub name: String,
+    /// Provider id, e.g. "openai", "deepseek"  (or any label for a custom relay)
+    pub provider: String,
+    /// Model id, e.g. "gpt-4o", "deepseek-chat"
+    pub model: String,
+    /// Optional human note
+    pub note: String,
+    /// Custom base URL for relay / self-hosted / proxy providers.
+    /// When set, this overrides the predefined provider endpoint.
+    /// e.g. "https://my-relay.example.com/v1"
+    #[serde(default)]
+    pub custom_base_url: Option<String>,
+    /// HTTP header name used to send the key (default: "Authorization").
+    /// e.g. "api-key" for Azure, or "Authorization" for most others.
+    #[serde(default)]
+    pub key_header: Option<String>,
+    /// Prefix placed before the key in the header (default: "Bearer ").
+    /// Set to "" if the provider wants a bare key.
+    #[serde(default)]
+    pub key_prefix: Option<String>,
 }
 
 // ─── Provider configuration ───────────────────────────────────────────────────
@@ -45,6 +312,45 @@ pub struct ProviderConfig {
     pub pricing: PricingInfo,
     pub requires_api_key: bool,
     pub description: String,
+    /// Header carrying the key when it is not `Authorization` (custom relays).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_header: Option<String>,
+    /// Prefix before the key when it is not `Bearer ` (custom relays).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_prefix: Option<String>,
+    /// Where this entry came from after merging the registry layers.
+    #[serde(default)]
+    pub source: ProviderSource,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum ProviderSource {
+    /// Embedded catalog shipped with this build.
+    #[default]
+    BuiltIn,
+    /// Built-in entry patched (or new entry added) by `vault/providers.json`.
+    Override,
+    /// User-defined relay from `vault/custom_providers.json`.
+    Custom,
+}
+
+/// A user-defined custom / relay API provider.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CustomProvider {
+    /// Short identifier used as the `provider` field in `AiEntity`, e.g. "my-relay"
+    pub id: String,
+    /// Human-readable display name, e.g. "My DeepSeek Relay"
+    pub name: String,
+    /// Base URL of the relay, e.g. "https://relay.example.com/v1"
+    pub base_url: String,
+    /// HTTP header name (leave empty for default "Authorization")
+    pub key_header: String,
+    /// Token prefix (leave empty for default "Bearer")
+    pub key_prefix: String,
+    /// Space- or comma-separated model list suggestion
+    pub models_hint: String,
+    /// Optional description
+    pub description: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
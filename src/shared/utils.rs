@@ -1,10 +1,9 @@
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+use sha2::{Digest, Sha256};
 
+/// Hex SHA-256 of `data`. Stable across runs and machines, so it can name
+/// files and cache entries.
 pub fn compute_hash(data: &[u8]) -> String {
-    let mut hasher = DefaultHasher::new();
-    data.hash(&mut hasher);
-    format!("{:x}", hasher.finish())
+    hex::encode(Sha256::digest(data))
 }
 
 pub fn generate_id() -> String {
@@ -20,4 +19,17 @@ pub fn format_duration(duration: std::time::Duration) -> String {
 
 pub fn parse_timestamp(ts: &str) -> Result<chrono::DateTime<chrono::Utc>, Box<dyn std::error::Error>> {
     Ok(chrono::DateTime::parse_from_rfc3339(ts)?.with_timezone(&chrono::Utc))
-}
\ No newline at end of file
+}
+
+/// At most `max` bytes of `s`, cut on a char boundary, with `…` appended when
+/// anything was dropped. For quoting response bodies in errors and logs.
+pub fn truncate(s: &str, max: usize) -> String {
+    if s.len() <= max {
+        return s.to_string();
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…", &s[..end])
+}